@@ -0,0 +1,209 @@
+//! Packet captures for reproducing a db session without the hardware that produced it
+//!
+//! A capture file starts with [`MAGIC`], followed by one record per packet: the time since recording started in
+//! microseconds as a little endian `u64`, then the packet exactly as it went over the wire, `u32` length prefix
+//! included. Vtables are recorded alongside the tables that use them, so a capture replays into an empty db.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::SocketAddr,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use impeller2::{
+    types::{ComponentId, LenPacket, PACKET_HEADER_LEN},
+    vtable::VTable,
+};
+
+pub const MAGIC: [u8; 8] = *b"IMPCAP\0\x01";
+
+/// The largest packet a capture may contain, so a corrupt length can't make the reader allocate unbounded memory
+const MAX_PACKET_LEN: usize = 64 * 1024 * 1024;
+
+/// Which packets [`crate::Client::record`] keeps
+#[derive(Clone, Debug, Default)]
+pub struct CaptureFilter {
+    /// Only record tables that include one of these components, or every table if empty
+    pub components: HashSet<ComponentId>,
+    /// Stop recording after this long
+    pub duration: Option<Duration>,
+}
+
+impl CaptureFilter {
+    pub fn matches(&self, vtable: &VTable) -> bool {
+        self.components.is_empty()
+            || vtable
+                .column_iter()
+                .flatten()
+                .any(|column| self.components.contains(&column.component_id))
+    }
+}
+
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+    packets: usize,
+}
+
+impl CaptureWriter<BufWriter<File>> {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> CaptureWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&MAGIC)?;
+        Ok(Self { writer, packets: 0 })
+    }
+
+    /// Appends `packet`, received `offset` after recording started
+    pub fn write(&mut self, offset: Duration, packet: &LenPacket) -> io::Result<()> {
+        let offset = u64::try_from(offset.as_micros()).unwrap_or(u64::MAX);
+        self.writer.write_all(&offset.to_le_bytes())?;
+        self.writer.write_all(&packet.inner)?;
+        self.packets += 1;
+        Ok(())
+    }
+
+    /// The number of packets written so far
+    pub fn packets(&self) -> usize {
+        self.packets
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+pub struct CaptureReader<R: Read> {
+    reader: R,
+}
+
+impl CaptureReader<BufReader<File>> {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    pub fn new(mut reader: R) -> anyhow::Result<Self> {
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(anyhow!("not an impeller2 packet capture"));
+        }
+        Ok(Self { reader })
+    }
+
+    /// The next packet and its offset from the start of the recording, or `None` at the end of the capture
+    ///
+    /// A record cut short by the recorder being killed is treated as the end of the capture.
+    pub fn next_packet(&mut self) -> anyhow::Result<Option<(Duration, LenPacket)>> {
+        let mut header = [0u8; 12];
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        let (offset, len) = header.split_at(8);
+        let offset = u64::from_le_bytes(offset.try_into().expect("offset is 8 bytes"));
+        let len = u32::from_le_bytes(len.try_into().expect("len is 4 bytes")) as usize;
+        if !(PACKET_HEADER_LEN..=MAX_PACKET_LEN).contains(&len) {
+            return Err(anyhow!("invalid packet length {len} in capture"));
+        }
+        let mut inner = vec![0u8; 4 + len];
+        inner[..4].copy_from_slice(&header[8..]);
+        match self.reader.read_exact(&mut inner[4..]) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        Ok(Some((Duration::from_micros(offset), LenPacket { inner })))
+    }
+}
+
+/// Sends every packet in the capture at `path` to the db at `addr`, returning the number of packets sent
+///
+/// Packets keep their recorded spacing divided by `speed`, so `2.0` replays twice as fast and `math.huge` as fast as
+/// the connection allows.
+pub async fn replay(path: &Path, addr: SocketAddr, speed: f64) -> anyhow::Result<usize> {
+    if speed.is_nan() || speed <= 0.0 {
+        return Err(anyhow!("replay speed must be positive"));
+    }
+    let mut reader = CaptureReader::open(path)?;
+    let mut client = impeller2_stellar::Client::connect(addr).await?;
+    let start = Instant::now();
+    let mut packets = 0;
+    while let Some((offset, packet)) = reader.next_packet()? {
+        let due = offset.div_f64(speed);
+        if let Some(wait) = due.checked_sub(start.elapsed()) {
+            stellarator::sleep(wait).await;
+        }
+        client.send(packet).await.0?;
+        packets += 1;
+    }
+    Ok(packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use impeller2::{
+        types::PrimType,
+        vtable::builder::{component, raw_field, schema, vtable},
+    };
+
+    #[test]
+    fn test_capture_round_trip() {
+        let mut table = LenPacket::table([1, 0], 8);
+        table.extend_aligned(&[1.5f64]);
+        let msg = LenPacket::msg([224, 0], 0);
+
+        let mut writer = CaptureWriter::new(vec![]).unwrap();
+        writer.write(Duration::ZERO, &msg).unwrap();
+        writer.write(Duration::from_millis(20), &table).unwrap();
+        assert_eq!(writer.packets(), 2);
+        let mut buf = writer.finish().unwrap();
+
+        // a record cut off mid packet ends the capture instead of failing it
+        let mut truncated = buf.clone();
+        truncated.extend_from_slice(&30_000u64.to_le_bytes());
+        truncated.extend_from_slice(&100u32.to_le_bytes());
+        truncated.extend_from_slice(&[0; 3]);
+        buf.extend_from_slice(&[0; 3]);
+
+        for buf in [buf, truncated] {
+            let mut reader = CaptureReader::new(&buf[..]).unwrap();
+            let (offset, packet) = reader.next_packet().unwrap().unwrap();
+            assert_eq!(offset, Duration::ZERO);
+            assert_eq!(packet.inner, msg.inner);
+            let (offset, packet) = reader.next_packet().unwrap().unwrap();
+            assert_eq!(offset, Duration::from_millis(20));
+            assert_eq!(packet.inner, table.inner);
+            assert!(reader.next_packet().unwrap().is_none());
+        }
+
+        assert!(CaptureReader::new(&b"not a capture"[..]).is_err());
+    }
+
+    #[test]
+    fn test_capture_filter() {
+        let accel = ComponentId::new("accel");
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            schema(PrimType::F64, &[1], component(accel)),
+        )]);
+        assert!(CaptureFilter::default().matches(&vtable));
+        let mut filter = CaptureFilter::default();
+        filter.components.insert(ComponentId::new("gyro"));
+        assert!(!filter.matches(&vtable));
+        filter.components.insert(accel);
+        assert!(filter.matches(&vtable));
+    }
+}
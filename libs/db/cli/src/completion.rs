@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+
+use impeller2_wkt::{MsgStream, SetComponentMetadata, Stream, UdpUnicast};
+use mlua::{Lua, Table, Value};
+use postcard_schema::{
+    Schema,
+    schema::owned::{OwnedDataModelType, OwnedNamedType},
+};
+use rustyline::{
+    Context,
+    completion::{Completer, FilenameCompleter, Pair},
+};
+
+/// Completes the REPL line against the live Lua state
+///
+/// Bare words complete to globals, `a.b` to the fields of table `a`, and `client:get_` to the methods of the
+/// userdata bound to `client`. Inside a msg constructor like `Stream { ... }` the msg's fields are offered too.
+/// Anything inside a string literal falls back to filename completion.
+pub struct LuaCompleter {
+    lua: Lua,
+    msg_fields: BTreeMap<&'static str, Vec<String>>,
+    files: FilenameCompleter,
+}
+
+impl LuaCompleter {
+    pub fn new(lua: Lua) -> Self {
+        let msg_fields = [
+            ("SetComponentMetadata", SetComponentMetadata::SCHEMA.into()),
+            ("Stream", Stream::SCHEMA.into()),
+            ("UdpUnicast", UdpUnicast::SCHEMA.into()),
+            ("MsgStream", MsgStream::SCHEMA.into()),
+        ]
+        .into_iter()
+        .map(|(name, schema): (_, OwnedNamedType)| (name, struct_fields(&schema)))
+        .collect();
+        Self {
+            lua,
+            msg_fields,
+            files: FilenameCompleter::new(),
+        }
+    }
+
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let line = &line[..pos];
+        let start = line
+            .rfind(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | ':')))
+            .map_or(0, |i| i + 1);
+        let word = &line[start..];
+
+        let mut names = match word.rfind(['.', ':']) {
+            Some(sep) => {
+                let (path, prefix) = (&word[..sep], &word[sep + 1..]);
+                self.members(path, word.as_bytes()[sep] == b':')
+                    .into_iter()
+                    .filter(|name| name.starts_with(prefix))
+                    .map(|name| format!("{}{name}", &word[..=sep]))
+                    .collect()
+            }
+            None => {
+                let mut names = self.keys(self.lua.globals());
+                if let Some(fields) = open_constructor(line).and_then(|m| self.msg_fields.get(m)) {
+                    names.extend(fields.iter().cloned());
+                }
+                names.retain(|name| name.starts_with(word));
+                names
+            }
+        };
+        names.sort();
+        names.dedup();
+        let pairs = names
+            .into_iter()
+            .map(|name| Pair {
+                display: name.clone(),
+                replacement: name,
+            })
+            .collect();
+        (start, pairs)
+    }
+
+    /// The names reachable from `path` with `.`, or only the callable ones if `methods` is set
+    fn members(&self, path: &str, methods: bool) -> Vec<String> {
+        let mut value = Value::Table(self.lua.globals());
+        for segment in path.split('.') {
+            value = match value {
+                Value::Table(table) => table.get(segment).unwrap_or(Value::Nil),
+                _ => return vec![],
+            };
+        }
+        let table = match value {
+            Value::Table(table) => table,
+            Value::UserData(ud) => {
+                let Ok(Value::Table(index)) =
+                    ud.metatable().and_then(|mt| mt.get::<Value>("__index"))
+                else {
+                    return vec![];
+                };
+                index
+            }
+            _ => return vec![],
+        };
+        if !methods {
+            return self.keys(table);
+        }
+        table
+            .pairs::<String, Value>()
+            .flatten()
+            .filter(|(_, value)| matches!(value, Value::Function(_)))
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    fn keys(&self, table: Table) -> Vec<String> {
+        table
+            .pairs::<Value, Value>()
+            .flatten()
+            .filter_map(|(key, _)| match key {
+                Value::String(key) => Some(key.to_str().ok()?.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Completer for LuaCompleter {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let quotes = line[..pos].matches(['"', '\'']).count();
+        if quotes % 2 == 1 {
+            return self.files.complete(line, pos, ctx);
+        }
+        Ok(self.candidates(line, pos))
+    }
+}
+
+fn struct_fields(schema: &OwnedNamedType) -> Vec<String> {
+    match &schema.ty {
+        OwnedDataModelType::Struct(fields) => fields.iter().map(|f| f.name.clone()).collect(),
+        OwnedDataModelType::NewtypeStruct(inner) => struct_fields(inner),
+        _ => vec![],
+    }
+}
+
+/// The name before the innermost `{` that hasn't been closed yet, like `Stream` in `Stream { beh`
+fn open_constructor(line: &str) -> Option<&str> {
+    let mut depth = 0usize;
+    for (i, c) in line.char_indices().rev() {
+        match c {
+            '}' => depth += 1,
+            '{' if depth > 0 => depth -= 1,
+            '{' => {
+                let name = line[..i].trim_end();
+                let start = name
+                    .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .map_or(0, |i| i + 1);
+                return Some(&name[start..]);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mlua::{UserData, UserDataMethods};
+
+    struct Probe;
+
+    impl UserData for Probe {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("get_schema", |_, _, ()| Ok(()));
+            methods.add_method("get_time_series", |_, _, ()| Ok(()));
+            methods.add_method("flush", |_, _, ()| Ok(()));
+        }
+    }
+
+    fn complete(completer: &LuaCompleter, line: &str) -> (usize, Vec<String>) {
+        let (start, pairs) = completer.candidates(line, line.len());
+        (start, pairs.into_iter().map(|p| p.replacement).collect())
+    }
+
+    #[test]
+    fn test_lua_completer() {
+        let lua = crate::lua().unwrap();
+        lua.globals().set("client", Probe).unwrap();
+        let completer = LuaCompleter::new(lua);
+
+        assert_eq!(
+            complete(&completer, "x = client:get_"),
+            (
+                4,
+                vec!["client:get_schema".into(), "client:get_time_series".into()]
+            )
+        );
+        assert_eq!(
+            complete(&completer, "SetComponentMeta"),
+            (0, vec!["SetComponentMetadata".into()])
+        );
+        assert_eq!(
+            complete(&completer, "string.up"),
+            (0, vec!["string.upper".into()])
+        );
+
+        let (_, fields) = complete(&completer, "client:stream(Stream { beh");
+        assert_eq!(fields, ["behavior"]);
+        let (_, fields) = complete(&completer, "Stream { behavior = {}, i");
+        assert!(fields.contains(&"id".to_string()));
+    }
+}
@@ -0,0 +1,225 @@
+//! Comparing the components of two databases, for checking that a migration between recorded sessions kept what it
+//! should
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use impeller2::{schema::Schema, types::ComponentId};
+use impeller2_wkt::ComponentMetadata;
+use nu_ansi_term::Color;
+
+/// One difference between database `a` and database `b`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComponentDiff {
+    OnlyInA(String),
+    OnlyInB(String),
+    /// The component's prim type or shape differ, formatted like `f64 [3]`
+    Schema {
+        name: String,
+        a: String,
+        b: String,
+    },
+    /// A metadata entry was added, removed or changed
+    Metadata {
+        name: String,
+        key: String,
+        a: Option<String>,
+        b: Option<String>,
+    },
+}
+
+impl ComponentDiff {
+    /// The diff as a line colored like a text diff: removals red, additions green and changes yellow
+    pub fn colored(&self) -> String {
+        let color = match self {
+            ComponentDiff::OnlyInA(_) => Color::Red,
+            ComponentDiff::OnlyInB(_) => Color::Green,
+            _ => Color::Yellow,
+        };
+        color.paint(self.to_string()).to_string()
+    }
+}
+
+impl fmt::Display for ComponentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |v: &Option<String>| v.clone().unwrap_or_else(|| "(none)".to_string());
+        match self {
+            ComponentDiff::OnlyInA(name) => write!(f, "- {name}"),
+            ComponentDiff::OnlyInB(name) => write!(f, "+ {name}"),
+            ComponentDiff::Schema { name, a, b } => write!(f, "~ {name}: {a} -> {b}"),
+            ComponentDiff::Metadata { name, key, a, b } => {
+                write!(f, "~ {name}.{key}: {} -> {}", value(a), value(b))
+            }
+        }
+    }
+}
+
+/// One database's components, from [`impeller2_wkt::DumpMetadata`] and [`impeller2_wkt::DumpSchema`]
+pub struct DbComponents<'a> {
+    pub metadata: &'a [ComponentMetadata],
+    pub schemas: &'a HashMap<ComponentId, Schema<Vec<u64>>>,
+}
+
+struct Component<'a> {
+    name: String,
+    schema: Option<&'a Schema<Vec<u64>>>,
+    metadata: Option<&'a HashMap<String, String>>,
+}
+
+impl<'a> DbComponents<'a> {
+    fn components(&self) -> HashMap<ComponentId, Component<'a>> {
+        let mut components = HashMap::new();
+        for metadata in self.metadata {
+            components.insert(
+                metadata.component_id,
+                Component {
+                    name: metadata.name.clone(),
+                    schema: self.schemas.get(&metadata.component_id),
+                    metadata: Some(&metadata.metadata),
+                },
+            );
+        }
+        for (id, schema) in self.schemas {
+            components.entry(*id).or_insert_with(|| Component {
+                name: id.to_string(),
+                schema: Some(schema),
+                metadata: None,
+            });
+        }
+        components
+    }
+}
+
+/// Every difference between `a` and `b`, ordered by component name
+///
+/// Components are matched by id, so a renamed component shows up as a change to its name metadata rather than as a
+/// removal and an addition.
+pub fn diff(a: &DbComponents<'_>, b: &DbComponents<'_>) -> Vec<ComponentDiff> {
+    let a = a.components();
+    let b = b.components();
+    let ids = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+    let mut diffs = BTreeMap::<String, Vec<ComponentDiff>>::new();
+    for id in ids {
+        let (name, entries) = match (a.get(id), b.get(id)) {
+            (Some(a), None) => (a.name.clone(), vec![ComponentDiff::OnlyInA(a.name.clone())]),
+            (None, Some(b)) => (b.name.clone(), vec![ComponentDiff::OnlyInB(b.name.clone())]),
+            (Some(a), Some(b)) => (b.name.clone(), component_diff(a, b)),
+            (None, None) => unreachable!("id came from one of the maps"),
+        };
+        diffs.entry(name).or_default().extend(entries);
+    }
+    diffs.into_values().flatten().collect()
+}
+
+fn component_diff(a: &Component<'_>, b: &Component<'_>) -> Vec<ComponentDiff> {
+    let mut diffs = vec![];
+    if a.name != b.name {
+        diffs.push(ComponentDiff::Metadata {
+            name: b.name.clone(),
+            key: "name".to_string(),
+            a: Some(a.name.clone()),
+            b: Some(b.name.clone()),
+        });
+    }
+    let (schema_a, schema_b) = (format_schema(a.schema), format_schema(b.schema));
+    if schema_a != schema_b {
+        diffs.push(ComponentDiff::Schema {
+            name: b.name.clone(),
+            a: schema_a,
+            b: schema_b,
+        });
+    }
+    let empty = HashMap::new();
+    let (meta_a, meta_b) = (a.metadata.unwrap_or(&empty), b.metadata.unwrap_or(&empty));
+    let keys = meta_a.keys().chain(meta_b.keys()).collect::<BTreeSet<_>>();
+    for key in keys {
+        let (value_a, value_b) = (meta_a.get(key), meta_b.get(key));
+        if value_a != value_b {
+            diffs.push(ComponentDiff::Metadata {
+                name: b.name.clone(),
+                key: key.clone(),
+                a: value_a.cloned(),
+                b: value_b.cloned(),
+            });
+        }
+    }
+    diffs
+}
+
+fn format_schema(schema: Option<&Schema<Vec<u64>>>) -> String {
+    match schema {
+        Some(schema) => format!("{} {:?}", schema.prim_type(), schema.dim()),
+        None => "no schema".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use impeller2::types::PrimType;
+
+    fn metadata(name: &str, entries: &[(&str, &str)]) -> ComponentMetadata {
+        ComponentMetadata {
+            component_id: ComponentId::new(name),
+            name: name.to_string(),
+            metadata: entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let schema = |ty, shape: &[u64]| Schema::new(ty, shape.to_vec()).unwrap();
+        let a_metadata = [
+            metadata("accel", &[("unit", "m/s^2")]),
+            metadata("gyro", &[]),
+            metadata("mag", &[]),
+        ];
+        let a_schemas = HashMap::from([
+            (ComponentId::new("accel"), schema(PrimType::F64, &[3])),
+            (ComponentId::new("gyro"), schema(PrimType::F64, &[3])),
+            (ComponentId::new("mag"), schema(PrimType::F32, &[3])),
+        ]);
+        let b_metadata = [
+            metadata("accel", &[("unit", "g"), ("priority", "5")]),
+            metadata("baro", &[]),
+            metadata("mag", &[]),
+        ];
+        let b_schemas = HashMap::from([
+            (ComponentId::new("accel"), schema(PrimType::F64, &[3])),
+            (ComponentId::new("baro"), schema(PrimType::F64, &[])),
+            (ComponentId::new("mag"), schema(PrimType::F64, &[3])),
+        ]);
+        let diffs = diff(
+            &DbComponents {
+                metadata: &a_metadata,
+                schemas: &a_schemas,
+            },
+            &DbComponents {
+                metadata: &b_metadata,
+                schemas: &b_schemas,
+            },
+        );
+        let lines = diffs.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "~ accel.priority: (none) -> 5",
+                "~ accel.unit: m/s^2 -> g",
+                "+ baro",
+                "- gyro",
+                "~ mag: f32 [3] -> f64 [3]",
+            ]
+        );
+
+        let same = DbComponents {
+            metadata: &a_metadata,
+            schemas: &a_schemas,
+        };
+        assert!(diff(&same, &same).is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+
+use impeller2::types::PacketId;
+use impeller2_wkt::StreamId;
+
+/// Hands out [`StreamId`]s that are unique for the lifetime of a [`crate::Client`]
+///
+/// Ids count up from a random seed, so two clients connected to the same db are unlikely to overlap, while a single
+/// client never reuses an id until the counter wraps. `0` is never returned, since a stream id of `0` asks the client
+/// to allocate one.
+pub struct StreamIdAllocator {
+    next: StreamId,
+}
+
+impl Default for StreamIdAllocator {
+    fn default() -> Self {
+        Self::new(fastrand::u64(1..))
+    }
+}
+
+impl StreamIdAllocator {
+    pub fn new(seed: StreamId) -> Self {
+        Self { next: seed.max(1) }
+    }
+
+    pub fn allocate(&mut self) -> StreamId {
+        let id = self.next;
+        self.next = self.next.checked_add(1).unwrap_or(1);
+        id
+    }
+}
+
+/// Hands out [`PacketId`]s that don't collide with any id still in use on a connection
+///
+/// A vtable registered under an id stays live until [`PacketIdAllocator::release`] is called, so a later
+/// allocation can't clobber it. Ids count up from a random seed and wrap, skipping live ones.
+pub struct PacketIdAllocator {
+    next: u16,
+    live: HashSet<u16>,
+}
+
+impl Default for PacketIdAllocator {
+    fn default() -> Self {
+        Self::new(fastrand::u16(..))
+    }
+}
+
+impl PacketIdAllocator {
+    pub fn new(seed: u16) -> Self {
+        Self {
+            next: seed,
+            live: HashSet::new(),
+        }
+    }
+
+    /// Returns an id that isn't live and marks it live, or `None` if every id is in use
+    pub fn allocate(&mut self) -> Option<PacketId> {
+        if self.live.len() > u16::MAX as usize {
+            return None;
+        }
+        while self.live.contains(&self.next) {
+            self.next = self.next.wrapping_add(1);
+        }
+        let id = self.next;
+        self.live.insert(id);
+        self.next = self.next.wrapping_add(1);
+        Some(id.to_le_bytes())
+    }
+
+    /// Makes `id` available for reuse
+    pub fn release(&mut self, id: PacketId) {
+        self.live.remove(&u16::from_le_bytes(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_ids_are_distinct() {
+        let mut ids = PacketIdAllocator::new(u16::MAX - 1);
+        let allocated = (0..1024)
+            .map(|_| ids.allocate().unwrap())
+            .collect::<HashSet<_>>();
+        assert_eq!(allocated.len(), 1024);
+    }
+
+    #[test]
+    fn test_packet_ids_skip_live_ids() {
+        let mut ids = PacketIdAllocator::new(0);
+        let first = ids.allocate().unwrap();
+        for _ in 1..=u16::MAX {
+            ids.allocate().unwrap();
+        }
+        assert_eq!(ids.allocate(), None);
+        ids.release(first);
+        assert_eq!(ids.allocate(), Some(first));
+    }
+
+    #[test]
+    fn test_stream_ids_are_unique_and_skip_zero() {
+        let mut ids = StreamIdAllocator::new(StreamId::MAX - 1);
+        assert_eq!(ids.allocate(), StreamId::MAX - 1);
+        assert_eq!(ids.allocate(), StreamId::MAX);
+        assert_eq!(ids.allocate(), 1);
+        assert_eq!(ids.allocate(), 2);
+    }
+}
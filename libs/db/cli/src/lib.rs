@@ -4,25 +4,31 @@ use arrow::{
     error::ArrowError,
     util::display::{ArrayFormatter, FormatOptions},
 };
+use convert_case::Casing;
 use impeller2::{
     com_de::Decomponentize,
-    schema::Schema,
-    types::{ComponentId, Msg, OwnedTimeSeries, PacketId, PrimType, Request, Timestamp, msg_id},
+    component::Asset,
+    schema::{Schema, TypedDecoder},
+    types::{
+        ComponentId, ComponentView, ElementValue, Msg, PACKET_HEADER_LEN, PacketId, PrimType,
+        Q15_16, Request, RequestId, Timestamp, bf16, f16, msg_id, var_len_payload, write_var_len,
+    },
     vtable::{
         self, VTable,
         builder::{FieldBuilder, OpBuilder, schema},
     },
 };
 
-use impeller2::types::{IntoLenPacket, LenPacket, OwnedPacket};
+use impeller2::types::{IntoLenPacket, LenPacket, OwnedPacket, OwnedTable};
+use impeller2_stellar::ReconnectPolicy;
 use impeller2_wkt::*;
 use mlua::{
-    AnyUserData, Error, Lua, LuaSerdeExt, MultiValue, ObjectLike, UserData, UserDataRef, Value,
+    AnyUserData, Error, Function, IntoLua, Lua, LuaSerdeExt, MultiValue, ObjectLike, UserData,
+    UserDataRef, Value,
 };
 use nu_ansi_term::Color;
 use rustyline::{
     Completer, CompletionType, Editor, Helper, Hinter, Validator,
-    completion::FilenameCompleter,
     highlight::{CmdKind, Highlighter},
     hint::HistoryHinter,
     history::History,
@@ -30,25 +36,76 @@ use rustyline::{
 };
 use std::{
     borrow::Cow::{self, Borrowed, Owned},
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     fmt::Display,
-    io::{self, Read},
-    net::ToSocketAddrs,
-    ops::Deref,
-    path::PathBuf,
+    io::{self, IsTerminal, Read},
+    net::{SocketAddr, ToSocketAddrs},
+    ops::{Deref, Range},
+    path::{Path, PathBuf},
     sync::{
         Arc,
         atomic::{self, AtomicBool},
     },
     time::Duration,
 };
-use stellarator::buf::Slice;
+use stellarator::{buf::Slice, sync::WaitQueue};
 use zerocopy::{Immutable, IntoBytes, TryFromBytes};
 
 pub use mlua;
 
+mod capture;
+mod completion;
+pub mod diff;
+mod ids;
+mod plot;
+mod progress;
+pub mod time_range;
+pub mod units;
+pub use capture::{CaptureFilter, CaptureReader, CaptureWriter, replay};
+use completion::LuaCompleter;
+use diff::{ComponentDiff, DbComponents};
+pub use ids::*;
+use progress::Progress;
+use time_range::TimeRange;
+
+/// The number of samples [`Client::export_time_series`] asks for per request
+pub const EXPORT_PAGE_LEN: usize = 4096;
+
+// Ctrl-C normally exits the cli, but while a query, export or stream is running it stops that instead.
+static OP_IN_FLIGHT: AtomicBool = AtomicBool::new(false);
+static OP_INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Woken on Ctrl-C, or enter during a [`StopSignal`], so an operation waiting on the db can stop without waiting for
+/// its next reply
+static OP_INTERRUPT_WAITERS: WaitQueue = WaitQueue::new();
+
+fn install_interrupt_handler() {
+    let res = ctrlc::set_handler(|| {
+        if OP_IN_FLIGHT.load(atomic::Ordering::SeqCst) {
+            OP_INTERRUPTED.store(true, atomic::Ordering::SeqCst);
+            OP_INTERRUPT_WAITERS.wake_all();
+        } else {
+            std::process::exit(130);
+        }
+    });
+    if let Err(err) = res {
+        eprintln!("failed to install ctrl-c handler: {err}");
+    }
+}
+
 pub struct Client {
+    addr: SocketAddr,
     client: impeller2_stellar::Client,
+    reconnect_policy: ReconnectPolicy,
+    reconnects: u32,
+    config: ClientConfig,
+    /// The codec set with [`Client::set_compression`], negotiated again after every reconnect
+    compression: Option<Compression>,
+    /// When the last reply was received, used to decide when a keepalive probe is due
+    last_reply: std::time::Instant,
+    stream_ids: StreamIdAllocator,
+    packet_ids: PacketIdAllocator,
+    /// The vtable id used by [`Client::send_batch`] for each set of columns, so repeated sends reuse one id
+    send_vtable_ids: BTreeMap<Vec<(ComponentId, PrimType, Vec<u64>)>, PacketId>,
 }
 
 impl Client {
@@ -59,22 +116,170 @@ impl Client {
             .next()
             .ok_or_else(|| anyhow!("missing socket ip"))?;
         let client = impeller2_stellar::Client::connect(addr).await?;
-        Ok(Client { client })
+        Ok(Client {
+            addr,
+            client,
+            reconnect_policy: ReconnectPolicy::default(),
+            reconnects: 0,
+            config: ClientConfig::default(),
+            compression: None,
+            last_reply: std::time::Instant::now(),
+            stream_ids: StreamIdAllocator::default(),
+            packet_ids: PacketIdAllocator::default(),
+            send_vtable_ids: BTreeMap::new(),
+        })
+    }
+
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect_policy = policy;
+    }
+
+    pub fn with_config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Reconnects to the db, backing off exponentially between attempts according to the [`ReconnectPolicy`]
+    pub async fn reconnect(&mut self) -> anyhow::Result<()> {
+        let mut backoff = self.reconnect_policy.initial_backoff;
+        let mut attempts = 0;
+        loop {
+            match impeller2_stellar::Client::connect(self.addr).await {
+                Ok(client) => {
+                    self.client = client;
+                    self.reconnects += 1;
+                    if let Some(codec) = self.compression {
+                        self.client.negotiate_compression(&[codec]).await?;
+                    }
+                    eprintln!(
+                        "{}",
+                        Color::Yellow.paint(format!("reconnected to {}", self.addr))
+                    );
+                    return Ok(());
+                }
+                Err(err) => {
+                    attempts += 1;
+                    if self
+                        .reconnect_policy
+                        .max_attempts
+                        .is_some_and(|max_attempts| attempts >= max_attempts)
+                    {
+                        return Err(err.into());
+                    }
+                    stellarator::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.reconnect_policy.max_backoff);
+                }
+            }
+        }
+    }
+
+    /// Compresses packets in both directions with `codec`, or turns compression off if `None`
+    ///
+    /// Returns the codec the db agreed to, which is `None` if it doesn't support `codec`.
+    pub async fn set_compression(
+        &mut self,
+        codec: Option<Compression>,
+    ) -> anyhow::Result<Option<Compression>> {
+        let accepted = self.client.negotiate_compression(codec.as_slice()).await?;
+        self.compression = accepted;
+        Ok(accepted)
+    }
+
+    /// Checks the connection by timing a [`GetServerTime`] round trip
+    pub async fn status(&mut self) -> ClientStatus {
+        let start = std::time::Instant::now();
+        let connected = self.request_once(&GetServerTime).await.is_ok();
+        ClientStatus {
+            addr: self.addr,
+            connected,
+            reconnects: self.reconnects,
+            rtt_ms: connected.then(|| start.elapsed().as_secs_f64() * 1000.0),
+        }
+    }
+
+    /// Sends `msg` and waits for its reply, retrying up to [`ClientConfig::retries`] times if the request times out
+    /// or the connection is lost
+    ///
+    /// Only read-only queries go through here, so resending one the db may already have seen is always safe. Each
+    /// retry goes out on a fresh connection, so a late reply to an abandoned attempt can't be mistaken for the reply
+    /// to a later request.
+    pub async fn request<M: Request + IntoLenPacket + Clone>(
+        &mut self,
+        msg: M,
+    ) -> anyhow::Result<M::Reply<Slice<Vec<u8>>>> {
+        self.keepalive().await?;
+        let mut attempts = 0;
+        loop {
+            match self.request_once(msg.clone()).await {
+                Err(err)
+                    if attempts < self.config.retries
+                        && (is_disconnect(&err) || is_timeout(&err)) =>
+                {
+                    attempts += 1;
+                    self.reconnect().await?;
+                }
+                res => return res,
+            }
+        }
     }
 
-    pub async fn request<M: Request + IntoLenPacket>(
+    async fn request_once<M: Request + IntoLenPacket>(
         &mut self,
         msg: M,
     ) -> anyhow::Result<M::Reply<Slice<Vec<u8>>>> {
+        let request_timeout = self.config.request_timeout;
         let resp = async {
             let resp = self.client.request(msg).await?;
             Ok(resp)
         };
         let timeout = async {
-            stellarator::sleep(Duration::from_secs(3)).await;
-            Err(anyhow!("request timed out"))
+            stellarator::sleep(request_timeout).await;
+            Err(RequestTimeout(request_timeout).into())
         };
-        futures_lite::future::race(timeout, resp).await
+        let resp = futures_lite::future::race(timeout, resp).await;
+        if resp.is_ok() {
+            self.last_reply = std::time::Instant::now();
+        }
+        resp
+    }
+
+    /// Sends `msg` once and waits for its reply however long it takes
+    ///
+    /// For requests that change the db and may run for a while, like compaction or snapshots, where a timeout would
+    /// give up on work the db is still doing and a retry would start it over.
+    async fn request_unbounded<M: Request + IntoLenPacket>(
+        &mut self,
+        msg: M,
+    ) -> anyhow::Result<M::Reply<Slice<Vec<u8>>>> {
+        self.keepalive().await?;
+        let resp = self.client.request(msg).await?;
+        self.last_reply = std::time::Instant::now();
+        Ok(resp)
+    }
+
+    /// Probes the connection if it has been idle for longer than [`ClientConfig::keepalive`], reconnecting if the
+    /// probe fails
+    async fn keepalive(&mut self) -> anyhow::Result<()> {
+        let Some(keepalive) = self.config.keepalive else {
+            return Ok(());
+        };
+        if self.last_reply.elapsed() < keepalive {
+            return Ok(());
+        }
+        if self.request_once(&GetServerTime).await.is_err() {
+            self.reconnect().await?;
+        }
+        Ok(())
+    }
+
+    fn allocate_packet_id(&mut self) -> anyhow::Result<PacketId> {
+        self.packet_ids
+            .allocate()
+            .ok_or_else(|| anyhow!("every packet id is in use on this connection"))
     }
 
     pub async fn get_time_series(
@@ -86,93 +291,325 @@ impl Client {
     ) -> anyhow::Result<()> {
         let start = start.unwrap_or(i64::MIN);
         let stop = stop.unwrap_or(i64::MAX);
-        let id = fastrand::u16(..);
 
         let component_id: ComponentId = lua.from_value(component_id)?;
-        let schema = self.client.request(&GetSchema { component_id }).await?;
+        let schema = self.request(&GetSchema { component_id }).await?;
+        let metadata = self
+            .request(&GetComponentMetadata {
+                component_id,
+                at: None,
+            })
+            .await?;
         let start = Timestamp(start);
         let stop = Timestamp(stop);
+        let id = self.allocate_packet_id()?;
         let msg = GetTimeSeries {
-            id: id.to_le_bytes(),
+            id,
             range: start..stop,
             component_id,
             limit: Some(256),
         };
 
-        let time_series = self.request(&msg).await?;
-
-        fn print_time_series_as_table<
-            T: Immutable + TryFromBytes + Copy + std::fmt::Display + Default + 'static,
-        >(
-            time_series: &OwnedTimeSeries<Slice<Vec<u8>>>,
-            schema: Schema<Vec<u64>>,
-        ) -> Result<(), anyhow::Error> {
-            let len = schema.shape().iter().product();
-            let data = time_series
-                .data()
-                .map_err(|err| anyhow!("{err:?} failed to get data"))?;
-            let buf = <[T]>::try_ref_from_bytes(data).map_err(|_| anyhow!("failed to get data"))?;
-            let mut builder = tabled::builder::Builder::default();
-            builder.push_record(["TIME".to_string(), "DATA".to_string()]);
-            for (chunk, timestamp) in buf
-                .chunks(len)
-                .zip(time_series.timestamps().unwrap().iter())
-            {
-                let view = nox::ArrayView::from_buf_shape_unchecked(chunk, schema.shape());
-                let epoch = hifitime::Epoch::from(*timestamp);
-                builder.push_record([epoch.to_string(), view.to_string()])
+        let time_series = self.request(&msg).await;
+        self.packet_ids.release(id);
+        let time_series = time_series?;
+        let timestamps = time_series
+            .timestamps()
+            .map_err(|err| anyhow!("{err:?} failed to get timestamps"))?;
+        let data = time_series
+            .data()
+            .map_err(|err| anyhow!("{err:?} failed to get data"))?;
+        let names = ValueNames::from_metadata(&metadata);
+        print_time_series_table(timestamps, data, &schema.0, names.as_ref())
+    }
+
+    pub async fn get_latest(&mut self, lua: &Lua, component_id: Value) -> anyhow::Result<Value> {
+        let component_id: ComponentId = lua.from_value(component_id)?;
+        let schema = self.request(&GetSchema { component_id }).await?;
+        let id = self.allocate_packet_id()?;
+        let msg = GetLatest { id, component_id };
+        let time_series = self.request(&msg).await;
+        self.packet_ids.release(id);
+        let time_series = time_series?;
+        let data = time_series
+            .data()
+            .map_err(|err| anyhow!("{err:?} failed to get data"))?;
+        if data.is_empty() {
+            return Ok(Value::Nil);
+        }
+        let view =
+            ComponentView::try_from_bytes_shape(data, schema.0.shape(), schema.0.prim_type())?;
+        Ok(view.into_lua(lua)?)
+    }
+
+    pub async fn get_time_series_all(
+        &mut self,
+        component: String,
+        start: Option<i64>,
+        stop: Option<i64>,
+    ) -> anyhow::Result<()> {
+        let start = Timestamp(start.unwrap_or(i64::MIN));
+        let stop = Timestamp(stop.unwrap_or(i64::MAX));
+        let msg = GetTimeSeriesAll {
+            component,
+            range: start..stop,
+            limit: Some(256),
+        };
+        let TimeSeriesAllResp { series } = self.request(&msg).await?;
+        for series in series {
+            println!("{}", Color::Blue.bold().paint(&series.entity));
+            print_time_series_table(&series.timestamps, &series.data, &series.schema, None)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a component's full history between `start` and `stop` to `path`, returning the number of samples
+    ///
+    /// The history is fetched in pages of [`EXPORT_PAGE_LEN`] samples, so it isn't bound by the limit that
+    /// [`Client::get_time_series`] prints.
+    pub async fn export_time_series(
+        &mut self,
+        component_id: ComponentId,
+        range: Range<Timestamp>,
+        path: &Path,
+        format: ExportFormat,
+    ) -> anyhow::Result<usize> {
+        let schema = self.request(&GetSchema { component_id }).await?.0;
+        let mut batches = vec![];
+        let mut start = Some(range.start);
+        let interrupt = Interruptible::begin();
+        let mut progress = Progress::new("exported");
+        while let Some(page_start) = start {
+            interrupt.check()?;
+            let page = self
+                .time_series_page(component_id, page_start..range.end)
+                .await?;
+            progress.add(page.timestamps.len(), page.data.len());
+            if !page.timestamps.is_empty() {
+                batches.push(time_series_batch(&page.timestamps, &page.data, &schema)?);
             }
-            println!(
-                "{}",
-                builder
-                    .build()
-                    .with(tabled::settings::Style::rounded())
-                    .with(tabled::settings::style::BorderColor::filled(
-                        tabled::settings::Color::FG_BLUE
-                    ))
-            );
-            Ok(())
+            start = page.next;
         }
+        if batches.is_empty() {
+            batches.push(time_series_batch(&[], &[], &schema)?);
+        }
+        write_batches(path, format, &batches)?;
+        Ok(batches.iter().map(RecordBatch::num_rows).sum())
+    }
+
+    /// Fetches up to [`EXPORT_PAGE_LEN`] samples from the start of `range`
+    async fn time_series_page(
+        &mut self,
+        component_id: ComponentId,
+        range: Range<Timestamp>,
+    ) -> anyhow::Result<TimeSeriesPage> {
+        let id = self.allocate_packet_id()?;
+        let msg = GetTimeSeriesPage {
+            id,
+            range: range.clone(),
+            component_id,
+            limit: EXPORT_PAGE_LEN,
+        };
+        let time_series = self.request(&msg).await;
+        self.packet_ids.release(id);
+        let time_series = time_series?;
+        let timestamps = time_series
+            .timestamps()
+            .map_err(|err| anyhow!("{err:?} failed to get timestamps"))?;
+        let data = time_series
+            .data()
+            .map_err(|err| anyhow!("{err:?} failed to get data"))?;
+        let next = match timestamps.last() {
+            Some(last) if timestamps.len() == EXPORT_PAGE_LEN && last.0 < i64::MAX => {
+                Some(Timestamp(last.0 + 1))
+            }
+            _ => None,
+        };
+        Ok(TimeSeriesPage {
+            timestamps: timestamps.to_vec(),
+            data: data.to_vec(),
+            next,
+        })
+    }
 
-        let schema = schema.0;
-        match schema.prim_type() {
-            PrimType::U8 => print_time_series_as_table::<u8>(&time_series, schema),
-            PrimType::U16 => print_time_series_as_table::<u16>(&time_series, schema),
-            PrimType::U32 => print_time_series_as_table::<u32>(&time_series, schema),
-            PrimType::U64 => print_time_series_as_table::<u64>(&time_series, schema),
-            PrimType::I8 => print_time_series_as_table::<i8>(&time_series, schema),
-            PrimType::I16 => print_time_series_as_table::<i16>(&time_series, schema),
-            PrimType::I32 => print_time_series_as_table::<i32>(&time_series, schema),
-            PrimType::I64 => print_time_series_as_table::<i64>(&time_series, schema),
-            PrimType::Bool => print_time_series_as_table::<bool>(&time_series, schema),
-            PrimType::F32 => print_time_series_as_table::<f32>(&time_series, schema),
-            PrimType::F64 => print_time_series_as_table::<f64>(&time_series, schema),
+    /// Renders element `index` of a component's history between `range` as a braille line plot
+    ///
+    /// `size` is the plot's width and height in characters, not counting the axis labels.
+    pub async fn plot(
+        &mut self,
+        component_id: ComponentId,
+        range: Range<Timestamp>,
+        index: usize,
+        size: (usize, usize),
+    ) -> anyhow::Result<String> {
+        let schema = self.request(&GetSchema { component_id }).await?.0;
+        let decoder = TypedDecoder::new(schema)?;
+        let elements = decoder.schema().shape().iter().product::<usize>();
+        if index >= elements {
+            return Err(anyhow!(
+                "index {index} is out of bounds for a component with {elements} elements"
+            ));
         }
+        let mut samples = vec![];
+        let mut start = Some(range.start);
+        let interrupt = Interruptible::begin();
+        let mut progress = Progress::new("fetched");
+        while let Some(page_start) = start {
+            interrupt.check()?;
+            let page = self
+                .time_series_page(component_id, page_start..range.end)
+                .await?;
+            progress.add(page.timestamps.len(), page.data.len());
+            for (timestamp, buf) in page.timestamps.iter().zip(page.data.chunks(decoder.size())) {
+                let view = decoder.decode(buf)?;
+                if let Some(value) = view.get(index) {
+                    samples.push((*timestamp, value.as_f64()));
+                }
+            }
+            start = page.next;
+        }
+        let (Some((first, _)), Some((last, _))) = (samples.first(), samples.last()) else {
+            return Err(anyhow!("no samples in range"));
+        };
+        let (first, last) = (*first, *last);
+        let points = samples
+            .iter()
+            .map(|(timestamp, value)| ((timestamp.0 - first.0) as f64 / 1e6, *value))
+            .collect::<Vec<_>>();
+        let labels = [
+            hifitime::Epoch::from(first).to_string(),
+            hifitime::Epoch::from(last).to_string(),
+        ];
+        Ok(plot::render(&points, size.0, size.1, &labels))
     }
 
     pub async fn sql(&mut self, sql: &str) -> anyhow::Result<()> {
-        let stream = self.client.stream(&SQLQuery(sql.to_string())).await?;
+        let batches = self.sql_batches(sql).await?;
+        let mut table = create_table(&batches, &FormatOptions::default())?;
+        println!(
+            "{}",
+            table.with(tabled::settings::Style::rounded()).with(
+                tabled::settings::style::BorderColor::filled(tabled::settings::Color::FG_BLUE)
+            )
+        );
+        Ok(())
+    }
+
+    async fn sql_meta_command(&mut self, cmd: SqlMetaCommand) -> anyhow::Result<()> {
+        let DumpMetadataResp {
+            component_metadata, ..
+        } = self.request(&DumpMetadata).await?;
+        let DumpSchemaResp { schemas } = self.request(&DumpSchema).await?;
+        let mut builder = tabled::builder::Builder::default();
+        match cmd {
+            SqlMetaCommand::ListTables => {
+                builder.push_record(["table", "component id", "type", "shape"]);
+                for metadata in &component_metadata {
+                    let Some(schema) = schemas.get(&metadata.component_id) else {
+                        continue;
+                    };
+                    builder.push_record([
+                        sql_table_name(&metadata.name),
+                        metadata.component_id.to_string(),
+                        schema.prim_type().to_string(),
+                        format!("{:?}", schema.dim()),
+                    ]);
+                }
+            }
+            SqlMetaCommand::Describe(name) => {
+                let metadata = component_metadata
+                    .iter()
+                    .find(|m| {
+                        m.name == name
+                            || sql_table_name(&m.name) == name
+                            || m.component_id == ComponentId::new(&name)
+                    })
+                    .ok_or_else(|| anyhow!("no component named {name}"))?;
+                let schema = schemas
+                    .get(&metadata.component_id)
+                    .ok_or_else(|| anyhow!("{name} has no schema yet"))?;
+                let mut rows = vec![
+                    ("name".to_string(), metadata.name.clone()),
+                    ("table".to_string(), sql_table_name(&metadata.name)),
+                    (
+                        "component id".to_string(),
+                        metadata.component_id.to_string(),
+                    ),
+                    ("type".to_string(), schema.prim_type().to_string()),
+                    ("shape".to_string(), format!("{:?}", schema.dim())),
+                ];
+                let mut entries: Vec<_> = metadata.metadata.clone().into_iter().collect();
+                entries.sort();
+                rows.extend(entries);
+                for (key, value) in rows {
+                    builder.push_record([key, value]);
+                }
+            }
+        }
+        println!(
+            "{}",
+            builder
+                .build()
+                .with(tabled::settings::Style::rounded())
+                .with(tabled::settings::style::BorderColor::filled(
+                    tabled::settings::Color::FG_BLUE
+                ))
+        );
+        Ok(())
+    }
+
+    /// Runs `sql` and writes the results to `path`, returning the number of rows written
+    pub async fn sql_export(
+        &mut self,
+        sql: &str,
+        path: &Path,
+        format: ExportFormat,
+    ) -> anyhow::Result<usize> {
+        let batches = self.sql_batches(sql).await?;
+        write_batches(path, format, &batches)?;
+        Ok(batches.iter().map(RecordBatch::num_rows).sum())
+    }
+
+    async fn sql_batches(&mut self, sql: &str) -> anyhow::Result<Vec<RecordBatch>> {
+        let query_id = fastrand::u64(..);
+        let stream = self
+            .client
+            .stream(&SQLQueryStream {
+                id: query_id,
+                query: sql.to_string(),
+            })
+            .await?;
         let mut batches = vec![];
         futures_lite::pin!(stream);
+        let interrupt = Interruptible::begin();
+        let mut progress = Progress::new("fetched");
+        let mut cancelled = false;
         loop {
-            let msg = stream.next().await?;
+            let msg = if cancelled {
+                stream.next().await?
+            } else {
+                // the query is cancelled right away, rather than once the db sends its next batch
+                let Some(msg) = interrupt.or_interrupted(stream.next()).await else {
+                    cancelled = true;
+                    stream.send(&CancelQuery { query_id }).await.0?;
+                    progress.finish();
+                    println!("{}", Color::Yellow.paint("cancelling query"));
+                    continue;
+                };
+                msg?
+            };
             let Some(batch) = msg.batch else {
                 break;
             };
             let mut decoder = arrow::ipc::reader::StreamDecoder::new();
             let mut buffer = arrow::buffer::Buffer::from(batch.into_owned());
+            let bytes = buffer.len();
             if let Some(batch) = decoder.decode(&mut buffer)? {
+                progress.add(batch.num_rows(), bytes);
                 batches.push(batch);
             }
         }
-        let mut table = create_table(&batches, &FormatOptions::default())?;
-        println!(
-            "{}",
-            table.with(tabled::settings::Style::rounded()).with(
-                tabled::settings::style::BorderColor::filled(tabled::settings::Color::FG_BLUE)
-            )
-        );
-        Ok(())
+        Ok(batches)
     }
 
     pub async fn send(
@@ -182,134 +619,433 @@ impl Client {
         prim_type: PrimType,
         shape: Vec<u64>,
         buf: Value,
+        checked: bool,
     ) -> anyhow::Result<()> {
-        use vtable::builder::*;
         let component_id = ComponentId(component_id);
-        let size = shape.iter().product::<u64>() as usize * prim_type.size();
-        let vtable = vtable([raw_field(
-            0,
-            size as u16,
-            schema(prim_type, &shape, component(component_id)),
-        )]);
-        let id: [u8; 2] = fastrand::u16(..).to_le_bytes();
-        let msg = VTableMsg { id, vtable };
-        self.client.send(&msg).await.0?;
-        let mut table = LenPacket::table(id, 8);
-        match prim_type {
-            PrimType::U8 => {
-                let buf: Vec<u8> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-            PrimType::U16 => {
-                let buf: Vec<u16> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-            PrimType::U32 => {
-                let buf: Vec<u32> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-            PrimType::U64 => {
-                let buf: Vec<u64> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-            PrimType::I8 => {
-                let buf: Vec<i8> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-            PrimType::I16 => {
-                let buf: Vec<i16> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-            PrimType::I32 => {
-                let buf: Vec<i32> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-            PrimType::I64 => {
-                let buf: Vec<i64> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-            PrimType::Bool => {
-                let buf: Vec<bool> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-            PrimType::F32 => {
-                let buf: Vec<f32> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-            PrimType::F64 => {
-                let buf: Vec<f64> = lua.from_value(buf)?;
-                let buf = buf.as_bytes();
-                table.extend_from_slice(buf);
-            }
-        }
-        self.client.send(table).await.0?;
+        let data = lua_component_bytes(lua, buf, component_id, prim_type, &shape, checked)?;
+        self.send_batch(vec![BatchColumn {
+            component_id,
+            prim_type,
+            shape,
+            data,
+        }])
+        .await
+    }
+
+    /// Sends several components' values in a single table packet, described by one vtable with a field per column
+    ///
+    /// The vtable is registered the first time a set of columns is sent, and reused for later batches with the
+    /// same columns.
+    pub async fn send_batch(&mut self, columns: Vec<BatchColumn>) -> anyhow::Result<()> {
+        if columns.is_empty() {
+            return Err(anyhow!("a batch needs at least one column"));
+        }
+        let mut fields = vec![];
+        let mut body = vec![];
+        for column in &columns {
+            let len = column.shape.iter().product::<u64>() as usize * column.prim_type.size();
+            if column.data.len() != len {
+                return Err(anyhow!(
+                    "component {} expects {} bytes of {} for shape {:?}, got {}",
+                    column.component_id,
+                    len,
+                    column.prim_type,
+                    column.shape,
+                    column.data.len()
+                ));
+            }
+            body.resize(body.len() + column.prim_type.padding(body.len()), 0);
+            fields.push(vtable::builder::raw_field(
+                body.len() as u16,
+                len as u16,
+                vtable::builder::schema(
+                    column.prim_type,
+                    &column.shape,
+                    vtable::builder::component(column.component_id),
+                ),
+            ));
+            body.extend_from_slice(&column.data);
+        }
+        if body.len() > u16::MAX as usize {
+            return Err(anyhow!("a batch can hold at most {} bytes", u16::MAX));
+        }
+        let key = columns
+            .into_iter()
+            .map(|column| (column.component_id, column.prim_type, column.shape))
+            .collect::<Vec<_>>();
+        let id = match self.send_vtable_ids.get(&key) {
+            Some(id) => *id,
+            None => {
+                let id = self.allocate_packet_id()?;
+                self.send_vtable_ids.insert(key, id);
+                id
+            }
+        };
+        let msg = VTableMsg {
+            id,
+            vtable: vtable::builder::vtable(fields),
+        };
+        self.send_packet(&msg).await?;
+        let mut table = LenPacket::table(id, body.len());
+        table.extend_from_slice(&body);
+        self.send_packet(table).await?;
+        Ok(())
+    }
+
+    /// Sets `component_id` to `values`, one per element, looking up its type and shape from the db
+    ///
+    /// Each value may carry a unit, and is converted to the component's `unit` and `scale` metadata before being
+    /// stored, so `(90.0, Some("deg"))` works for a component stored in radians or in fixed-point degrees.
+    pub async fn set(
+        &mut self,
+        component_id: ComponentId,
+        values: &[(f64, Option<&str>)],
+    ) -> anyhow::Result<()> {
+        let SchemaMsg(schema) = self.request(&GetSchema { component_id }).await?;
+        let metadata = self
+            .request(&GetComponentMetadata {
+                component_id,
+                at: None,
+            })
+            .await?;
+        let prim_type = schema.prim_type();
+        if prim_type.is_var_len() {
+            return Err(anyhow!(
+                "{} holds {prim_type} values, not numbers",
+                metadata.name
+            ));
+        }
+        let len = schema.shape().iter().product::<u64>() as usize;
+        if values.len() != len {
+            return Err(anyhow!(
+                "{} has {len} elements, got {} values",
+                metadata.name,
+                values.len()
+            ));
+        }
+        let mut data = vec![];
+        for &(value, unit) in values {
+            let stored = units::to_stored(value, unit, &metadata)?;
+            let stored = match prim_type {
+                PrimType::F16
+                | PrimType::BF16
+                | PrimType::F32
+                | PrimType::F64
+                | PrimType::Q15_16 => stored,
+                _ => stored.round(),
+            };
+            let cast = prim_type
+                .checked_cast(ElementValue::F64(stored))
+                .ok_or_else(|| anyhow!("{stored} is out of range for {prim_type}"))?;
+            data.extend_from_slice(cast.as_bytes());
+        }
+        self.send_batch(vec![BatchColumn {
+            component_id,
+            prim_type,
+            shape: schema.shape().to_vec(),
+            data,
+        }])
+        .await
+    }
+
+    /// Sends `packet`, reconnecting and resending once if the connection was lost
+    async fn send_packet(&mut self, packet: impl IntoLenPacket + Clone) -> anyhow::Result<()> {
+        match self.client.send(packet.clone()).await.0 {
+            Err(impeller2_stellar::Error::Stellar(_)) => {
+                self.reconnect().await?;
+                self.client.send(packet).await.0?;
+            }
+            res => res?,
+        }
         Ok(())
     }
 
-    pub async fn stream(&mut self, mut stream: Stream) -> anyhow::Result<()> {
-        if stream.id == 0 {
-            stream.id = fastrand::u64(..);
+    /// Every difference in components, schemas and metadata between this db and `other`, see [`diff::diff`]
+    pub async fn diff(&mut self, other: &mut Client) -> anyhow::Result<Vec<ComponentDiff>> {
+        let a_metadata = self.request(&DumpMetadata).await?;
+        let a_schemas = self.request(&DumpSchema).await?;
+        let b_metadata = other.request(&DumpMetadata).await?;
+        let b_schemas = other.request(&DumpSchema).await?;
+        Ok(diff::diff(
+            &DbComponents {
+                metadata: &a_metadata.component_metadata,
+                schemas: &a_schemas.schemas,
+            },
+            &DbComponents {
+                metadata: &b_metadata.component_metadata,
+                schemas: &b_schemas.schemas,
+            },
+        ))
+    }
+
+    /// The metadata of the components and msgs matching `query`, see [`QueryMetadata`]
+    pub async fn find(&mut self, query: QueryMetadata) -> anyhow::Result<QueryMetadataResp> {
+        Ok(self.request(&query).await?)
+    }
+
+    /// The ids of every component whose name matches the glob `pattern`, like `imu.*`
+    pub async fn match_components(&mut self, pattern: &str) -> anyhow::Result<Vec<ComponentId>> {
+        let QueryMetadataResp {
+            component_metadata, ..
+        } = self
+            .find(QueryMetadata {
+                name_glob: Some(pattern.to_string()),
+                kind: Some(MetadataKind::Component),
+                ..Default::default()
+            })
+            .await?;
+        let component_ids = component_metadata
+            .iter()
+            .map(|metadata| metadata.component_id)
+            .collect::<Vec<_>>();
+        if component_ids.is_empty() {
+            return Err(anyhow!("no components match {pattern}"));
         }
-        let stream = self.client.stream(&stream).await?;
-        let cancel = Arc::new(AtomicBool::new(true));
-        let canceler = cancel.clone();
-        let mut vtable: HashMap<PacketId, VTable> = HashMap::new();
-        std::thread::spawn(move || {
-            let mut stdin = io::stdin().lock();
-            let mut buf = [0u8];
-            let _ = stdin.read(&mut buf);
-            canceler.store(false, atomic::Ordering::SeqCst);
-        });
+        Ok(component_ids)
+    }
 
-        futures_lite::pin!(stream);
-        while cancel.load(atomic::Ordering::SeqCst) {
-            let msg = stream.next().await?;
-            match msg {
+    /// Reads a stream passed from Lua, resolving `filter = { component = "imu.*" }` with [`Client::match_components`]
+    async fn lua_stream_request(
+        &mut self,
+        lua: &Lua,
+        stream: Value,
+    ) -> anyhow::Result<StreamRequest> {
+        let pattern = match &stream {
+            Value::Table(table) => table
+                .get::<Option<mlua::Table>>("filter")?
+                .map(|filter| filter.get::<String>("component"))
+                .transpose()?,
+            _ => None,
+        };
+        let stream: Stream = lua.from_value(stream)?;
+        let component_ids = match pattern {
+            Some(pattern) => Some(self.match_components(&pattern).await?),
+            None => None,
+        };
+        Ok(StreamRequest {
+            stream,
+            component_ids,
+        })
+    }
+
+    /// Streams `stream` to stdout, printing only every `decimate`th value of each component
+    pub async fn stream(
+        &mut self,
+        stream: impl Into<StreamRequest>,
+        decimate: usize,
+    ) -> anyhow::Result<()> {
+        let stop = StopSignal::new();
+        let metadata = self.request(&DumpMetadata).await?;
+        let mut sink = DebugSink::new(decimate).with_value_names(&metadata.component_metadata);
+        let mut sub = self.subscribe(stream).await?;
+        while let Some(msg) = stop.or_stopped(sub.recv()).await {
+            match msg? {
                 StreamReply::Table(table) => {
-                    if let Some(vtable) = vtable.get(&table.id) {
-                        vtable.apply(&table.buf[..], &mut DebugSink)??;
+                    if sub.fetch_vtable(table.id).await? {
+                        sub.vtables[&table.id].apply(&table.buf[..], &mut sink)??;
                     } else {
                         println!("table ({:?}) = {:?}", table.id, &table.buf[..]);
                     }
                 }
                 StreamReply::VTable(msg) => {
-                    vtable.insert(msg.id, msg.vtable);
+                    sub.vtables.insert(msg.id, msg.vtable);
                 }
             }
         }
         Ok(())
     }
 
+    /// Subscribes to `stream`, yielding each decoded component value as it arrives
+    ///
+    /// If the connection drops, the client reconnects and resubscribes with the same stream id.
+    pub async fn subscribe(
+        &mut self,
+        stream: impl Into<StreamRequest>,
+    ) -> anyhow::Result<Subscription<'_>> {
+        let mut stream = stream.into();
+        if stream.stream.id == 0 {
+            stream.stream.id = self.stream_ids.allocate();
+        }
+        let req_id = self.client.next_request_id();
+        self.send_packet(stream.packet(req_id)).await?;
+        Ok(Subscription {
+            client: self,
+            stream,
+            req_id,
+            vtables: HashMap::new(),
+            fetching: HashSet::new(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Streams `stream`, calling `callback(component_id, timestamp, value)` for every decoded value
+    ///
+    /// Each call crosses into Lua, which is far slower than the native sinks. For high rate components, use a
+    /// fixed rate stream with a lower frequency rather than processing every sample. Returning `false` from the
+    /// callback ends the stream.
+    pub async fn stream_with(
+        &mut self,
+        lua: &Lua,
+        stream: impl Into<StreamRequest>,
+        callback: Function,
+    ) -> anyhow::Result<()> {
+        let stop = StopSignal::new();
+
+        let mut sub = self.subscribe(stream).await?;
+        // the callback is called after decoding so it can await other client methods
+        while let Some(value) = sub.next_or_stopped(&stop).await? {
+            let component_id = lua.to_value(&value.component_id)?;
+            let timestamp = value.timestamp.map(|timestamp| timestamp.0);
+            let view = value.view()?.into_lua(lua)?;
+            let keep_going = callback
+                .call_async::<Option<bool>>((component_id, timestamp, view))
+                .await?;
+            if keep_going == Some(false) {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Redraws a table of `component_id`'s latest value every `interval` until enter is pressed
+    ///
+    /// Alongside the value, each element's min and max since the watch started and the sample rate are shown.
+    pub async fn watch(
+        &mut self,
+        component_id: ComponentId,
+        interval: Duration,
+    ) -> anyhow::Result<()> {
+        let metadata = self
+            .request(&GetComponentMetadata {
+                component_id,
+                at: None,
+            })
+            .await?;
+        let stop = StopSignal::new();
+
+        let stream = Stream {
+            behavior: StreamBehavior::RealTime,
+            id: 0,
+        };
+        let mut sub = self.subscribe(stream).await?;
+        let mut stats = WatchStats::default();
+        let start = std::time::Instant::now();
+        let mut last_draw = None::<std::time::Instant>;
+        while let Some(value) = sub.next_or_stopped(&stop).await? {
+            if value.component_id != component_id {
+                continue;
+            }
+            stats.push(value.timestamp, value.view()?.iter().map(|e| e.as_f64()));
+            if last_draw.is_some_and(|last_draw| last_draw.elapsed() < interval) {
+                continue;
+            }
+            last_draw = Some(std::time::Instant::now());
+            // clear the screen and move the cursor home, like watch(1)
+            print!("\x1b[2J\x1b[H");
+            println!(
+                "{} ({component_id}) {} samples, {:.1} Hz",
+                Color::Blue.bold().paint(&metadata.name),
+                stats.samples,
+                stats.rate(start.elapsed()),
+            );
+            if let Some(timestamp) = stats.timestamp {
+                println!("{}", hifitime::Epoch::from(timestamp));
+            }
+            println!("{}", stats.table());
+            println!("{}", Color::DarkGray.paint("press enter to stop"));
+        }
+        Ok(())
+    }
+
+    /// Records the real-time stream to a packet capture at `path` until enter is pressed or `filter.duration` has
+    /// passed, returning the number of packets written
+    ///
+    /// The capture can be sent to another db with [`replay`].
+    pub async fn record(&mut self, path: &Path, filter: CaptureFilter) -> anyhow::Result<usize> {
+        let mut writer = CaptureWriter::create(path)?;
+        let stop = StopSignal::new();
+        let stream = Stream {
+            behavior: StreamBehavior::RealTime,
+            id: 0,
+        };
+        let mut sub = self.subscribe(stream).await?;
+        let start = std::time::Instant::now();
+        let deadline = filter.duration.map(|duration| start + duration);
+        // the vtables whose tables pass the filter
+        let mut recorded = HashSet::new();
+        while let Some(reply) = stop.or_stopped(sub.recv_until(deadline)).await {
+            let Some(reply) = reply? else {
+                break;
+            };
+            let packet = match reply {
+                StreamReply::VTable(msg) => {
+                    if !filter.matches(&msg.vtable) {
+                        recorded.remove(&msg.id);
+                        continue;
+                    }
+                    recorded.insert(msg.id);
+                    (&msg).into_len_packet()
+                }
+                StreamReply::Table(table) => {
+                    if !recorded.contains(&table.id) {
+                        continue;
+                    }
+                    table_packet(&table)
+                }
+            };
+            writer.write(start.elapsed(), &packet)?;
+        }
+        let packets = writer.packets();
+        writer.finish()?;
+        Ok(packets)
+    }
+
+    /// Forwards this db's real-time stream to `dest` until enter is pressed or `duration` has passed, returning the
+    /// number of packets forwarded
+    ///
+    /// Vtables keep their ids, so `dest` can decode the tables that follow them. Pass `component_ids` to forward
+    /// only some components.
+    pub async fn mirror(
+        &mut self,
+        dest: &mut Client,
+        component_ids: Option<Vec<ComponentId>>,
+        duration: Option<Duration>,
+    ) -> anyhow::Result<usize> {
+        let stop = StopSignal::new();
+        let stream = StreamRequest {
+            stream: Stream {
+                behavior: StreamBehavior::RealTime,
+                id: 0,
+            },
+            component_ids,
+        };
+        let mut sub = self.subscribe(stream).await?;
+        let deadline = duration.map(|duration| std::time::Instant::now() + duration);
+        let mut packets = 0;
+        while let Some(reply) = stop.or_stopped(sub.recv_until(deadline)).await {
+            match reply? {
+                Some(StreamReply::VTable(msg)) => dest.send_packet(&msg).await?,
+                Some(StreamReply::Table(table)) => dest.send_packet(table_packet(&table)).await?,
+                None => break,
+            }
+            packets += 1;
+        }
+        Ok(packets)
+    }
+
     pub async fn vtable_stream(&mut self, vtable: VTable) -> anyhow::Result<()> {
-        let id = fastrand::u16(..).to_le_bytes();
+        let id = self.allocate_packet_id()?;
         let vtable_msg = VTableMsg { vtable, id };
         self.client.send(&vtable_msg).await.0?;
         let stream = self.client.stream(&VTableStream { id }).await?;
-        let cancel = Arc::new(AtomicBool::new(true));
-        let canceler = cancel.clone();
-        std::thread::spawn(move || {
-            let mut stdin = io::stdin().lock();
-            let mut buf = [0u8];
-            let _ = stdin.read(&mut buf);
-            canceler.store(false, atomic::Ordering::SeqCst);
-        });
+        let stop = StopSignal::new();
 
+        let mut sink = DebugSink::new(1);
         futures_lite::pin!(stream);
-        while cancel.load(atomic::Ordering::SeqCst) {
-            let msg = stream.next().await?;
-            match msg {
+        while let Some(msg) = stop.or_stopped(stream.next()).await {
+            match msg? {
                 StreamReply::Table(table) => {
-                    vtable_msg.vtable.apply(&table.buf[..], &mut DebugSink)??;
+                    vtable_msg.vtable.apply(&table.buf[..], &mut sink)??;
                 }
                 StreamReply::VTable(_) => {}
             }
@@ -317,62 +1053,133 @@ impl Client {
         Ok(())
     }
 
-    pub async fn stream_msgs(&mut self, stream_msgs: MsgStream) -> anyhow::Result<()> {
+    /// Prints each msg streamed for `stream_msgs`, decoded with the schema from its [`MsgMetadata`]
+    pub async fn stream_msgs(
+        &mut self,
+        stream_msgs: MsgStream,
+        format: MsgFormat,
+    ) -> anyhow::Result<()> {
         let metadata = self
             .request(&GetMsgMetadata {
                 msg_id: stream_msgs.msg_id,
             })
             .await?;
 
-        let request_id = fastrand::u8(..);
-        self.client
-            .send(stream_msgs.with_request_id(request_id))
-            .await
-            .0?;
+        let request_id = self.client.send_req(stream_msgs).await?;
 
-        let cancel = Arc::new(AtomicBool::new(true));
-        let canceler = cancel.clone();
-        std::thread::spawn(move || {
-            let mut stdin = io::stdin().lock();
-            let mut buf = [0u8];
-            let _ = stdin.read(&mut buf);
-            canceler.store(false, atomic::Ordering::SeqCst);
-        });
+        let stop = StopSignal::new();
 
-        while cancel.load(atomic::Ordering::SeqCst) {
-            let packet = self
-                .client
-                .recv::<OwnedPacket<Slice<Vec<u8>>>>(request_id)
-                .await?;
-            if let OwnedPacket::Msg(msg) = packet {
+        while let Some(packet) = stop
+            .or_stopped(self.client.recv::<OwnedPacket<Slice<Vec<u8>>>>(request_id))
+            .await
+        {
+            if let OwnedPacket::Msg(msg) = packet? {
                 let data = postcard_dyn::from_slice_dyn(&metadata.schema, &msg.buf[..])
                     .map_err(|e| anyhow!("failed to deserialize msg: {:?}", e))?;
-                println!("{:?}", data);
+                match format {
+                    MsgFormat::Table => {
+                        println!("{}", Color::Blue.bold().paint(&metadata.name));
+                        println!("{}", msg_table(&metadata, &data));
+                    }
+                    MsgFormat::Json => println!("{}", serde_json::to_string(&data)?),
+                }
             }
         }
         Ok(())
     }
 
-    pub async fn get_msgs(
+    pub async fn get_events(
         &mut self,
-        msg_id: PacketId,
         start: Option<i64>,
         stop: Option<i64>,
+        min_severity: EventSeverity,
     ) -> anyhow::Result<()> {
         let start = Timestamp(start.unwrap_or(i64::MIN));
         let stop = Timestamp(stop.unwrap_or(i64::MAX));
-        let metadata = self.request(&GetMsgMetadata { msg_id }).await?;
-        let get_msgs = GetMsgs {
-            msg_id,
+        let get_events = GetEvents {
             range: start..stop,
+            min_severity,
             limit: Some(1000),
         };
-        let batch = self.request(&get_msgs).await?;
+        let EventBatch { events } = self.request(&get_events).await?;
+        let mut builder = tabled::builder::Builder::default();
+        builder.push_record(["TIME", "SEVERITY", "CODE", "MESSAGE"].map(String::from));
+        for event in events {
+            let color = match event.severity {
+                EventSeverity::Debug => Color::DarkGray,
+                EventSeverity::Info => Color::Blue,
+                EventSeverity::Warning => Color::Yellow,
+                EventSeverity::Error => Color::Red,
+                EventSeverity::Critical => Color::Magenta,
+            };
+            let epoch = hifitime::Epoch::from(event.timestamp);
+            builder.push_record([
+                epoch.to_string(),
+                color
+                    .bold()
+                    .paint(format!("{:?}", event.severity))
+                    .to_string(),
+                event.code.to_string(),
+                color.paint(event.message).to_string(),
+            ]);
+        }
+        println!(
+            "{}",
+            builder
+                .build()
+                .with(tabled::settings::Style::rounded())
+                .with(tabled::settings::style::BorderColor::filled(
+                    tabled::settings::Color::FG_BLUE
+                ))
+        );
+        Ok(())
+    }
+
+    pub async fn get_msgs_stream(
+        &mut self,
+        msg_id: PacketId,
+        range: Range<Timestamp>,
+    ) -> anyhow::Result<impl futures_lite::Stream<Item = anyhow::Result<(Timestamp, Vec<u8>)>> + '_>
+    {
+        let stream = self
+            .client
+            .stream(&GetMsgsStream {
+                msg_id,
+                range,
+                limit: None,
+            })
+            .await?;
+        Ok(futures_lite::stream::unfold(
+            Some(stream),
+            |stream| async move {
+                let mut stream = stream?;
+                match stream.next().await {
+                    Ok(MsgEntry {
+                        msg: Some((timestamp, msg)),
+                    }) => Some((Ok((timestamp, msg.into_owned())), Some(stream))),
+                    Ok(MsgEntry { msg: None }) => None,
+                    Err(err) => Some((Err(err.into()), None)),
+                }
+            },
+        ))
+    }
+
+    pub async fn get_msgs(
+        &mut self,
+        msg_id: PacketId,
+        start: Option<i64>,
+        stop: Option<i64>,
+    ) -> anyhow::Result<()> {
+        let start = Timestamp(start.unwrap_or(i64::MIN));
+        let stop = Timestamp(stop.unwrap_or(i64::MAX));
+        let metadata = self.request(&GetMsgMetadata { msg_id }).await?;
+        let stream = self.get_msgs_stream(msg_id, start..stop).await?;
+        futures_lite::pin!(stream);
         let mut builder = tabled::builder::Builder::default();
-        for (timestamp, msg) in batch.data {
+        while let Some(res) = futures_lite::StreamExt::next(&mut stream).await {
+            let (timestamp, msg) = res?;
             let data = postcard_dyn::from_slice_dyn(&metadata.schema, &msg[..])
                 .map_err(|e| anyhow!("failed to deserialize msg: {:?}", e))?;
-
             let epoch = hifitime::Epoch::from(timestamp);
             builder.push_record([epoch.to_string(), data.to_string()]);
         }
@@ -398,9 +1205,601 @@ impl Client {
             postcard_dyn::to_stdvec_dyn(&metadata.schema, &msg).map_err(|e| anyhow!("{e:?}"))?;
         let mut pkt = LenPacket::msg(msg_id, bytes.len());
         pkt.extend_from_slice(&bytes);
-        self.client.send(pkt).await.0?;
+        self.send_packet(pkt).await?;
+        Ok(())
+    }
+
+    /// The timestamp of the oldest data in the db
+    pub async fn earliest_timestamp(&mut self) -> anyhow::Result<Timestamp> {
+        let EarliestTimestamp(timestamp) = self.request(&GetEarliestTimestamp).await?;
+        Ok(timestamp)
+    }
+
+    /// The timestamp of the newest data in the db, or `Timestamp(i64::MIN)` if it's empty
+    ///
+    /// The db only sends this as a subscription, so it's read on a separate connection that's closed straight after,
+    /// keeping later updates from arriving on this one.
+    pub async fn last_updated(&mut self) -> anyhow::Result<Timestamp> {
+        let request_timeout = self.config.request_timeout;
+        let mut client = impeller2_stellar::Client::connect(self.addr).await?;
+        let resp = async {
+            let mut stream = client.stream(&SubscribeLastUpdated).await?;
+            let LastUpdated(timestamp) = stream.next().await?;
+            Ok(timestamp)
+        };
+        let timeout = async {
+            stellarator::sleep(request_timeout).await;
+            Err(RequestTimeout(request_timeout).into())
+        };
+        futures_lite::future::race(timeout, resp).await
+    }
+
+    /// Resolves a [`TimeRange`] like `last 5m` to absolute timestamps, asking the db for its earliest and latest
+    /// timestamps only if the range needs them
+    pub async fn resolve_time_range(
+        &mut self,
+        range: &TimeRange,
+    ) -> anyhow::Result<Range<Timestamp>> {
+        let earliest = match range.needs_earliest() {
+            true => self.earliest_timestamp().await?,
+            false => Timestamp(i64::MIN),
+        };
+        let latest = match range.needs_latest() {
+            true => self.last_updated().await?,
+            false => Timestamp(i64::MAX),
+        };
+        Ok(range.resolve(earliest, latest))
+    }
+
+    /// The range given to a Lua query: either a relative range string like `"last 5m"` in place of `start`, or
+    /// optional absolute `start` and `stop` timestamps
+    async fn lua_time_range(
+        &mut self,
+        lua: &Lua,
+        start: Value,
+        stop: Value,
+    ) -> anyhow::Result<(Option<i64>, Option<i64>)> {
+        if let Value::String(range) = &start {
+            if !stop.is_nil() {
+                return Err(anyhow!(
+                    "a relative range can't be combined with a stop time"
+                ));
+            }
+            let range = TimeRange::parse(&range.to_str()?)?;
+            let range = self.resolve_time_range(&range).await?;
+            return Ok((Some(range.start.0), Some(range.end.0)));
+        }
+        Ok((lua.from_value(start)?, lua.from_value(stop)?))
+    }
+
+    /// The server's current time
+    pub async fn server_time(&mut self) -> anyhow::Result<Timestamp> {
+        let ServerTime(timestamp) = self.request(&GetServerTime).await?;
+        Ok(timestamp)
+    }
+
+    /// Waits until everything sent on this connection has been synced to disk
+    pub async fn flush(&mut self) -> anyhow::Result<()> {
+        // syncing a large db can take longer than the usual request timeout
+        self.client.request(&Flush).await?;
+        Ok(())
+    }
+
+    /// Sets the state of several streams at once, see [`SetStreamStateBatch`]
+    pub async fn set_stream_state_batch(
+        &mut self,
+        states: Vec<SetStreamState>,
+    ) -> anyhow::Result<()> {
+        self.send_packet(&SetStreamStateBatch(states)).await?;
+        Ok(())
+    }
+
+    /// Uploads a `.glb` file to the db, returning once it has been sent
+    ///
+    /// The db has no dedicated asset store, so the file is registered as a msg named after the file and stored as a
+    /// single `Vec<u8>` entry in the msg log under `id`, where viewers can fetch it with [`GetMsgs`].
+    pub async fn set_glb_asset(
+        &mut self,
+        id: PacketId,
+        path: impl AsRef<Path>,
+        validate: bool,
+    ) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let glb = std::fs::read(path)?;
+        if validate {
+            validate_glb(&glb)?;
+        }
+        self.put_asset_bytes(id, asset_file_name(path), Glb::NAME, glb)
+            .await
+    }
+
+    /// Stores `asset` in the msg log under `id`, alongside `.glb` files uploaded with [`Client::set_glb_asset`]
+    pub async fn put_asset(
+        &mut self,
+        id: PacketId,
+        name: String,
+        asset: &AssetValue,
+    ) -> anyhow::Result<()> {
+        let bytes = asset.to_postcard()?;
+        self.put_asset_bytes(id, name, asset.kind(), bytes).await
+    }
+
+    /// Uploads the asset in `path`, either a `.glb` file or an [`AssetValue`] as JSON, under `id` or an id derived
+    /// from the file name, returning the id used
+    pub async fn put_asset_file(
+        &mut self,
+        id: Option<PacketId>,
+        path: &Path,
+    ) -> anyhow::Result<PacketId> {
+        let name = asset_file_name(path);
+        let id = id.unwrap_or_else(|| msg_id(&name));
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("glb") => self.set_glb_asset(id, path, true).await?,
+            Some("json") => {
+                let asset: AssetValue = serde_json::from_slice(&std::fs::read(path)?)?;
+                self.put_asset(id, name, &asset).await?;
+            }
+            _ => {
+                return Err(anyhow!("{} isn't a .glb or .json asset", path.display()));
+            }
+        }
+        Ok(id)
+    }
+
+    async fn put_asset_bytes(
+        &mut self,
+        id: PacketId,
+        name: String,
+        kind: &str,
+        asset: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let bytes = postcard::to_stdvec(&asset)?;
+        if bytes.len() > MAX_PACKET_LEN - PACKET_HEADER_LEN {
+            return Err(anyhow!(
+                "{name} is {} bytes, larger than the db accepts in a single packet",
+                asset.len()
+            ));
+        }
+        let metadata = MsgMetadata {
+            name,
+            schema: <Vec<u8> as postcard_schema::Schema>::SCHEMA.into(),
+            metadata: HashMap::from([("asset".to_string(), kind.to_string())]),
+        };
+        self.send_packet(&SetMsgMetadata { id, metadata }).await?;
+        let mut pkt = LenPacket::msg(id, bytes.len());
+        pkt.extend_from_slice(&bytes);
+        self.send_packet(pkt).await?;
+        Ok(())
+    }
+
+    /// Writes the newest version of the asset stored under `id` to `path`
+    ///
+    /// `.glb` files are written back byte for byte, and other assets as JSON that [`Client::put_asset_file`] reads.
+    pub async fn get_asset_to_file(&mut self, id: PacketId, path: &Path) -> anyhow::Result<()> {
+        let metadata = self.request(&GetMsgMetadata { msg_id: id }).await?;
+        let kind = metadata
+            .metadata
+            .get("asset")
+            .ok_or_else(|| anyhow!("{} isn't an asset", metadata.name))?
+            .clone();
+        let stream = self
+            .get_msgs_stream(id, Timestamp(i64::MIN)..Timestamp(i64::MAX))
+            .await?;
+        futures_lite::pin!(stream);
+        let mut newest = None;
+        while let Some(res) = futures_lite::StreamExt::next(&mut stream).await {
+            newest = Some(res?.1);
+        }
+        let newest = newest.ok_or_else(|| anyhow!("{} has no stored value", metadata.name))?;
+        let asset: Vec<u8> = postcard::from_bytes(&newest)?;
+        if kind == Glb::NAME {
+            std::fs::write(path, asset)?;
+        } else {
+            let asset = AssetValue::from_postcard(&kind, &asset)?;
+            std::fs::write(path, serde_json::to_string_pretty(&asset)?)?;
+        }
+        Ok(())
+    }
+
+    /// Names a component's integer values, so they print as names, see [`SetComponentEnum`]
+    pub async fn set_component_enum(
+        &mut self,
+        component_id: ComponentId,
+        values: BTreeMap<i64, String>,
+    ) -> anyhow::Result<()> {
+        self.client
+            .send(&SetComponentEnum {
+                component_id,
+                values,
+            })
+            .await
+            .0?;
+        Ok(())
+    }
+
+    /// Names the bits of a flags component, so they print by name, see [`SetComponentBits`]
+    pub async fn set_component_bits(
+        &mut self,
+        component_id: ComponentId,
+        bits: BTreeMap<u8, String>,
+    ) -> anyhow::Result<()> {
+        self.client
+            .send(&SetComponentBits { component_id, bits })
+            .await
+            .0?;
+        Ok(())
+    }
+
+    /// Includes or excludes a component from recording, see [`SetComponentRecording`]
+    pub async fn set_component_recording(
+        &mut self,
+        component_id: ComponentId,
+        recording: bool,
+    ) -> anyhow::Result<()> {
+        self.client
+            .send(&SetComponentRecording {
+                component_id,
+                recording,
+            })
+            .await
+            .0?;
+        Ok(())
+    }
+
+    /// Limits how much of a component's time series the db keeps, see [`SetRetention`]
+    pub async fn set_retention(
+        &mut self,
+        component_id: ComponentId,
+        policy: RetentionPolicy,
+    ) -> anyhow::Result<()> {
+        self.client
+            .send(&SetRetention {
+                component_id,
+                policy,
+            })
+            .await
+            .0?;
+        Ok(())
+    }
+
+    /// Enforces every retention policy now, returning what was dropped
+    pub async fn compact(&mut self) -> anyhow::Result<CompactionReport> {
+        let report = self.request_unbounded(&Compact).await?;
+        Ok(report)
+    }
+}
+
+/// Encodes the Lua array passed to [`Client::send`] as `prim_type` values, range checking each one if `checked` is set
+///
+/// Bytes and string components take a Lua string instead, written into a slot `shape` bytes long
+fn lua_component_bytes(
+    lua: &Lua,
+    buf: Value,
+    component_id: ComponentId,
+    prim_type: PrimType,
+    shape: &[u64],
+    checked: bool,
+) -> anyhow::Result<Vec<u8>> {
+    if prim_type.is_var_len() {
+        let Value::String(payload) = buf else {
+            return Err(anyhow!(
+                "component {component_id} expects a string, got {}",
+                describe_lua_value(&buf)
+            ));
+        };
+        let payload = payload.as_bytes();
+        if prim_type == PrimType::String && std::str::from_utf8(&payload).is_err() {
+            return Err(anyhow!("component {component_id} expects UTF-8 text"));
+        }
+        let mut slot = vec![0; shape.iter().product::<u64>() as usize];
+        write_var_len(&payload, &mut slot).map_err(|_| {
+            anyhow!(
+                "{} bytes don't fit component {component_id}'s {}-byte slot",
+                payload.len(),
+                slot.len()
+            )
+        })?;
+        return Ok(slot);
+    }
+    let mut data = vec![];
+    if checked {
+        let values: Vec<Value> = lua.unpack(buf)?;
+        let len = shape.iter().product::<u64>() as usize;
+        if values.len() != len {
+            return Err(anyhow!(
+                "component {component_id} has {len} elements, got {}",
+                values.len()
+            ));
+        }
+        for value in values {
+            let value = match value {
+                Value::Integer(v) => ElementValue::I64(v),
+                Value::Number(v) => ElementValue::F64(v),
+                Value::Boolean(v) => ElementValue::Bool(v),
+                other => return Err(anyhow!("expected a number, found {}", other.type_name())),
+            };
+            let cast = prim_type
+                .checked_cast(value)
+                .ok_or_else(|| anyhow!("{} is out of range for {prim_type}", value.as_f64()))?;
+            data.extend_from_slice(cast.as_bytes());
+        }
+        return Ok(data);
+    }
+    match prim_type {
+        PrimType::U8 => {
+            let buf: Vec<u8> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::U16 => {
+            let buf: Vec<u16> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::U32 => {
+            let buf: Vec<u32> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::U64 => {
+            let buf: Vec<u64> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::I8 => {
+            let buf: Vec<i8> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::I16 => {
+            let buf: Vec<i16> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::I32 => {
+            let buf: Vec<i32> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::I64 => {
+            let buf: Vec<i64> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::Bool => {
+            let buf: Vec<bool> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::F32 => {
+            let buf: Vec<f32> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::F64 => {
+            let buf: Vec<f64> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.as_bytes();
+            data.extend_from_slice(buf);
+        }
+        PrimType::F16 => {
+            let buf: Vec<f64> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.into_iter().map(f16::from_f64).collect::<Vec<_>>();
+            data.extend_from_slice(buf.as_bytes());
+        }
+        PrimType::BF16 => {
+            let buf: Vec<f64> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.into_iter().map(bf16::from_f64).collect::<Vec<_>>();
+            data.extend_from_slice(buf.as_bytes());
+        }
+        PrimType::Q15_16 => {
+            let buf: Vec<f64> = lua_array(lua, buf, component_id, prim_type)?;
+            let buf = buf.into_iter().map(Q15_16::from_f64).collect::<Vec<_>>();
+            data.extend_from_slice(buf.as_bytes());
+        }
+        PrimType::Bytes | PrimType::String => unreachable!("slots are encoded above"),
+    }
+    Ok(data)
+}
+
+/// Converts the Lua array passed to [`Client::send`], naming the component and expected type if it doesn't fit
+fn lua_array<T: serde::de::DeserializeOwned>(
+    lua: &Lua,
+    buf: Value,
+    component_id: ComponentId,
+    prim_type: PrimType,
+) -> anyhow::Result<Vec<T>> {
+    let found = describe_lua_value(&buf);
+    lua.from_value(buf).map_err(|err| {
+        anyhow!("component {component_id} expects an array of {prim_type}, got {found} ({err})")
+    })
+}
+
+fn describe_lua_value(value: &Value) -> String {
+    let Value::Table(table) = value else {
+        return value.type_name().to_string();
+    };
+    let mut types: Vec<&str> = vec![];
+    for value in table.clone().sequence_values::<Value>().flatten() {
+        if !types.contains(&value.type_name()) {
+            types.push(value.type_name());
+        }
+    }
+    if types.is_empty() {
+        "an empty table".to_string()
+    } else {
+        format!("a table of {}", types.join(" and "))
+    }
+}
+
+/// The size of the db's receive buffer, which bounds the largest packet it accepts
+const MAX_PACKET_LEN: usize = 8 * 1024 * 1024;
+
+/// Checks for a binary glTF 2.0 header whose declared length matches the file
+fn validate_glb(glb: &[u8]) -> anyhow::Result<()> {
+    let Some(header) = glb.get(..12) else {
+        return Err(anyhow!("glb is too short to contain a header"));
+    };
+    let word = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().expect("4 byte slice"));
+    if &header[..4] != b"glTF" {
+        return Err(anyhow!("glb is missing the glTF magic"));
+    }
+    if word(4) != 2 {
+        return Err(anyhow!("unsupported glb version {}, expected 2", word(4)));
+    }
+    if word(8) as usize != glb.len() {
+        return Err(anyhow!(
+            "glb header declares {} bytes but the file is {} bytes",
+            word(8),
+            glb.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Prints a table of samples, naming values or bits with `names` if the component has any
+fn print_time_series_table(
+    timestamps: &[Timestamp],
+    data: &[u8],
+    schema: &Schema<Vec<u64>>,
+    names: Option<&ValueNames>,
+) -> anyhow::Result<()> {
+    fn print_time_series_as_table<
+        T: Immutable + TryFromBytes + Copy + std::fmt::Display + Default + 'static,
+    >(
+        timestamps: &[Timestamp],
+        data: &[u8],
+        schema: &Schema<Vec<u64>>,
+    ) -> Result<(), anyhow::Error> {
+        let len = schema.shape().iter().product();
+        let buf = <[T]>::try_ref_from_bytes(data).map_err(|_| anyhow!("failed to get data"))?;
+        let rows = buf.chunks(len).map(|chunk| {
+            nox::ArrayView::from_buf_shape_unchecked(chunk, schema.shape()).to_string()
+        });
+        print_rows(timestamps, rows);
         Ok(())
     }
+
+    fn print_rows(timestamps: &[Timestamp], rows: impl Iterator<Item = String>) {
+        let mut builder = tabled::builder::Builder::default();
+        builder.push_record(["TIME".to_string(), "DATA".to_string()]);
+        for (row, timestamp) in rows.zip(timestamps.iter()) {
+            let epoch = hifitime::Epoch::from(*timestamp);
+            builder.push_record([epoch.to_string(), row])
+        }
+        println!(
+            "{}",
+            builder
+                .build()
+                .with(tabled::settings::Style::rounded())
+                .with(tabled::settings::style::BorderColor::filled(
+                    tabled::settings::Color::FG_BLUE
+                ))
+        );
+    }
+
+    let decoder = TypedDecoder::new(schema.clone())?;
+    if let Some(names) = names {
+        let size = decoder.size().max(1);
+        let rows = data
+            .chunks(size)
+            .map(|chunk| names.format(&decoder.decode(chunk).ok()?))
+            .collect::<Option<Vec<_>>>();
+        if let Some(rows) = rows {
+            print_rows(timestamps, rows.into_iter());
+            return Ok(());
+        }
+    }
+
+    match schema.prim_type() {
+        PrimType::U8 => print_time_series_as_table::<u8>(timestamps, data, schema),
+        PrimType::U16 => print_time_series_as_table::<u16>(timestamps, data, schema),
+        PrimType::U32 => print_time_series_as_table::<u32>(timestamps, data, schema),
+        PrimType::U64 => print_time_series_as_table::<u64>(timestamps, data, schema),
+        PrimType::I8 => print_time_series_as_table::<i8>(timestamps, data, schema),
+        PrimType::I16 => print_time_series_as_table::<i16>(timestamps, data, schema),
+        PrimType::I32 => print_time_series_as_table::<i32>(timestamps, data, schema),
+        PrimType::I64 => print_time_series_as_table::<i64>(timestamps, data, schema),
+        PrimType::Bool => print_time_series_as_table::<bool>(timestamps, data, schema),
+        PrimType::F32 => print_time_series_as_table::<f32>(timestamps, data, schema),
+        PrimType::F64 => print_time_series_as_table::<f64>(timestamps, data, schema),
+        PrimType::F16 => print_time_series_as_table::<f16>(timestamps, data, schema),
+        PrimType::BF16 => print_time_series_as_table::<bf16>(timestamps, data, schema),
+        PrimType::Q15_16 => print_time_series_as_table::<Q15_16>(timestamps, data, schema),
+        PrimType::Bytes | PrimType::String => {
+            let rows = data.chunks(decoder.size().max(1)).map(|slot| {
+                decoder
+                    .decode(slot)
+                    .map(|view| view.to_string())
+                    .unwrap_or_else(|err| format!("invalid slot: {err}"))
+            });
+            print_rows(timestamps, rows);
+            Ok(())
+        }
+    }
+}
+
+/// The names a component's metadata gives its values or bits, used to print them by name
+enum ValueNames {
+    Enum(BTreeMap<i64, String>),
+    Bits(BTreeMap<u8, String>),
+}
+
+impl ValueNames {
+    /// The component's `enum` names, or else its `bits` names
+    fn from_metadata(metadata: &ComponentMetadata) -> Option<Self> {
+        metadata
+            .enum_values()
+            .map(ValueNames::Enum)
+            .or_else(|| metadata.bit_names().map(ValueNames::Bits))
+    }
+
+    /// Formats `view` by name, or returns `None` if it isn't the kind of value the names are for
+    fn format(&self, view: &ComponentView<'_>) -> Option<String> {
+        match self {
+            ValueNames::Enum(values) => format_enum(view, values),
+            ValueNames::Bits(names) => format_bits(view, names),
+        }
+    }
+}
+
+/// Formats a flags value as its set bits joined by `|`, using the names from a component's `bits` metadata and
+/// `bit{n}` for bits without one
+///
+/// A value with no bits set formats as `0`. Returns `None` for values that aren't a scalar `u8`, `u16` or `u32`.
+fn format_bits(view: &ComponentView<'_>, names: &BTreeMap<u8, String>) -> Option<String> {
+    let bits = view.as_bits()?;
+    let set = bits
+        .iter_set()
+        .map(|bit| match names.get(&(bit as u8)) {
+            Some(name) => name.clone(),
+            None => format!("bit{bit}"),
+        })
+        .collect::<Vec<_>>();
+    if set.is_empty() {
+        return Some("0".to_string());
+    }
+    Some(set.join("|"))
+}
+
+/// Formats an integer value with the names from a component's `enum` metadata, falling back to the number for values
+/// without a name
+///
+/// Returns `None` for values that aren't integers.
+fn format_enum(view: &ComponentView<'_>, values: &BTreeMap<i64, String>) -> Option<String> {
+    let names = view
+        .iter()
+        .map(|elem| {
+            let value = elem.as_i128()?;
+            let name = i64::try_from(value)
+                .ok()
+                .and_then(|value| values.get(&value));
+            Some(name.cloned().unwrap_or_else(|| value.to_string()))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    match &names[..] {
+        [name] => Some(name.clone()),
+        names => Some(format!("[{}]", names.join(", "))),
+    }
 }
 
 fn create_table(
@@ -440,50 +1839,758 @@ fn create_table(
     Ok(builder.build())
 }
 
-impl UserData for Client {
-    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_async_method_mut(
-            "send_table",
-            |lua, mut this, (component_id, ty, shape, buf): (Value, _, Vec<u64>, _)| async move {
-                let component_id =
-                    if let Ok(id) = lua.from_value::<ComponentId>(component_id.clone()) {
-                        id
-                    } else if let Ok(name) = lua.from_value::<String>(component_id.clone()) {
-                        ComponentId::new(&name)
-                    } else if let Ok(id) = lua.from_value::<i64>(component_id) {
-                        ComponentId(id as u64)
-                    } else {
-                        return Err(anyhow!("component id must be a ComponentId or String").into());
-                    };
-                let ty: PrimType = lua.from_value(ty)?;
-                this.send(&lua, component_id.0, ty, shape, buf).await?;
-                Ok(())
-            },
-        );
-        methods.add_async_method_mut(
-            "send_msg",
-            |lua, mut this, (msg_or_id, val): (Value, Option<Value>)| async move {
-                if let Some(msg) = msg_or_id.as_userdata() {
-                    let msg = msg.call_method::<Vec<u8>>("msg", ())?;
-                    this.client
-                        .send(LenPacket { inner: msg })
-                        .await
-                        .0
-                        .map_err(anyhow::Error::from)?;
-                } else if let Some(msg) = val {
-                    let id = msg_or_id;
-                    let msg_id = if let Ok(id) = lua.from_value::<PacketId>(id.clone()) {
-                        id
-                    } else if let Ok(name) = lua.from_value::<String>(id) {
-                        msg_id(&name)
-                    } else {
-                        return Err(anyhow!("msg id must be a PacketId or String").into());
-                    };
-                    let msg = lua.from_value(msg)?;
-                    this.send_msg(msg_id, msg).await?;
-                } else {
-                    return Err(anyhow!(
-                        "send_msg requires either a native msg or a id and a table"
+/// The connection state returned by [`Client::status`]
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ClientStatus {
+    pub addr: SocketAddr,
+    pub connected: bool,
+    /// How many times the client has reconnected since it was created
+    pub reconnects: u32,
+    /// The round trip time of the status check, if it succeeded
+    pub rtt_ms: Option<f64>,
+}
+
+/// How a [`Client`] waits for and retries requests
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClientConfig {
+    /// How long to wait for the reply to a request before giving up on it
+    pub request_timeout: Duration,
+    /// How many times [`Client::request`] resends a request that timed out or lost its connection
+    pub retries: u32,
+    /// Check the connection with a [`GetServerTime`] round trip before a request if nothing has been received for
+    /// this long, so a link that dropped while idle is noticed before the request rather than after its timeout
+    pub keepalive: Option<Duration>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(3),
+            retries: 1,
+            keepalive: None,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Overrides the defaults with the `timeout` and `keepalive` (in seconds) and `retries` fields of a Lua table
+    fn from_lua_opts(opts: &mlua::Table) -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        if let Some(secs) = opts.get::<Option<f64>>("timeout")? {
+            config.request_timeout = Duration::try_from_secs_f64(secs)?;
+        }
+        if let Some(retries) = opts.get::<Option<u32>>("retries")? {
+            config.retries = retries;
+        }
+        if let Some(secs) = opts.get::<Option<f64>>("keepalive")? {
+            config.keepalive = Some(Duration::try_from_secs_f64(secs)?);
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug)]
+struct RequestTimeout(Duration);
+
+impl Display for RequestTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for RequestTimeout {}
+
+fn is_timeout(err: &anyhow::Error) -> bool {
+    err.is::<RequestTimeout>()
+}
+
+fn is_disconnect(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<impeller2_stellar::Error>(),
+        Some(impeller2_stellar::Error::Stellar(_))
+    )
+}
+
+/// One component's value in a [`Client::send_batch`]
+#[derive(Clone, Debug)]
+pub struct BatchColumn {
+    pub component_id: ComponentId,
+    pub prim_type: PrimType,
+    pub shape: Vec<u64>,
+    /// The value's elements as little endian `prim_type`s
+    pub data: Vec<u8>,
+}
+
+/// A component value received on a [`Subscription`]
+#[derive(Clone, Debug)]
+pub struct StreamValue {
+    pub component_id: ComponentId,
+    pub timestamp: Option<Timestamp>,
+    pub schema: Schema<Vec<u64>>,
+    pub data: Vec<u8>,
+}
+
+impl StreamValue {
+    pub fn view(&self) -> Result<ComponentView<'_>, impeller2::error::Error> {
+        ComponentView::try_from_bytes_shape(
+            &self.data,
+            self.schema.shape(),
+            self.schema.prim_type(),
+        )
+    }
+}
+
+/// Marks a long-running operation as in flight until dropped, so Ctrl-C interrupts it instead of exiting the cli
+struct Interruptible;
+
+impl Interruptible {
+    fn begin() -> Self {
+        OP_INTERRUPTED.store(false, atomic::Ordering::SeqCst);
+        OP_IN_FLIGHT.store(true, atomic::Ordering::SeqCst);
+        Interruptible
+    }
+
+    fn interrupted(&self) -> bool {
+        OP_INTERRUPTED.load(atomic::Ordering::SeqCst)
+    }
+
+    /// Errors if Ctrl-C has been pressed, for operations that give up part way through
+    fn check(&self) -> anyhow::Result<()> {
+        match self.interrupted() {
+            true => Err(anyhow!("interrupted")),
+            false => Ok(()),
+        }
+    }
+
+    /// Waits for `fut`, or returns `None` as soon as Ctrl-C is pressed
+    ///
+    /// `fut` is dropped when interrupted, so it has to be safe to drop part way through, like
+    /// [`impeller2_stellar::Client::recv`].
+    async fn or_interrupted<T>(&self, fut: impl Future<Output = T>) -> Option<T> {
+        futures_lite::future::or(async { Some(fut.await) }, async {
+            let _ = OP_INTERRUPT_WAITERS.wait_for(|| self.interrupted()).await;
+            None
+        })
+        .await
+    }
+}
+
+impl Drop for Interruptible {
+    fn drop(&mut self) {
+        OP_IN_FLIGHT.store(false, atomic::Ordering::SeqCst);
+    }
+}
+
+/// Tells a command that runs until the user stops it when enter or Ctrl-C has been pressed
+///
+/// When stdin isn't a terminal, like in a script or under `exec`, enter is never seen, so only Ctrl-C stops it.
+struct StopSignal {
+    enter: Arc<AtomicBool>,
+    interrupt: Interruptible,
+}
+
+impl StopSignal {
+    fn new() -> Self {
+        let enter = Arc::new(AtomicBool::new(false));
+        if io::stdin().is_terminal() {
+            let pressed = enter.clone();
+            std::thread::spawn(move || {
+                let mut stdin = io::stdin().lock();
+                let mut buf = [0u8];
+                let _ = stdin.read(&mut buf);
+                pressed.store(true, atomic::Ordering::SeqCst);
+                OP_INTERRUPT_WAITERS.wake_all();
+            });
+        }
+        Self {
+            enter,
+            interrupt: Interruptible::begin(),
+        }
+    }
+
+    fn running(&self) -> bool {
+        !self.enter.load(atomic::Ordering::SeqCst) && !self.interrupt.interrupted()
+    }
+
+    /// Waits for `fut`, or returns `None` as soon as enter or Ctrl-C is pressed
+    ///
+    /// Like [`Interruptible::or_interrupted`], `fut` has to be safe to drop part way through.
+    async fn or_stopped<T>(&self, fut: impl Future<Output = T>) -> Option<T> {
+        futures_lite::future::or(async { Some(fut.await) }, async {
+            let _ = OP_INTERRUPT_WAITERS.wait_for(|| !self.running()).await;
+            None
+        })
+        .await
+    }
+}
+
+/// Re-encodes a received table so it can be sent on, or written to a capture
+fn table_packet(table: &OwnedTable<Slice<Vec<u8>>>) -> LenPacket {
+    let mut packet = LenPacket::table(table.id, table.buf.len());
+    packet.extend_from_slice(&table.buf[..]);
+    packet
+}
+
+/// One page of a component's history, see [`EXPORT_PAGE_LEN`]
+struct TimeSeriesPage {
+    timestamps: Vec<Timestamp>,
+    data: Vec<u8>,
+    /// Where the next page starts, or `None` if this was the last one
+    next: Option<Timestamp>,
+}
+
+/// A [`Stream`], optionally limited to a set of components with a [`FilteredStream`]
+#[derive(Clone, Debug)]
+pub struct StreamRequest {
+    pub stream: Stream,
+    /// The components the stream carries, or every component if `None`
+    pub component_ids: Option<Vec<ComponentId>>,
+}
+
+impl StreamRequest {
+    fn packet(&self, req_id: RequestId) -> LenPacket {
+        match &self.component_ids {
+            Some(component_ids) => (&FilteredStream {
+                stream: self.stream.clone(),
+                component_ids: component_ids.clone(),
+            })
+                .with_request_id(req_id),
+            None => (&self.stream).with_request_id(req_id),
+        }
+    }
+}
+
+impl From<Stream> for StreamRequest {
+    fn from(stream: Stream) -> Self {
+        Self {
+            stream,
+            component_ids: None,
+        }
+    }
+}
+
+/// A live stream of decoded component values, created by [`Client::subscribe`]
+pub struct Subscription<'a> {
+    client: &'a mut Client,
+    stream: StreamRequest,
+    req_id: RequestId,
+    vtables: HashMap<PacketId, VTable>,
+    /// Ids of the vtables requested with [`GetVTable`] whose reply hasn't arrived yet
+    fetching: HashSet<PacketId>,
+    pending: VecDeque<StreamValue>,
+}
+
+impl Subscription<'_> {
+    /// Waits for the next component value
+    ///
+    /// A table whose vtable is unknown triggers a [`GetVTable`] request, and is skipped along with any others that
+    /// arrive before the reply.
+    pub async fn next(&mut self) -> anyhow::Result<StreamValue> {
+        loop {
+            if let Some(value) = self.pending.pop_front() {
+                return Ok(value);
+            }
+            let reply = self.recv().await?;
+            self.decode(reply).await?;
+        }
+    }
+
+    /// Like [`Subscription::next`], but returns `None` as soon as `stop` is signalled rather than after the next reply
+    async fn next_or_stopped(&mut self, stop: &StopSignal) -> anyhow::Result<Option<StreamValue>> {
+        loop {
+            if let Some(value) = self.pending.pop_front() {
+                return Ok(Some(value));
+            }
+            let Some(reply) = stop.or_stopped(self.recv()).await else {
+                return Ok(None);
+            };
+            self.decode(reply?).await?;
+        }
+    }
+
+    /// Queues the values in a table, or records a vtable
+    async fn decode(&mut self, reply: StreamReply<Slice<Vec<u8>>>) -> anyhow::Result<()> {
+        match reply {
+            StreamReply::Table(table) => {
+                if !self.fetch_vtable(table.id).await? {
+                    return Ok(());
+                }
+                self.vtables[&table.id].apply(
+                    &table.buf[..],
+                    &mut |component_id: ComponentId,
+                          view: ComponentView<'_>,
+                          timestamp: Option<Timestamp>| {
+                        self.pending.push_back(StreamValue {
+                            component_id,
+                            timestamp,
+                            schema: Schema::new(view.prim_type(), view.shape())
+                                .expect("vec schema can't overflow"),
+                            data: view.as_bytes().to_vec(),
+                        });
+                    },
+                )??;
+            }
+            StreamReply::VTable(msg) => {
+                self.fetching.remove(&msg.id);
+                self.vtables.insert(msg.id, msg.vtable);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the vtable for tables with `id` has arrived, asking the db for it with [`GetVTable`] on the first miss
+    ///
+    /// The request is tagged with the stream's request id, so the reply arrives as a [`StreamReply::VTable`] in the
+    /// stream itself rather than being awaited here.
+    async fn fetch_vtable(&mut self, id: PacketId) -> anyhow::Result<bool> {
+        if self.vtables.contains_key(&id) {
+            return Ok(true);
+        }
+        if self.fetching.insert(id) {
+            self.client
+                .send_packet((&GetVTable { id }).with_request_id(self.req_id))
+                .await?;
+        }
+        Ok(false)
+    }
+
+    /// Like [`Subscription::recv`], but gives up with `None` once `deadline` passes
+    async fn recv_until(
+        &mut self,
+        deadline: Option<std::time::Instant>,
+    ) -> anyhow::Result<Option<StreamReply<Slice<Vec<u8>>>>> {
+        let Some(deadline) = deadline else {
+            return self.recv().await.map(Some);
+        };
+        let Some(remaining) = deadline.checked_duration_since(std::time::Instant::now()) else {
+            return Ok(None);
+        };
+        futures_lite::future::or(async { self.recv().await.map(Some) }, async {
+            stellarator::sleep(remaining).await;
+            Ok(None)
+        })
+        .await
+    }
+
+    async fn recv(&mut self) -> anyhow::Result<StreamReply<Slice<Vec<u8>>>> {
+        match self.client.client.recv(self.req_id).await {
+            Err(impeller2_stellar::Error::Stellar(_)) => {
+                self.client.reconnect().await?;
+                // the db sends every vtable again to a new subscriber
+                self.vtables.clear();
+                self.fetching.clear();
+                self.client
+                    .send_packet(self.stream.packet(self.req_id))
+                    .await?;
+                Ok(self.client.client.recv(self.req_id).await?)
+            }
+            res => Ok(res?),
+        }
+    }
+}
+
+/// An editor asset that [`Client::put_asset`] stores, written like `{ mesh = { Sphere = { radius = 1.0 } } }` in Lua
+/// and `{"mesh": {"Sphere": {"radius": 1.0}}}` in JSON
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetValue {
+    Panel(Panel),
+    Mesh(Mesh),
+    Material(Material),
+}
+
+impl AssetValue {
+    /// The asset's [`Asset::NAME`], recorded in its msg metadata
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AssetValue::Panel(_) => Panel::NAME,
+            AssetValue::Mesh(_) => Mesh::NAME,
+            AssetValue::Material(_) => Material::NAME,
+        }
+    }
+
+    fn to_postcard(&self) -> postcard::Result<Vec<u8>> {
+        match self {
+            AssetValue::Panel(panel) => postcard::to_stdvec(panel),
+            AssetValue::Mesh(mesh) => postcard::to_stdvec(mesh),
+            AssetValue::Material(material) => postcard::to_stdvec(material),
+        }
+    }
+
+    fn from_postcard(kind: &str, bytes: &[u8]) -> anyhow::Result<Self> {
+        let asset = match kind {
+            Panel::NAME => AssetValue::Panel(postcard::from_bytes(bytes)?),
+            Mesh::NAME => AssetValue::Mesh(postcard::from_bytes(bytes)?),
+            Material::NAME => AssetValue::Material(postcard::from_bytes(bytes)?),
+            kind => return Err(anyhow!("unknown asset kind {kind}")),
+        };
+        Ok(asset)
+    }
+}
+
+/// The name an asset uploaded from `path` is stored under
+fn asset_file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// How [`Client::stream_msgs`] prints each msg
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MsgFormat {
+    /// A table of field names, values and units
+    #[default]
+    Table,
+    /// One JSON object per msg, per line
+    Json,
+}
+
+/// Renders a decoded msg as one row per field, with units from [`MsgMetadata::field_unit`]
+fn msg_table(metadata: &MsgMetadata, msg: &serde_json::Value) -> tabled::Table {
+    let mut fields = vec![];
+    flatten_msg_fields(String::new(), msg, &mut fields);
+    let mut builder = tabled::builder::Builder::default();
+    builder.push_record(["FIELD", "VALUE", "UNIT"].map(String::from));
+    for (path, value) in fields {
+        let unit = metadata.field_unit(&path).unwrap_or_default().to_string();
+        let path = if path.is_empty() {
+            "value".to_string()
+        } else {
+            path
+        };
+        builder.push_record([path, value, unit]);
+    }
+    let mut table = builder.build();
+    table.with(tabled::settings::Style::rounded()).with(
+        tabled::settings::style::BorderColor::filled(tabled::settings::Color::FG_BLUE),
+    );
+    table
+}
+
+/// Flattens nested structs into dotted paths, keeping arrays of scalars on one line
+fn flatten_msg_fields(path: String, value: &serde_json::Value, out: &mut Vec<(String, String)>) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        }
+    };
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (key, value) in fields {
+                flatten_msg_fields(join(key), value, out);
+            }
+        }
+        serde_json::Value::Array(elems)
+            if elems.iter().any(|e| {
+                matches!(
+                    e,
+                    serde_json::Value::Object(_) | serde_json::Value::Array(_)
+                )
+            }) =>
+        {
+            for (i, elem) in elems.iter().enumerate() {
+                flatten_msg_fields(format!("{path}[{i}]"), elem, out);
+            }
+        }
+        serde_json::Value::String(s) => out.push((path, s.clone())),
+        value => out.push((path, value.to_string())),
+    }
+}
+
+/// The file formats [`Client::sql_export`] and [`Client::export_time_series`] can write
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    /// One JSON object per row, per line
+    JsonLines,
+    Parquet,
+    /// The Arrow IPC file format
+    ArrowIpc,
+}
+
+impl ExportFormat {
+    /// Guesses the format from `path`'s extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "jsonl" | "ndjson" | "json" => Some(Self::JsonLines),
+            "parquet" | "pq" => Some(Self::Parquet),
+            "arrow" | "ipc" | "feather" => Some(Self::ArrowIpc),
+            _ => None,
+        }
+    }
+}
+
+fn write_batches(path: &Path, format: ExportFormat, batches: &[RecordBatch]) -> anyhow::Result<()> {
+    let file = std::fs::File::create(path)?;
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = arrow::csv::Writer::new(file);
+            for batch in batches {
+                writer.write(batch)?;
+            }
+        }
+        ExportFormat::JsonLines => {
+            let mut writer = arrow::json::LineDelimitedWriter::new(file);
+            writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+            writer.finish()?;
+        }
+        ExportFormat::Parquet => {
+            let schema = batches
+                .first()
+                .map(RecordBatch::schema)
+                .unwrap_or_else(|| Arc::new(arrow::datatypes::Schema::empty()));
+            let mut writer = parquet::arrow::ArrowWriter::try_new(file, schema, None)?;
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            writer.close()?;
+        }
+        ExportFormat::ArrowIpc => {
+            let schema = batches
+                .first()
+                .map(RecordBatch::schema)
+                .unwrap_or_else(|| Arc::new(arrow::datatypes::Schema::empty()));
+            let mut writer = arrow::ipc::writer::FileWriter::try_new(file, &schema)?;
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            writer.finish()?;
+        }
+    }
+    Ok(())
+}
+
+fn lua_export_format(lua: &Lua, format: Option<Value>, path: &Path) -> mlua::Result<ExportFormat> {
+    match format {
+        Some(format) => lua.from_value(format),
+        None => ExportFormat::from_path(path).ok_or_else(|| {
+            anyhow!(
+                "can't tell the format of {} from its extension, pass 'csv', 'json_lines', 'parquet' or 'arrow_ipc'",
+                path.display()
+            )
+            .into()
+        }),
+    }
+}
+
+/// Converts one page of a time series into a batch with a `time` column and a `value` column
+///
+/// Non-scalar components become a fixed size list per sample. `bf16` and `q15_16` have no Arrow equivalent, so
+/// they're widened to `f32` and `f64`. Bytes and string slots become one binary or string value per sample.
+fn time_series_batch(
+    timestamps: &[Timestamp],
+    data: &[u8],
+    schema: &Schema<Vec<u64>>,
+) -> anyhow::Result<RecordBatch> {
+    use arrow::{
+        array::{
+            ArrayData, ArrayRef, BinaryArray, BooleanArray, FixedSizeListArray, Float32Array,
+            Float64Array, StringArray, TimestampMicrosecondArray, make_array,
+        },
+        buffer::Buffer,
+        datatypes::{DataType, Field, TimeUnit},
+    };
+
+    let prim_type = schema.prim_type();
+    let elems = schema.dim().iter().product::<u64>() as usize;
+    let len = timestamps.len() * elems;
+    if data.len() != len * prim_type.size() {
+        return Err(anyhow!(
+            "expected {len} {prim_type} values, got {} bytes",
+            data.len()
+        ));
+    }
+    let values: ArrayRef = match prim_type {
+        PrimType::Bytes | PrimType::String => {
+            let payloads = data
+                .chunks_exact(elems.max(1))
+                .map(|slot| var_len_payload(slot).unwrap_or_default());
+            if prim_type == PrimType::String {
+                Arc::new(StringArray::from_iter_values(
+                    payloads.map(|p| String::from_utf8_lossy(p).into_owned()),
+                ))
+            } else {
+                Arc::new(BinaryArray::from_iter_values(payloads))
+            }
+        }
+        PrimType::Bool => Arc::new(BooleanArray::from(
+            data.iter().map(|b| *b != 0).collect::<Vec<_>>(),
+        )),
+        PrimType::BF16 => Arc::new(Float32Array::from(
+            data.chunks_exact(2)
+                .map(|b| bf16::from_le_bytes([b[0], b[1]]).to_f32())
+                .collect::<Vec<_>>(),
+        )),
+        PrimType::Q15_16 => Arc::new(Float64Array::from(
+            data.chunks_exact(4)
+                .map(|b| Q15_16::from_bits(i32::from_le_bytes([b[0], b[1], b[2], b[3]])).to_f64())
+                .collect::<Vec<_>>(),
+        )),
+        ty => {
+            let data_type = match ty {
+                PrimType::U8 => DataType::UInt8,
+                PrimType::U16 => DataType::UInt16,
+                PrimType::U32 => DataType::UInt32,
+                PrimType::U64 => DataType::UInt64,
+                PrimType::I8 => DataType::Int8,
+                PrimType::I16 => DataType::Int16,
+                PrimType::I32 => DataType::Int32,
+                PrimType::I64 => DataType::Int64,
+                PrimType::F16 => DataType::Float16,
+                PrimType::F32 => DataType::Float32,
+                PrimType::F64 => DataType::Float64,
+                PrimType::Bool
+                | PrimType::BF16
+                | PrimType::Q15_16
+                | PrimType::Bytes
+                | PrimType::String => {
+                    unreachable!()
+                }
+            };
+            let data = ArrayData::builder(data_type)
+                .len(len)
+                .add_buffer(Buffer::from_slice_ref(data))
+                .build()?;
+            make_array(data)
+        }
+    };
+    let values = if schema.dim().is_empty() || prim_type.is_var_len() {
+        values
+    } else {
+        let field = Arc::new(Field::new("item", values.data_type().clone(), false));
+        Arc::new(FixedSizeListArray::try_new(
+            field,
+            elems as i32,
+            values,
+            None,
+        )?)
+    };
+    let time = Arc::new(TimestampMicrosecondArray::from(
+        timestamps.iter().map(|t| t.0).collect::<Vec<_>>(),
+    ));
+    let arrow_schema = Arc::new(arrow::datatypes::Schema::new(vec![
+        Field::new(
+            "time",
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+            false,
+        ),
+        Field::new("value", values.data_type().clone(), false),
+    ]));
+    Ok(RecordBatch::try_new(arrow_schema, vec![time, values])?)
+}
+
+impl UserData for Client {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method_mut(
+            "send_table",
+            |lua,
+             mut this,
+             (c_id, ty, shape, buf, checked): (Value, _, Vec<u64>, _, Option<bool>)| async move {
+                let component_id = lua_component_id(&lua, c_id)?;
+                let ty: PrimType = lua.from_value(ty)?;
+                this.send(
+                    &lua,
+                    component_id.0,
+                    ty,
+                    shape,
+                    buf,
+                    checked.unwrap_or(false),
+                )
+                .await?;
+                Ok(())
+            },
+        );
+        methods.add_async_method_mut(
+            "send_batch",
+            |lua, mut this, batch: mlua::Table| async move {
+                let mut columns = vec![];
+                for column in batch.sequence_values::<mlua::Table>() {
+                    let column = column?;
+                    let component_id = lua_component_id(&lua, column.get("component")?)?;
+                    let prim_type: PrimType = lua.from_value(column.get("ty")?)?;
+                    let shape = column.get::<Option<Vec<u64>>>("shape")?.unwrap_or_default();
+                    let checked = column.get::<Option<bool>>("checked")?.unwrap_or(false);
+                    let data = lua_component_bytes(
+                        &lua,
+                        column.get("data")?,
+                        component_id,
+                        prim_type,
+                        &shape,
+                        checked,
+                    )?;
+                    columns.push(BatchColumn {
+                        component_id,
+                        prim_type,
+                        shape,
+                        data,
+                    });
+                }
+                this.send_batch(columns).await?;
+                Ok(())
+            },
+        );
+        methods.add_async_method_mut(
+            "set",
+            |lua, mut this, (component_id, value): (Value, Value)| async move {
+                let component_id = lua_component_id(&lua, component_id)?;
+                let values = match value {
+                    Value::Table(table) => {
+                        table.sequence_values::<Value>().collect::<Result<_, _>>()?
+                    }
+                    value => vec![value],
+                };
+                let values = values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Integer(v) => Ok((*v as f64, None)),
+                        Value::Number(v) => Ok((*v, None)),
+                        Value::String(s) => {
+                            let (v, unit) = units::parse_value(s.to_str()?.as_ref())?;
+                            Ok((v, unit.map(str::to_string)))
+                        }
+                        other => Err(anyhow!(
+                            "expected a number or a string like '90 deg', found {}",
+                            other.type_name()
+                        )),
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let values = values
+                    .iter()
+                    .map(|(v, unit)| (*v, unit.as_deref()))
+                    .collect::<Vec<_>>();
+                this.set(component_id, &values).await?;
+                Ok(())
+            },
+        );
+        methods.add_async_method_mut(
+            "send_msg",
+            |lua, mut this, (msg_or_id, val): (Value, Option<Value>)| async move {
+                if let Some(msg) = msg_or_id.as_userdata() {
+                    let msg = msg.call_method::<Vec<u8>>("msg", ())?;
+                    this.client
+                        .send(LenPacket { inner: msg })
+                        .await
+                        .0
+                        .map_err(anyhow::Error::from)?;
+                } else if let Some(msg) = val {
+                    let id = msg_or_id;
+                    let msg_id = if let Ok(id) = lua.from_value::<PacketId>(id.clone()) {
+                        id
+                    } else if let Ok(name) = lua.from_value::<String>(id) {
+                        msg_id(&name)
+                    } else {
+                        return Err(anyhow!("msg id must be a PacketId or String").into());
+                    };
+                    let msg = lua.from_value(msg)?;
+                    this.send_msg(msg_id, msg).await?;
+                } else {
+                    return Err(anyhow!(
+                        "send_msg requires either a native msg or a id and a table"
                     )
                     .into());
                 };
@@ -506,22 +2613,289 @@ impl UserData for Client {
             },
         );
 
+        methods.add_async_method_mut(
+            "set_stream_state_batch",
+            |lua, mut this, states: Value| async move {
+                let states: Vec<SetStreamState> = lua.from_value(states)?;
+                this.set_stream_state_batch(states).await?;
+                Ok(())
+            },
+        );
+
+        methods.add_async_method_mut(
+            "set_glb",
+            |lua, mut this, (id, path, validate): (Value, PathBuf, Option<bool>)| async move {
+                let id = lua_msg_id(&lua, id)?;
+                this.set_glb_asset(id, path, validate.unwrap_or(true))
+                    .await?;
+                Ok(())
+            },
+        );
+
+        methods.add_async_method_mut(
+            "put_asset",
+            |lua, mut this, (asset, opts): (Value, Option<mlua::Table>)| async move {
+                let (id, name) = match &opts {
+                    Some(opts) => (
+                        opts.get::<Option<Value>>("id")?,
+                        opts.get::<Option<String>>("name")?,
+                    ),
+                    None => (None, None),
+                };
+                let id = id.map(|id| lua_msg_id(&lua, id)).transpose()?;
+                let id = match asset {
+                    Value::String(path) => {
+                        let path = PathBuf::from(path.to_str()?.to_string());
+                        this.put_asset_file(id, &path).await?
+                    }
+                    asset => {
+                        let asset: AssetValue = lua.from_value(asset)?;
+                        let name = name.ok_or_else(|| anyhow!("an asset table needs a name"))?;
+                        let id = id.unwrap_or_else(|| msg_id(&name));
+                        this.put_asset(id, name, &asset).await?;
+                        id
+                    }
+                };
+                Ok(lua.to_value(&id)?)
+            },
+        );
+        methods.add_async_method_mut(
+            "get_asset_to_file",
+            |lua, mut this, (id, path): (Value, PathBuf)| async move {
+                let id = lua_msg_id(&lua, id)?;
+                this.get_asset_to_file(id, &path).await?;
+                Ok(())
+            },
+        );
+
+        methods.add_async_method_mut(
+            "set_component_enum",
+            |lua, mut this, (component_id, values): (Value, BTreeMap<i64, String>)| async move {
+                let component_id = lua_component_id(&lua, component_id)?;
+                this.set_component_enum(component_id, values).await?;
+                Ok(())
+            },
+        );
+
+        methods.add_async_method_mut(
+            "find",
+            |lua, mut this, (pattern, opts): (String, Option<mlua::Table>)| async move {
+                let mut query = QueryMetadata {
+                    name_glob: Some(pattern),
+                    ..Default::default()
+                };
+                if let Some(opts) = opts {
+                    query.kind = opts
+                        .get::<Option<Value>>("kind")?
+                        .map(|kind| lua.from_value(kind))
+                        .transpose()?;
+                    if let Some(filters) = opts.get::<Option<mlua::Table>>("metadata")? {
+                        for pair in filters.pairs::<String, Value>() {
+                            let (key, value) = pair?;
+                            // `key = true` only requires the key, any other value is a glob its value has to match
+                            let value = match value {
+                                Value::Boolean(true) => None,
+                                value => Some(lua.from_value::<String>(value)?),
+                            };
+                            query.metadata_filters.push(MetadataFilter { key, value });
+                        }
+                    }
+                }
+                let resp = this.find(query).await?;
+                lua.to_value(&resp)
+            },
+        );
+
+        methods.add_async_method_mut(
+            "set_component_bits",
+            |lua, mut this, (component_id, bits): (Value, BTreeMap<u8, String>)| async move {
+                let component_id = lua_component_id(&lua, component_id)?;
+                this.set_component_bits(component_id, bits).await?;
+                Ok(())
+            },
+        );
+
+        methods.add_async_method_mut(
+            "set_component_recording",
+            |lua, mut this, (component_id, recording): (Value, bool)| async move {
+                let component_id = lua_component_id(&lua, component_id)?;
+                this.set_component_recording(component_id, recording)
+                    .await?;
+                Ok(())
+            },
+        );
+
+        methods.add_async_method_mut(
+            "set_retention",
+            |lua, mut this, (component_id, policy): (Value, mlua::Table)| async move {
+                let component_id = lua_component_id(&lua, component_id)?;
+                let max_age = policy
+                    .get::<Option<f64>>("max_age")?
+                    .map(Duration::try_from_secs_f64)
+                    .transpose()
+                    .map_err(mlua::Error::external)?;
+                let policy = RetentionPolicy {
+                    max_age,
+                    max_bytes: policy.get("max_bytes")?,
+                    max_samples: policy.get("max_samples")?,
+                };
+                this.set_retention(component_id, policy).await?;
+                Ok(())
+            },
+        );
+        methods.add_async_method_mut("compact", |lua, mut this, ()| async move {
+            let report = this.compact().await?;
+            lua.to_value(&report)
+        });
+
+        methods.add_async_method_mut("server_time", |_lua, mut this, ()| async move {
+            let timestamp = this.server_time().await?;
+            Ok(timestamp.0)
+        });
+        methods.add_async_method_mut("status", |lua, mut this, ()| async move {
+            let status = this.status().await;
+            lua.to_value(&status)
+        });
+        methods.add_async_method_mut("flush", |_lua, mut this, ()| async move {
+            this.flush().await?;
+            Ok(())
+        });
         methods.add_async_method_mut("sql", |_lua, mut this, sql: String| async move {
             this.sql(&sql).await?;
             Ok(())
         });
+        methods.add_async_method_mut(
+            "sql_to_file",
+            |lua, mut this, (sql, path, format): (String, PathBuf, Option<Value>)| async move {
+                let format = lua_export_format(&lua, format, &path)?;
+                let rows = this.sql_export(&sql, &path, format).await?;
+                Ok(rows)
+            },
+        );
         methods.add_async_method_mut(
             "get_time_series",
-            |lua, mut this, (c_id, start, stop)| async move {
+            |lua, mut this, (c_id, start, stop): (Value, Value, Value)| async move {
+                let (start, stop) = this.lua_time_range(&lua, start, stop).await?;
                 this.get_time_series(&lua, c_id, start, stop).await?;
                 Ok(())
             },
         );
-        methods.add_async_method_mut("stream", |lua, mut this, stream| async move {
-            let msg: Stream = lua.from_value(stream)?;
-            this.stream(msg).await?;
-            Ok(())
+        methods.add_async_method_mut(
+            "plot",
+            |lua, mut this, (component_id, opts): (Value, Option<mlua::Table>)| async move {
+                let component_id = lua_component_id(&lua, component_id)?;
+                let mut index = 0;
+                let mut range = Timestamp(i64::MIN)..Timestamp(i64::MAX);
+                let (mut width, mut height) = (80, 16);
+                if let Some(opts) = opts {
+                    index = opts.get::<Option<usize>>("index")?.unwrap_or(index);
+                    if let Some((start, stop)) =
+                        opts.get::<Option<(Option<i64>, Option<i64>)>>("range")?
+                    {
+                        range = Timestamp(start.unwrap_or(i64::MIN))
+                            ..Timestamp(stop.unwrap_or(i64::MAX));
+                    }
+                    width = opts.get::<Option<usize>>("width")?.unwrap_or(width);
+                    height = opts.get::<Option<usize>>("height")?.unwrap_or(height);
+                }
+                let plot = this
+                    .plot(component_id, range, index, (width, height))
+                    .await?;
+                println!("{plot}");
+                Ok(())
+            },
+        );
+        methods.add_async_method_mut("get_latest", |lua, mut this, c_id| async move {
+            let value = this.get_latest(&lua, c_id).await?;
+            Ok(value)
         });
+        methods.add_async_method_mut(
+            "export_time_series",
+            |lua,
+             mut this,
+             (c_id, start, stop, path, format): (
+                Value,
+                Option<i64>,
+                Option<i64>,
+                PathBuf,
+                Option<Value>,
+            )| async move {
+                let component_id = lua_component_id(&lua, c_id)?;
+                let format = lua_export_format(&lua, format, &path)?;
+                let range =
+                    Timestamp(start.unwrap_or(i64::MIN))..Timestamp(stop.unwrap_or(i64::MAX));
+                let rows = this
+                    .export_time_series(component_id, range, &path, format)
+                    .await?;
+                Ok(rows)
+            },
+        );
+        methods.add_async_method_mut(
+            "get_time_series_all",
+            |_lua, mut this, (component, start, stop)| async move {
+                this.get_time_series_all(component, start, stop).await?;
+                Ok(())
+            },
+        );
+        methods.add_async_method_mut(
+            "stream",
+            |lua, mut this, (stream, opts): (Value, Option<mlua::Table>)| async move {
+                let msg = this.lua_stream_request(&lua, stream).await?;
+                let every = match opts {
+                    Some(opts) => opts.get::<Option<usize>>("every")?.unwrap_or(1),
+                    None => 1,
+                };
+                this.stream(msg, every).await?;
+                Ok(())
+            },
+        );
+
+        methods.add_async_method_mut(
+            "stream_with",
+            |lua, mut this, (stream, callback): (Value, Function)| async move {
+                let msg = this.lua_stream_request(&lua, stream).await?;
+                this.stream_with(&lua, msg, callback).await?;
+                Ok(())
+            },
+        );
+
+        methods.add_async_method_mut(
+            "watch",
+            |lua, mut this, (component_id, opts): (Value, Option<mlua::Table>)| async move {
+                let component_id = lua_component_id(&lua, component_id)?;
+                let interval = match opts {
+                    Some(opts) => opts.get::<Option<f64>>("interval")?.unwrap_or(0.5),
+                    None => 0.5,
+                };
+                let interval =
+                    Duration::try_from_secs_f64(interval).map_err(anyhow::Error::from)?;
+                this.watch(component_id, interval).await?;
+                Ok(())
+            },
+        );
+
+        methods.add_async_method_mut(
+            "record",
+            |lua, mut this, (path, opts): (PathBuf, Option<mlua::Table>)| async move {
+                let mut filter = CaptureFilter::default();
+                if let Some(opts) = opts {
+                    for component_id in opts
+                        .get::<Option<Vec<Value>>>("components")?
+                        .unwrap_or_default()
+                    {
+                        filter
+                            .components
+                            .insert(lua_component_id(&lua, component_id)?);
+                    }
+                    if let Some(duration) = opts.get::<Option<f64>>("duration")? {
+                        filter.duration = Some(
+                            Duration::try_from_secs_f64(duration).map_err(anyhow::Error::from)?,
+                        );
+                    }
+                }
+                Ok(this.record(&path, filter).await?)
+            },
+        );
 
         methods.add_async_method_mut(
             "vtable_stream",
@@ -533,21 +2907,32 @@ impl UserData for Client {
             },
         );
 
-        methods.add_async_method_mut("stream_msgs", |lua, mut this, id: Value| async move {
-            let msg_id = if let Ok(id) = lua.from_value::<PacketId>(id.clone()) {
-                id
-            } else if let Ok(name) = lua.from_value::<String>(id) {
-                msg_id(&name)
-            } else {
-                return Err(anyhow!("msg id must be a PacketId or String").into());
-            };
-            this.stream_msgs(MsgStream { msg_id }).await?;
-            Ok(())
-        });
+        methods.add_async_method_mut(
+            "stream_msgs",
+            |lua, mut this, (id, opts): (Value, Option<mlua::Table>)| async move {
+                let msg_id = if let Ok(id) = lua.from_value::<PacketId>(id.clone()) {
+                    id
+                } else if let Ok(name) = lua.from_value::<String>(id) {
+                    msg_id(&name)
+                } else {
+                    return Err(anyhow!("msg id must be a PacketId or String").into());
+                };
+                let format = match opts {
+                    Some(opts) => match opts.get::<Option<Value>>("format")? {
+                        Some(format) => lua.from_value(format)?,
+                        None => MsgFormat::Table,
+                    },
+                    None => MsgFormat::Table,
+                };
+                this.stream_msgs(MsgStream { msg_id }, format).await?;
+                Ok(())
+            },
+        );
 
         methods.add_async_method_mut(
             "get_msgs",
-            |lua, mut this, (id, start, stop): (Value, Option<i64>, Option<i64>)| async move {
+            |lua, mut this, (id, start, stop): (Value, Value, Value)| async move {
+                let (start, stop) = this.lua_time_range(&lua, start, stop).await?;
                 let msg_id = if let Ok(id) = lua.from_value::<PacketId>(id.clone()) {
                     id
                 } else if let Ok(name) = lua.from_value::<String>(id) {
@@ -559,6 +2944,19 @@ impl UserData for Client {
                 Ok(())
             },
         );
+        methods.add_async_method_mut(
+            "get_events",
+            |lua,
+             mut this,
+             (start, stop, min_severity): (Option<i64>, Option<i64>, Option<Value>)| async move {
+                let min_severity = match min_severity {
+                    Some(severity) => lua.from_value(severity)?,
+                    None => EventSeverity::Debug,
+                };
+                this.get_events(start, stop, min_severity).await?;
+                Ok(())
+            },
+        );
         methods.add_async_method_mut(
             "save_archive",
             |lua, mut this, (path, format): (PathBuf, Option<Value>)| async move {
@@ -571,6 +2969,20 @@ impl UserData for Client {
                 Ok(())
             },
         );
+        methods.add_async_method_mut(
+            "create_snapshot",
+            |_lua, mut this, path: PathBuf| async move {
+                this.request_unbounded(&CreateSnapshot { path }).await?;
+                Ok(())
+            },
+        );
+        methods.add_async_method_mut(
+            "restore_snapshot",
+            |_lua, mut this, path: PathBuf| async move {
+                this.request_unbounded(&RestoreSnapshot { path }).await?;
+                Ok(())
+            },
+        );
 
         macro_rules! add_req_reply_method {
             ($name:tt, $ty:tt, $req:tt) => {
@@ -591,13 +3003,14 @@ impl UserData for Client {
         );
         add_req_reply_method!(dump_metadata, DumpMetadata, DumpMetadataResp);
         add_req_reply_method!(get_schema, GetSchema, SchemaMsg);
+        add_req_reply_method!(migrate_component, MigrateComponent, SchemaMsg);
     }
 }
 
 #[derive(Helper, Completer, Validator, Hinter)]
 struct CliHelper {
     #[rustyline(Completer)]
-    completer: FilenameCompleter,
+    completer: LuaCompleter,
     #[rustyline(Validator)]
     validator: MatchingBracketValidator,
     #[rustyline(Hinter)]
@@ -645,6 +3058,95 @@ impl Highlighter for CliHelper {
 #[derive(clap::Args, Clone, Debug)]
 pub struct Args {
     pub path: Option<PathBuf>,
+    #[clap(
+        short,
+        long,
+        requires = "path",
+        help = "Start the REPL after running the script, keeping its globals"
+    )]
+    pub interactive: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+pub enum Command {
+    #[command(about = "Run a single Lua chunk or SQL query against a db and exit")]
+    Exec(ExecArgs),
+}
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct ExecArgs {
+    #[clap(
+        long,
+        default_value = "localhost:2240",
+        help = "Address of the db, connected as the `client` global"
+    )]
+    pub addr: String,
+    #[clap(long, help = "Run the input as a SQL query instead of Lua")]
+    pub sql: bool,
+    #[clap(long, help = "Write the result to stdout as JSON")]
+    pub json: bool,
+    #[clap(help = "The Lua chunk or SQL query, e.g. 'return client:dump_metadata()'")]
+    pub code: String,
+}
+
+/// Runs `args.code` once, printing the result as the REPL would or as JSON if `args.json` is set
+///
+/// Any connection, Lua or query error is returned, so a caller exiting with it gets a non-zero status.
+pub async fn exec(args: ExecArgs) -> anyhow::Result<()> {
+    if args.json {
+        let json = exec_json(&args).await?;
+        println!("{}", serde_json::to_string(&json)?);
+        return Ok(());
+    }
+    let mut client = Client::connect(&args.addr).await?;
+    if args.sql {
+        return client.sql(&args.code).await;
+    }
+    let lua = lua()?;
+    lua.globals().set("client", client)?;
+    let values = lua.load(&args.code).eval_async::<MultiValue>().await?;
+    let out = values.iter().map(colorize_value).collect::<Vec<_>>();
+    if !out.is_empty() {
+        println!("{}", out.join("\t"));
+    }
+    Ok(())
+}
+
+/// Runs `args.code` once and converts the result to JSON
+///
+/// SQL results become an array of row objects. A Lua chunk returning a single value becomes that value, several
+/// values become an array of them, and no values become `null`.
+pub async fn exec_json(args: &ExecArgs) -> anyhow::Result<serde_json::Value> {
+    let mut client = Client::connect(&args.addr).await?;
+    if args.sql {
+        let batches = client.sql_batches(&args.code).await?;
+        return batches_to_json(&batches);
+    }
+    let lua = lua()?;
+    lua.globals().set("client", client)?;
+    let values = lua.load(&args.code).eval_async::<MultiValue>().await?;
+    lua_values_to_json(values)
+}
+
+fn batches_to_json(batches: &[RecordBatch]) -> anyhow::Result<serde_json::Value> {
+    let mut writer = arrow::json::ArrayWriter::new(vec![]);
+    writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+    writer.finish()?;
+    Ok(serde_json::from_slice(&writer.into_inner())?)
+}
+
+fn lua_values_to_json(values: MultiValue) -> anyhow::Result<serde_json::Value> {
+    let mut values = values
+        .into_iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(match values.len() {
+        0 => serde_json::Value::Null,
+        1 => values.remove(0),
+        _ => serde_json::Value::Array(values),
+    })
 }
 
 struct LuaMsg<M: Msg>(M);
@@ -660,11 +3162,75 @@ impl<M: Msg> UserData for LuaMsg<M> {
 
 pub fn lua() -> anyhow::Result<Lua> {
     let lua = Lua::new();
-    let client = lua.create_async_function(|_lua, addr: String| async move {
-        let c = Client::connect(addr).await?;
-        Ok(c)
-    })?;
+    let client = lua.create_async_function(
+        |lua, (addr, opts): (String, Option<mlua::Table>)| async move {
+            let mut c = Client::connect(addr).await?;
+            if let Some(opts) = opts {
+                c = c.with_config(ClientConfig::from_lua_opts(&opts)?);
+                if let Some(codec) = opts.get::<Option<mlua::Value>>("compression")? {
+                    let codec = lua.from_value(codec)?;
+                    if c.set_compression(Some(codec)).await?.is_none() {
+                        return Err(anyhow!("the db doesn't support {codec:?} compression").into());
+                    }
+                }
+            }
+            Ok(c)
+        },
+    )?;
     lua.globals().set("connect", client)?;
+    lua.globals().set(
+        "mirror",
+        lua.create_async_function(
+            |_lua, (src, dest, opts): (AnyUserData, AnyUserData, Option<mlua::Table>)| async move {
+                let mut src = src.borrow_mut::<Client>()?;
+                let mut dest = dest.borrow_mut::<Client>()?;
+                let mut component_ids = None;
+                let mut duration = None;
+                if let Some(opts) = opts {
+                    if let Some(pattern) = opts.get::<Option<String>>("component")? {
+                        component_ids = Some(src.match_components(&pattern).await?);
+                    }
+                    if let Some(secs) = opts.get::<Option<f64>>("duration")? {
+                        duration =
+                            Some(Duration::try_from_secs_f64(secs).map_err(anyhow::Error::from)?);
+                    }
+                }
+                Ok(src.mirror(&mut dest, component_ids, duration).await?)
+            },
+        )?,
+    )?;
+    lua.globals().set(
+        "diff",
+        lua.create_async_function(|_lua, (addr_a, addr_b): (String, String)| async move {
+            let mut a = Client::connect(addr_a).await?;
+            let mut b = Client::connect(addr_b).await?;
+            let diffs = a.diff(&mut b).await?;
+            if diffs.is_empty() {
+                println!("{}", Color::Green.paint("no differences"));
+            }
+            for diff in &diffs {
+                println!("{}", diff.colored());
+            }
+            Ok(diffs.len())
+        })?,
+    )?;
+    lua.globals().set(
+        "replay",
+        lua.create_async_function(
+            |_lua, (path, addr, opts): (PathBuf, String, Option<mlua::Table>)| async move {
+                let addr = addr
+                    .to_socket_addrs()
+                    .map_err(anyhow::Error::from)?
+                    .next()
+                    .ok_or_else(|| anyhow!("missing socket ip"))?;
+                let speed = match opts {
+                    Some(opts) => opts.get::<Option<f64>>("speed")?.unwrap_or(1.0),
+                    None => 1.0,
+                };
+                Ok(replay(&path, addr, speed).await?)
+            },
+        )?,
+    )?;
     lua.globals().set(
         "ComponentId",
         lua.create_function(|lua, name: String| lua.create_ser_userdata(ComponentId::new(&name)))?,
@@ -723,15 +3289,7 @@ pub fn lua() -> anyhow::Result<Lua> {
     lua.globals().set(
         "component",
         lua.create_function(|lua, component_id: Value| {
-            let component_id = if let Ok(id) = lua.from_value::<ComponentId>(component_id.clone()) {
-                id
-            } else if let Ok(name) = lua.from_value::<String>(component_id.clone()) {
-                ComponentId::new(&name)
-            } else if let Ok(id) = lua.from_value::<i64>(component_id) {
-                ComponentId(id as u64)
-            } else {
-                return Err(anyhow!("component id must be a ComponentId or String").into());
-            };
+            let component_id = lua_component_id(lua, component_id)?;
             Ok(LuaOpBuilder(vtable::builder::component(component_id)))
         })?,
     )?;
@@ -762,6 +3320,16 @@ pub fn lua() -> anyhow::Result<Lua> {
             Ok(LuaOpBuilder(vtable::builder::ext(MeanOp { window }, arg)))
         })?,
     )?;
+    lua.globals().set(
+        "big_endian",
+        lua.create_function(|_, arg: UserDataRef<LuaOpBuilder>| {
+            let arg = arg.deref().0.clone();
+            Ok(LuaOpBuilder(vtable::builder::endian(
+                vtable::Endian::Big,
+                arg,
+            )))
+        })?,
+    )?;
     lua.globals().set(
         "vtable_msg",
         lua.create_function(
@@ -773,6 +3341,14 @@ pub fn lua() -> anyhow::Result<Lua> {
             },
         )?,
     )?;
+    lua.globals().set(
+        "table_vtable",
+        lua.create_function(|lua, (component_id, ty, shape): (Value, Value, Vec<u64>)| {
+            let component_id = lua_component_id(lua, component_id)?;
+            let ty: PrimType = lua.from_value(ty)?;
+            Ok(send_table_vtable(component_id, ty, &shape).to_string())
+        })?,
+    )?;
     lua.globals().set(
         "udp_vtable_stream",
         lua.create_function(|lua, (id, addr): (u16, String)| {
@@ -781,7 +3357,41 @@ pub fn lua() -> anyhow::Result<Lua> {
         })?,
     )?;
 
-    Ok(lua)
+    Ok(lua)
+}
+
+fn lua_component_id(lua: &Lua, component_id: Value) -> mlua::Result<ComponentId> {
+    if let Ok(id) = lua.from_value::<ComponentId>(component_id.clone()) {
+        Ok(id)
+    } else if let Ok(name) = lua.from_value::<String>(component_id.clone()) {
+        Ok(ComponentId::new(&name))
+    } else if let Ok(id) = lua.from_value::<i64>(component_id) {
+        Ok(ComponentId(id as u64))
+    } else {
+        Err(anyhow!("component id must be a ComponentId or String").into())
+    }
+}
+
+/// A msg or asset id given as a `PacketId` or a name
+fn lua_msg_id(lua: &Lua, id: Value) -> mlua::Result<PacketId> {
+    if let Ok(id) = lua.from_value::<PacketId>(id.clone()) {
+        Ok(id)
+    } else if let Ok(name) = lua.from_value::<String>(id) {
+        Ok(msg_id(&name))
+    } else {
+        Err(anyhow!("id must be a PacketId or String").into())
+    }
+}
+
+/// The vtable `Client:send_table` registers before sending a single component's data
+fn send_table_vtable(component_id: ComponentId, prim_type: PrimType, shape: &[u64]) -> VTable {
+    use vtable::builder::*;
+    let size = shape.iter().product::<u64>() as usize * prim_type.size();
+    vtable([raw_field(
+        0,
+        size as u16,
+        schema(prim_type, shape, component(component_id)),
+    )])
 }
 
 pub struct LuaOpBuilder(Arc<OpBuilder>);
@@ -791,174 +3401,360 @@ pub struct LuaFieldBuilder(FieldBuilder);
 impl UserData for LuaFieldBuilder {}
 
 pub async fn run(args: Args) -> anyhow::Result<()> {
+    if let Some(Command::Exec(args)) = args.command {
+        return exec(args).await;
+    }
+    install_interrupt_handler();
     let lua = lua()?;
     if let Some(path) = args.path {
         let script = std::fs::read_to_string(path)?;
         lua.load(&script).eval_async::<MultiValue>().await?;
-        Ok(())
-    } else {
-        let config = rustyline::Config::builder()
-            .history_ignore_space(true)
-            .completion_type(CompletionType::List)
-            .auto_add_history(true)
-            .build();
-        let h = CliHelper {
-            completer: FilenameCompleter::new(),
-            hinter: HistoryHinter::new(),
-            validator: MatchingBracketValidator::new(),
+        if !args.interactive {
+            return Ok(());
+        }
+    }
+    let config = rustyline::Config::builder()
+        .history_ignore_space(true)
+        .completion_type(CompletionType::List)
+        .auto_add_history(true)
+        .build();
+    let h = CliHelper {
+        completer: LuaCompleter::new(lua.clone()),
+        hinter: HistoryHinter::new(),
+        validator: MatchingBracketValidator::new(),
+    };
+    let mut history = rustyline::history::FileHistory::with_config(config);
+    let dirs = directories::ProjectDirs::from("systems", "elodin", "impeller2-cli")
+        .ok_or_else(|| anyhow!("dir not found"))?;
+    std::fs::create_dir_all(dirs.data_dir())?;
+    let lua_history_path = dirs.data_dir().join("impeller2-history");
+    let mut history_path = lua_history_path.clone();
+    if history_path.exists() {
+        history.load(&history_path)?;
+    }
+    let mut editor: Editor<_, _> = Editor::with_history(config, history)?;
+    editor.set_helper(Some(h));
+
+    let mut mode = Mode::Lua;
+    loop {
+        let mut prompt = match &mode {
+            Mode::Lua => "db ❯❯ ",
+            Mode::Sql { .. } => "sql ❯❯ ",
         };
-        let mut history = rustyline::history::FileHistory::with_config(config);
-        let dirs = directories::ProjectDirs::from("systems", "elodin", "impeller2-cli")
-            .ok_or_else(|| anyhow!("dir not found"))?;
-        std::fs::create_dir_all(dirs.data_dir())?;
-        let history_path = dirs.data_dir().join("impeller2-history");
-        if history_path.exists() {
-            history.load(&history_path)?;
-        }
-        let mut editor: Editor<_, _> = Editor::with_history(config, history)?;
-        editor.set_helper(Some(h));
-
-        let mut mode = Mode::Lua;
+        let mut line = String::new();
         loop {
-            let mut prompt = match &mode {
-                Mode::Lua => "db ❯❯ ",
-                Mode::Sql(..) => "sql ❯❯ ",
-            };
-            let mut line = String::new();
-            loop {
-                line.clear();
-                match editor.readline(prompt) {
-                    Ok(input) => line.push_str(&input),
-                    Err(_) => return Ok(()),
-                }
+            line.clear();
+            match editor.readline(prompt) {
+                Ok(input) => line.push_str(&input),
+                Err(_) => return Ok(()),
+            }
 
-                if line == ":exit" {
-                    if matches!(mode, Mode::Sql(_)) {
-                        mode = Mode::Lua;
-                        break;
-                    }
-                    std::process::exit(0);
-                }
-                if line.starts_with(":sql") {
-                    let addr = &line.strip_prefix(":sql ").unwrap_or_default();
-                    let addr = if addr.is_empty() {
-                        "localhost:2240"
-                    } else {
-                        addr
-                    };
-                    let client = match Client::connect(addr).await {
-                        Ok(c) => c,
-                        Err(err) => {
-                            println!("{err}");
-                            continue;
-                        }
-                    };
-                    mode = Mode::Sql(client);
+            if line == ":exit" {
+                if matches!(mode, Mode::Sql { .. }) {
+                    switch_history(&mut editor, &mut history_path, lua_history_path.clone())?;
+                    mode = Mode::Lua;
                     break;
                 }
-                if line == ":help" || line == ":h" {
-                    println!("{}", Color::Yellow.bold().paint("Impeller Lua REPL"));
-                    print_usage_line(
-                        ":sql addr",
-                        "Connects to a database and drops you into a sql repl",
-                    );
-                    print_usage_line(
-                        "connect(addr) -> Client",
-                        "Connects to a database and returns a client",
-                    );
-                    print_message("udp_vtable_stream(id, addr) -> UdpVTableStream");
-                    print_usage_line(
-                        "Client:send_table(component_id, ty, shape, data)",
-                        "Sends a new ComponentValue to the db",
-                    );
-                    print_usage_line("Client:send_msg(msg)", "Sends a raw message to the db");
-                    print_usage_line(
-                        "Client:send_msgs(msgs)",
-                        "Sends a list of raw messages to the db",
-                    );
-                    print_usage_line(
-                        "Client:get_component_metadata(GetComponentMetadata)",
-                        format!(
-                            "Gets a component's metadata using {} {{ id }}",
-                            Color::Blue.bold().paint("GetComponentMetadata")
-                        ),
-                    );
+                std::process::exit(0);
+            }
+            if line.starts_with(":sql") {
+                let addr = &line.strip_prefix(":sql ").unwrap_or_default();
+                let addr = if addr.is_empty() {
+                    "localhost:2240"
+                } else {
+                    addr
+                };
+                let client = match Client::connect(addr).await {
+                    Ok(c) => c,
+                    Err(err) => {
+                        println!("{err}");
+                        continue;
+                    }
+                };
+                let sql_history_path = dirs.data_dir().join(sql_history_file(addr));
+                switch_history(&mut editor, &mut history_path, sql_history_path)?;
+                mode = Mode::Sql {
+                    client,
+                    query: String::new(),
+                };
+                break;
+            }
+            if line == ":help" || line == ":h" {
+                println!("{}", Color::Yellow.bold().paint("Impeller Lua REPL"));
+                print_usage_line(
+                    ":sql addr",
+                    "Connects to a database and drops you into a sql repl. Statements end with ';', \\d or \\dt \
+                     lists tables, and \\describe <component> shows a component's schema and metadata",
+                );
+                print_usage_line(
+                    "connect(addr, [{ timeout = secs, retries = N, keepalive = secs, compression = 'lz4' }]) \
+                     -> Client",
+                    "Connects to a database and returns a client, waiting timeout for each reply and retrying \
+                     read-only requests up to retries times. compression is 'lz4' or 'zstd'",
+                );
+                print_usage_line(
+                    "mirror(src, dest, [{ component = 'imu.*', duration = secs }])",
+                    "Forwards src's real-time stream to dest, both clients from connect, until enter is pressed",
+                );
+                print_usage_line(
+                    "diff(addr_a, addr_b) -> count",
+                    "Prints the components, schemas and metadata that differ between two dbs, removals from a in \
+                     red, additions in b in green and changes in yellow",
+                );
+                print_usage_line(
+                    "replay(path, addr, [{ speed = N }])",
+                    "Sends a capture made by Client:record to a db, keeping its timing scaled by speed",
+                );
+                print_message("udp_vtable_stream(id, addr) -> UdpVTableStream");
+                print_usage_line(
+                    "table_vtable(component_id, ty, shape)",
+                    "Describes the vtable layout Client:send_table uses for a component",
+                );
 
-                    print_usage_line("Client:dump_metadata()", "Dumps all metadata from the db ");
-                    print_usage_line(
-                        "Client:get_schema(GetSchema)",
-                        format!(
-                            "Gets a components schema {} {{ id }}",
-                            Color::Blue.bold().paint("GetSchema")
-                        ),
-                    );
-                    print_usage_line(
-                        "Client:save_archive(path, format)",
-                        r#"Dumps the database to arrow-ipc or parquet files at the specified path
+                print_usage_line(
+                    "Client:send_table(component_id, ty, shape, data, checked)",
+                    "Sends a new ComponentValue to the db, erroring on out of range values if checked is true",
+                );
+                print_usage_line(
+                    "Client:set(component_id, value)",
+                    "Sets a component to a number, a string with a unit like '90 deg', or a table of either, \
+                     converting to its unit and scale metadata",
+                );
+                print_usage_line(
+                    "Client:send_batch({ { component, ty, shape, data, checked }, ... })",
+                    "Sends several components' values in one table packet, omitting shape for scalars",
+                );
+                print_usage_line("Client:send_msg(msg)", "Sends a raw message to the db");
+                print_usage_line(
+                    "Client:send_msgs(msgs)",
+                    "Sends a list of raw messages to the db",
+                );
+                print_usage_line(
+                    "Client:stream(Stream, [{ every = N }])",
+                    "Prints streamed values until enter is pressed, only every Nth value per component if set",
+                );
+                print_usage_line(
+                    "Client:stream{ behavior, filter = { component = 'imu.*' } }",
+                    "Streams only the components whose names match the glob, for stream and stream_with",
+                );
+                print_usage_line(
+                    "Client:stream_with(Stream, fn(component_id, timestamp, value))",
+                    "Calls fn for every streamed value until it returns false. Slow for high rate components, \
+                     so prefer a fixed rate stream with a low frequency",
+                );
+                print_usage_line(
+                    "Client:watch(component_id, [{ interval = secs }])",
+                    "Redraws a component's latest value with its min, max and sample rate until enter is pressed",
+                );
+                print_usage_line(
+                    "Client:record(path, [{ components = { id, ... }, duration = secs }])",
+                    "Records the real-time stream, with vtables and timing, to a capture file until enter is pressed",
+                );
+                print_usage_line(
+                    "Client:stream_msgs(msg_id, [{ format = 'table' | 'json' }])",
+                    "Prints streamed msgs as tables of fields and units, or as JSON lines, until enter is pressed",
+                );
+                print_usage_line(
+                    "Client:set_stream_state_batch({ SetStreamState, ... })",
+                    "Sets several streams' state together, so they resume emitting in step",
+                );
+                print_usage_line(
+                    "Client:get_component_metadata(GetComponentMetadata)",
+                    format!(
+                        "Gets a component's metadata, optionally as of a timestamp, using {} {{ id, at }}",
+                        Color::Blue.bold().paint("GetComponentMetadata")
+                    ),
+                );
+
+                print_usage_line(
+                    "Client:put_asset(path_or_table, [{ id, name }]) -> id",
+                    "Uploads a .glb or .json asset file, or a table like { mesh = { Sphere = { radius = 1.0 } } } \
+                     with a name. Panels, meshes and materials are supported",
+                );
+                print_usage_line(
+                    "Client:get_asset_to_file(id, path)",
+                    "Writes the newest version of an asset to a file, as .glb bytes or JSON",
+                );
+                print_usage_line(
+                    "Client:set_glb(id, path, [validate])",
+                    "Uploads a .glb file to the msg log under id, checking its glTF header unless validate is false",
+                );
+                print_usage_line(
+                    "Client:set_component_enum(component_id, { [0] = 'IDLE', [1] = 'ARMED' })",
+                    "Names a component's integer values, which are then printed and queried by name",
+                );
+                print_usage_line(
+                    "Client:set_component_bits(component_id, { [0] = 'ARMED', [3] = 'GPS_LOCK' })",
+                    "Names the bits of a flags component, which are then printed by name and queried as bool columns",
+                );
+                print_usage_line(
+                    "Client:set_component_recording(component_id, recording)",
+                    "Stops or resumes writing a component to disk, it's still streamed live while excluded",
+                );
+                print_usage_line(
+                    "Client:set_retention(component_id, { max_age = secs, max_bytes = n, max_samples = n })",
+                    "Limits how much of a component's history the db keeps, any unset limit is unbounded",
+                );
+                print_usage_line(
+                    "Client:compact()",
+                    "Drops the samples past each retention policy now, returning { samples_dropped, bytes_reclaimed }",
+                );
+                print_usage_line(
+                    "Client:server_time()",
+                    "Gets the server's current time in microseconds since the unix epoch",
+                );
+                print_usage_line(
+                    "Client:status()",
+                    "Checks the connection, returning { addr, connected, reconnects, rtt_ms }",
+                );
+                print_usage_line(
+                    "Client:flush()",
+                    "Waits until everything sent so far has been synced to disk",
+                );
+                print_usage_line(
+                    "Client:sql_to_file(sql, path, [format])",
+                    "Writes a query's results to a file and returns the row count. The format is 'csv', \
+                     'json_lines', 'parquet' or 'arrow_ipc', and is guessed from path's extension if not set",
+                );
+                print_usage_line("Client:dump_metadata()", "Dumps all metadata from the db ");
+                print_usage_line(
+                    "Client:find(glob, [{ kind = 'component' | 'msg', metadata = { key = glob | true } }])",
+                    "Finds the metadata of the components and msgs whose names match the glob, like 'imu*'",
+                );
+                print_usage_line(
+                    "Client:get_schema(GetSchema)",
+                    format!(
+                        "Gets a components schema {} {{ id }}",
+                        Color::Blue.bold().paint("GetSchema")
+                    ),
+                );
+                print_usage_line(
+                    "Client:migrate_component(MigrateComponent)",
+                    format!(
+                        "Changes a component's shape or type, keeping old data readable {} {{ component_id, schema }}",
+                        Color::Blue.bold().paint("MigrateComponent")
+                    ),
+                );
+                print_usage_line(
+                    "Client:get_latest(component_id)",
+                    "Gets a component's latest value as a number, or nested tables for arrays",
+                );
+                print_usage_line(
+                    "Client:get_time_series(component_id, start, stop)",
+                    "Prints a component's history. start can instead be a relative range like 'last 5m', \
+                     '-30s..' or '+1s..-1s', measured from the newest (-) or oldest (+) data in the db. \
+                     Long ranges are shown as per second or per minute means",
+                );
+                print_usage_line(
+                    "Client:get_msgs(msg_id, start, stop)",
+                    "Prints a msg's log entries, taking the same ranges as get_time_series",
+                );
+                print_usage_line(
+                    "Client:export_time_series(component_id, start, stop, path, [format])",
+                    "Writes a component's whole history to a file and returns the sample count. The format is \
+                     'arrow_ipc', 'parquet', 'csv' or 'json_lines', and is guessed from path's extension if not set",
+                );
+                print_usage_line(
+                    "Client:plot(component_id, [{ index = 0, range = { start, stop }, width = 80, height = 16 }])",
+                    "Draws one element of a component's history as a braille line plot in the terminal",
+                );
+                print_usage_line(
+                    "Client:get_time_series_all(component, start, stop)",
+                    "Gets a component's time series for every entity that has it",
+                );
+                print_usage_line(
+                    "Client:get_events(start, stop, min_severity)",
+                    "Prints logged events, optionally filtered to 'warning', 'error', etc. and above",
+                );
+                print_usage_line(
+                    "Client:save_archive(path, format)",
+                    r#"Dumps the database to arrow-ipc or parquet files at the specified path
  - path - the path to the folder where the contents will be dumped
  - format - 'arrow-ipc' (default), 'parquet' - the format that will be used"#,
-                    );
-                    println!("{}", Color::Yellow.bold().paint("Messages"));
-                    print_message("SetComponentMetadata { component_id, name, metadata }");
-                    print_message(
-                        "UdpUnicast { stream = { filter = { component_id }, id }, addr }",
-                    );
-                    print_message("SetStreamState { id, playing, tick, time_step }");
-                    break;
-                }
-                editor.save_history(&history_path)?;
-                editor.add_history_entry(line.clone())?;
-                match &mut mode {
-                    Mode::Sql(client) => {
-                        if line.is_empty() {
+                );
+                print_usage_line(
+                    "Client:create_snapshot(path)",
+                    "Copies the database as it is now to a new folder on the server, without stopping it",
+                );
+                print_usage_line(
+                    "Client:restore_snapshot(path)",
+                    "Replaces the database's contents with a snapshot on the server. Subscribers should reconnect",
+                );
+                println!("{}", Color::Yellow.bold().paint("Messages"));
+                print_message("SetComponentMetadata { component_id, name, metadata }");
+                print_message("UdpUnicast { stream = { filter = { component_id }, id }, addr }");
+                print_message("SetStreamState { id, playing, tick, time_step }");
+                break;
+            }
+            editor.save_history(&history_path)?;
+            editor.add_history_entry(line.clone())?;
+            match &mut mode {
+                Mode::Sql { client, query } => {
+                    if query.is_empty() {
+                        if let Some(cmd) = SqlMetaCommand::parse(&line) {
+                            if let Err(err) = client.sql_meta_command(cmd).await {
+                                let err = err.to_string();
+                                println!("{}", Color::Red.paint(&err));
+                            }
                             continue;
                         }
-                        if let Err(err) = client.sql(&line).await {
-                            let err = err.to_string();
-                            println!("{}", Color::Red.paint(&err));
+                        if line.trim().is_empty() {
+                            continue;
                         }
                     }
-                    Mode::Lua => match lua.load(&line).eval_async::<MultiValue>().await {
-                        Ok(values) => {
-                            println!(
-                                "{}",
-                                values
-                                    .iter()
-                                    .map(|value| {
-                                        #[cfg(not(feature = "highlight"))]
-                                        let out = format!("{:#?}", value);
-                                        #[cfg(feature = "highlight")]
-                                        let out = syntastica::highlight(
-                                            format!("{:#?}", value),
-                                            syntastica_parsers::Lang::Lua,
-                                            &syntastica_parsers::LanguageSetImpl::new(),
-                                            &mut syntastica::renderer::TerminalRenderer::new(None),
-                                            syntastica_themes::catppuccin::mocha(),
-                                        )
-                                        .unwrap()
-                                        .to_string();
-                                        out
-                                    })
-                                    .collect::<Vec<_>>()
-                                    .join("\t")
-                            );
-                            break;
-                        }
-                        Err(Error::SyntaxError {
-                            incomplete_input: true,
-                            ..
-                        }) => {
-                            line.push('\n');
-                            prompt = ">> ";
-                        }
-                        Err(e) => {
-                            let err = e.to_string();
-                            let err = Color::Red.paint(&err);
-                            eprintln!("{}", err);
-                            break;
-                        }
-                    },
+                    query.push_str(&line);
+                    query.push('\n');
+                    let Some(sql) = query.trim_end().strip_suffix(';') else {
+                        prompt = "  ❯❯ ";
+                        continue;
+                    };
+                    if let Err(err) = client.sql(sql).await {
+                        let err = err.to_string();
+                        println!("{}", Color::Red.paint(&err));
+                    }
+                    query.clear();
+                    break;
                 }
+                Mode::Lua => match lua.load(&line).eval_async::<MultiValue>().await {
+                    Ok(values) => {
+                        println!(
+                            "{}",
+                            values
+                                .iter()
+                                .map(|value| {
+                                    #[cfg(not(feature = "highlight"))]
+                                    let out = colorize_value(value);
+                                    #[cfg(feature = "highlight")]
+                                    let out = syntastica::highlight(
+                                        format!("{:#?}", value),
+                                        syntastica_parsers::Lang::Lua,
+                                        &syntastica_parsers::LanguageSetImpl::new(),
+                                        &mut syntastica::renderer::TerminalRenderer::new(None),
+                                        syntastica_themes::catppuccin::mocha(),
+                                    )
+                                    .unwrap()
+                                    .to_string();
+                                    out
+                                })
+                                .collect::<Vec<_>>()
+                                .join("\t")
+                        );
+                        break;
+                    }
+                    Err(Error::SyntaxError {
+                        incomplete_input: true,
+                        ..
+                    }) => {
+                        line.push('\n');
+                        prompt = ">> ";
+                    }
+                    Err(e) => {
+                        let err = e.to_string();
+                        let err = Color::Red.paint(&err);
+                        eprintln!("{}", err);
+                        break;
+                    }
+                },
             }
         }
     }
@@ -966,7 +3762,135 @@ pub async fn run(args: Args) -> anyhow::Result<()> {
 
 enum Mode {
     Lua,
-    Sql(Client),
+    Sql {
+        client: Client,
+        /// The lines of a statement that hasn't been terminated with `;` yet
+        query: String,
+    },
+}
+
+/// A psql style backslash command in the sql repl
+#[derive(Debug, PartialEq, Eq)]
+enum SqlMetaCommand {
+    ListTables,
+    Describe(String),
+}
+
+impl SqlMetaCommand {
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.trim().trim_end_matches(';').split_whitespace();
+        let cmd = words.next()?;
+        let arg = words.next();
+        match (cmd, arg) {
+            ("\\d" | "\\dt", None) => Some(Self::ListTables),
+            ("\\d" | "\\describe", Some(name)) => Some(Self::Describe(name.to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// The name the db registers a component's sql table under
+fn sql_table_name(component_name: &str) -> String {
+    component_name
+        .to_case(convert_case::Case::Snake)
+        .replace('.', "_")
+}
+
+/// Each db address gets its own sql history, so queries for one db don't clutter another's
+fn sql_history_file(addr: &str) -> String {
+    let addr: String = addr
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("impeller2-sql-history-{addr}")
+}
+
+fn switch_history<H: Helper>(
+    editor: &mut Editor<H, rustyline::history::FileHistory>,
+    current: &mut PathBuf,
+    next: PathBuf,
+) -> anyhow::Result<()> {
+    editor.save_history(current)?;
+    editor.clear_history()?;
+    if next.exists() {
+        editor.load_history(&next)?;
+    }
+    *current = next;
+    Ok(())
+}
+
+/// Renders a lua value with basic ANSI colors, a lightweight stand-in for syntastica when `highlight` is disabled
+#[cfg(not(feature = "highlight"))]
+fn colorize_value(value: &Value) -> String {
+    use std::{collections::HashSet, ffi::c_void, fmt::Write};
+
+    fn render(
+        value: &Value,
+        indent: usize,
+        visited: &mut HashSet<*const c_void>,
+        out: &mut String,
+    ) {
+        match value {
+            Value::Nil => {
+                let _ = write!(out, "{}", Color::DarkGray.paint("nil"));
+            }
+            Value::Boolean(b) => {
+                let _ = write!(out, "{}", Color::Yellow.paint(b.to_string()));
+            }
+            Value::Integer(i) => {
+                let _ = write!(out, "{}", Color::Cyan.paint(i.to_string()));
+            }
+            Value::Number(n) => {
+                let _ = write!(out, "{}", Color::Cyan.paint(n.to_string()));
+            }
+            Value::String(s) => {
+                let s = format!("{:?}", s.to_string_lossy());
+                let _ = write!(out, "{}", Color::Green.paint(s));
+            }
+            Value::Table(table) => {
+                let ptr = table.to_pointer();
+                if !visited.insert(ptr) {
+                    let _ = write!(out, "{}", Color::DarkGray.paint("<recursive table>"));
+                    return;
+                }
+                let pairs = table
+                    .pairs::<Value, Value>()
+                    .filter_map(Result::ok)
+                    .collect::<Vec<_>>();
+                if pairs.is_empty() {
+                    out.push_str("{}");
+                } else {
+                    out.push_str("{\n");
+                    for (key, value) in pairs {
+                        out.push_str(&"    ".repeat(indent + 1));
+                        if let Value::String(key) = &key {
+                            out.push_str(&key.to_string_lossy());
+                        } else {
+                            out.push('[');
+                            render(&key, indent + 1, visited, out);
+                            out.push(']');
+                        }
+                        out.push_str(" = ");
+                        render(&value, indent + 1, visited, out);
+                        out.push_str(",\n");
+                    }
+                    out.push_str(&"    ".repeat(indent));
+                    out.push('}');
+                }
+                visited.remove(&ptr);
+            }
+            Value::Error(err) => {
+                let _ = write!(out, "{}", Color::Red.paint(err.to_string()));
+            }
+            other => {
+                let _ = write!(out, "{}", Color::Purple.paint(format!("{other:?}")));
+            }
+        }
+    }
+
+    let mut out = String::new();
+    render(value, 0, &mut HashSet::new(), &mut out);
+    out
 }
 
 fn print_usage_line(name: impl Display, desc: impl Display) {
@@ -980,7 +3904,100 @@ fn print_message(msg: impl Display) {
     println!("{msg}");
 }
 
-struct DebugSink;
+/// Prints values to stdout, skipping all but every `decimate`th value of each component
+/// The latest value of a watched component and the running min and max of each of its elements
+#[derive(Default)]
+struct WatchStats {
+    latest: Vec<f64>,
+    min: Vec<f64>,
+    max: Vec<f64>,
+    timestamp: Option<Timestamp>,
+    samples: u64,
+}
+
+impl WatchStats {
+    fn push(&mut self, timestamp: Option<Timestamp>, values: impl Iterator<Item = f64>) {
+        self.latest.clear();
+        self.latest.extend(values);
+        // a component's shape doesn't change, but start over if it somehow does rather than mixing elements
+        if self.min.len() != self.latest.len() {
+            self.min = self.latest.clone();
+            self.max = self.latest.clone();
+        }
+        for ((value, min), max) in self.latest.iter().zip(&mut self.min).zip(&mut self.max) {
+            *min = min.min(*value);
+            *max = max.max(*value);
+        }
+        self.timestamp = timestamp.or(self.timestamp);
+        self.samples += 1;
+    }
+
+    /// The average sample rate in Hz since the watch started
+    fn rate(&self, elapsed: Duration) -> f64 {
+        let secs = elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.samples as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    fn table(&self) -> tabled::Table {
+        let mut builder = tabled::builder::Builder::default();
+        builder.push_record(["ELEMENT", "VALUE", "MIN", "MAX"].map(String::from));
+        for (i, ((value, min), max)) in self.latest.iter().zip(&self.min).zip(&self.max).enumerate()
+        {
+            let element = if self.latest.len() == 1 {
+                "value".to_string()
+            } else {
+                format!("[{i}]")
+            };
+            builder.push_record([element, value.to_string(), min.to_string(), max.to_string()]);
+        }
+        let mut table = builder.build();
+        table.with(tabled::settings::Style::rounded()).with(
+            tabled::settings::style::BorderColor::filled(tabled::settings::Color::FG_BLUE),
+        );
+        table
+    }
+}
+
+struct DebugSink {
+    decimate: usize,
+    counts: HashMap<ComponentId, usize>,
+    names: HashMap<ComponentId, ValueNames>,
+}
+
+impl DebugSink {
+    fn new(decimate: usize) -> Self {
+        Self {
+            decimate: decimate.max(1),
+            counts: HashMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Prints the values of components with `enum` or `bits` metadata by name
+    fn with_value_names<'a>(
+        mut self,
+        metadata: impl IntoIterator<Item = &'a ComponentMetadata>,
+    ) -> Self {
+        self.names = metadata
+            .into_iter()
+            .filter_map(|metadata| {
+                Some((metadata.component_id, ValueNames::from_metadata(metadata)?))
+            })
+            .collect();
+        self
+    }
+
+    fn should_print(&mut self, component_id: ComponentId) -> bool {
+        let count = self.counts.entry(component_id).or_default();
+        let print = *count % self.decimate == 0;
+        *count = count.wrapping_add(1);
+        print
+    }
+}
 
 impl Decomponentize for DebugSink {
     type Error = core::convert::Infallible;
@@ -991,8 +4008,480 @@ impl Decomponentize for DebugSink {
         value: impeller2::types::ComponentView<'_>,
         timestamp: Option<Timestamp>,
     ) -> Result<(), Self::Error> {
+        if !self.should_print(component_id) {
+            return Ok(());
+        }
         let epoch = timestamp.map(hifitime::Epoch::from);
+        if let Some(name) = self
+            .names
+            .get(&component_id)
+            .and_then(|names| names.format(&value))
+        {
+            println!("{component_id:?} @ {epoch:?} = {name}");
+            return Ok(());
+        }
         println!("{component_id:?} @ {epoch:?} = {value:?}");
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::{Int64Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+
+    #[test]
+    fn test_create_table_preserves_column_order() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("c", DataType::Int64, false),
+            Field::new("a", DataType::Int64, false),
+            Field::new("b", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![3])),
+                Arc::new(Int64Array::from(vec![1])),
+                Arc::new(Int64Array::from(vec![2])),
+            ],
+        )
+        .unwrap();
+        let table = create_table(&[batch], &FormatOptions::default()).unwrap();
+        let rendered = table.to_string();
+        let rows = rendered
+            .lines()
+            .filter(|line| line.starts_with('|'))
+            .map(|line| {
+                line.split('|')
+                    .map(str::trim)
+                    .filter(|cell| !cell.is_empty())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rows, [["c", "a", "b"], ["3", "1", "2"]]);
+    }
+
+    #[test]
+    fn test_write_batches() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("time", DataType::Int64, false),
+            Field::new("value", DataType::Int64, false),
+        ]));
+        let batches = [1, 3].map(|start| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(vec![start, start + 1])),
+                    Arc::new(Int64Array::from(vec![start * 10, (start + 1) * 10])),
+                ],
+            )
+            .unwrap()
+        });
+        let dir = std::env::temp_dir().join(format!("impeller2_cli_export_{}", fastrand::u64(..)));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let csv = dir.join("out.csv");
+        write_batches(&csv, ExportFormat::from_path(&csv).unwrap(), &batches).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&csv).unwrap(),
+            "time,value\n1,10\n2,20\n3,30\n4,40\n"
+        );
+
+        let jsonl = dir.join("out.jsonl");
+        write_batches(&jsonl, ExportFormat::from_path(&jsonl).unwrap(), &batches).unwrap();
+        let jsonl = std::fs::read_to_string(&jsonl).unwrap();
+        assert_eq!(jsonl.lines().count(), 4);
+        assert_eq!(jsonl.lines().next(), Some(r#"{"time":1,"value":10}"#));
+
+        let parquet = dir.join("out.parquet");
+        write_batches(&parquet, ExportFormat::Parquet, &batches).unwrap();
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+            std::fs::File::open(&parquet).unwrap(),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        let read: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(read.iter().map(RecordBatch::num_rows).sum::<usize>(), 4);
+        assert_eq!(read[0].schema().fields(), schema.fields());
+
+        assert_eq!(ExportFormat::from_path(Path::new("out.txt")), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_asset_value() {
+        let lua = Lua::new();
+        let table = lua
+            .load("{ mesh = { Sphere = { radius = 2.0 } } }")
+            .eval::<Value>()
+            .unwrap();
+        let asset: AssetValue = lua.from_value(table).unwrap();
+        assert_eq!(asset.kind(), "mesh");
+        let json = serde_json::to_value(&asset).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "mesh": { "Sphere": { "radius": 2.0 } } })
+        );
+
+        let bytes = asset.to_postcard().unwrap();
+        let AssetValue::Mesh(Mesh::Sphere { radius }) =
+            AssetValue::from_postcard("mesh", &bytes).unwrap()
+        else {
+            panic!("expected a sphere mesh");
+        };
+        assert_eq!(radius, 2.0);
+        assert!(AssetValue::from_postcard("glb", &bytes).is_err());
+    }
+
+    #[test]
+    fn test_validate_glb() {
+        let mut glb = b"glTF".to_vec();
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&20u32.to_le_bytes());
+        glb.extend_from_slice(&[0; 8]);
+        validate_glb(&glb).unwrap();
+
+        assert!(validate_glb(&glb[..8]).is_err());
+        assert!(validate_glb(&glb[..16]).is_err());
+
+        let mut bad_magic = glb.clone();
+        bad_magic[..4].copy_from_slice(b"glTf");
+        assert!(validate_glb(&bad_magic).is_err());
+
+        let mut bad_version = glb.clone();
+        bad_version[4..8].copy_from_slice(&1u32.to_le_bytes());
+        assert!(validate_glb(&bad_version).is_err());
+    }
+
+    #[test]
+    fn test_debug_sink_decimation() {
+        let a = ComponentId::new("a");
+        let b = ComponentId::new("b");
+        let mut sink = DebugSink::new(3);
+        let printed = (0..6).map(|_| sink.should_print(a)).collect::<Vec<_>>();
+        assert_eq!(printed, [true, false, false, true, false, false]);
+        // each component keeps its own count
+        assert!(sink.should_print(b));
+
+        let mut sink = DebugSink::new(1);
+        assert!((0..4).all(|_| sink.should_print(a)));
+    }
+
+    #[test]
+    fn test_send_type_mismatch_message() {
+        let lua = Lua::new();
+        let buf = lua.load(r#"{ "a", "b" }"#).eval::<Value>().unwrap();
+        let err = lua_array::<f64>(&lua, buf, ComponentId(12), PrimType::F64).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("component 12 expects an array of f64, got a table of string"),
+            "{err}"
+        );
+
+        let buf = lua.load("{ 1.0, 2.5 }").eval::<Value>().unwrap();
+        let values = lua_array::<f64>(&lua, buf, ComponentId(12), PrimType::F64).unwrap();
+        assert_eq!(values, [1.0, 2.5]);
+    }
+
+    #[test]
+    fn test_checked_send_element_count() {
+        let lua = Lua::new();
+        let bytes = |src: &str| {
+            let buf = lua.load(src).eval::<Value>().unwrap();
+            lua_component_bytes(&lua, buf, ComponentId(12), PrimType::U8, &[3], true)
+        };
+        assert_eq!(bytes("{ 1, 2, 3 }").unwrap(), [1, 2, 3]);
+        let err = bytes("{ 1, 2 }").unwrap_err();
+        assert_eq!(err.to_string(), "component 12 has 3 elements, got 2");
+        assert!(bytes("{ 1, 2, 3, 4 }").is_err());
+    }
+
+    #[stellarator::test]
+    async fn test_client_reconnects() {
+        use impeller2_stellar::{PacketSink, PacketStream};
+        use stellarator::{io::SplitExt, net::TcpListener};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        stellarator::spawn(async move {
+            // the first connection drops as soon as a request arrives, like a db restarting
+            let (rx, tx) = listener.accept().await.unwrap().split();
+            let mut rx = PacketStream::new(rx);
+            rx.next(vec![0; 128]).await.unwrap();
+            drop((rx, tx));
+
+            let (rx, tx) = listener.accept().await.unwrap().split();
+            let mut rx = PacketStream::new(rx);
+            let tx = PacketSink::new(tx);
+            loop {
+                let OwnedPacket::Msg(m) = rx.next(vec![0; 128]).await.unwrap() else {
+                    panic!("non msg pkt");
+                };
+                assert_eq!(m.id, GetServerTime::ID);
+                let reply = ServerTime(Timestamp(42)).with_request_id(m.req_id);
+                tx.send(reply).await.0.unwrap();
+            }
+        });
+
+        let mut client = Client::connect(addr).await.unwrap();
+        client.set_reconnect_policy(ReconnectPolicy {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(10),
+            max_attempts: Some(5),
+        });
+        assert_eq!(client.server_time().await.unwrap(), Timestamp(42));
+        let status = client.status().await;
+        assert!(status.connected);
+        assert_eq!(status.reconnects, 1);
+        assert!(status.rtt_ms.is_some());
+    }
+
+    #[stellarator::test]
+    async fn test_client_request_timeout() {
+        use impeller2_stellar::{PacketSink, PacketStream};
+        use stellarator::{io::SplitExt, net::TcpListener};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        stellarator::spawn(async move {
+            // the first connection stays open but never replies, like a link that has gone quiet
+            let (rx, _tx) = listener.accept().await.unwrap().split();
+            let mut quiet_rx = PacketStream::new(rx);
+            quiet_rx.next(vec![0; 128]).await.unwrap();
+
+            let (rx, tx) = listener.accept().await.unwrap().split();
+            let mut rx = PacketStream::new(rx);
+            let tx = PacketSink::new(tx);
+            loop {
+                let OwnedPacket::Msg(m) = rx.next(vec![0; 128]).await.unwrap() else {
+                    panic!("non msg pkt");
+                };
+                let reply = ServerTime(Timestamp(42)).with_request_id(m.req_id);
+                tx.send(reply).await.0.unwrap();
+            }
+        });
+
+        let config = ClientConfig {
+            request_timeout: Duration::from_millis(50),
+            retries: 0,
+            keepalive: None,
+        };
+        let mut client = Client::connect(addr).await.unwrap().with_config(config);
+        client.set_reconnect_policy(ReconnectPolicy {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(10),
+            max_attempts: Some(5),
+        });
+        let err = client.server_time().await.unwrap_err();
+        assert!(is_timeout(&err), "{err}");
+
+        // with a retry the request is resent on a fresh connection, which answers
+        let mut client = client.with_config(ClientConfig {
+            retries: 1,
+            ..config
+        });
+        assert_eq!(client.server_time().await.unwrap(), Timestamp(42));
+        assert_eq!(client.reconnects, 1);
+    }
+
+    #[test]
+    fn test_time_series_batch() {
+        use arrow::array::{Array, AsArray};
+        use arrow::datatypes::Float64Type;
+
+        let schema = impeller2::schema::Schema::<Vec<u64>>::new(PrimType::F64, [2u64]).unwrap();
+        let timestamps = [Timestamp(10), Timestamp(20)];
+        let data = [1.0f64, 2.0, 3.0, 4.0];
+        let batch = time_series_batch(&timestamps, data.as_bytes(), &schema).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let values = batch.column(1).as_fixed_size_list();
+        assert_eq!(values.value_length(), 2);
+        let row = values.value(1);
+        assert_eq!(row.as_primitive::<Float64Type>().values(), &[3.0, 4.0]);
+        assert!(time_series_batch(&timestamps, &data.as_bytes()[..8], &schema).is_err());
+
+        let path =
+            std::env::temp_dir().join(format!("impeller2_cli_ts_{}.arrow", fastrand::u64(..)));
+        write_batches(
+            &path,
+            ExportFormat::from_path(&path).unwrap(),
+            &[batch.clone()],
+        )
+        .unwrap();
+        let reader =
+            arrow::ipc::reader::FileReader::try_new(std::fs::File::open(&path).unwrap(), None)
+                .unwrap();
+        let read: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(read, [batch]);
+        assert_eq!(read[0].column(0).len(), 2);
+        std::fs::remove_file(&path).unwrap();
+
+        let schema = impeller2::schema::Schema::<Vec<u64>>::new(PrimType::Bool, [0u64; 0]).unwrap();
+        let batch = time_series_batch(&timestamps, &[1, 0], &schema).unwrap();
+        assert!(batch.column(1).as_boolean().value(0));
+        assert!(!batch.column(1).as_boolean().value(1));
+    }
+
+    #[test]
+    fn test_sql_meta_command() {
+        assert_eq!(
+            SqlMetaCommand::parse("\\dt"),
+            Some(SqlMetaCommand::ListTables)
+        );
+        assert_eq!(
+            SqlMetaCommand::parse(" \\d "),
+            Some(SqlMetaCommand::ListTables)
+        );
+        assert_eq!(
+            SqlMetaCommand::parse("\\describe rocket.world_pos;"),
+            Some(SqlMetaCommand::Describe("rocket.world_pos".to_string()))
+        );
+        assert_eq!(
+            SqlMetaCommand::parse("\\d accel"),
+            Some(SqlMetaCommand::Describe("accel".to_string()))
+        );
+        assert_eq!(SqlMetaCommand::parse("select * from accel;"), None);
+        assert_eq!(SqlMetaCommand::parse("\\dt extra"), None);
+
+        assert_eq!(sql_table_name("rocket.WorldPos"), "rocket_world_pos");
+        assert_eq!(
+            sql_history_file("localhost:2240"),
+            "impeller2-sql-history-localhost_2240"
+        );
+    }
+
+    #[test]
+    fn test_exec_json_conversion() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("time", DataType::Int64, false),
+            Field::new("value", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(Int64Array::from(vec![10, 20])),
+            ],
+        )
+        .unwrap();
+        let json = batches_to_json(&[batch.clone(), batch]).unwrap();
+        let rows = json.as_array().unwrap();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[1]["time"], 2);
+        assert_eq!(rows[1]["value"], 20);
+        assert_eq!(batches_to_json(&[]).unwrap(), serde_json::json!([]));
+
+        let lua = Lua::new();
+        let values = lua
+            .load("return { name = 'accel', shape = { 3 } }")
+            .eval::<MultiValue>()
+            .unwrap();
+        assert_eq!(
+            lua_values_to_json(values).unwrap(),
+            serde_json::json!({ "name": "accel", "shape": [3] })
+        );
+        let values = lua.load("return 1, 'two'").eval::<MultiValue>().unwrap();
+        assert_eq!(
+            lua_values_to_json(values).unwrap(),
+            serde_json::json!([1, "two"])
+        );
+        let values = lua.load("x = 1").eval::<MultiValue>().unwrap();
+        assert_eq!(lua_values_to_json(values).unwrap(), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_watch_stats() {
+        let mut stats = WatchStats::default();
+        stats.push(Some(Timestamp(10)), [1.0, -2.0].into_iter());
+        stats.push(None, [3.0, -5.0].into_iter());
+        stats.push(Some(Timestamp(30)), [2.0, -3.0].into_iter());
+        assert_eq!(stats.latest, [2.0, -3.0]);
+        assert_eq!(stats.min, [1.0, -5.0]);
+        assert_eq!(stats.max, [3.0, -2.0]);
+        assert_eq!(stats.timestamp, Some(Timestamp(30)));
+        assert_eq!(stats.samples, 3);
+        assert_eq!(stats.rate(Duration::from_secs(2)), 1.5);
+        assert_eq!(stats.rate(Duration::ZERO), 0.0);
+
+        stats.push(None, [7.0].into_iter());
+        assert_eq!(stats.min, [7.0]);
+        assert_eq!(stats.max, [7.0]);
+        assert!(stats.table().to_string().contains("value"));
+    }
+
+    #[test]
+    fn test_msg_table() {
+        let metadata = MsgMetadata {
+            name: "GpsFix".to_string(),
+            schema: <() as postcard_schema::Schema>::SCHEMA.into(),
+            metadata: [
+                ("unit.alt".to_string(), "m".to_string()),
+                ("unit.pos.lat".to_string(), "deg".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let msg = serde_json::json!({
+            "alt": 120.5,
+            "pos": { "lat": 37.7, "lon": -122.4 },
+            "sats": [3, 7, 12],
+            "fixes": [{ "ok": true }],
+            "label": "rtk",
+        });
+        let mut fields = vec![];
+        flatten_msg_fields(String::new(), &msg, &mut fields);
+        let fields = fields
+            .iter()
+            .map(|(path, value)| (path.as_str(), value.as_str()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(fields["alt"], "120.5");
+        assert_eq!(fields["pos.lat"], "37.7");
+        assert_eq!(fields["pos.lon"], "-122.4");
+        assert_eq!(fields["sats"], "[3,7,12]");
+        assert_eq!(fields["fixes[0].ok"], "true");
+        assert_eq!(fields["label"], "rtk");
+        assert_eq!(metadata.field_unit("pos.lat"), Some("deg"));
+
+        let table = msg_table(&metadata, &msg).to_string();
+        assert!(table.contains("pos.lat"));
+        assert!(table.contains("deg"));
+        let table = msg_table(&metadata, &serde_json::json!(42)).to_string();
+        assert!(table.contains("value"));
+    }
+
+    #[test]
+    fn test_interruptible() {
+        let interrupt = Interruptible::begin();
+        assert!(OP_IN_FLIGHT.load(atomic::Ordering::SeqCst));
+        assert!(interrupt.check().is_ok());
+        // what the ctrl-c handler does while an operation is in flight
+        OP_INTERRUPTED.store(true, atomic::Ordering::SeqCst);
+        assert!(interrupt.check().is_err());
+        drop(interrupt);
+        assert!(!OP_IN_FLIGHT.load(atomic::Ordering::SeqCst));
+
+        // a new operation starts uninterrupted
+        let stop = StopSignal::new();
+        assert!(stop.running());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("imu.*", "imu.accel"));
+        assert!(glob_match("imu.*", "imu."));
+        assert!(!glob_match("imu.*", "imu_accel"));
+        assert!(!glob_match("imu.*", "gps.imu.accel"));
+        assert!(glob_match("*.accel", "gps.imu.accel"));
+        assert!(glob_match("*imu*", "gps.imu.accel"));
+        assert!(glob_match("imu.gyro_?", "imu.gyro_x"));
+        assert!(!glob_match("imu.gyro_?", "imu.gyro_xy"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+        assert!(glob_match("accel", "accel"));
+        assert!(glob_match("*", ""));
+    }
+}
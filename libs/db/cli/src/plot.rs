@@ -0,0 +1,209 @@
+//! Braille line plots for looking at a signal without launching the editor
+//!
+//! Each character cell holds a 2x4 grid of braille dots, so a plot `width` characters wide and `height` tall has
+//! `2 * width` by `4 * height` points of resolution.
+
+use std::fmt::Write;
+
+/// A grid of braille characters that dots can be drawn on
+pub struct BrailleCanvas {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+}
+
+impl BrailleCanvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![0; width * height],
+        }
+    }
+
+    /// The canvas size in dots
+    pub fn dots(&self) -> (usize, usize) {
+        (self.width * 2, self.height * 4)
+    }
+
+    /// Sets the dot at `(x, y)`, where `y = 0` is the bottom row. Dots off the canvas are ignored.
+    pub fn set(&mut self, x: usize, y: usize) {
+        let (dots_x, dots_y) = self.dots();
+        if x >= dots_x || y >= dots_y {
+            return;
+        }
+        let row = dots_y - 1 - y;
+        let cell = &mut self.cells[(row / 4) * self.width + x / 2];
+        // braille numbers its dots down the left column, then down the right, with the bottom row added last
+        const BITS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+        *cell |= BITS[x % 2][row % 4];
+    }
+
+    /// Draws a straight line between two dots
+    pub fn line(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let (mut x, mut y) = (from.0 as isize, from.1 as isize);
+        let (x1, y1) = (to.0 as isize, to.1 as isize);
+        let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+        let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+        let mut err = dx + dy;
+        loop {
+            self.set(x as usize, y as usize);
+            if x == x1 && y == y1 {
+                return;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// The canvas as lines of text, top row first
+    pub fn rows(&self) -> impl Iterator<Item = String> + '_ {
+        self.cells.chunks(self.width.max(1)).map(|row| {
+            row.iter()
+                .map(|&bits| char::from_u32(0x2800 + bits as u32).expect("braille is valid"))
+                .collect()
+        })
+    }
+}
+
+/// Plots `points` as a line, with the y range labelled on the left and `x_labels` under the start and end
+///
+/// Points with a non-finite coordinate are skipped. A flat signal is drawn through the middle of the plot.
+pub fn render(
+    points: &[(f64, f64)],
+    width: usize,
+    height: usize,
+    x_labels: &[String; 2],
+) -> String {
+    let (width, height) = (width.max(1), height.max(1));
+    let points = points
+        .iter()
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .copied()
+        .collect::<Vec<_>>();
+    let bounds = |coords: &mut dyn Iterator<Item = f64>| {
+        coords.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        })
+    };
+    let (x_min, x_max) = bounds(&mut points.iter().map(|p| p.0));
+    let (mut y_min, mut y_max) = bounds(&mut points.iter().map(|p| p.1));
+    if y_min == y_max {
+        y_min -= 1.0;
+        y_max += 1.0;
+    }
+
+    let mut canvas = BrailleCanvas::new(width, height);
+    let (dots_x, dots_y) = canvas.dots();
+    let scale = |v: f64, min: f64, max: f64, dots: usize| {
+        if max > min {
+            ((v - min) / (max - min) * (dots - 1) as f64).round() as usize
+        } else {
+            0
+        }
+    };
+    let dots = points
+        .iter()
+        .map(|&(x, y)| {
+            (
+                scale(x, x_min, x_max, dots_x),
+                scale(y, y_min, y_max, dots_y),
+            )
+        })
+        .collect::<Vec<_>>();
+    match dots.as_slice() {
+        [] => {}
+        [dot] => canvas.set(dot.0, dot.1),
+        dots => {
+            for pair in dots.windows(2) {
+                canvas.line(pair[0], pair[1]);
+            }
+        }
+    }
+
+    let (top, bottom) = if points.is_empty() {
+        (String::new(), String::new())
+    } else {
+        (format!("{y_max:.4}"), format!("{y_min:.4}"))
+    };
+    let label_width = top.len().max(bottom.len());
+    let mut out = String::new();
+    for (i, row) in canvas.rows().enumerate() {
+        let label = match i {
+            0 => top.as_str(),
+            i if i == height - 1 => bottom.as_str(),
+            _ => "",
+        };
+        let _ = writeln!(out, "{label:>label_width$} ┤{row}");
+    }
+    let _ = writeln!(out, "{:>label_width$} └{}", "", "─".repeat(width));
+    let [start, end] = x_labels;
+    let gap = (width + 1).saturating_sub(start.chars().count() + end.chars().count());
+    let _ = write!(
+        out,
+        "{:>label_width$}  {start}{}{end}",
+        "",
+        " ".repeat(gap.max(1))
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_braille_canvas() {
+        let mut canvas = BrailleCanvas::new(2, 1);
+        assert_eq!(canvas.dots(), (4, 4));
+        canvas.set(0, 3);
+        canvas.set(1, 0);
+        canvas.set(4, 0);
+        let rows = canvas.rows().collect::<Vec<_>>();
+        assert_eq!(rows, ["\u{2881}\u{2800}"]);
+
+        let mut canvas = BrailleCanvas::new(2, 1);
+        canvas.line((0, 0), (3, 3));
+        assert_eq!(canvas.rows().next().unwrap(), "\u{2860}\u{280a}");
+    }
+
+    #[test]
+    fn test_render_plot() {
+        let points = (0..=20)
+            .map(|i| (i as f64, (i as f64 / 20.0 * std::f64::consts::TAU).sin()))
+            .collect::<Vec<_>>();
+        let labels = ["start".to_string(), "end".to_string()];
+        let plot = render(&points, 20, 5, &labels);
+        let lines = plot.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5 + 2);
+        assert!(lines[0].starts_with(" 1.0000 ┤"));
+        assert!(lines[4].starts_with("-1.0000 ┤"));
+        assert!(lines[6].trim_start().starts_with("start"));
+        assert!(lines[6].ends_with("end"));
+        // every column has at least one dot, since consecutive points are joined by lines
+        for column in 0..20 {
+            assert!(
+                lines[..5]
+                    .iter()
+                    .any(|line| line.chars().rev().nth(19 - column) != Some('\u{2800}'))
+            );
+        }
+
+        let flat = render(&[(0.0, 3.0), (1.0, 3.0)], 4, 3, &labels);
+        let drawn = flat
+            .lines()
+            .take(3)
+            .map(|line| line.chars().any(|c| ('\u{2801}'..='\u{28ff}').contains(&c)))
+            .collect::<Vec<_>>();
+        assert_eq!(drawn, [false, true, false]);
+        let empty = render(&[], 4, 2, &labels);
+        assert_eq!(empty.lines().count(), 4);
+    }
+}
@@ -0,0 +1,107 @@
+//! A one-line progress indicator for transfers that arrive in several batches
+
+use std::{
+    io::{self, IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+/// Shows rows fetched, bytes received and time elapsed on stderr, redrawn as each batch arrives
+///
+/// Nothing is drawn for a transfer that finishes in a single batch, or when stderr isn't a terminal.
+pub struct Progress {
+    label: &'static str,
+    start: Instant,
+    rows: usize,
+    bytes: usize,
+    batches: usize,
+    enabled: bool,
+    drawn: bool,
+}
+
+impl Progress {
+    pub fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            start: Instant::now(),
+            rows: 0,
+            bytes: 0,
+            batches: 0,
+            enabled: io::stderr().is_terminal(),
+            drawn: false,
+        }
+    }
+
+    pub fn add(&mut self, rows: usize, bytes: usize) {
+        self.rows += rows;
+        self.bytes += bytes;
+        self.batches += 1;
+        if self.enabled && self.batches > 1 {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K{}", self.line(self.start.elapsed()));
+            let _ = stderr.flush();
+            self.drawn = true;
+        }
+    }
+
+    /// Clears the progress line, so the result can be printed in its place
+    pub fn finish(&mut self) {
+        if self.drawn {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+            self.drawn = false;
+        }
+    }
+
+    fn line(&self, elapsed: Duration) -> String {
+        format!(
+            "{} {} rows, {} in {:.1}s (ctrl-c to stop)",
+            self.label,
+            self.rows,
+            format_bytes(self.bytes),
+            elapsed.as_secs_f64()
+        )
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{value:.1} {}", UNITS[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_line() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+
+        let mut progress = Progress::new("fetched");
+        progress.enabled = false;
+        progress.add(4096, 32 * 1024);
+        progress.add(100, 800);
+        assert_eq!(
+            progress.line(Duration::from_millis(1250)),
+            "fetched 4196 rows, 32.8 KiB in 1.2s (ctrl-c to stop)"
+        );
+        assert!(!progress.drawn);
+    }
+}
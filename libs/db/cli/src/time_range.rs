@@ -0,0 +1,176 @@
+//! Time ranges written relative to the data in the db, like `last 5m` or `-30s..`
+//!
+//! A range is `start..stop`, where each end is empty for unbounded, an absolute timestamp in microseconds, `-<dur>`
+//! for a time before the latest data, or `+<dur>` for a time after the earliest. `last <dur>` is shorthand for
+//! `-<dur>..` and `first <dur>` for `..+<dur>`. Durations are a number followed by `us`, `ms`, `s`, `m`, `h` or `d`.
+
+use std::{ops::Range, time::Duration};
+
+use anyhow::anyhow;
+use impeller2::types::Timestamp;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Unbounded,
+    Absolute(Timestamp),
+    AfterEarliest(Duration),
+    BeforeLatest(Duration),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeRange {
+    pub start: Bound,
+    pub stop: Bound,
+}
+
+impl TimeRange {
+    pub fn parse(range: &str) -> anyhow::Result<Self> {
+        let range = range.trim();
+        if let Some(dur) = range.strip_prefix("last ") {
+            return Ok(Self {
+                start: Bound::BeforeLatest(parse_duration(dur)?),
+                stop: Bound::Unbounded,
+            });
+        }
+        if let Some(dur) = range.strip_prefix("first ") {
+            return Ok(Self {
+                start: Bound::Unbounded,
+                stop: Bound::AfterEarliest(parse_duration(dur)?),
+            });
+        }
+        let (start, stop) = range
+            .split_once("..")
+            .ok_or_else(|| anyhow!("expected a range like 'last 5m' or '-30s..', got {range:?}"))?;
+        Ok(Self {
+            start: parse_bound(start)?,
+            stop: parse_bound(stop)?,
+        })
+    }
+
+    /// Whether resolving the range needs the earliest timestamp in the db
+    pub fn needs_earliest(&self) -> bool {
+        [self.start, self.stop]
+            .iter()
+            .any(|bound| matches!(bound, Bound::AfterEarliest(_)))
+    }
+
+    /// Whether resolving the range needs the latest timestamp in the db
+    pub fn needs_latest(&self) -> bool {
+        [self.start, self.stop]
+            .iter()
+            .any(|bound| matches!(bound, Bound::BeforeLatest(_)))
+    }
+
+    /// The absolute range, given the earliest and latest timestamps in the db
+    pub fn resolve(&self, earliest: Timestamp, latest: Timestamp) -> Range<Timestamp> {
+        let resolve = |bound: Bound, unbounded: i64| match bound {
+            Bound::Unbounded => Timestamp(unbounded),
+            Bound::Absolute(timestamp) => timestamp,
+            Bound::AfterEarliest(dur) => Timestamp(earliest.0.saturating_add(micros(dur))),
+            Bound::BeforeLatest(dur) => Timestamp(latest.0.saturating_sub(micros(dur))),
+        };
+        resolve(self.start, i64::MIN)..resolve(self.stop, i64::MAX)
+    }
+}
+
+fn micros(dur: Duration) -> i64 {
+    i64::try_from(dur.as_micros()).unwrap_or(i64::MAX)
+}
+
+fn parse_bound(bound: &str) -> anyhow::Result<Bound> {
+    let bound = bound.trim();
+    if bound.is_empty() {
+        Ok(Bound::Unbounded)
+    } else if let Some(dur) = bound.strip_prefix('-') {
+        Ok(Bound::BeforeLatest(parse_duration(dur)?))
+    } else if let Some(dur) = bound.strip_prefix('+') {
+        Ok(Bound::AfterEarliest(parse_duration(dur)?))
+    } else {
+        let timestamp = bound
+            .parse()
+            .map_err(|_| anyhow!("expected a timestamp or a relative time, got {bound:?}"))?;
+        Ok(Bound::Absolute(Timestamp(timestamp)))
+    }
+}
+
+/// Parses a duration like `5m`, `1.5s` or `250 ms`
+pub fn parse_duration(dur: &str) -> anyhow::Result<Duration> {
+    let dur = dur.trim();
+    let split = dur
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(dur.len());
+    let (value, unit) = dur.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| anyhow!("expected a duration like '5m', got {dur:?}"))?;
+    let secs = match unit.trim() {
+        "us" => 1e-6,
+        "ms" => 1e-3,
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        "d" => 86400.0,
+        unit => return Err(anyhow!("unknown duration unit {unit:?} in {dur:?}")),
+    };
+    Ok(Duration::try_from_secs_f64(value * secs)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(
+            parse_duration("250 ms").unwrap(),
+            Duration::from_millis(250)
+        );
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("5 fortnights").is_err());
+    }
+
+    #[test]
+    fn test_time_range() {
+        let earliest = Timestamp(1_000_000);
+        let latest = Timestamp(100_000_000);
+
+        let last = TimeRange::parse("last 5s").unwrap();
+        assert!(last.needs_latest() && !last.needs_earliest());
+        assert_eq!(
+            last.resolve(earliest, latest),
+            Timestamp(95_000_000)..Timestamp(i64::MAX)
+        );
+        assert_eq!(TimeRange::parse("-5s..").unwrap(), last);
+
+        let first = TimeRange::parse("first 2s").unwrap();
+        assert!(first.needs_earliest() && !first.needs_latest());
+        assert_eq!(
+            first.resolve(earliest, latest),
+            Timestamp(i64::MIN)..Timestamp(3_000_000)
+        );
+
+        let range = TimeRange::parse("+1s..-1s").unwrap();
+        assert_eq!(
+            range.resolve(earliest, latest),
+            Timestamp(2_000_000)..Timestamp(99_000_000)
+        );
+        let range = TimeRange::parse("5..").unwrap();
+        assert_eq!(
+            range.resolve(earliest, latest),
+            Timestamp(5)..Timestamp(i64::MAX)
+        );
+
+        // an empty db has no latest timestamp, which must not overflow
+        let empty = TimeRange::parse("last 1h").unwrap();
+        assert_eq!(
+            empty.resolve(earliest, Timestamp(i64::MIN)).start,
+            Timestamp(i64::MIN)
+        );
+
+        assert!(TimeRange::parse("5m").is_err());
+        assert!(TimeRange::parse("x..").is_err());
+    }
+}
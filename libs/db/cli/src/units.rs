@@ -0,0 +1,148 @@
+//! Unit conversion for entering component values in whatever unit is convenient
+//!
+//! Only linear units are supported, so every conversion is a single multiplication. Units are grouped by the
+//! quantity they measure, and converting between two quantities is an error.
+
+use anyhow::anyhow;
+use impeller2_wkt::ComponentMetadata;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Quantity {
+    Angle,
+    Length,
+    Time,
+    Mass,
+    Velocity,
+    AngularVelocity,
+    Frequency,
+}
+
+/// The quantity `unit` measures, and how many of that quantity's base unit one `unit` is
+fn lookup(unit: &str) -> Option<(Quantity, f64)> {
+    use Quantity::*;
+    use std::f64::consts::PI;
+    let def = match unit {
+        "rad" => (Angle, 1.0),
+        "deg" | "°" => (Angle, PI / 180.0),
+        "m" => (Length, 1.0),
+        "km" => (Length, 1e3),
+        "cm" => (Length, 1e-2),
+        "mm" => (Length, 1e-3),
+        "ft" => (Length, 0.3048),
+        "in" => (Length, 0.0254),
+        "s" => (Time, 1.0),
+        "ms" => (Time, 1e-3),
+        "us" | "µs" => (Time, 1e-6),
+        "ns" => (Time, 1e-9),
+        "min" => (Time, 60.0),
+        "h" => (Time, 3600.0),
+        "kg" => (Mass, 1.0),
+        "g" => (Mass, 1e-3),
+        "m/s" => (Velocity, 1.0),
+        "km/h" => (Velocity, 1.0 / 3.6),
+        "kn" => (Velocity, 1852.0 / 3600.0),
+        "rad/s" => (AngularVelocity, 1.0),
+        "deg/s" => (AngularVelocity, PI / 180.0),
+        "rpm" => (AngularVelocity, 2.0 * PI / 60.0),
+        "Hz" => (Frequency, 1.0),
+        "kHz" => (Frequency, 1e3),
+        _ => return None,
+    };
+    Some(def)
+}
+
+/// Converts `value` from `from` units to `to` units
+pub fn convert(value: f64, from: &str, to: &str) -> anyhow::Result<f64> {
+    if from == to {
+        return Ok(value);
+    }
+    let unknown = |unit: &str| anyhow!("unknown unit {unit:?}");
+    let (from_quantity, from_factor) = lookup(from).ok_or_else(|| unknown(from))?;
+    let (to_quantity, to_factor) = lookup(to).ok_or_else(|| unknown(to))?;
+    if from_quantity != to_quantity {
+        return Err(anyhow!("can't convert {from} to {to}"));
+    }
+    Ok(value * from_factor / to_factor)
+}
+
+/// Splits a value like `90 deg` or `1.5e3m` into its number and unit
+pub fn parse_value(value: &str) -> anyhow::Result<(f64, Option<&str>)> {
+    let value = value.trim();
+    let (number, unit) = value
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain([value.len()])
+        .rev()
+        .find_map(|i| Some((value[..i].parse::<f64>().ok()?, value[i..].trim())))
+        .ok_or_else(|| anyhow!("{value:?} doesn't start with a number"))?;
+    Ok((number, (!unit.is_empty()).then_some(unit)))
+}
+
+/// Converts `value`, given in `unit` or in the component's own unit if `None`, to the number stored in the db
+///
+/// The value is first converted to the component's `unit` metadata, then divided by its `scale` so fixed-point
+/// components store the right number of steps.
+pub fn to_stored(
+    value: f64,
+    unit: Option<&str>,
+    metadata: &ComponentMetadata,
+) -> anyhow::Result<f64> {
+    let value = match (unit, metadata.unit()) {
+        (None, _) => value,
+        (Some(from), Some(to)) => convert(value, from, to)?,
+        (Some(from), None) => {
+            return Err(anyhow!(
+                "{} has no unit metadata, so a value in {from} can't be converted",
+                metadata.name
+            ));
+        }
+    };
+    Ok(value / metadata.scale().unwrap_or(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use impeller2::types::ComponentId;
+
+    #[test]
+    fn test_convert() {
+        assert_eq!(convert(2.0, "km", "m").unwrap(), 2000.0);
+        assert!((convert(180.0, "deg", "rad").unwrap() - std::f64::consts::PI).abs() < 1e-12);
+        assert!((convert(60.0, "rpm", "deg/s").unwrap() - 360.0).abs() < 1e-9);
+        assert_eq!(convert(1.0, "furlong", "furlong").unwrap(), 1.0);
+        assert!(convert(1.0, "m", "s").is_err());
+        assert!(convert(1.0, "furlong", "m").is_err());
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("90 deg").unwrap(), (90.0, Some("deg")));
+        assert_eq!(parse_value("1.5e3m").unwrap(), (1500.0, Some("m")));
+        assert_eq!(parse_value(" -2 ").unwrap(), (-2.0, None));
+        assert_eq!(parse_value("3in").unwrap(), (3.0, Some("in")));
+        assert!(parse_value("deg").is_err());
+    }
+
+    #[test]
+    fn test_to_stored() {
+        let mut metadata = ComponentMetadata {
+            component_id: ComponentId::new("heading"),
+            name: "heading".to_string(),
+            metadata: Default::default(),
+        };
+        assert_eq!(to_stored(90.0, None, &metadata).unwrap(), 90.0);
+        assert!(to_stored(90.0, Some("deg"), &metadata).is_err());
+
+        // heading stored as hundredths of a degree
+        metadata
+            .metadata
+            .insert("unit".to_string(), "deg".to_string());
+        metadata
+            .metadata
+            .insert("scale".to_string(), "0.01".to_string());
+        assert!((to_stored(90.0, None, &metadata).unwrap() - 9000.0).abs() < 1e-9);
+        let stored = to_stored(std::f64::consts::FRAC_PI_2, Some("rad"), &metadata).unwrap();
+        assert!((stored - 9000.0).abs() < 1e-9);
+    }
+}
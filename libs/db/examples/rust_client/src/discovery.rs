@@ -123,6 +123,11 @@ fn format_prim_type(prim_type: PrimType) -> &'static str {
         PrimType::Bool => "bool",
         PrimType::F32 => "f32",
         PrimType::F64 => "f64",
+        PrimType::F16 => "f16",
+        PrimType::BF16 => "bf16",
+        PrimType::Bytes => "bytes",
+        PrimType::String => "string",
+        PrimType::Q15_16 => "q15_16",
     }
 }
 
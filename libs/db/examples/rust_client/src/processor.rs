@@ -179,11 +179,16 @@ impl Decomponentize for TelemetryExtractor<'_> {
                 ComponentView::I16(array) => array.buf().iter().map(|&v| v as f64).collect(),
                 ComponentView::U8(array) => array.buf().iter().map(|&v| v as f64).collect(),
                 ComponentView::I8(array) => array.buf().iter().map(|&v| v as f64).collect(),
+                ComponentView::F16(array) => array.buf().iter().map(|v| v.to_f64()).collect(),
+                ComponentView::BF16(array) => array.buf().iter().map(|v| v.to_f64()).collect(),
+                ComponentView::Q15_16(array) => array.buf().iter().map(|v| v.to_f64()).collect(),
                 ComponentView::Bool(array) => array
                     .buf()
                     .iter()
                     .map(|&v| if v { 1.0 } else { 0.0 })
                     .collect(),
+                // not numeric telemetry
+                ComponentView::Bytes(_) | ComponentView::String(_) => return Ok(()),
             };
 
             // Extract unit from metadata
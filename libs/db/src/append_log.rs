@@ -6,14 +6,16 @@ use std::{
     fs::OpenOptions,
     io::{Seek, SeekFrom, Write as _},
     marker::PhantomData,
-    os::fd::AsRawFd,
+    ops::Range,
+    os::{fd::AsRawFd, unix::fs::FileExt},
     path::Path,
     slice::{self, SliceIndex},
     sync::{
         Arc, Mutex,
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
     },
 };
+use tracing::warn;
 
 /// [`AppendLog`] is a memory-mapped append-only time-series data file.
 /// It works by mapping a large amount of memory ~8g to a sparse file. The file contains a
@@ -23,12 +25,17 @@ use std::{
 /// | Header | Committed Data | Head |
 /// ```
 ///
+/// Data can be dropped from the front with [`AppendLog::trim_front`]. Offsets aren't shifted when it is, so the
+/// dropped prefix stays in the map but reads as zeros once its pages are handed back to the filesystem. Pages are only
+/// handed back once every reader that pinned the log with [`AppendLog::pin`] before the trim is done with it.
+///
 /// When a `AppendLog` is created or opened you get access to both a [`TimeSeries`] and an associated [`TimeSeriesWriter`]. [`TimeSeries`] is a read only view into the time series,
 /// giving you access to only the committed data. [`AppendLogWriter`] provides write access to the `head` of the [`TimeSeries`] with [`TimeSeriesWriter::write_head`].
 pub struct AppendLog<E> {
     map: Arc<memmap2::MmapRaw>,
     header_extra: PhantomData<E>,
     write_lock: Arc<Mutex<()>>,
+    readers: Arc<Readers>,
 }
 
 impl<E> Clone for AppendLog<E> {
@@ -37,6 +44,47 @@ impl<E> Clone for AppendLog<E> {
             map: self.map.clone(),
             header_extra: PhantomData,
             write_lock: self.write_lock.clone(),
+            readers: self.readers.clone(),
+        }
+    }
+}
+
+/// The readers pinning a log, see [`AppendLog::pin`]
+#[derive(Default)]
+struct Readers {
+    count: AtomicUsize,
+    /// The pages trimmed while the log was pinned, which are handed back once the last pin is dropped
+    pending: Mutex<Option<Range<usize>>>,
+}
+
+impl Readers {
+    /// Hands back the pending pages, unless the log has been pinned since
+    fn reclaim_unpinned(&self, map: &MmapRaw) -> Result<(), Error> {
+        let mut pending = self.pending.lock().unwrap();
+        if self.count.load(Ordering::SeqCst) > 0 {
+            return Ok(());
+        }
+        match pending.take() {
+            Some(range) => reclaim(map, range),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Keeps the data of an [`AppendLog`] that was readable when it was pinned from being reclaimed, see
+/// [`AppendLog::pin`]
+pub struct ReadPin {
+    map: Arc<MmapRaw>,
+    readers: Arc<Readers>,
+}
+
+impl Drop for ReadPin {
+    fn drop(&mut self) {
+        if self.readers.count.fetch_sub(1, Ordering::SeqCst) > 1 {
+            return;
+        }
+        if let Err(err) = self.readers.reclaim_unpinned(&self.map) {
+            warn!(?err, "failed to reclaim trimmed pages");
         }
     }
 }
@@ -44,13 +92,39 @@ impl<E> Clone for AppendLog<E> {
 #[repr(C)]
 struct Header<E> {
     pub committed_len: AtomicU64,
-    pub head_len: AtomicU64,
+    /// How many bytes of data have been trimmed from the front
+    ///
+    /// This used to be the length of an uncommitted head, which was always zero, so older logs read as untrimmed.
+    pub trimmed_len: AtomicU64,
     pub extra: E,
 }
 
+/// The alignment of the regions handed back to the filesystem by [`AppendLog::trim_front`], which is a multiple of
+/// every page size the db runs on
+const RECLAIM_ALIGN: u64 = 64 * 1024;
+
+/// The size of the sparse file behind each log
+const FILE_SIZE: u64 = 1024 * 1024 * 1024 * 8; // 8gb
+
+/// Hands the pages in `range` back to the filesystem
+#[cfg(target_os = "linux")]
+fn reclaim(map: &MmapRaw, range: Range<usize>) -> Result<(), Error> {
+    // SAFETY: the range is page aligned and entirely before the retained data, and no reader that pinned the log
+    // before it was trimmed is left, so nothing still reads it through the map. An unpinned reader that raced the trim
+    // sees zeros rather than freed memory.
+    unsafe {
+        map.unchecked_advise_range(memmap2::UncheckedAdvice::Remove, range.start, range.len())?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reclaim(_map: &MmapRaw, _range: Range<usize>) -> Result<(), Error> {
+    Ok(())
+}
+
 impl<E: IntoBytes + Immutable> AppendLog<E> {
     pub fn create(path: impl AsRef<Path>, extra: E) -> Result<Self, Error> {
-        const FILE_SIZE: u64 = 1024 * 1024 * 1024 * 8; // 8gb
         let mut file = OpenOptions::new()
             .create_new(true)
             .write(true)
@@ -63,6 +137,7 @@ impl<E: IntoBytes + Immutable> AppendLog<E> {
             map,
             header_extra: PhantomData,
             write_lock: Arc::new(Mutex::new(())),
+            readers: Arc::default(),
         };
         unsafe {
             let map = map.map.as_mut_ptr().add(size_of::<AtomicU64>() * 2);
@@ -71,7 +146,7 @@ impl<E: IntoBytes + Immutable> AppendLog<E> {
         }
         map.committed_len()
             .store(size_of::<Header<E>>() as u64, Ordering::SeqCst);
-        map.head_len().store(0, Ordering::SeqCst);
+        map.header().trimmed_len.store(0, Ordering::SeqCst);
         Ok(map)
     }
 
@@ -82,10 +157,22 @@ impl<E: IntoBytes + Immutable> AppendLog<E> {
             map,
             header_extra: PhantomData,
             write_lock: Arc::new(Mutex::new(())),
+            readers: Arc::default(),
         };
         Ok(map)
     }
 
+    /// Keeps the data that's readable now from being reclaimed until the pin is dropped, even if it's trimmed
+    ///
+    /// Take the pin before reading [`Self::trimmed_len`] or any data, and hold it for as long as the data is used.
+    pub fn pin(&self) -> ReadPin {
+        self.readers.count.fetch_add(1, Ordering::SeqCst);
+        ReadPin {
+            map: self.map.clone(),
+            readers: self.readers.clone(),
+        }
+    }
+
     /// Returns a slice of data offset into the committed data region of the [`AppendLog`]
     pub fn get(&self, range: impl SliceIndex<[u8], Output = [u8]>) -> Option<&'_ [u8]> {
         self.data().get(range)
@@ -106,17 +193,14 @@ impl<E: IntoBytes + Immutable> AppendLog<E> {
         &self.header().committed_len
     }
 
-    /// Returns a reference to the [`AppendLog`]'s head length. This is the length of the current "head" of the writer -- aka the data waiting to be committed.
-    fn head_len(&self) -> &AtomicU64 {
-        &self.header().head_len
-    }
-
     /// The extra data stored in the header
     pub fn extra(&self) -> &E {
         &self.header().extra
     }
 
     /// The current committed length, excluding the `HEADER_SIZE`
+    ///
+    /// This includes any data that's been trimmed from the front.
     pub fn len(&self) -> u64 {
         self.committed_len().load(Ordering::Acquire) - size_of::<Header<E>>() as u64
     }
@@ -131,8 +215,76 @@ impl<E: IntoBytes + Immutable> AppendLog<E> {
         &slice[size_of::<Header<E>>()..end]
     }
 
-    pub(crate) fn raw_mmap(&self) -> &Arc<MmapRaw> {
-        &self.map
+    /// Flushes the header and committed data to disk
+    pub fn sync(&self) -> Result<(), Error> {
+        let end = self.committed_len().load(Ordering::Acquire) as usize;
+        self.map.flush_range(0, end)?;
+        Ok(())
+    }
+
+    /// How many bytes have been trimmed from the front of the data by [`Self::trim_front`]
+    pub fn trimmed_len(&self) -> u64 {
+        // sequentially consistent with pinning, so a reader that pinned after a trim sees it, see `trim_front`
+        self.header().trimmed_len.load(Ordering::SeqCst)
+    }
+
+    /// Drops the data before offset `len`, returning how many bytes were newly dropped
+    ///
+    /// `len` is clamped to the committed length, and trimming less than has already been trimmed does nothing. On
+    /// Linux the whole pages behind the dropped data are punched out of the file, so the space is reclaimed without
+    /// moving the data after it. While the log is pinned the pages are kept, and they're punched out when the last
+    /// pin is dropped. Unpinned readers still holding a slice of the dropped data may see zeros.
+    pub fn trim_front(&self, len: u64) -> Result<u64, Error> {
+        let _guard = self.write_lock.lock().unwrap();
+        let prev = self.trimmed_len();
+        let len = len.min(self.len());
+        if len <= prev {
+            return Ok(0);
+        }
+        // stored before the readers are counted, so a reader that pins after the count was read sees the new length
+        // and never reads the pages being reclaimed
+        self.header().trimmed_len.store(len, Ordering::SeqCst);
+        // only whole pages are released, so the page holding the header and the partial page at the end are kept
+        let header_len = size_of::<Header<E>>() as u64;
+        let start = ((header_len + prev) / RECLAIM_ALIGN * RECLAIM_ALIGN).max(RECLAIM_ALIGN);
+        let end = (header_len + len) / RECLAIM_ALIGN * RECLAIM_ALIGN;
+        if end > start {
+            let range = start as usize..end as usize;
+            let mut pending = self.readers.pending.lock().unwrap();
+            *pending = Some(match pending.take() {
+                Some(prev) => prev.start.min(range.start)..prev.end.max(range.end),
+                None => range,
+            });
+            drop(pending);
+            self.readers.reclaim_unpinned(&self.map)?;
+        }
+        Ok(len - prev)
+    }
+
+    /// Copies the log to a new file at `path`, keeping only the first `len` bytes of data
+    ///
+    /// Data is only ever written past the committed length, so copying up to a length read earlier gives a consistent
+    /// copy of the log at that point while writes carry on. The trimmed front and the space after the data are left as
+    /// holes, like in the original. The log is pinned while the copy is made, so a trim in the meantime doesn't zero
+    /// data it still needs.
+    pub fn snapshot(&self, path: impl AsRef<Path>, len: u64) -> Result<(), Error> {
+        let _pin = self.pin();
+        let len = len.min(self.len());
+        let trimmed_len = self.trimmed_len().min(len);
+        let file = OpenOptions::new().create_new(true).write(true).open(path)?;
+        file.set_len(FILE_SIZE + 1)?;
+        let header_len = size_of::<Header<E>>() as u64;
+        let mut header = vec![];
+        header.extend_from_slice(&(header_len + len).to_ne_bytes());
+        header.extend_from_slice(&trimmed_len.to_ne_bytes());
+        header.extend_from_slice(self.extra().as_bytes());
+        file.write_all_at(&header, 0)?;
+        // copied from the start of the page holding the first retained byte, the pages before it are holes
+        let start = ((header_len + trimmed_len) / RECLAIM_ALIGN * RECLAIM_ALIGN).max(header_len);
+        let map: &[u8] = unsafe { slice::from_raw_parts(self.map.as_mut_ptr(), self.map.len()) };
+        file.write_all_at(&map[start as usize..(header_len + len) as usize], start)?;
+        file.sync_all()?;
+        Ok(())
     }
 
     pub fn write(&self, buf: &[u8]) -> Result<usize, Error> {
@@ -1,7 +1,8 @@
 use arrow::{
     array::{
-        Array, ArrayRef, ArrowPrimitiveType, BooleanArray, FixedSizeListArray, Int32Array,
-        PrimitiveArray, RecordBatch, TimestampMicrosecondArray,
+        Array, ArrayRef, ArrowPrimitiveType, BinaryArray, BooleanArray, FixedSizeListArray,
+        Int32Array, Int64Array, PrimitiveArray, RecordBatch, StringArray,
+        TimestampMicrosecondArray, UInt32Array,
     },
     buffer::{BooleanBuffer, Buffer, ScalarBuffer},
     compute,
@@ -16,6 +17,7 @@ use futures_lite::{Stream, StreamExt, pin};
 use impeller2::types::{PrimType, Timestamp};
 use impeller2_wkt::ArchiveFormat;
 use std::{
+    collections::BTreeMap,
     fs::File,
     ops::{Bound, RangeBounds},
     path::Path,
@@ -56,16 +58,38 @@ impl<T: IntoBytes + Immutable> AppendLog<T> {
         let end = end.min(data.len());
         let len = end.saturating_sub(start);
 
+        // the buffer pins the log, so the pages it points at aren't reclaimed while it's alive
         unsafe {
             let ptr = NonNull::new(data.as_ptr().add(start) as *mut _).expect("mmap null");
-            Buffer::from_custom_allocation(ptr, len, self.raw_mmap().clone())
+            Buffer::from_custom_allocation(ptr, len, Arc::new(self.pin()))
         }
     }
 }
 
 impl Component {
+    /// Narrows a range of sample positions to the samples that haven't been trimmed by retention
+    ///
+    /// The first position is read once, so the time and data columns built from the range line up even if a
+    /// compaction runs in between. Callers pin the time series first, so the range is still mapped when the columns
+    /// are built from it.
+    fn retained_range(&self, range: impl RangeBounds<usize>) -> std::ops::Range<usize> {
+        let first = self.time_series.first_position();
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.time_series.index().len() as usize / size_of::<Timestamp>(),
+        };
+        start.max(first)..end.max(first)
+    }
+
     pub fn as_data_array(&self, name: impl ToString) -> (FieldRef, ArrayRef) {
-        self.as_data_array_range(name, ..)
+        let _pin = self.time_series.pin();
+        self.as_data_array_range(name, self.retained_range(..))
     }
 
     pub fn as_data_array_range<R: RangeBounds<usize>>(
@@ -88,6 +112,28 @@ impl Component {
             PrimType::I16 => array_ref::<Int16Type, _>(buf, range, element_size),
             PrimType::I8 => array_ref::<Int8Type, _>(buf, range, element_size),
             PrimType::Bool => bool_ref(buf, range, element_size),
+            PrimType::F16 => widened_ref::<Float32Type, u16, _>(buf, range, element_size, |bits| {
+                impeller2::types::f16::from_bits(bits).to_f32()
+            }),
+            PrimType::BF16 => {
+                widened_ref::<Float32Type, u16, _>(buf, range, element_size, |bits| {
+                    impeller2::types::bf16::from_bits(bits).to_f32()
+                })
+            }
+            PrimType::Q15_16 => {
+                widened_ref::<Float64Type, i32, _>(buf, range, element_size, |bits| {
+                    impeller2::types::Q15_16::from_bits(bits).to_f64()
+                })
+            }
+            PrimType::Bytes | PrimType::String => {
+                let array = var_len_ref(buf, range, element_size, self.schema.prim_type);
+                let field = Arc::new(Field::new(
+                    name.to_string(),
+                    array.data_type().clone(),
+                    false,
+                ));
+                return (field, array);
+            }
         };
 
         let inner_field = Arc::new(Field::new(
@@ -120,7 +166,7 @@ impl Component {
         let name = name.to_string();
         let size = self.schema.dim.iter().product::<usize>();
         let (field, array) = self.as_data_array_range(name.clone(), range);
-        if self.schema.dim.is_empty() || size <= 1 {
+        if self.schema.dim.is_empty() || size <= 1 || self.schema.prim_type.is_var_len() {
             return (vec![field], vec![array]);
         }
 
@@ -164,7 +210,8 @@ impl Component {
     }
 
     pub fn as_time_series_array(&self) -> ArrayRef {
-        self.as_time_series_array_range(..)
+        let _pin = self.time_series.pin();
+        self.as_time_series_array_range(self.retained_range(..))
     }
 
     pub fn as_time_series_array_range<R: RangeBounds<usize>>(&self, range: R) -> ArrayRef {
@@ -185,6 +232,8 @@ impl Component {
         range: impl RangeBounds<usize> + Clone,
     ) -> RecordBatch {
         let name = name.to_string();
+        let _pin = self.time_series.pin();
+        let range = self.retained_range(range);
         let (data_field, data_array) = self.as_data_array_range(name.clone(), range.clone());
         let time_array = self.as_time_series_array_range(range);
         let len = data_array.len().min(time_array.len());
@@ -202,8 +251,10 @@ impl Component {
 
     pub fn as_flat_record_batch(&self, name: impl ToString, element_names: &str) -> RecordBatch {
         let name = name.to_string();
-        let range = ..;
-        let (data_fields, data_arrays) = self.as_flattened_columns(name, range, element_names);
+        let _pin = self.time_series.pin();
+        let range = self.retained_range(..);
+        let (data_fields, data_arrays) =
+            self.as_flattened_columns(name, range.clone(), element_names);
         let time_array = self.as_time_series_array_range(range);
 
         let min_len = data_arrays
@@ -236,19 +287,120 @@ impl Component {
             .with_infinite_table(true)
     }
     pub fn as_mem_table(&self, name: impl ToString) -> MemTable {
-        let record_batch = self.as_record_batch(name);
-        let schema = record_batch.schema();
-        let record_batches = vec![record_batch];
-        MemTable::try_new(schema, vec![record_batches])
-            .expect("mem table create failed")
-            .with_sort_order(vec![vec![datafusion::logical_expr::SortExpr::new(
-                datafusion::prelude::col("time"),
+        mem_table(self.as_record_batch(name))
+    }
+
+    /// Like [`Component::as_mem_table`], with a `{name}_name` column of each value's name from `values`
+    ///
+    /// Only scalar integer and bool components get the column, and values without a name are null in it.
+    pub fn as_enum_mem_table(
+        &self,
+        name: impl ToString,
+        values: &BTreeMap<i64, String>,
+    ) -> MemTable {
+        let name = name.to_string();
+        let record_batch = self.as_record_batch(&name);
+        let Some(names) = self.enum_names(record_batch.column(1), values) else {
+            return mem_table(record_batch);
+        };
+        let mut fields = record_batch.schema().fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            format!("{name}_name"),
+            DataType::Utf8,
+            true,
+        )));
+        let mut columns = record_batch.columns().to_vec();
+        columns.push(names);
+        let record_batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+            .expect("record batch params wrong");
+        mem_table(record_batch)
+    }
+
+    fn enum_names(&self, data: &ArrayRef, values: &BTreeMap<i64, String>) -> Option<ArrayRef> {
+        let integer = !matches!(
+            self.schema.prim_type,
+            PrimType::F64
+                | PrimType::F32
+                | PrimType::F16
+                | PrimType::BF16
+                | PrimType::Q15_16
+                | PrimType::Bytes
+                | PrimType::String
+        );
+        if !integer || self.schema.dim.iter().product::<usize>() != 1 {
+            return None;
+        }
+        let data = compute::cast(&scalar_values(data), &DataType::Int64).ok()?;
+        let names = data
+            .as_any()
+            .downcast_ref::<Int64Array>()?
+            .iter()
+            .map(|value| value.and_then(|value| values.get(&value)))
+            .collect::<StringArray>();
+        Some(Arc::new(names))
+    }
+
+    /// Like [`Component::as_mem_table`], with a bool `{name}_{bit}` column for each of the named `bits`
+    ///
+    /// Only scalar `u8`, `u16` and `u32` components get the columns, see [`impeller2::bits::Bits`].
+    pub fn as_bits_mem_table(&self, name: impl ToString, bits: &BTreeMap<u8, String>) -> MemTable {
+        let name = name.to_string();
+        let record_batch = self.as_record_batch(&name);
+        let flags = matches!(
+            self.schema.prim_type,
+            PrimType::U8 | PrimType::U16 | PrimType::U32
+        );
+        if !flags || self.schema.dim.iter().product::<usize>() != 1 {
+            return mem_table(record_batch);
+        }
+        let Ok(data) = compute::cast(&scalar_values(record_batch.column(1)), &DataType::UInt32)
+        else {
+            return mem_table(record_batch);
+        };
+        let Some(data) = data.as_any().downcast_ref::<UInt32Array>() else {
+            return mem_table(record_batch);
+        };
+        let width = self.schema.prim_type.size() as u32 * 8;
+        let mut fields = record_batch.schema().fields().to_vec();
+        let mut columns = record_batch.columns().to_vec();
+        for (&bit, bit_name) in bits.iter().filter(|(bit, _)| (**bit as u32) < width) {
+            fields.push(Arc::new(Field::new(
+                format!("{name}_{}", bit_name.to_case(convert_case::Case::Snake)),
+                DataType::Boolean,
                 true,
-                false,
-            )]])
+            )));
+            let set = data
+                .iter()
+                .map(|value| value.map(|value| value & (1 << bit) != 0))
+                .collect::<BooleanArray>();
+            columns.push(Arc::new(set));
+        }
+        let record_batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+            .expect("record batch params wrong");
+        mem_table(record_batch)
     }
 }
 
+/// The elements of a scalar component's data column, which is a one element list for some prim types
+fn scalar_values(data: &ArrayRef) -> ArrayRef {
+    match data.as_any().downcast_ref::<FixedSizeListArray>() {
+        Some(list) => list.values().clone(),
+        None => data.clone(),
+    }
+}
+
+fn mem_table(record_batch: RecordBatch) -> MemTable {
+    let schema = record_batch.schema();
+    let record_batches = vec![record_batch];
+    MemTable::try_new(schema, vec![record_batches])
+        .expect("mem table create failed")
+        .with_sort_order(vec![vec![datafusion::logical_expr::SortExpr::new(
+            datafusion::prelude::col("time"),
+            true,
+            false,
+        )]])
+}
+
 impl DB {
     pub fn as_session_context(&self) -> Result<SessionContext, datafusion::error::DataFusionError> {
         use datafusion::prelude::*;
@@ -273,12 +425,28 @@ impl DB {
                     .to_case(convert_case::Case::Snake)
                     .replace(".", "_");
                 let name = component_name.clone();
-                let mem_table = component.as_mem_table(&component_name);
+                let mem_table = match (
+                    component_metadata.enum_values(),
+                    component_metadata.bit_names(),
+                ) {
+                    (Some(values), _) => component.as_enum_mem_table(&component_name, &values),
+                    (None, Some(bits)) => component.as_bits_mem_table(&component_name, &bits),
+                    (None, None) => component.as_mem_table(&component_name),
+                };
                 ctx.register_table(name, Arc::new(mem_table))?;
 
                 let stream_name = format!("{component_name}_stream");
-                let stream_table = component.as_stream_table(component_name);
+                let stream_table = component.as_stream_table(component_name.clone());
                 ctx.register_table(stream_name, Arc::new(stream_table))?;
+
+                // migrated components also get a table per schema version, e.g. `speed_v0`
+                if !component.epochs.is_empty() {
+                    for version in component.versions() {
+                        let mem_table = version.as_mem_table(&component_name);
+                        let version_name = format!("{component_name}_v{}", version.version);
+                        ctx.register_table(version_name, Arc::new(mem_table))?;
+                    }
+                }
             }
             Ok::<_, datafusion::error::DataFusionError>(())
         })?;
@@ -364,6 +532,39 @@ fn bool_ref<T: IntoBytes + Immutable, R: RangeBounds<usize>>(
     Arc::new(BooleanArray::new(buf, None))
 }
 
+// arrow has no bfloat16 or fixed-point float types, and most datafusion functions reject Float16, so those columns
+// are widened from their raw bits to f32, or to f64 for Q15_16 which has more precision than an f32
+fn widened_ref<P: ArrowPrimitiveType, S: ArrowNativeType, T: IntoBytes + Immutable>(
+    buf: &AppendLog<T>,
+    range: impl RangeBounds<usize>,
+    element_size: usize,
+    widen: impl Fn(S) -> P::Native,
+) -> ArrayRef {
+    let buffer = buf.as_arrow_buffer_range(range, element_size);
+    let values = buffer.typed_data::<S>().iter().map(|&bits| widen(bits));
+    Arc::new(PrimitiveArray::<P>::from_iter_values(values))
+}
+
+/// One binary or string value per slot, holding just the slot's payload
+fn var_len_ref<T: IntoBytes + Immutable, R: RangeBounds<usize>>(
+    buf: &AppendLog<T>,
+    range: R,
+    element_size: usize,
+    prim_type: PrimType,
+) -> ArrayRef {
+    let buffer = buf.as_arrow_buffer_range(range, element_size);
+    let payloads = buffer
+        .as_slice()
+        .chunks_exact(element_size.max(1))
+        .map(|slot| impeller2::types::var_len_payload(slot).unwrap_or_default());
+    match prim_type {
+        PrimType::String => Arc::new(StringArray::from_iter_values(
+            payloads.map(|p| String::from_utf8_lossy(p).into_owned()),
+        )),
+        _ => Arc::new(BinaryArray::from_iter_values(payloads)),
+    }
+}
+
 fn array_ref<P: ArrowPrimitiveType, T: IntoBytes + Immutable>(
     buf: &AppendLog<T>,
     range: impl RangeBounds<usize>,
@@ -146,9 +146,11 @@ pub fn component_stream(component: Component) -> impl futures_lite::Stream<Item
     futures_lite::stream::try_unfold(component, |component| async move {
         let waiter = component.time_series.waiter();
         let _ = waiter.wait().await;
-        let Some((&timestamp, buf)) = component.time_series.latest() else {
+        let _pin = component.time_series.pin();
+        let Some((timestamp, buf)) = component.latest() else {
             return Ok(None);
         };
+        let buf = &buf[..];
         pub fn buf_to_json<T: TryFromBytes + Immutable + Serialize>(
             buf: &[u8],
             shape: &[usize],
@@ -171,6 +173,59 @@ pub fn component_stream(component: Component) -> impl futures_lite::Stream<Item
             };
             serde_json::to_value(&val).expect("failed to serialize value")
         }
+        // half's and Q15_16's serde impls use the raw bits, so widen them to get plain json numbers
+        pub fn widened_buf_to_json<W: Serialize, T: TryFromBytes + Immutable + Copy + Into<W>>(
+            buf: &[u8],
+            shape: &[usize],
+            timestamp: Timestamp,
+        ) -> Value {
+            let data = match <[T]>::try_ref_from_bytes(buf)
+                .map_err(impeller2::error::Error::from)
+                .map_err(Error::from)
+            {
+                Ok(d) => d.iter().map(|&x| x.into()).collect::<Vec<W>>(),
+                Err(err) => {
+                    let err = ErrorResponse::from(err);
+                    return serde_json::to_value(&err).expect("failed to serialize error");
+                }
+            };
+            let val = StreamValue {
+                timestamp,
+                data: &data[..],
+                shape,
+            };
+            serde_json::to_value(&val).expect("failed to serialize value")
+        }
+        // slots are sent as their payload, without the length prefix and zero padding
+        pub fn var_len_to_json(
+            buf: &[u8],
+            is_string: bool,
+            shape: &[usize],
+            timestamp: Timestamp,
+        ) -> Value {
+            let payload = match impeller2::types::var_len_payload(buf).map_err(Error::from) {
+                Ok(p) => p,
+                Err(err) => {
+                    let err = ErrorResponse::from(err);
+                    return serde_json::to_value(&err).expect("failed to serialize error");
+                }
+            };
+            let val = if is_string {
+                let data = [String::from_utf8_lossy(payload)];
+                serde_json::to_value(StreamValue {
+                    timestamp,
+                    data: &data[..],
+                    shape,
+                })
+            } else {
+                serde_json::to_value(StreamValue {
+                    timestamp,
+                    data: payload,
+                    shape,
+                })
+            };
+            val.expect("failed to serialize value")
+        }
         let shape = &component.schema.dim[..];
         let json = match component.schema.prim_type {
             impeller2::types::PrimType::U8 => buf_to_json::<u8>(buf, shape, timestamp),
@@ -184,6 +239,17 @@ pub fn component_stream(component: Component) -> impl futures_lite::Stream<Item
             impeller2::types::PrimType::Bool => buf_to_json::<bool>(buf, shape, timestamp),
             impeller2::types::PrimType::F32 => buf_to_json::<f32>(buf, shape, timestamp),
             impeller2::types::PrimType::F64 => buf_to_json::<f64>(buf, shape, timestamp),
+            impeller2::types::PrimType::F16 => {
+                widened_buf_to_json::<f32, impeller2::types::f16>(buf, shape, timestamp)
+            }
+            impeller2::types::PrimType::BF16 => {
+                widened_buf_to_json::<f32, impeller2::types::bf16>(buf, shape, timestamp)
+            }
+            impeller2::types::PrimType::Bytes => var_len_to_json(buf, false, shape, timestamp),
+            impeller2::types::PrimType::String => var_len_to_json(buf, true, shape, timestamp),
+            impeller2::types::PrimType::Q15_16 => {
+                widened_buf_to_json::<f64, impeller2::types::Q15_16>(buf, shape, timestamp)
+            }
         };
         Ok::<_, Error>(Some((json, component)))
     })
@@ -1,7 +1,7 @@
 use std::io;
 
-use impeller2::types::{ComponentId, PacketId};
-use impeller2_wkt::{ErrorResponse, StreamId};
+use impeller2::types::{ComponentId, PacketId, Timestamp};
+use impeller2_wkt::{ErrorResponse, QueryId, StreamId};
 use thiserror::Error;
 #[derive(Debug, Error)]
 pub enum Error {
@@ -23,6 +23,8 @@ pub enum Error {
     InvalidComponentId,
     #[error("time travel - you tried to push a time stamp in the past")]
     TimeTravel,
+    #[error("duplicate timestamp {0:?}")]
+    DuplicateTimestamp(Timestamp),
     #[error("datafusion {0}")]
     DataFusion(#[from] datafusion::error::DataFusionError),
     #[error("arrow  {0}")]
@@ -44,6 +46,12 @@ pub enum Error {
     Parquet(#[from] parquet::errors::ParquetError),
     #[error("schema mismatch")]
     SchemaMismatch,
+    #[error("stream write timed out")]
+    StreamWriteTimeout,
+    #[error("snapshot path is inside the db")]
+    SnapshotInsideDb,
+    #[error("query id {0} is already in use by a running query")]
+    QueryIdInUse(QueryId),
 }
 
 impl From<impeller2_stellar::Error> for Error {
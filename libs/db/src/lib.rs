@@ -3,7 +3,7 @@ use futures_lite::StreamExt;
 use impeller2::registry::VTableRegistry;
 use impeller2::types::{PacketHeader, PacketTy};
 use impeller2::vtable::builder::{
-    OpBuilder, component, raw_field, raw_table, schema, timestamp, vtable,
+    OpBuilder, component, hashed_schema, raw_field, raw_table, timestamp, vtable,
 };
 use impeller2::vtable::{RealizedField, builder};
 use impeller2::{
@@ -11,14 +11,15 @@ use impeller2::{
     registry,
     schema::Schema,
     types::{
-        ComponentId, ComponentView, IntoLenPacket, LenPacket, Msg, OwnedPacket as Packet, PacketId,
-        PrimType, RequestId, Timestamp,
+        ComponentId, ComponentView, ElementValue, IntoLenPacket, LenPacket, Msg,
+        OwnedPacket as Packet, PacketId, PrimType, RequestId, Timestamp,
     },
     vtable::VTable,
 };
-use impeller2_stellar::{PacketSink, PacketStream};
+use impeller2_stellar::{AckWindow, PacketSink, PacketStream};
 use impeller2_wkt::*;
 use msg_log::MsgLog;
+use rollup::Rollups;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use smallvec::SmallVec;
 use std::{
@@ -49,12 +50,18 @@ use vtable_stream::handle_vtable_stream;
 use zerocopy::IntoBytes;
 
 pub use error::Error;
+pub use replication::Follower;
 
 pub mod append_log;
 mod arrow;
 pub mod axum;
 mod error;
+#[cfg(feature = "mock")]
+pub mod mock;
 mod msg_log;
+mod replication;
+pub(crate) mod rollup;
+mod snapshot;
 pub(crate) mod time_series;
 mod vtable_stream;
 
@@ -68,17 +75,25 @@ pub struct DB {
     pub default_stream_time_step: AtomicU64,
     pub last_updated: AtomicCell<Timestamp>,
     pub earliest_timestamp: Timestamp,
+    /// Held while the logs are trimmed, so a snapshot never copies data that's being dropped
+    compaction_lock: std::sync::Mutex<()>,
 }
 
 #[derive(Default)]
 pub struct State {
     components: HashMap<ComponentId, Component>,
     component_metadata: HashMap<ComponentId, ComponentMetadata>,
+    component_metadata_history: HashMap<ComponentId, MetadataHistory>,
+    retention: HashMap<ComponentId, RetentionPolicy>,
 
     msg_logs: HashMap<PacketId, MsgLog>,
 
     vtable_registry: registry::HashMapRegistry,
+    /// The vtables generated for outgoing streams, so a subscriber that missed one can fetch it with [`GetVTable`]
+    stream_vtables: HashMap<PacketId, VTable>,
     streams: HashMap<StreamId, Arc<FixedRateStreamState>>,
+    /// The running [`SQLQueryStream`]s, keyed by the connection that sent them since each client picks its own ids
+    queries: HashMap<(ConnId, QueryId), Arc<AtomicBool>>,
 
     udp_vtable_streams: HashSet<(SocketAddr, [u8; 2])>,
 
@@ -110,6 +125,7 @@ impl DB {
             default_stream_time_step,
             last_updated: AtomicCell::new(Timestamp(i64::MIN)),
             earliest_timestamp: Timestamp::now(),
+            compaction_lock: Default::default(),
         };
         db.save_db_state()?;
         Ok(db)
@@ -136,6 +152,8 @@ impl DB {
 
     pub fn open(path: PathBuf) -> Result<Self, Error> {
         let mut component_metadata = HashMap::new();
+        let mut component_metadata_history = HashMap::new();
+        let mut retention = HashMap::new();
         let mut components = HashMap::new();
         let mut msg_logs = HashMap::new();
         let mut last_updated = i64::MIN;
@@ -161,14 +179,25 @@ impl DB {
             let metadata = ComponentMetadata::read(path.join("metadata"))?;
             trace!("Read component metadata for {}", metadata.name);
             component_metadata.insert(component_id, metadata);
+            let history_path = path.join("metadata_history");
+            if history_path.exists() {
+                component_metadata_history
+                    .insert(component_id, MetadataHistory::read(history_path)?);
+            }
+            let retention_path = path.join("retention");
+            if retention_path.exists() {
+                retention.insert(component_id, RetentionPolicy::read(retention_path)?);
+            }
 
             trace!("Opening component file {}", path.display());
 
             let component = Component::open(&path, component_id, schema.clone())?;
-            if let Some((timestamp, _)) = component.time_series.latest() {
-                last_updated = timestamp.0.max(last_updated);
-            };
-            start_timestamp = start_timestamp.min(component.time_series.start_timestamp().0);
+            for version in component.versions() {
+                if let Some((timestamp, _)) = version.time_series.latest() {
+                    last_updated = timestamp.0.max(last_updated);
+                };
+                start_timestamp = start_timestamp.min(version.time_series.start_timestamp().0);
+            }
             components.insert(component_id, component);
         }
         if let Ok(msgs_dir) = std::fs::read_dir(path.join("msgs")) {
@@ -198,6 +227,8 @@ impl DB {
         let state = State {
             components,
             component_metadata,
+            component_metadata_history,
+            retention,
             msg_logs,
             ..Default::default()
         };
@@ -216,12 +247,23 @@ impl DB {
             ),
             last_updated: AtomicCell::new(Timestamp(last_updated)),
             earliest_timestamp,
+            compaction_lock: Default::default(),
         })
     }
 
     pub fn insert_vtable(&self, vtable: VTableMsg) -> Result<(), Error> {
         info!(id = ?vtable.id, "inserting vtable");
+        if self.with_state(|s| s.db_config.validate_vtables()) {
+            vtable.vtable.validate()?;
+        }
         self.with_state_mut(|state| {
+            // checked up front so a stale producer is rejected before any of its new components are created
+            vtable.vtable.verify_schemas(|component_id| {
+                state
+                    .components
+                    .get(&component_id)
+                    .map(|component| component.schema.stable_hash())
+            })?;
             for res in vtable.vtable.realize_fields(None) {
                 let RealizedField {
                     component_id,
@@ -239,6 +281,99 @@ impl DB {
         Ok(())
     }
 
+    /// Sets a component's retention policy and saves it with the component, see [`SetRetention`]
+    pub fn set_retention(
+        &self,
+        component_id: ComponentId,
+        policy: RetentionPolicy,
+    ) -> Result<(), Error> {
+        self.with_state_mut(|state| {
+            if !state.components.contains_key(&component_id) {
+                return Err(Error::ComponentNotFound(component_id));
+            }
+            let path = self.path.join(component_id.to_string()).join("retention");
+            if policy.is_unbounded() {
+                state.retention.remove(&component_id);
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+            } else {
+                policy.write(path)?;
+                state.retention.insert(component_id, policy);
+            }
+            Ok(())
+        })
+    }
+
+    /// Drops the samples each component's retention policy doesn't keep, from every schema version of the component
+    ///
+    /// Ages are measured back from the component's latest sample across all of its versions. The state lock is only
+    /// held while the components are collected, so writes carry on while the time series are trimmed.
+    pub fn compact(&self) -> Result<CompactionReport, Error> {
+        let _compaction = self
+            .compaction_lock
+            .lock()
+            .expect("compaction lock poisoned");
+        let components = self.with_state(|state| {
+            state
+                .retention
+                .iter()
+                .filter_map(|(id, policy)| Some((state.components.get(id)?.clone(), *policy)))
+                .collect::<Vec<_>>()
+        });
+        let mut report = CompactionReport::default();
+        for (component, policy) in components {
+            let Some(latest) = component
+                .versions()
+                .filter_map(|version| {
+                    version
+                        .time_series
+                        .latest()
+                        .map(|(timestamp, _)| *timestamp)
+                })
+                .max()
+            else {
+                continue;
+            };
+            for version in component.versions() {
+                report += version.time_series.apply_retention(&policy, latest)?;
+            }
+            report += component.rollups.apply_retention(&policy, latest)?;
+        }
+        if report.samples_dropped > 0 {
+            info!(
+                report.samples_dropped,
+                report.bytes_reclaimed, "compacted time series"
+            );
+        }
+        Ok(report)
+    }
+
+    /// Rolls up the closed buckets of every component, returning how many were written
+    ///
+    /// Like [`Self::compact`], the state lock is only held while the components are collected.
+    pub fn update_rollups(&self) -> Result<usize, Error> {
+        let components =
+            self.with_state(|state| state.components.values().cloned().collect::<Vec<_>>());
+        let mut written = 0;
+        for component in components {
+            written += component.update_rollups()?;
+        }
+        Ok(written)
+    }
+
+    /// Migrates a component to `schema`, see [`State::migrate_component`]
+    pub fn migrate_component(
+        &self,
+        component_id: ComponentId,
+        schema: ComponentSchema,
+    ) -> Result<u16, Error> {
+        let version =
+            self.with_state_mut(|state| state.migrate_component(component_id, schema, &self.path))?;
+        self.vtable_gen.fetch_add(1, atomic::Ordering::SeqCst);
+        Ok(version)
+    }
+
     pub fn push_msg(&self, timestamp: Timestamp, id: PacketId, msg: &[u8]) -> Result<(), Error> {
         let exists = self.with_state(|s| {
             if let Some(msg_log) = s.msg_logs.get(&id) {
@@ -263,15 +398,17 @@ impl DB {
         &self,
         stream_id: StreamId,
         behavior: FixedRateBehavior,
+        filter: Option<&HashSet<ComponentId>>,
     ) -> Arc<FixedRateStreamState> {
         let mut state = self.state.write().expect("poisoned lock");
+        let timestep = self.fixed_rate_timestep(&state, behavior.timestep, filter);
         state
             .streams
             .entry(stream_id)
             .or_insert_with(|| {
                 Arc::new(FixedRateStreamState::new(
                     stream_id,
-                    Duration::from_nanos(behavior.timestep),
+                    timestep,
                     match behavior.initial_timestamp {
                         InitialTimestamp::Earliest => self.earliest_timestamp,
                         InitialTimestamp::Latest => self.last_updated.latest(),
@@ -282,6 +419,35 @@ impl DB {
             })
             .clone()
     }
+
+    /// Resolves the timestep of a fixed rate stream, where a `timestep` of 0 means the client didn't specify one
+    ///
+    /// Unspecified timesteps use the fastest `nominal_rate_hz` of the streamed components, every component unless
+    /// `filter` limits the stream, so that no samples are skipped. Otherwise they fall back to the default stream time
+    /// step.
+    pub fn fixed_rate_timestep(
+        &self,
+        state: &State,
+        timestep: u64,
+        filter: Option<&HashSet<ComponentId>>,
+    ) -> Duration {
+        if timestep != 0 {
+            return Duration::from_nanos(timestep);
+        }
+        state
+            .component_metadata
+            .iter()
+            .filter(|(component_id, _)| filter.is_none_or(|filter| filter.contains(*component_id)))
+            .filter_map(|(_, metadata)| metadata.nominal_rate_hz())
+            .max_by(f64::total_cmp)
+            .and_then(|hz| Duration::try_from_secs_f64(hz.recip()).ok())
+            .unwrap_or_else(|| {
+                Duration::from_nanos(
+                    self.default_stream_time_step
+                        .load(atomic::Ordering::Relaxed),
+                )
+            })
+    }
 }
 
 impl State {
@@ -314,6 +480,34 @@ impl State {
         Ok(())
     }
 
+    /// Moves a component to a new schema version if `schema` differs from its current one, returning the version
+    ///
+    /// The new version gets its own time series, so values recorded under earlier versions are left as they were.
+    /// Components that don't exist yet are inserted at version 0.
+    pub fn migrate_component(
+        &mut self,
+        component_id: ComponentId,
+        schema: ComponentSchema,
+        db_path: &Path,
+    ) -> Result<u16, Error> {
+        let Some(component) = self.components.get_mut(&component_id) else {
+            self.insert_component(component_id, schema, db_path)?;
+            return Ok(0);
+        };
+        if component.schema == schema {
+            return Ok(component.version);
+        }
+        let version = component
+            .version
+            .checked_add(1)
+            .ok_or(Error::SchemaMismatch)?;
+        info!(component.id = ?component_id.0, ?version, "migrating schema");
+        let next =
+            Component::create_version(db_path, component_id, schema, version, Timestamp::now())?;
+        component.push_epoch(next);
+        Ok(version)
+    }
+
     pub fn get_component_metadata(&self, component_id: ComponentId) -> Option<&ComponentMetadata> {
         self.component_metadata.get(&component_id)
     }
@@ -342,6 +536,103 @@ impl State {
         Ok(())
     }
 
+    /// Appends `metadata` to the component's metadata history, if metadata versioning is enabled
+    ///
+    /// Records are kept in timestamp order, so a record older than the latest one is stored at the latest timestamp.
+    /// A history is started with the component's current metadata, which stays in effect for every earlier timestamp.
+    pub fn record_component_metadata(
+        &mut self,
+        metadata: ComponentMetadata,
+        timestamp: Timestamp,
+        db_path: &Path,
+    ) -> Result<(), Error> {
+        if !self.db_config.version_metadata() {
+            return Ok(());
+        }
+        let component_id = metadata.component_id;
+        let history = self
+            .component_metadata_history
+            .entry(component_id)
+            .or_default();
+        let current = self.component_metadata.get(&component_id);
+        if let Some(current) = current.filter(|_| history.is_empty()) {
+            history.push((Timestamp(i64::MIN), current.clone()));
+        }
+        let timestamp = match history.last() {
+            Some((_, last)) if *last == metadata => return Ok(()),
+            Some((last_timestamp, _)) => timestamp.max(*last_timestamp),
+            None => timestamp,
+        };
+        history.push((timestamp, metadata));
+        let component_path = db_path.join(component_id.to_string());
+        std::fs::create_dir_all(&component_path)?;
+        history.write(component_path.join("metadata_history"))
+    }
+
+    /// Returns the metadata that was in effect for a component at `timestamp`
+    ///
+    /// Falls back to the earliest record for timestamps before the history starts, and to the current metadata for
+    /// components without any history or when metadata versioning is disabled.
+    pub fn get_component_metadata_at(
+        &self,
+        component_id: ComponentId,
+        timestamp: Timestamp,
+    ) -> Option<&ComponentMetadata> {
+        if !self.db_config.version_metadata() {
+            return self.component_metadata.get(&component_id);
+        }
+        match self.component_metadata_history.get(&component_id) {
+            Some(history) if !history.is_empty() => {
+                let i = history.partition_point(|(t, _)| *t <= timestamp);
+                Some(&history[i.saturating_sub(1)].1)
+            }
+            _ => self.component_metadata.get(&component_id),
+        }
+    }
+
+    /// Flushes every time series and msg log to disk
+    pub fn sync(&self) -> Result<(), Error> {
+        for component in self.components.values() {
+            component.time_series.sync()?;
+            component.rollups.sync()?;
+        }
+        for msg_log in self.msg_logs.values() {
+            msg_log.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Applies every state in `states` before any of the affected streams resume emitting
+    ///
+    /// All stream ids are resolved up front, so an unknown id leaves every stream untouched.
+    pub fn apply_stream_states(&self, states: &[SetStreamState]) -> Result<(), Error> {
+        let streams = states
+            .iter()
+            .map(|state| {
+                self.streams
+                    .get(&state.id)
+                    .map(|stream| (stream, state))
+                    .ok_or(Error::StreamNotFound(state.id))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let was_playing = streams
+            .iter()
+            .map(|(stream, _)| stream.hold())
+            .collect::<Vec<_>>();
+        for (stream, state) in &streams {
+            if let Some(timestamp) = state.timestamp {
+                stream.store_timestamp(timestamp);
+            }
+            if let Some(frequency) = state.frequency {
+                stream.set_frequency(frequency);
+            }
+        }
+        for ((stream, state), was_playing) in streams.into_iter().zip(was_playing) {
+            stream.set_playing(state.playing.unwrap_or(was_playing));
+        }
+        Ok(())
+    }
+
     pub fn get_or_insert_msg_log(
         &mut self,
         id: PacketId,
@@ -360,6 +651,36 @@ impl State {
         })
     }
 
+    /// Lists every component and msg log for a follower, see [`GetReplicationState`]
+    pub fn replication_state(&self) -> ReplicationState {
+        let components = self
+            .components
+            .values()
+            .map(|component| ReplicatedComponent {
+                component_id: component.component_id,
+                schema: component.schema.to_schema(),
+                metadata: self
+                    .component_metadata
+                    .get(&component.component_id)
+                    .cloned(),
+                last: component
+                    .time_series
+                    .latest()
+                    .map(|(timestamp, _)| *timestamp),
+            })
+            .collect();
+        let msgs = self
+            .msg_logs
+            .iter()
+            .map(|(msg_id, msg_log)| ReplicatedMsgLog {
+                msg_id: *msg_id,
+                metadata: msg_log.metadata().cloned(),
+                last: msg_log.latest().map(|(timestamp, _)| timestamp),
+            })
+            .collect();
+        ReplicationState { components, msgs }
+    }
+
     pub fn set_msg_metadata(
         &mut self,
         id: PacketId,
@@ -370,6 +691,17 @@ impl State {
         msg_log.set_metadata(metadata)?;
         Ok(())
     }
+
+    /// Cancels and forgets every query started over a connection
+    fn cancel_queries(&mut self, conn_id: ConnId) {
+        self.queries.retain(|(query_conn_id, _), cancel| {
+            if *query_conn_id != conn_id {
+                return true;
+            }
+            cancel.store(true, atomic::Ordering::Relaxed);
+            false
+        });
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -378,17 +710,33 @@ pub struct ComponentSchema {
     pub dim: SmallVec<[usize; 4]>,
 }
 
+/// The on-disk form of a [`ComponentSchema`]
+///
+/// It has no version, each version of a component lives in its own directory, so schema files written before versioning
+/// still read.
+#[derive(Serialize, Deserialize)]
+struct StoredSchema {
+    prim_type: PrimType,
+    shape: Vec<u64>,
+}
+
 impl Serialize for ComponentSchema {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let schema = self.to_schema();
-        schema.serialize(serializer)
+        StoredSchema {
+            prim_type: self.prim_type,
+            shape: self.shape().to_vec(),
+        }
+        .serialize(serializer)
     }
 }
 
 impl<'de> Deserialize<'de> for ComponentSchema {
     fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        let schema = Schema::<Vec<u64>>::deserialize(deserializer)?;
-        Ok(schema.into())
+        let StoredSchema { prim_type, shape } = StoredSchema::deserialize(deserializer)?;
+        Ok(ComponentSchema {
+            prim_type,
+            dim: shape.into_iter().map(|x| x as usize).collect(),
+        })
     }
 }
 
@@ -402,6 +750,11 @@ impl ComponentSchema {
         self.dim.iter().product::<usize>() * self.prim_type.size()
     }
 
+    /// The [`Schema::stable_hash`] of the schema, which vtables are verified against
+    pub fn stable_hash(&self) -> u64 {
+        impeller2::schema::schema_hash(self.prim_type, &self.shape())
+    }
+
     pub fn read(path: impl AsRef<Path>) -> Result<Self, Error> {
         let data = std::fs::read(path)?;
         Ok(postcard::from_bytes(&data)?)
@@ -472,6 +825,27 @@ impl ComponentSchema {
                 nox::ArrayView::from_bytes_shape_unchecked(buf, dim)
                     .ok_or(Error::Impeller(impeller2::error::Error::BufferOverflow))?,
             ),
+            PrimType::F16 => ComponentView::F16(
+                nox::ArrayView::from_bytes_shape_unchecked(buf, dim)
+                    .ok_or(Error::Impeller(impeller2::error::Error::BufferOverflow))?,
+            ),
+            PrimType::BF16 => ComponentView::BF16(
+                nox::ArrayView::from_bytes_shape_unchecked(buf, dim)
+                    .ok_or(Error::Impeller(impeller2::error::Error::BufferOverflow))?,
+            ),
+
+            PrimType::Bytes => ComponentView::Bytes(
+                nox::ArrayView::from_bytes_shape_unchecked(buf, dim)
+                    .ok_or(Error::Impeller(impeller2::error::Error::BufferOverflow))?,
+            ),
+            PrimType::String => ComponentView::String(
+                nox::ArrayView::from_bytes_shape_unchecked(buf, dim)
+                    .ok_or(Error::Impeller(impeller2::error::Error::BufferOverflow))?,
+            ),
+            PrimType::Q15_16 => ComponentView::Q15_16(
+                nox::ArrayView::from_bytes_shape_unchecked(buf, dim)
+                    .ok_or(Error::Impeller(impeller2::error::Error::BufferOverflow))?,
+            ),
         };
         Ok((size, view))
     }
@@ -508,22 +882,73 @@ pub trait MetadataExt: Sized + Serialize + DeserializeOwned {
 impl MetadataExt for EntityMetadata {}
 impl MetadataExt for ComponentMetadata {}
 impl MetadataExt for MsgMetadata {}
+impl MetadataExt for MetadataHistory {}
+impl MetadataExt for RetentionPolicy {}
+
+type MetadataHistory = Vec<(Timestamp, ComponentMetadata)>;
 
 #[derive(Clone)]
 pub struct Component {
     pub component_id: ComponentId,
     pub time_series: TimeSeries,
     pub schema: ComponentSchema,
+    /// The schema version, bumped by each [`MigrateComponent`]
+    pub version: u16,
+    /// The read-only time series of earlier schema versions, oldest first
+    pub epochs: Vec<Component>,
+    /// The downsampled tiers of the current version, see [`rollup`]
+    pub rollups: Rollups,
+    recording: Arc<AtomicBool>,
+    live_value: Arc<RwLock<Option<(Timestamp, Vec<u8>)>>>,
 }
 
 impl Component {
+    fn new(
+        component_id: ComponentId,
+        time_series: TimeSeries,
+        schema: ComponentSchema,
+        version: u16,
+    ) -> Self {
+        Component {
+            component_id,
+            time_series,
+            schema,
+            version,
+            epochs: vec![],
+            rollups: Rollups::default(),
+            recording: Arc::new(AtomicBool::new(true)),
+            live_value: Arc::default(),
+        }
+    }
+
+    /// The directory holding a schema version of a component
+    ///
+    /// Version 0 is the component directory itself, later versions are `v1`, `v2` and so on inside it.
+    pub fn version_path(db_path: &Path, component_id: ComponentId, version: u16) -> PathBuf {
+        let component_path = db_path.join(component_id.to_string());
+        match version {
+            0 => component_path,
+            version => component_path.join(format!("v{version}")),
+        }
+    }
+
     pub fn create(
         db_path: &Path,
         component_id: ComponentId,
         schema: ComponentSchema,
         start_timestamp: Timestamp,
     ) -> Result<Self, Error> {
-        let component_path = db_path.join(component_id.to_string());
+        Self::create_version(db_path, component_id, schema, 0, start_timestamp)
+    }
+
+    fn create_version(
+        db_path: &Path,
+        component_id: ComponentId,
+        schema: ComponentSchema,
+        version: u16,
+        start_timestamp: Timestamp,
+    ) -> Result<Self, Error> {
+        let component_path = Self::version_path(db_path, component_id, version);
         std::fs::create_dir_all(&component_path)?;
         let component_schema_path = component_path.join("schema");
         if !component_schema_path.exists() {
@@ -534,34 +959,108 @@ impl Component {
             start_timestamp,
             schema.size() as u64,
         )?;
-        Ok(Component {
-            component_id,
-            time_series,
-            schema,
-        })
+        let rollups = Rollups::open(&component_path, &schema, start_timestamp)?;
+        let mut component = Component::new(component_id, time_series, schema, version);
+        component.rollups = rollups;
+        Ok(component)
     }
 
+    /// Opens a component along with every schema version after the one in `path`
     pub fn open(
         path: impl AsRef<Path>,
         component_id: ComponentId,
         schema: ComponentSchema,
     ) -> Result<Self, Error> {
-        let time_series = TimeSeries::open(path)?;
-        Ok(Component {
-            component_id,
-            time_series,
-            schema,
-        })
+        let path = path.as_ref();
+        let time_series = TimeSeries::open_expecting(path, schema.size() as u64)?;
+        let mut component = Component::new(component_id, time_series, schema, 0);
+
+        let mut versions = vec![];
+        for elem in std::fs::read_dir(path)? {
+            let Ok(elem) = elem else { continue };
+            let Some(version) = elem
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix('v'))
+                .and_then(|version| version.parse::<u16>().ok())
+            else {
+                continue;
+            };
+            if elem.path().is_dir() {
+                versions.push(version);
+            }
+        }
+        versions.sort_unstable();
+        for version in versions {
+            let path = path.join(format!("v{version}"));
+            let schema = ComponentSchema::read(path.join("schema"))?;
+            let time_series = TimeSeries::open_expecting(&path, schema.size() as u64)?;
+            let next = Component::new(component_id, time_series, schema, version);
+            component.push_epoch(next);
+        }
+        // only the current version is rolled up, earlier ones don't change
+        let current_path = match component.version {
+            0 => path.to_path_buf(),
+            version => path.join(format!("v{version}")),
+        };
+        component.rollups = Rollups::open(
+            &current_path,
+            &component.schema,
+            component.time_series.start_timestamp(),
+        )?;
+        Ok(component)
+    }
+
+    /// Makes `next` the current version, keeping this one as a read-only epoch
+    fn push_epoch(&mut self, mut next: Component) {
+        next.recording = self.recording.clone();
+        let mut prev = std::mem::replace(self, next);
+        self.epochs = std::mem::take(&mut prev.epochs);
+        self.epochs.push(prev);
+    }
+
+    /// Every schema version of the component, oldest first, ending with the current one
+    pub fn versions(&self) -> impl Iterator<Item = &Component> {
+        self.epochs.iter().chain(std::iter::once(self))
     }
 
     fn as_vtable_op(&self) -> Arc<OpBuilder> {
-        schema(
+        hashed_schema(
             self.schema.prim_type,
             &self.schema.shape(),
             component(self.component_id),
         )
     }
 
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_recording(&self, recording: bool) {
+        self.recording.store(recording, atomic::Ordering::Relaxed);
+    }
+
+    /// Hands a value to real-time subscribers without writing it to the time series
+    fn push_live(&self, timestamp: Timestamp, buf: &[u8]) {
+        *self.live_value.write().expect("live value lock poisoned") =
+            Some((timestamp, buf.to_vec()));
+        self.time_series.waiter().wake_all();
+    }
+
+    /// The newest value received, whether or not it was recorded
+    pub fn latest(&self) -> Option<(Timestamp, Cow<'_, [u8]>)> {
+        let recorded = self.time_series.latest();
+        let live = self.live_value.read().expect("live value lock poisoned");
+        match (live.as_ref(), recorded) {
+            (Some((live_ts, buf)), recorded)
+                if recorded.is_none_or(|(recorded_ts, _)| live_ts > recorded_ts) =>
+            {
+                Some((*live_ts, Cow::Owned(buf.clone())))
+            }
+            (_, recorded) => recorded.map(|(ts, buf)| (*ts, Cow::Borrowed(buf))),
+        }
+    }
+
     fn get_nearest(&self, timestamp: Timestamp) -> Option<(Timestamp, &[u8])> {
         self.time_series.get_nearest(timestamp)
     }
@@ -569,6 +1068,12 @@ impl Component {
     fn get_range(&self, range: Range<Timestamp>) -> Option<(&[Timestamp], &[u8])> {
         self.time_series.get_range(range)
     }
+
+    /// Rolls the closed buckets of the current version up into its tiers, see [`Rollups::update`]
+    pub fn update_rollups(&self) -> Result<usize, Error> {
+        let _pin = self.time_series.pin();
+        self.rollups.update(&self.time_series, &self.schema)
+    }
 }
 
 struct DBSink<'a> {
@@ -591,6 +1096,14 @@ impl Decomponentize for DBSink<'_> {
         let Some(component) = self.components.get(&component_id) else {
             return Err(Error::ComponentNotFound(component_id));
         };
+        // a vtable from before a migration would otherwise write values of the old size into the new time series
+        if value_buf.len() != component.schema.size() {
+            return Err(Error::SchemaMismatch);
+        }
+        if !component.is_recording() {
+            component.push_live(timestamp, value_buf);
+            return Ok(());
+        }
         let time_series_empty = component.time_series.index().is_empty();
         component.time_series.push_buf(timestamp, value_buf)?;
         if time_series_empty {
@@ -607,6 +1120,10 @@ pub struct Server {
     pub db: Arc<DB>,
 }
 
+/// How often the server enforces retention policies, see [`DB::compact`]
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(30);
+const ROLLUP_INTERVAL: Duration = Duration::from_secs(1);
+
 impl Server {
     pub fn new(path: impl AsRef<Path>, addr: SocketAddr) -> Result<Server, Error> {
         info!(?addr, "listening");
@@ -630,6 +1147,8 @@ impl Server {
         let addr = listener.local_addr()?;
         let udp_db = db.clone();
         stellarator::struc_con::stellar(move || Self::handle_udp(addr, udp_db));
+        stellarator::spawn(Self::compact_periodically(db.clone()));
+        stellarator::spawn(Self::roll_up_periodically(db.clone()));
         loop {
             let stream = listener.accept().await?;
             let conn_db = db.clone();
@@ -637,6 +1156,24 @@ impl Server {
         }
     }
 
+    async fn compact_periodically(db: Arc<DB>) {
+        loop {
+            stellarator::sleep(COMPACTION_INTERVAL).await;
+            if let Err(err) = db.compact() {
+                warn!(?err, "compaction failed");
+            }
+        }
+    }
+
+    async fn roll_up_periodically(db: Arc<DB>) {
+        loop {
+            stellarator::sleep(ROLLUP_INTERVAL).await;
+            if let Err(err) = db.update_rollups() {
+                warn!(?err, "rollup failed");
+            }
+        }
+    }
+
     pub async fn handle_udp(addr: SocketAddr, db: Arc<DB>) -> Result<(), Error> {
         let socket = UdpSocket::bind(addr)?;
         let (rx, tx) = socket.split();
@@ -660,25 +1197,59 @@ pub async fn handle_conn(stream: TcpStream, db: Arc<DB>) {
     }
 }
 
+/// Identifies a connection for as long as the db runs
+type ConnId = u64;
+
+static NEXT_CONN_ID: AtomicU64 = AtomicU64::new(0);
+
 async fn handle_conn_inner<A: AsyncRead + AsyncWrite + 'static>(
+    tx: Arc<Mutex<PacketSink<OwnedWriter<A>>>>,
+    rx: PacketStream<OwnedReader<A>>,
+    db: Arc<DB>,
+) -> Result<(), Error> {
+    let conn_id = NEXT_CONN_ID.fetch_add(1, atomic::Ordering::Relaxed);
+    let res = handle_packets(conn_id, tx, rx, &db).await;
+    // nobody is left to read the results of the queries this connection started
+    db.with_state_mut(|s| s.cancel_queries(conn_id));
+    res
+}
+
+async fn handle_packets<A: AsyncRead + AsyncWrite + 'static>(
+    conn_id: ConnId,
     mut tx: Arc<Mutex<PacketSink<OwnedWriter<A>>>>,
     mut rx: PacketStream<OwnedReader<A>>,
-    db: Arc<DB>,
+    db: &Arc<DB>,
 ) -> Result<(), Error> {
     let mut buf = vec![0u8; 8 * 1024 * 1024];
     let mut resp_pkt = LenPacket::new(PacketTy::Msg, [0, 0], 8 * 1024 * 1024);
+    let mut ack_window = AckWindow::default();
     loop {
         let pkt = rx.next(buf).await?;
         let req_id = pkt.req_id();
         let mut pkt_tx = PacketTx {
+            conn_id,
             req_id,
             tx,
             pkt: Some(resp_pkt),
         };
-        let result = handle_packet(&pkt, &db, &mut pkt_tx).await;
+        let ack_seq = match &pkt {
+            Packet::Msg(m) => m.ack_seq,
+            _ => None,
+        };
+        // every copy is acked, since the sender retransmits when an ack is lost, but only the first is handled
+        let result = match ack_seq {
+            Some(seq) if ack_window.contains(seq) => Ok(()),
+            _ => handle_packet(&pkt, db, &mut pkt_tx).await,
+        };
         buf = pkt.into_buf().into_inner();
         match result {
-            Ok(_) => {}
+            // acked only once handled, so a msg that failed is retransmitted rather than reported as delivered
+            Ok(_) => {
+                if let Some(seq) = ack_seq {
+                    ack_window.insert(seq);
+                    pkt_tx.send_msg(&Ack { seq }).await?;
+                }
+            }
             Err(err) if err.is_stream_closed() => {}
             Err(err) => {
                 warn!(?err, "error handling packet");
@@ -698,6 +1269,7 @@ async fn handle_conn_inner<A: AsyncRead + AsyncWrite + 'static>(
 }
 
 pub struct PacketTx<A: AsyncWrite + 'static> {
+    conn_id: ConnId,
     req_id: RequestId,
     tx: Arc<Mutex<PacketSink<OwnedWriter<A>>>>,
     pkt: Option<LenPacket>,
@@ -706,6 +1278,7 @@ pub struct PacketTx<A: AsyncWrite + 'static> {
 impl<A: AsyncWrite + 'static> Clone for PacketTx<A> {
     fn clone(&self) -> Self {
         Self {
+            conn_id: self.conn_id,
             req_id: self.req_id,
             tx: self.tx.clone(),
             pkt: self.pkt.clone(),
@@ -800,27 +1373,27 @@ async fn handle_packet<A: AsyncWrite + 'static>(
             let stream = m.parse::<Stream>()?;
             let tx = tx.tx.clone();
             let db = db.clone();
-            handle_stream(tx, stream, db, m.req_id);
+            handle_stream(tx, stream, None, db, m.req_id);
+        }
+        Packet::Msg(m) if m.id == FilteredStream::ID => {
+            let FilteredStream {
+                stream,
+                component_ids,
+            } = m.parse::<FilteredStream>()?;
+            let tx = tx.tx.clone();
+            let db = db.clone();
+            let filter = Arc::new(component_ids.into_iter().collect());
+            handle_stream(tx, stream, Some(filter), db, m.req_id);
         }
         Packet::Msg(m) if m.id == SetStreamState::ID => {
             let set_stream_state = m.parse::<SetStreamState>()?;
-            let stream_id = set_stream_state.id;
-            db.with_state(|s| {
-                let Some(state) = s.streams.get(&stream_id) else {
-                    return Err(Error::StreamNotFound(stream_id));
-                };
-                debug!(msg = ?set_stream_state, "set_stream_state received");
-                if let Some(playing) = set_stream_state.playing {
-                    state.set_playing(playing);
-                }
-                if let Some(timestamp) = set_stream_state.timestamp {
-                    state.set_timestamp(timestamp);
-                }
-                if let Some(frequency) = set_stream_state.frequency {
-                    state.set_frequency(frequency);
-                }
-                Ok(())
-            })?;
+            debug!(msg = ?set_stream_state, "set_stream_state received");
+            db.with_state(|s| s.apply_stream_states(std::slice::from_ref(&set_stream_state)))?;
+        }
+        Packet::Msg(m) if m.id == SetStreamStateBatch::ID => {
+            let SetStreamStateBatch(states) = m.parse::<SetStreamStateBatch>()?;
+            debug!(msg = ?states, "set_stream_state_batch received");
+            db.with_state(|s| s.apply_stream_states(&states))?;
         }
         Packet::Msg(m) if m.id == GetSchema::ID => {
             let get_schema = m.parse::<GetSchema>()?;
@@ -829,12 +1402,35 @@ async fn handle_packet<A: AsyncWrite + 'static>(
                     .components
                     .iter()
                     .filter(|(component_id, _)| *component_id == &get_schema.component_id)
-                    .map(|(_, component)| component.schema.to_schema())
+                    .map(|(_, component)| {
+                        component.schema.to_schema().with_version(component.version)
+                    })
                     .next()
                     .ok_or(Error::ComponentNotFound(get_schema.component_id))
             })?;
             tx.send_msg(&SchemaMsg(schema)).await?;
         }
+        Packet::Msg(m) if m.id == MigrateComponent::ID => {
+            let MigrateComponent {
+                component_id,
+                schema,
+            } = m.parse::<MigrateComponent>()?;
+            let version =
+                db.migrate_component(component_id, ComponentSchema::from(schema.clone()))?;
+            tx.send_msg(&SchemaMsg(schema.with_version(version)))
+                .await?;
+        }
+        Packet::Msg(m) if m.id == SetRetention::ID => {
+            let SetRetention {
+                component_id,
+                policy,
+            } = m.parse::<SetRetention>()?;
+            db.set_retention(component_id, policy)?;
+        }
+        Packet::Msg(m) if m.id == Compact::ID => {
+            let report = db.compact()?;
+            tx.send_msg(&report).await?;
+        }
         Packet::Msg(m) if m.id == GetTimeSeries::ID => {
             let get_time_series = m.parse::<GetTimeSeries>()?;
             let GetTimeSeries {
@@ -849,24 +1445,162 @@ async fn handle_packet<A: AsyncWrite + 'static>(
                 };
                 Ok(component.clone())
             })?;
-            let Some((timestamps, data)) = component.get_range(range) else {
+            let _pin = component.time_series.pin();
+            let Some((timestamps, data)) = component.get_range(range.clone()) else {
                 return Err(Error::TimeRangeOutOfBounds);
             };
             let size = component.schema.size();
-            let (timestamps, data) = if let Some(limit) = limit {
-                let len = timestamps.len().min(limit);
-                (&timestamps[..len], &data[..len * size])
-            } else {
-                (timestamps, data)
+            match limit {
+                // a range with more samples than fit is downsampled to the bucket means of a rollup tier, so long
+                // ranges don't read every sample
+                Some(limit) if timestamps.len() > limit => {
+                    let range = range.start..Timestamp(range.end.0.saturating_add(1));
+                    match component.rollups.downsample(
+                        &component.time_series,
+                        &component.schema,
+                        range,
+                        limit,
+                    )? {
+                        Some(buckets) => {
+                            let (timestamps, data) =
+                                rollup::means(&buckets, component.schema.prim_type);
+                            tx.send_time_series(id, &timestamps, &data).await?;
+                        }
+                        None => {
+                            tx.send_time_series(id, &timestamps[..limit], &data[..limit * size])
+                                .await?;
+                        }
+                    }
+                }
+                _ => tx.send_time_series(id, timestamps, data).await?,
+            }
+        }
+        Packet::Msg(m) if m.id == GetTimeSeriesPage::ID => {
+            let GetTimeSeriesPage {
+                id,
+                range,
+                component_id,
+                limit,
+            } = m.parse::<GetTimeSeriesPage>()?;
+            let component = db.with_state(|state| {
+                let Some(component) = state.components.get(&component_id) else {
+                    return Err(Error::ComponentNotFound(component_id));
+                };
+                Ok(component.clone())
+            })?;
+            let _pin = component.time_series.pin();
+            let Some((timestamps, data)) = component.get_range(range) else {
+                return Err(Error::TimeRangeOutOfBounds);
             };
-            tx.send_time_series(id, timestamps, data).await?;
+            let len = timestamps.len().min(limit);
+            let size = component.schema.size();
+            tx.send_time_series(id, &timestamps[..len], &data[..len * size])
+                .await?;
+        }
+        Packet::Msg(m) if m.id == GetLatest::ID => {
+            let GetLatest { id, component_id } = m.parse::<GetLatest>()?;
+            let component = db.with_state(|state| {
+                let Some(component) = state.components.get(&component_id) else {
+                    return Err(Error::ComponentNotFound(component_id));
+                };
+                Ok(component.clone())
+            })?;
+            let _pin = component.time_series.pin();
+            match component.latest() {
+                Some((timestamp, data)) => tx.send_time_series(id, &[timestamp], &data).await?,
+                None => tx.send_time_series(id, &[], &[]).await?,
+            }
+        }
+        Packet::Msg(m) if m.id == GetTimeSeriesAll::ID => {
+            let GetTimeSeriesAll {
+                component,
+                range,
+                limit,
+            } = m.parse::<GetTimeSeriesAll>()?;
+            let suffix = format!(".{component}");
+            let components = db.with_state(|state| {
+                state
+                    .component_metadata
+                    .values()
+                    .filter_map(|metadata| {
+                        let entity = metadata.name.strip_suffix(&suffix)?;
+                        let component = state.components.get(&metadata.component_id)?;
+                        Some((entity.to_string(), component.clone()))
+                    })
+                    .collect::<Vec<_>>()
+            });
+            let mut series = Vec::with_capacity(components.len());
+            for (entity, component) in components {
+                let _pin = component.time_series.pin();
+                let Some((timestamps, data)) = component.get_range(range.clone()) else {
+                    continue;
+                };
+                let size = component.schema.size();
+                let len = limit.map_or(timestamps.len(), |limit| timestamps.len().min(limit));
+                series.push(EntityTimeSeries {
+                    entity,
+                    component_id: component.component_id,
+                    schema: component.schema.to_schema(),
+                    timestamps: timestamps[..len].to_vec(),
+                    data: data[..len * size].to_vec(),
+                });
+            }
+            tx.send_msg(&TimeSeriesAllResp { series }).await?;
         }
         Packet::Msg(m) if m.id == SetComponentMetadata::ID => {
             let SetComponentMetadata(metadata) = m.parse::<SetComponentMetadata>()?;
-            db.with_state_mut(|state| state.set_component_metadata(metadata, &db.path))?;
+            let timestamp = m.timestamp.unwrap_or_else(|| db.last_updated.latest());
+            db.with_state_mut(|state| {
+                state.record_component_metadata(metadata.clone(), timestamp, &db.path)?;
+                state.set_component_metadata(metadata, &db.path)
+            })?;
+        }
+        Packet::Msg(m) if m.id == SetComponentEnum::ID => {
+            let SetComponentEnum {
+                component_id,
+                values,
+            } = m.parse::<SetComponentEnum>()?;
+            let timestamp = m.timestamp.unwrap_or_else(|| db.last_updated.latest());
+            db.with_state_mut(|state| {
+                let mut metadata = state
+                    .get_component_metadata(component_id)
+                    .cloned()
+                    .unwrap_or_else(|| ComponentMetadata {
+                        component_id,
+                        name: component_id.to_string(),
+                        metadata: Default::default(),
+                    });
+                metadata
+                    .set_enum_values(&values)
+                    .map_err(|_| Error::BadMessage)?;
+                state.record_component_metadata(metadata.clone(), timestamp, &db.path)?;
+                state.set_component_metadata(metadata, &db.path)
+            })?;
+        }
+        Packet::Msg(m) if m.id == SetComponentBits::ID => {
+            let SetComponentBits { component_id, bits } = m.parse::<SetComponentBits>()?;
+            if bits.keys().any(|&bit| bit as u32 >= u32::BITS) {
+                return Err(Error::BadMessage);
+            }
+            let timestamp = m.timestamp.unwrap_or_else(|| db.last_updated.latest());
+            db.with_state_mut(|state| {
+                let mut metadata = state
+                    .get_component_metadata(component_id)
+                    .cloned()
+                    .unwrap_or_else(|| ComponentMetadata {
+                        component_id,
+                        name: component_id.to_string(),
+                        metadata: Default::default(),
+                    });
+                metadata
+                    .set_bit_names(&bits)
+                    .map_err(|_| Error::BadMessage)?;
+                state.record_component_metadata(metadata.clone(), timestamp, &db.path)?;
+                state.set_component_metadata(metadata, &db.path)
+            })?;
         }
         Packet::Msg(m) if m.id == GetComponentMetadata::ID => {
-            let GetComponentMetadata { component_id } = m.parse::<GetComponentMetadata>()?;
+            let GetComponentMetadata { component_id, at } = m.parse::<GetComponentMetadata>()?;
 
             tx.send_with_builder(|pkt| {
                 let header = PacketHeader {
@@ -877,7 +1611,11 @@ async fn handle_packet<A: AsyncWrite + 'static>(
                 pkt.as_mut_packet().header = header;
                 pkt.clear();
                 db.with_state(|state| {
-                    let Some(metadata) = state.component_metadata.get(&component_id) else {
+                    let metadata = match at {
+                        Some(at) => state.get_component_metadata_at(component_id, at),
+                        None => state.component_metadata.get(&component_id),
+                    };
+                    let Some(metadata) = metadata else {
                         return Err(Error::ComponentNotFound(component_id));
                     };
                     postcard::serialize_with_flavor(&metadata, pkt).map_err(Error::from)
@@ -888,14 +1626,18 @@ async fn handle_packet<A: AsyncWrite + 'static>(
 
         Packet::Msg(m) if m.id == DumpMetadata::ID => {
             let msg = db.with_state(|state| {
-                let component_metadata = state.component_metadata.values().cloned().collect();
+                let mut component_metadata: Vec<_> =
+                    state.component_metadata.values().cloned().collect();
+                component_metadata
+                    .sort_by(|a, b| (&a.name, a.component_id).cmp(&(&b.name, b.component_id)));
 
-                let msg_metadata = state
+                let mut msg_metadata: Vec<_> = state
                     .msg_logs
                     .values()
                     .flat_map(|m| m.metadata())
                     .cloned()
                     .collect();
+                msg_metadata.sort_by(|a, b| a.name.cmp(&b.name));
                 DumpMetadataResp {
                     component_metadata,
                     msg_metadata,
@@ -904,6 +1646,33 @@ async fn handle_packet<A: AsyncWrite + 'static>(
             });
             tx.send_msg(&msg).await?;
         }
+        Packet::Msg(m) if m.id == QueryMetadata::ID => {
+            let query = m.parse::<QueryMetadata>()?;
+            let msg = db.with_state(|state| {
+                let mut component_metadata: Vec<_> = state
+                    .component_metadata
+                    .values()
+                    .filter(|m| query.matches(MetadataKind::Component, &m.name, &m.metadata))
+                    .cloned()
+                    .collect();
+                component_metadata
+                    .sort_by(|a, b| (&a.name, a.component_id).cmp(&(&b.name, b.component_id)));
+
+                let mut msg_metadata: Vec<_> = state
+                    .msg_logs
+                    .values()
+                    .flat_map(|m| m.metadata())
+                    .filter(|m| query.matches(MetadataKind::Msg, &m.name, &m.metadata))
+                    .cloned()
+                    .collect();
+                msg_metadata.sort_by(|a, b| a.name.cmp(&b.name));
+                QueryMetadataResp {
+                    component_metadata,
+                    msg_metadata,
+                }
+            });
+            tx.send_msg(&msg).await?;
+        }
         Packet::Msg(m) if m.id == DumpSchema::ID => {
             let msg = db.with_state(|state| {
                 let schemas = state
@@ -954,6 +1723,22 @@ async fn handle_packet<A: AsyncWrite + 'static>(
             db.save_db_state()?;
             tx.send_msg(&db.db_config()).await?;
         }
+        Packet::Msg(m) if m.id == SetComponentRecording::ID => {
+            let SetComponentRecording {
+                component_id,
+                recording,
+            } = m.parse::<SetComponentRecording>()?;
+            db.with_state(|state| {
+                let component = state
+                    .get_component(component_id)
+                    .ok_or(Error::ComponentNotFound(component_id))?;
+                component.set_recording(recording);
+                Ok::<_, Error>(())
+            })?;
+        }
+        Packet::Msg(m) if m.id == GetServerTime::ID => {
+            tx.send_msg(&ServerTime(Timestamp::now())).await?;
+        }
         Packet::Msg(m) if m.id == GetEarliestTimestamp::ID => {
             tx.send_msg(&EarliestTimestamp(db.earliest_timestamp))
                 .await?;
@@ -983,36 +1768,59 @@ async fn handle_packet<A: AsyncWrite + 'static>(
             let settings = db.db_config();
             tx.send_msg(&settings).await?;
         }
+        Packet::Msg(m) if m.id == Flush::ID => {
+            db.with_state(|s| s.sync())?;
+            tx.send_msg(&FlushAck).await?;
+        }
         Packet::Msg(m) if m.id == SQLQuery::ID => {
             let SQLQuery(query) = m.parse::<SQLQuery>()?;
+            let cancel = Arc::new(AtomicBool::new(false));
+            handle_sql_query(query, db.clone(), cancel, tx).await?;
+        }
+        Packet::Msg(m) if m.id == SQLQueryStream::ID => {
+            let SQLQueryStream { id, query } = m.parse::<SQLQueryStream>()?;
+            let cancel = Arc::new(AtomicBool::new(false));
+            let key = (tx.conn_id, id);
+            // a second query with the same id would leave the first one impossible to cancel
+            db.with_state_mut(|s| {
+                if s.queries.contains_key(&key) {
+                    return Err(Error::QueryIdInUse(id));
+                }
+                s.queries.insert(key, cancel.clone());
+                Ok(())
+            })?;
+            let mut tx = PacketTx {
+                conn_id: tx.conn_id,
+                req_id: m.req_id,
+                tx: tx.tx.clone(),
+                pkt: Some(LenPacket::new(PacketTy::Msg, [0, 0], 64 * 1024)),
+            };
             let db = db.clone();
-            let (tokio_tx, rx) = thingbuf::mpsc::channel::<Vec<u8>>(4);
-            let res = stellarator::struc_con::tokio(move |_| async move {
-                let mut ctx = db.as_session_context()?;
-                db.insert_views(&mut ctx).await?;
-                let df = ctx.sql(&query).await?;
-                let mut stream = df.execute_stream().await?;
-
-                while let Some(batch) = stream.next().await {
-                    let batch = batch?;
-                    let mut buf = vec![];
-                    let mut writer =
-                        ::arrow::ipc::writer::StreamWriter::try_new(&mut buf, batch.schema_ref())?;
-                    writer.write(&batch)?;
-                    writer.finish()?;
-                    let _ = tokio_tx.send(buf).await;
+            stellarator::spawn(async move {
+                let res = handle_sql_query(query, db.clone(), cancel, &mut tx).await;
+                db.with_state_mut(|s| s.queries.remove(&key));
+                match res {
+                    Ok(_) => {}
+                    Err(err) if err.is_stream_closed() => {}
+                    Err(err) => {
+                        warn!(?err, "error running sql query");
+                        let _ = tx
+                            .send_msg(&ErrorResponse {
+                                description: err.to_string(),
+                            })
+                            .await;
+                    }
                 }
-                Ok::<_, Error>(())
-            })
-            .join();
-            while let Some(batch) = rx.recv().await {
-                tx.send_msg(&ArrowIPC {
-                    batch: Some(Cow::Owned(batch)),
-                })
-                .await?;
-            }
-            res.await??;
-            tx.send_msg(&ArrowIPC { batch: None }).await?;
+            });
+        }
+        Packet::Msg(m) if m.id == CancelQuery::ID => {
+            let CancelQuery { query_id } = m.parse::<CancelQuery>()?;
+            db.with_state(|s| {
+                if let Some(cancel) = s.queries.get(&(tx.conn_id, query_id)) {
+                    debug!(?query_id, "cancelling sql query");
+                    cancel.store(true, atomic::Ordering::Relaxed);
+                }
+            });
         }
         Packet::Msg(m) if m.id == SetMsgMetadata::ID => {
             let SetMsgMetadata { id, metadata } = m.parse::<SetMsgMetadata>()?;
@@ -1030,7 +1838,7 @@ async fn handle_packet<A: AsyncWrite + 'static>(
             let msg_log =
                 db.with_state_mut(|s| s.get_or_insert_msg_log(msg_id, &db.path).cloned())?;
             let stream_state =
-                db.get_or_insert_fixed_rate_state(fixed_rate.stream_id, fixed_rate.behavior);
+                db.get_or_insert_fixed_rate_state(fixed_rate.stream_id, fixed_rate.behavior, None);
             stellarator::spawn(handle_fixed_rate_msg_stream(
                 msg_id,
                 m.req_id,
@@ -1068,6 +1876,60 @@ async fn handle_packet<A: AsyncWrite + 'static>(
             };
             tx.send_msg(&MsgBatch { data }).await?;
         }
+        Packet::Msg(m) if m.id == GetMsgsStream::ID => {
+            let GetMsgsStream {
+                msg_id,
+                range,
+                limit,
+            } = m.parse::<GetMsgsStream>()?;
+            let msg_log = db.with_state_mut(|s| {
+                s.msg_logs
+                    .get(&msg_id)
+                    .ok_or(Error::MsgNotFound(msg_id))
+                    .cloned()
+            })?;
+            for (timestamp, buf) in msg_log.get_range(range).take(limit.unwrap_or(usize::MAX)) {
+                tx.send_msg(&MsgEntry {
+                    msg: Some((timestamp, Cow::Borrowed(buf))),
+                })
+                .await?;
+            }
+            tx.send_msg(&MsgEntry { msg: None }).await?;
+        }
+        Packet::Msg(m) if m.id == Event::ID => {
+            let event = m.parse::<Event>()?;
+            db.push_msg(event.timestamp, Event::ID, &m.buf)?;
+        }
+        Packet::Msg(m) if m.id == GetEvents::ID => {
+            let GetEvents {
+                range,
+                min_severity,
+                limit,
+            } = m.parse::<GetEvents>()?;
+            let msg_log = db.with_state(|s| s.msg_logs.get(&Event::ID).cloned());
+            let events = msg_log
+                .iter()
+                .flat_map(|msg_log| msg_log.get_range(range.clone()))
+                .filter_map(|(_, buf)| postcard::from_bytes::<Event>(buf).ok())
+                .filter(|event| event.severity >= min_severity)
+                .take(limit.unwrap_or(usize::MAX))
+                .collect();
+            tx.send_msg(&EventBatch { events }).await?;
+        }
+        Packet::Msg(m) if m.id == CreateSnapshot::ID => {
+            let CreateSnapshot { path } = m.parse()?;
+            db.snapshot(&path)?;
+            tx.send_msg(&SnapshotCreated { path }).await?;
+        }
+        Packet::Msg(m) if m.id == RestoreSnapshot::ID => {
+            let RestoreSnapshot { path } = m.parse()?;
+            db.restore_snapshot(&path)?;
+            tx.send_msg(&SnapshotRestored { path }).await?;
+        }
+        Packet::Msg(m) if m.id == GetReplicationState::ID => {
+            let state = db.with_state(|state| state.replication_state());
+            tx.send_msg(&state).await?;
+        }
         Packet::Msg(m) if m.id == SaveArchive::ID => {
             let SaveArchive { path, format } = m.parse()?;
             db.save_archive(&path, format)?;
@@ -1086,6 +1948,27 @@ async fn handle_packet<A: AsyncWrite + 'static>(
                 m.req_id,
             ));
         }
+        Packet::Msg(m) if m.id == NegotiateCompression::ID => {
+            let NegotiateCompression { codecs } = m.parse::<NegotiateCompression>()?;
+            let codec = codecs
+                .into_iter()
+                .find(|codec| impeller2_stellar::compression::SUPPORTED_CODECS.contains(codec));
+            tx.send_msg(&CompressionAccepted { codec }).await?;
+            tx.tx.lock().await.set_compression(codec);
+        }
+        Packet::Msg(m) if m.id == GetVTable::ID => {
+            let GetVTable { id } = m.parse::<GetVTable>()?;
+            let vtable = db
+                .with_state(|state| {
+                    state
+                        .vtable_registry
+                        .get(&id)
+                        .or_else(|| state.stream_vtables.get(&id))
+                        .cloned()
+                })
+                .ok_or(Error::InvalidMsgId)?;
+            tx.send_msg(&VTableMsg { id, vtable }).await?;
+        }
         Packet::Msg(m) if m.id == UdpVTableStream::ID => {
             let UdpVTableStream { id, addr } = m.parse::<UdpVTableStream>()?;
             let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
@@ -1175,7 +2058,7 @@ pub async fn handle_fixed_rate_msg_stream<A: AsyncWrite>(
         {
             let tx = tx.lock().await;
             pkt.clear();
-            pkt.extend_from_slice(msg_timestamp.as_bytes());
+            pkt.extend_from_slice(&msg_timestamp.to_le_bytes());
             pkt.extend_from_slice(msg);
             rent!(tx.send(pkt).await, pkt)?;
         }
@@ -1218,10 +2101,15 @@ impl FixedRateStreamState {
     }
 
     fn set_timestamp(&self, timestamp: Timestamp) {
+        self.store_timestamp(timestamp);
+        self.playing_cell.wait_cell.wake_all();
+    }
+
+    /// Moves the stream to `timestamp` without waking the stream task
+    fn store_timestamp(&self, timestamp: Timestamp) {
         self.is_scrubbed.store(true, atomic::Ordering::SeqCst);
         self.current_tick
             .store(timestamp.0, atomic::Ordering::SeqCst);
-        self.playing_cell.wait_cell.wake_all();
     }
 
     fn set_frequency(&self, frequency: u64) {
@@ -1258,6 +2146,13 @@ impl FixedRateStreamState {
         self.playing_cell.set_playing(playing)
     }
 
+    /// Pauses the stream without waking the stream task, returning whether it was playing
+    fn hold(&self) -> bool {
+        self.playing_cell
+            .is_playing
+            .swap(false, atomic::Ordering::SeqCst)
+    }
+
     async fn wait_for_playing(&self) -> bool {
         self.playing_cell
             .wait_cell
@@ -1286,33 +2181,115 @@ fn handle_unicast_stream(addr: SocketAddr, stream: Stream, db: Arc<DB>) {
         socket.connect(addr);
         let (_, tx) = socket.split();
         let tx = Arc::new(Mutex::new(PacketSink::new(tx)));
-        handle_stream(tx.clone(), stream.clone(), db.clone(), 0)
+        handle_stream(tx.clone(), stream.clone(), None, db.clone(), 0)
             .await
             .unwrap();
         Ok::<_, Error>(())
     });
 }
 
+/// Runs a SQL query, replying with one [`ArrowIPC`] msg per record batch followed by an empty one.
+///
+/// `cancel` is only checked between record batches, so cancelling a query stops it after the batch that is currently
+/// being produced.
+async fn handle_sql_query<A: AsyncWrite + 'static>(
+    query: String,
+    db: Arc<DB>,
+    cancel: Arc<AtomicBool>,
+    tx: &mut PacketTx<A>,
+) -> Result<(), Error> {
+    let (tokio_tx, rx) = thingbuf::mpsc::channel::<Vec<u8>>(4);
+    let res = stellarator::struc_con::tokio(move |_| async move {
+        let mut ctx = db.as_session_context()?;
+        db.insert_views(&mut ctx).await?;
+        let df = ctx.sql(&query).await?;
+        let mut stream = df.execute_stream().await?;
+
+        while !cancel.load(atomic::Ordering::Relaxed) {
+            let Some(batch) = stream.next().await else {
+                break;
+            };
+            let batch = batch?;
+            let mut buf = vec![];
+            let mut writer =
+                ::arrow::ipc::writer::StreamWriter::try_new(&mut buf, batch.schema_ref())?;
+            writer.write(&batch)?;
+            writer.finish()?;
+            let _ = tokio_tx.send(buf).await;
+        }
+        Ok::<_, Error>(())
+    })
+    .join();
+    while let Some(batch) = rx.recv().await {
+        tx.send_msg(&ArrowIPC {
+            batch: Some(Cow::Owned(batch)),
+        })
+        .await?;
+    }
+    res.await??;
+    tx.send_msg(&ArrowIPC { batch: None }).await?;
+    Ok(())
+}
+
+/// How long a single stream write may stay pending before the subscriber is considered dead
+///
+/// A stream is torn down as soon as a write to its socket errors, or when a write is still pending after this timeout
+/// because the client stopped reading. Teardown stops all of the stream's tasks, removes a fixed rate stream's state
+/// from the DB, and drops the stream's handle on the connection, so one stuck client can't back up the server.
+///
+/// A timed out write is abandoned partway through its packet, so the whole connection is shut down as well: nothing
+/// written after it could be framed, and the connection's other streams and replies end with it.
+pub const STREAM_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn stream_write<A: AsyncWrite, F: Future>(
+    sink: &PacketSink<A>,
+    write: F,
+) -> Result<F::Output, Error> {
+    futures_lite::future::or(async { Ok(write.await) }, async {
+        stellarator::sleep(STREAM_WRITE_TIMEOUT).await;
+        if let Err(err) = sink.shutdown() {
+            debug!(?err, "error shutting down stalled connection");
+        }
+        Err(Error::StreamWriteTimeout)
+    })
+    .await
+}
+
+fn log_stream_exit(stream_id: StreamId, res: Result<(), Error>) {
+    match res {
+        Ok(_) => {}
+        Err(err) if err.is_stream_closed() => debug!(stream.id = ?stream_id, "stream closed"),
+        Err(err) => warn!(stream.id = ?stream_id, ?err, "stream dead, tearing down"),
+    }
+}
+
+/// The components a [`FilteredStream`] is limited to
+type ComponentFilter = Arc<HashSet<ComponentId>>;
+
 fn handle_stream<A: AsyncWrite + 'static>(
     tx: Arc<Mutex<PacketSink<A>>>,
     stream: Stream,
+    filter: Option<ComponentFilter>,
     db: Arc<DB>,
     req_id: RequestId,
 ) -> stellarator::JoinHandle<()> {
     match stream.behavior {
         StreamBehavior::RealTime => stellarator::spawn(async move {
-            match handle_real_time_stream(tx, req_id, db).await {
-                Ok(_) => {}
-                Err(err) if err.is_stream_closed() => {}
-                Err(err) => {
-                    warn!(?err, "error streaming data");
-                }
-            }
+            let res = handle_real_time_stream(tx, req_id, stream.id, filter, None, db).await;
+            log_stream_exit(stream.id, res);
+        }),
+        StreamBehavior::Decimated { max_rate, method } => stellarator::spawn(async move {
+            let decimation = Decimation::new(max_rate, method);
+            let res =
+                handle_real_time_stream(tx, req_id, stream.id, filter, Some(decimation), db).await;
+            log_stream_exit(stream.id, res);
         }),
         StreamBehavior::FixedRate(fixed_rate) => {
+            let timestep = db
+                .with_state(|s| db.fixed_rate_timestep(s, fixed_rate.timestep, filter.as_deref()));
             let state = Arc::new(FixedRateStreamState::new(
                 stream.id,
-                Duration::from_nanos(fixed_rate.timestep),
+                timestep,
                 match fixed_rate.initial_timestamp {
                     InitialTimestamp::Earliest => db.earliest_timestamp,
                     InitialTimestamp::Latest => db.last_updated.latest(),
@@ -1323,13 +2300,18 @@ fn handle_stream<A: AsyncWrite + 'static>(
             debug!(stream.id = ?stream.id, "inserting stream");
             db.with_state_mut(|s| s.streams.insert(stream.id, state.clone()));
             stellarator::spawn(async move {
-                match handle_fixed_stream(tx, req_id, state, db).await {
-                    Ok(_) => {}
-                    Err(err) if err.is_stream_closed() => {}
-                    Err(err) => {
-                        warn!(?err, "error streaming data");
-                    }
+                let res = handle_fixed_stream(tx, req_id, state.clone(), filter, db.clone()).await;
+                if res.is_err() {
+                    db.with_state_mut(|s| {
+                        if s.streams
+                            .get(&stream.id)
+                            .is_some_and(|s| Arc::ptr_eq(s, &state))
+                        {
+                            s.streams.remove(&stream.id);
+                        }
+                    });
                 }
+                log_stream_exit(stream.id, res);
             })
         }
     }
@@ -1338,23 +2320,53 @@ fn handle_stream<A: AsyncWrite + 'static>(
 async fn handle_real_time_stream<A: AsyncWrite + 'static>(
     sink: Arc<Mutex<PacketSink<A>>>,
     req_id: RequestId,
+    stream_id: StreamId,
+    filter: Option<ComponentFilter>,
+    decimation: Option<Decimation>,
     db: Arc<DB>,
 ) -> Result<(), Error> {
     let mut visited_ids = HashSet::new();
+    // closed by the first component task whose write fails, which stops every other task in the stream
+    let dead = Arc::new(WaitQueue::new());
     loop {
         db.with_state(|state| {
             DBVisitor.visit(&state.components, |component| {
-                if visited_ids.contains(&component.component_id) {
+                if visited_ids.contains(&component.component_id)
+                    || filter
+                        .as_ref()
+                        .is_some_and(|filter| !filter.contains(&component.component_id))
+                {
                     return Ok(());
                 }
                 visited_ids.insert(component.component_id);
                 let sink = sink.clone();
                 let component = component.clone();
-                stellarator::spawn(handle_real_time_component(sink, component, req_id));
+                let dead = dead.clone();
+                let db = db.clone();
+                stellarator::spawn(async move {
+                    let res =
+                        handle_real_time_component(sink, component, req_id, decimation, &dead, &db)
+                            .await;
+                    if res.is_err() {
+                        dead.close();
+                        log_stream_exit(stream_id, res);
+                    }
+                });
                 Ok(())
             })
         })?;
-        db.vtable_gen.wait().await;
+        futures_lite::future::or(
+            async {
+                db.vtable_gen.wait().await;
+            },
+            async {
+                let _ = dead.wait().await;
+            },
+        )
+        .await;
+        if dead.is_closed() {
+            return Ok(());
+        }
     }
 }
 
@@ -1362,6 +2374,9 @@ async fn handle_real_time_component<A: AsyncWrite>(
     stream: Arc<Mutex<PacketSink<A>>>,
     component: Component,
     req_id: RequestId,
+    decimation: Option<Decimation>,
+    dead: &WaitQueue,
+    db: &DB,
 ) -> Result<(), Error> {
     let timestamp_loc = raw_table(0, size_of::<Timestamp>() as u16);
     let prim_type = component.schema.prim_type;
@@ -1372,51 +2387,175 @@ async fn handle_real_time_component<A: AsyncWrite>(
     )]);
     let waiter = component.time_series.waiter();
     let vtable_id: PacketId = fastrand::u16(..).to_le_bytes();
+    db.with_state_mut(|state| state.stream_vtables.insert(vtable_id, vtable.clone()));
     {
         let stream = stream.lock().await;
-        stream
-            .send(
-                VTableMsg {
-                    id: vtable_id,
-                    vtable,
-                }
-                .with_request_id(req_id),
-            )
-            .await
+        let msg = VTableMsg {
+            id: vtable_id,
+            vtable,
+        };
+        stream_write(&stream, stream.send(msg.with_request_id(req_id)))
+            .await?
             .0?;
     }
 
     let mut table = LenPacket::table(vtable_id, 2048 - 16);
+    let mut last_sent: Option<(Timestamp, Instant)> = None;
     loop {
-        let _ = waiter.wait().await;
-        let Some((&timestamp, buf)) = component.time_series.latest() else {
+        futures_lite::future::or(
+            async {
+                let _ = waiter.wait().await;
+            },
+            async {
+                let _ = dead.wait().await;
+            },
+        )
+        .await;
+        if dead.is_closed() {
+            return Ok(());
+        }
+        if let (Some(decimation), Some((_, sent_at))) = (decimation, last_sent) {
+            let elapsed = sent_at.elapsed();
+            if elapsed < decimation.period {
+                futures_lite::future::or(
+                    async {
+                        stellarator::sleep(decimation.period - elapsed).await;
+                    },
+                    async {
+                        let _ = dead.wait().await;
+                    },
+                )
+                .await;
+                if dead.is_closed() {
+                    return Ok(());
+                }
+            }
+        }
+        let pin = component.time_series.pin();
+        let Some((timestamp, buf)) = component.latest() else {
             continue;
         };
+        let buf = match decimation {
+            Some(decimation) => {
+                let since = last_sent.map(|(timestamp, _)| timestamp);
+                last_sent = Some((timestamp, Instant::now()));
+                decimation.combine(&component, since, timestamp, buf)?
+            }
+            None => buf,
+        };
         table.push_aligned(timestamp);
         table.pad_for_type(prim_type);
-        table.extend_from_slice(buf);
+        table.extend_from_slice(&buf);
+        drop(pin);
         {
             let stream = stream.lock().await;
-            if let Err(err) = rent!(stream.send(table.with_request_id(req_id)).await, table) {
-                debug!(%err, "error sending table");
-                if Error::from(err).is_stream_closed() {
-                    return Ok(());
-                }
-            }
+            rent!(
+                stream_write(&stream, stream.send(table.with_request_id(req_id))).await?,
+                table
+            )?;
         }
         table.clear();
     }
 }
 
+/// The rate limit of a [`StreamBehavior::Decimated`] stream, and how it combines the samples between two sends
+#[derive(Clone, Copy)]
+struct Decimation {
+    period: Duration,
+    method: DecimationMethod,
+}
+
+impl Decimation {
+    fn new(max_rate: u64, method: DecimationMethod) -> Self {
+        Decimation {
+            period: Duration::from_secs_f64(1.0 / max_rate.max(1) as f64),
+            method,
+        }
+    }
+
+    /// Combines the samples recorded after `since`, up to `timestamp`, into a single value
+    ///
+    /// Falls back to `latest` when there is nothing to combine, which is the case for the first send of a stream and
+    /// for components that aren't being recorded.
+    fn combine<'a>(
+        &self,
+        component: &'a Component,
+        since: Option<Timestamp>,
+        timestamp: Timestamp,
+        latest: Cow<'a, [u8]>,
+    ) -> Result<Cow<'a, [u8]>, Error> {
+        let schema = &component.schema;
+        if self.method == DecimationMethod::Latest || schema.prim_type.is_var_len() {
+            return Ok(latest);
+        }
+        let Some(since) = since else {
+            return Ok(latest);
+        };
+        let samples = match component.get_range(Timestamp(since.0.saturating_add(1))..timestamp) {
+            Some((timestamps, samples)) if !timestamps.is_empty() => samples,
+            _ => return Ok(latest),
+        };
+        let views = samples
+            .chunks_exact(schema.size())
+            .map(|sample| schema.parse_value(sample).map(|(_, view)| view))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let elements = schema.dim.iter().product::<usize>();
+        let mut out = Vec::with_capacity(schema.size());
+        for i in 0..elements {
+            let values = views.iter().filter_map(|view| view.get(i));
+            let value = match self.method {
+                DecimationMethod::Latest => unreachable!("latest samples are sent as-is"),
+                DecimationMethod::Min => values.min_by(cmp_elements),
+                DecimationMethod::Max => values.max_by(cmp_elements),
+                DecimationMethod::Mean => {
+                    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| {
+                        (sum + value.as_f64(), count + 1)
+                    });
+                    (count > 0).then(|| {
+                        schema
+                            .prim_type
+                            .saturating_cast(ElementValue::F64(sum / count as f64))
+                    })
+                }
+            };
+            let Some(value) = value else {
+                return Ok(latest);
+            };
+            out.extend_from_slice(value.as_bytes());
+        }
+        Ok(Cow::Owned(out))
+    }
+}
+
+/// Orders integers exactly, since 64-bit values don't all fit in an `f64`, and everything else by its `f64` value
+fn cmp_elements(a: &ElementValue, b: &ElementValue) -> std::cmp::Ordering {
+    match (a.as_i128(), b.as_i128()) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a.as_f64().total_cmp(&b.as_f64()),
+    }
+}
+
 async fn handle_fixed_stream<A: AsyncWrite>(
     stream: Arc<Mutex<PacketSink<A>>>,
     req_id: RequestId,
     state: Arc<FixedRateStreamState>,
+    filter: Option<ComponentFilter>,
     db: Arc<DB>,
 ) -> Result<(), Error> {
+    let filtered_components = || {
+        db.with_state(|state| match &filter {
+            Some(filter) => state
+                .components
+                .iter()
+                .filter(|(id, _)| filter.contains(id))
+                .map(|(id, component)| (*id, component.clone()))
+                .collect(),
+            None => state.components.clone(),
+        })
+    };
     let mut current_gen = u64::MAX;
     let mut table = LenPacket::table([0; 2], 2048 - 16);
-    let mut components = db.with_state(|state| state.components.clone());
+    let mut components: HashMap<ComponentId, Component> = filtered_components();
     loop {
         if !state.wait_for_playing().await {
             return Ok(());
@@ -1425,13 +2564,16 @@ async fn handle_fixed_stream<A: AsyncWrite>(
         let current_timestamp = state.current_timestamp();
         let vtable_gen = db.vtable_gen.latest();
         if vtable_gen != current_gen {
-            components = db.with_state(|state| state.components.clone());
+            components = filtered_components();
             let stream = stream.lock().await;
             let id: PacketId = state.stream_id.to_le_bytes()[..2].try_into().unwrap();
             table = LenPacket::table(id, 2048 - 16);
             let vtable = DBVisitor.vtable(&components)?;
+            db.with_state_mut(|state| state.stream_vtables.insert(id, vtable.clone()));
             let msg = VTableMsg { id, vtable };
-            stream.send(msg.with_request_id(req_id)).await.0?;
+            stream_write(&stream, stream.send(msg.with_request_id(req_id)))
+                .await?
+                .0?;
             current_gen = vtable_gen;
         }
         table.clear();
@@ -1440,17 +2582,17 @@ async fn handle_fixed_stream<A: AsyncWrite>(
         }
         {
             let stream = stream.lock().await;
-            stream
-                .send(
-                    StreamTimestamp {
-                        timestamp: current_timestamp,
-                        stream_id: state.stream_id,
-                    }
-                    .with_request_id(req_id),
-                )
-                .await
+            let msg = StreamTimestamp {
+                timestamp: current_timestamp,
+                stream_id: state.stream_id,
+            };
+            stream_write(&stream, stream.send(msg.with_request_id(req_id)))
+                .await?
                 .0?;
-            rent!(stream.send(table.with_request_id(req_id)).await, table)?;
+            rent!(
+                stream_write(&stream, stream.send(table.with_request_id(req_id))).await?,
+                table
+            )?;
         }
         state
             .wait_for_tick(start.elapsed(), current_timestamp)
@@ -1487,6 +2629,7 @@ impl DBVisitor {
         timestamp: Timestamp,
     ) -> Result<(), Error> {
         self.visit(components, |entity| {
+            let _pin = entity.time_series.pin();
             let tick = entity.time_series.start_timestamp().max(timestamp);
             let Some((timestamp, buf)) = entity.get_nearest(tick) else {
                 return Ok(());
@@ -1,7 +1,7 @@
 use std::{io::Write, net::SocketAddr, path::PathBuf};
 
 use clap::{Parser, Subcommand};
-use elodin_db::Server;
+use elodin_db::{Follower, Server};
 use impeller2::vtable;
 use miette::IntoDiagnostic;
 use postcard_c_codegen::SchemaExt;
@@ -38,6 +38,8 @@ struct RunArgs {
     http_addr: Option<SocketAddr>,
     #[clap(long, hide = true)]
     reset: bool,
+    #[clap(long, help = "Address of a database to mirror into this one")]
+    follow: Option<SocketAddr>,
 }
 
 #[stellarator::main]
@@ -63,6 +65,7 @@ async fn main() -> miette::Result<()> {
             path,
             config,
             reset,
+            follow,
         }) => {
             let path = path.unwrap_or_else(|| {
                 let dirs =
@@ -78,6 +81,14 @@ async fn main() -> miette::Result<()> {
             info!(?path, "starting db");
             let server = Server::new(path, addr).into_diagnostic()?;
             let axum_db = server.db.clone();
+            if let Some(leader) = follow {
+                let follower = Follower::new(leader, server.db.clone());
+                stellarator::spawn(async move {
+                    if let Err(err) = follower.run().await {
+                        tracing::warn!(?err, "stopped following");
+                    }
+                });
+            }
             let db = stellarator::spawn(server.run());
             if let Some(http_addr) = http_addr {
                 stellarator::struc_con::tokio(move |_| async move {
@@ -87,6 +98,8 @@ async fn main() -> miette::Result<()> {
             if let Some(lua_config) = config {
                 let args = impeller2_cli::Args {
                     path: Some(lua_config),
+                    interactive: false,
+                    command: None,
                 };
                 impeller2_cli::run(args)
                     .await
@@ -104,6 +117,7 @@ async fn main() -> miette::Result<()> {
                     include_str!("../../postcard-c/postcard.h").to_string(),
                     impeller2_wkt::InitialTimestamp::to_cpp()?,
                     impeller2_wkt::FixedRateBehavior::to_cpp()?,
+                    impeller2_wkt::DecimationMethod::to_cpp()?,
                     impeller2_wkt::StreamBehavior::to_cpp()?,
                     impeller2_wkt::Stream::to_cpp()?,
                     impeller2_wkt::MsgStream::to_cpp()?,
@@ -0,0 +1,136 @@
+//! A stand-in for the db that records what producers send it
+//!
+//! [`MockServer`] accepts connections like [`crate::Server`], but instead of storing anything it keeps every packet
+//! it receives so tests can assert on exactly what went over the wire.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use impeller2::{
+    types::{Msg, OwnedPacket, PacketId},
+    vtable::VTable,
+};
+use impeller2_stellar::PacketStream;
+use impeller2_wkt::VTableMsg;
+use stellarator::{
+    buf::Slice,
+    io::SplitExt,
+    net::{TcpListener, TcpStream},
+    sync::WaitQueue,
+};
+
+use crate::Error;
+
+type Packets = Mutex<Vec<OwnedPacket<Slice<Vec<u8>>>>>;
+
+pub struct MockServer {
+    addr: SocketAddr,
+    packets: Arc<Packets>,
+    received: Arc<WaitQueue>,
+}
+
+impl MockServer {
+    /// Starts listening on an ephemeral loopback port
+    pub fn bind() -> Result<Self, Error> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let packets = Arc::new(Packets::default());
+        let received = Arc::new(WaitQueue::new());
+        let (accept_packets, accept_received) = (packets.clone(), received.clone());
+        stellarator::struc_con::stellar(move || accept(listener, accept_packets, accept_received));
+        Ok(Self {
+            addr,
+            packets,
+            received,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Every packet received so far, across all connections, in arrival order
+    pub fn packets(&self) -> Vec<OwnedPacket<Slice<Vec<u8>>>> {
+        self.packets.lock().expect("packets lock poisoned").clone()
+    }
+
+    /// Waits until at least `count` packets have been received
+    pub async fn wait_for_packets(&self, count: usize) {
+        let _ = self
+            .received
+            .wait_for(|| self.packets.lock().expect("packets lock poisoned").len() >= count)
+            .await;
+    }
+
+    /// Every received msg with the id of `M` that parses as one
+    pub fn msgs<M: Msg + serde::de::DeserializeOwned>(&self) -> Vec<M> {
+        self.packets()
+            .iter()
+            .filter_map(|packet| match packet {
+                OwnedPacket::Msg(m) if m.id == M::ID => m.parse::<M>().ok(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The id the most recent [`VTableMsg`] with exactly this layout was registered under
+    pub fn vtable_id<O, D, F>(&self, vtable: &VTable<O, D, F>) -> Option<PacketId>
+    where
+        VTable<O, D, F>: serde::Serialize,
+    {
+        let expected = postcard::to_allocvec(vtable).ok()?;
+        self.msgs::<VTableMsg>()
+            .into_iter()
+            .rev()
+            .find(|msg| postcard::to_allocvec(&msg.vtable).ok().as_ref() == Some(&expected))
+            .map(|msg| msg.id)
+    }
+
+    /// The bodies of the tables received for vtable `id`, in arrival order
+    pub fn tables(&self, id: PacketId) -> Vec<Vec<u8>> {
+        self.packets()
+            .iter()
+            .filter_map(|packet| match packet {
+                OwnedPacket::Table(table) if table.id == id => Some(table.buf[..].to_vec()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+async fn accept(
+    listener: TcpListener,
+    packets: Arc<Packets>,
+    received: Arc<WaitQueue>,
+) -> Result<(), Error> {
+    loop {
+        let stream = listener.accept().await?;
+        let (packets, received) = (packets.clone(), received.clone());
+        stellarator::struc_con::stellar(move || record_conn(stream, packets, received));
+    }
+}
+
+async fn record_conn(
+    stream: TcpStream,
+    packets: Arc<Packets>,
+    received: Arc<WaitQueue>,
+) -> Result<(), Error> {
+    let (rx, _tx) = stream.split();
+    let mut rx = PacketStream::new(rx);
+    loop {
+        let packet = match rx.next_grow(vec![0u8; 256]).await {
+            Ok(packet) => packet,
+            Err(err) => {
+                let err = Error::from(err);
+                if err.is_stream_closed() {
+                    return Ok(());
+                }
+                return Err(err);
+            }
+        };
+        packets.lock().expect("packets lock poisoned").push(packet);
+        received.wake_all();
+    }
+}
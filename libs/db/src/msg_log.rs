@@ -66,6 +66,37 @@ impl MsgLog {
         Ok(())
     }
 
+    /// Points the log at `path` after its directory was moved there, so its metadata is written in the right place
+    pub(crate) fn relocate(&mut self, path: PathBuf) {
+        self.path = path;
+    }
+
+    /// Flushes the msgs and their timestamps to disk
+    pub fn sync(&self) -> Result<(), Error> {
+        self.bufs.data_log.sync()?;
+        self.bufs.offsets.sync()?;
+        self.timestamps.sync()
+    }
+
+    /// Copies the first `end` msgs to a new log at `path`, see [`AppendLog::snapshot`]
+    ///
+    /// `end` should be the length of [`Self::timestamps`] before the copy. A msg's timestamp is written last, so every
+    /// msg with a timestamp then has its offset and data.
+    pub fn snapshot(&self, path: impl AsRef<Path>, end: usize) -> Result<(), Error> {
+        let path = path.as_ref();
+        std::fs::create_dir_all(path)?;
+        self.timestamps.snapshot(
+            path.join("timestamps"),
+            (end * size_of::<Timestamp>()) as u64,
+        )?;
+        self.bufs
+            .offsets
+            .snapshot(path.join("offsets"), (end * size_of::<UmbraBuf>()) as u64)?;
+        self.bufs
+            .data_log
+            .snapshot(path.join("data_log"), self.bufs.data_log.len())
+    }
+
     pub fn timestamps(&self) -> &[Timestamp] {
         <[Timestamp]>::ref_from_bytes(self.timestamps.get(..).expect("couldn't get full range"))
             .expect("mmep unaligned")
@@ -101,14 +132,16 @@ impl MsgLog {
         range: std::ops::Range<Timestamp>,
     ) -> impl Iterator<Item = (Timestamp, &[u8])> {
         let timestamps = self.timestamps();
-        let start_index = match timestamps.binary_search(&range.start) {
-            Ok(i) => i,
-            Err(i) => i.saturating_sub(1),
-        };
-        let end_index = match timestamps.binary_search(&range.end) {
-            Ok(i) => i,
-            Err(i) => i.saturating_sub(1),
+        // starts at the first msg at `range.start`, or the one before it if there's none
+        let start_index = timestamps.partition_point(|timestamp| *timestamp < range.start);
+        let start_index = if timestamps.get(start_index) == Some(&range.start) {
+            start_index
+        } else {
+            start_index.saturating_sub(1)
         };
+        let end_index = timestamps
+            .partition_point(|timestamp| *timestamp <= range.end)
+            .saturating_sub(1);
         (start_index..=end_index).flat_map(|i| {
             let timestamp = timestamps.get(i)?;
             let buf = self.bufs.get_msg(i)?;
@@ -0,0 +1,407 @@
+//! Follower mode, where a db mirrors another db over a link that may drop, see [`Follower`]
+//!
+//! The follower pulls instead of subscribing to a real-time stream, since those only carry the latest sample of each
+//! component and skip whatever was written between two sends. Every round it asks the leader for its
+//! [`ReplicationState`] and pages through the samples and msgs newer than its own copy. A follower that lost the link
+//! picks up from its latest samples once it reconnects, so nothing the leader still keeps is missed.
+
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    net::SocketAddr,
+    ops::Range,
+    sync::{Arc, atomic},
+    time::{Duration, Instant},
+};
+
+use impeller2::types::{ComponentId, OwnedTimeSeries, PacketId, RequestId, Timestamp};
+use impeller2_stellar::{Client, ReconnectPolicy, compression::SUPPORTED_CODECS};
+use impeller2_wkt::{
+    ComponentMetadata, GetMsgs, GetReplicationState, GetTimeSeriesPage, MsgBatch, MsgMetadata,
+    ReplicatedComponent, ReplicatedMsgLog, ReplicationState,
+};
+use stellarator::buf::Slice;
+use tracing::{debug, info, warn};
+
+use crate::{AtomicTimestampExt, ComponentSchema, DB, Error};
+
+/// How long a follower waits between rounds by default
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+/// The most bytes of samples requested in one page
+const PAGE_BYTES: usize = 1024 * 1024;
+/// The most samples or msgs requested in one page
+const MAX_PAGE_LEN: usize = 4096;
+/// The packet id of time series pages, which are told apart by their request ids instead
+const PAGE_PACKET_ID: PacketId = [0, 0];
+/// The most pages a follower has in flight, so a round over a slow link doesn't take a round trip per component
+const MAX_IN_FLIGHT: usize = 32;
+
+/// Mirrors every component and msg log of the leader at `leader` into a local db
+///
+/// Components the leader migrated to a new schema are migrated locally as well. The follower's own vtables, streams,
+/// retention policies and config are left alone, so it can keep a shorter history than the leader.
+pub struct Follower {
+    leader: SocketAddr,
+    db: Arc<DB>,
+    policy: ReconnectPolicy,
+    interval: Duration,
+}
+
+/// A page of samples or msgs a round is waiting on
+///
+/// Pages resume at the timestamp the follower already has rather than after it, since several samples can share a
+/// timestamp. `skip` is how many of those at the start of `range` the follower already holds.
+enum Pull {
+    TimeSeries {
+        component_id: ComponentId,
+        range: Range<Timestamp>,
+        skip: usize,
+        limit: usize,
+    },
+    Msgs {
+        msg_id: PacketId,
+        range: Range<Timestamp>,
+        skip: usize,
+    },
+}
+
+impl Follower {
+    pub fn new(leader: SocketAddr, db: Arc<DB>) -> Self {
+        Self {
+            leader,
+            db,
+            policy: ReconnectPolicy::default(),
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets how long the follower waits between rounds, which bounds how far behind the leader it runs
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Follows the leader, reconnecting whenever the link drops, until the reconnect policy gives up
+    pub async fn run(self) -> Result<(), Error> {
+        let mut backoff = self.policy.initial_backoff;
+        let mut attempts = 0;
+        loop {
+            let mut rounds = 0;
+            let Err(err) = self.follow(&mut rounds).await;
+            if rounds > 0 {
+                backoff = self.policy.initial_backoff;
+                attempts = 0;
+            }
+            attempts += 1;
+            if self
+                .policy
+                .max_attempts
+                .is_some_and(|max_attempts| attempts >= max_attempts)
+            {
+                return Err(err);
+            }
+            warn!(?err, leader = ?self.leader, "lost leader, reconnecting");
+            stellarator::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.policy.max_backoff);
+        }
+    }
+
+    /// Connects to the leader and mirrors it until something fails, counting the rounds that completed
+    async fn follow(&self, rounds: &mut usize) -> Result<Infallible, Error> {
+        let mut client = Client::connect(self.leader).await?;
+        client.negotiate_compression(SUPPORTED_CODECS).await?;
+        info!(leader = ?self.leader, "following");
+        loop {
+            let start = Instant::now();
+            let state = client.request(&GetReplicationState).await?;
+            let written = self.sync(&mut client, state).await?;
+            if written > 0 {
+                debug!(written, "mirrored leader");
+            }
+            *rounds += 1;
+            stellarator::sleep(self.interval.saturating_sub(start.elapsed())).await;
+        }
+    }
+
+    /// Brings the local db up to `state`, returning how many samples and msgs were written
+    async fn sync(&self, client: &mut Client, state: ReplicationState) -> Result<usize, Error> {
+        let mut pulls = VecDeque::new();
+        for component in state.components {
+            pulls.extend(self.apply_component(component)?);
+        }
+        for msg_log in state.msgs {
+            pulls.extend(self.apply_msg_log(msg_log)?);
+        }
+
+        // the leader replies in the order it was asked, so the oldest page in flight is always the next reply
+        let mut in_flight = VecDeque::new();
+        let mut written = 0;
+        loop {
+            while in_flight.len() < MAX_IN_FLIGHT {
+                let Some(pull) = pulls.pop_front() else {
+                    break;
+                };
+                let req_id = match &pull {
+                    Pull::TimeSeries {
+                        component_id,
+                        range,
+                        skip,
+                        limit,
+                    } => {
+                        client
+                            .send_req(&GetTimeSeriesPage {
+                                id: PAGE_PACKET_ID,
+                                range: range.clone(),
+                                component_id: *component_id,
+                                limit: skip + limit,
+                            })
+                            .await?
+                    }
+                    Pull::Msgs {
+                        msg_id,
+                        range,
+                        skip,
+                    } => {
+                        client
+                            .send_req(&GetMsgs {
+                                msg_id: *msg_id,
+                                range: range.clone(),
+                                limit: Some(skip + MAX_PAGE_LEN),
+                            })
+                            .await?
+                    }
+                };
+                in_flight.push_back((req_id, pull));
+            }
+            let Some((req_id, pull)) = in_flight.pop_front() else {
+                return Ok(written);
+            };
+            let (page_written, next) = self.receive(client, req_id, pull).await?;
+            written += page_written;
+            pulls.extend(next);
+        }
+    }
+
+    /// Creates, migrates and names a component to match the leader, returning the samples it's missing
+    fn apply_component(&self, component: ReplicatedComponent) -> Result<Option<Pull>, Error> {
+        let ReplicatedComponent {
+            component_id,
+            schema,
+            metadata,
+            last,
+        } = component;
+        let schema = ComponentSchema::from(schema);
+        let local = self.db.with_state(|state| {
+            let component = state.get_component(component_id)?;
+            let _pin = component.time_series.pin();
+            let last = latest_run(component.time_series.timestamps());
+            Some((component.schema.clone(), last))
+        });
+        let local_last = match local {
+            Some((local_schema, local_last)) if local_schema == schema => local_last,
+            _ => {
+                self.db.migrate_component(component_id, schema.clone())?;
+                None
+            }
+        };
+        if let Some(metadata) = metadata {
+            self.apply_component_metadata(metadata)?;
+        }
+        let Some((range, skip)) = missing(local_last, last) else {
+            return Ok(None);
+        };
+        let limit = (PAGE_BYTES / schema.size().max(1)).clamp(1, MAX_PAGE_LEN);
+        Ok(Some(Pull::TimeSeries {
+            component_id,
+            range,
+            skip,
+            limit,
+        }))
+    }
+
+    fn apply_component_metadata(&self, metadata: ComponentMetadata) -> Result<(), Error> {
+        let changed = self.db.with_state(|state| {
+            state.get_component_metadata(metadata.component_id) != Some(&metadata)
+        });
+        if !changed {
+            return Ok(());
+        }
+        let timestamp = self.db.last_updated.latest();
+        self.db.with_state_mut(|state| {
+            state.record_component_metadata(metadata.clone(), timestamp, &self.db.path)?;
+            state.set_component_metadata(metadata, &self.db.path)
+        })
+    }
+
+    /// Creates and describes a msg log to match the leader, returning the msgs it's missing
+    fn apply_msg_log(&self, msg_log: ReplicatedMsgLog) -> Result<Option<Pull>, Error> {
+        let ReplicatedMsgLog {
+            msg_id,
+            metadata,
+            last,
+        } = msg_log;
+        let (local_metadata, local_last) = self.db.with_state(|state| {
+            let msg_log = state.msg_logs.get(&msg_id);
+            (
+                msg_log.and_then(|msg_log| msg_log.metadata().cloned()),
+                msg_log.and_then(|msg_log| latest_run(msg_log.timestamps())),
+            )
+        });
+        if let Some(metadata) =
+            metadata.filter(|metadata| local_metadata.as_ref() != Some(metadata))
+        {
+            self.apply_msg_metadata(msg_id, metadata)?;
+        }
+        Ok(missing(local_last, last).map(|(range, skip)| Pull::Msgs {
+            msg_id,
+            range,
+            skip,
+        }))
+    }
+
+    fn apply_msg_metadata(&self, msg_id: PacketId, metadata: MsgMetadata) -> Result<(), Error> {
+        self.db
+            .with_state_mut(|state| state.set_msg_metadata(msg_id, metadata, &self.db.path))
+    }
+
+    /// Writes the page replying to `pull`, returning how much was written and the rest of the pull if the page was full
+    async fn receive(
+        &self,
+        client: &mut Client,
+        req_id: RequestId,
+        pull: Pull,
+    ) -> Result<(usize, Option<Pull>), Error> {
+        match pull {
+            Pull::TimeSeries {
+                component_id,
+                range,
+                skip,
+                limit,
+            } => {
+                let time_series: OwnedTimeSeries<Slice<Vec<u8>>> = client.recv(req_id).await?;
+                let timestamps = time_series.timestamps()?;
+                let held = held(timestamps.iter().copied(), range.start, skip);
+                let written =
+                    self.push_time_series(component_id, timestamps, time_series.data()?, held)?;
+                let next = next_page(&range, timestamps.iter().copied(), skip + limit).map(
+                    |(range, skip)| Pull::TimeSeries {
+                        component_id,
+                        range,
+                        skip,
+                        limit,
+                    },
+                );
+                Ok((written, next))
+            }
+            Pull::Msgs {
+                msg_id,
+                range,
+                skip,
+            } => {
+                let MsgBatch { data } = client.recv(req_id).await?;
+                // the reply starts with the msg before the range when there's none at its start
+                let start = range.start;
+                let msgs = || data.iter().filter(move |(t, _)| *t >= start);
+                let held = held(msgs().map(|(timestamp, _)| *timestamp), range.start, skip);
+                let mut written = 0;
+                for (timestamp, msg) in msgs().skip(held) {
+                    self.db.push_msg(*timestamp, msg_id, msg)?;
+                    written += 1;
+                }
+                let timestamps = data.iter().map(|(timestamp, _)| *timestamp);
+                let next =
+                    next_page(&range, timestamps, skip + MAX_PAGE_LEN).map(|(range, skip)| {
+                        Pull::Msgs {
+                            msg_id,
+                            range,
+                            skip,
+                        }
+                    });
+                Ok((written, next))
+            }
+        }
+    }
+
+    /// Appends a page of samples to the current version of a component, after the first `held` the follower already has
+    fn push_time_series(
+        &self,
+        component_id: ComponentId,
+        timestamps: &[Timestamp],
+        data: &[u8],
+        held: usize,
+    ) -> Result<usize, Error> {
+        let component = self
+            .db
+            .with_state(|state| state.get_component(component_id).cloned())
+            .ok_or(Error::ComponentNotFound(component_id))?;
+        let size = component.schema.size();
+        if data.len() != timestamps.len() * size {
+            return Err(Error::SchemaMismatch);
+        }
+        let time_series_empty = component.time_series.index().is_empty();
+        let samples = timestamps
+            .iter()
+            .enumerate()
+            .skip(held)
+            .map(|(i, timestamp)| (*timestamp, &data[i * size..(i + 1) * size]));
+        let written = component.time_series.push_batch(samples)?;
+        if let Some(last) = timestamps.last() {
+            self.db.last_updated.update_max(*last);
+        }
+        // lets the follower's own real-time streams pick the component up, like a table sinking into a new time series
+        if time_series_empty && written > 0 {
+            self.db.vtable_gen.fetch_add(1, atomic::Ordering::SeqCst);
+        }
+        Ok(written)
+    }
+}
+
+/// The latest of `timestamps` and how many entries share it
+fn latest_run(timestamps: &[Timestamp]) -> Option<(Timestamp, usize)> {
+    let last = *timestamps.last()?;
+    let len = timestamps.iter().rev().take_while(|t| **t == last).count();
+    Some((last, len))
+}
+
+/// The range from the follower's `local_last` up to the leader's `last` and how many entries at its start the follower
+/// already has, if the leader has anything newer
+///
+/// Entries the leader added at its own `last` after the follower caught up to it are pulled once something newer
+/// arrives.
+fn missing(
+    local_last: Option<(Timestamp, usize)>,
+    last: Option<Timestamp>,
+) -> Option<(Range<Timestamp>, usize)> {
+    let last = last?;
+    match local_last {
+        Some((local_last, _)) if local_last >= last => None,
+        Some((local_last, held)) => Some((local_last..last, held)),
+        None => Some((Timestamp(i64::MIN)..last, 0)),
+    }
+}
+
+/// How many of the `skip` entries at `start` the follower already holds lead a page, fewer if the leader dropped some
+fn held(timestamps: impl Iterator<Item = Timestamp>, start: Timestamp, skip: usize) -> usize {
+    timestamps.take(skip).take_while(|t| *t == start).count()
+}
+
+/// The rest of `range` from the end of a page of `timestamps` and how many entries at its start were already pulled,
+/// if the page held `limit` entries
+fn next_page(
+    range: &Range<Timestamp>,
+    timestamps: impl DoubleEndedIterator<Item = Timestamp> + ExactSizeIterator,
+    limit: usize,
+) -> Option<(Range<Timestamp>, usize)> {
+    if timestamps.len() < limit {
+        return None;
+    }
+    let mut timestamps = timestamps.rev();
+    let last = timestamps.next()?;
+    let pulled = 1 + timestamps.take_while(|t| *t == last).count();
+    Some((last..range.end, pulled))
+}
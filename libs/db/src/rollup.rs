@@ -0,0 +1,463 @@
+//! Downsampled tiers of a component's time series, so long ranges can be read without touching every sample
+//!
+//! Each tier buckets the samples by a fixed period and stores the count, min, max and mean of every element, widened
+//! to `f64`. The tiers are stored next to the raw time series and filled in by [`crate::Server`] in the background,
+//! so they lag behind it. [`Rollups::downsample`] folds the samples the tiers don't cover yet straight from the raw
+//! time series, so a read always covers its whole range.
+
+use std::{ops::Range, path::Path, time::Duration};
+
+use impeller2::types::{ElementValue, PrimType, Timestamp};
+use impeller2_wkt::{CompactionReport, RetentionPolicy};
+
+use crate::{ComponentSchema, Error, time_series::TimeSeries};
+
+/// The tiers each numeric component is rolled up into, finest first, along with the directory each is stored in
+pub const TIERS: [(&str, Duration); 2] = [
+    ("rollup_1s", Duration::from_secs(1)),
+    ("rollup_1m", Duration::from_secs(60)),
+];
+
+/// The stats of the samples in one bucket of a tier
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bucket {
+    pub start: Timestamp,
+    pub count: u64,
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+    pub mean: Vec<f64>,
+}
+
+impl Bucket {
+    fn empty(start: Timestamp, len: usize) -> Self {
+        Bucket {
+            start,
+            count: 0,
+            min: vec![f64::INFINITY; len],
+            max: vec![f64::NEG_INFINITY; len],
+            mean: vec![0.0; len],
+        }
+    }
+
+    /// The size of a stored bucket of a component with `len` elements
+    fn size(len: usize) -> u64 {
+        (size_of::<u64>() + 3 * len * size_of::<f64>()) as u64
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.clear();
+        buf.extend_from_slice(&self.count.to_le_bytes());
+        for x in self.min.iter().chain(&self.max).chain(&self.mean) {
+            buf.extend_from_slice(&x.to_le_bytes());
+        }
+    }
+
+    fn decode(start: Timestamp, buf: &[u8]) -> Self {
+        let (count, values) = buf.split_at(size_of::<u64>());
+        let values = values
+            .chunks_exact(size_of::<f64>())
+            .map(|x| f64::from_le_bytes(x.try_into().expect("chunk is 8 bytes")))
+            .collect::<Vec<_>>();
+        let len = values.len() / 3;
+        Bucket {
+            start,
+            count: u64::from_le_bytes(count.try_into().expect("count is 8 bytes")),
+            min: values[..len].to_vec(),
+            max: values[len..2 * len].to_vec(),
+            mean: values[2 * len..].to_vec(),
+        }
+    }
+}
+
+fn bucket_start(timestamp: Timestamp, period: i64) -> Timestamp {
+    Timestamp(timestamp.0.div_euclid(period) * period)
+}
+
+/// Folds samples, in timestamp order, into buckets, emitting each bucket once the samples move past it
+///
+/// The open bucket keeps sums rather than means, so buckets of integer samples have exact means.
+struct Folder {
+    period: i64,
+    len: usize,
+    bucket: Option<Bucket>,
+    sum: Vec<f64>,
+}
+
+impl Folder {
+    fn new(period: i64, len: usize) -> Self {
+        Folder {
+            period,
+            len,
+            bucket: None,
+            sum: vec![0.0; len],
+        }
+    }
+
+    /// Folds in the stats of `count` samples
+    fn push(
+        &mut self,
+        timestamp: Timestamp,
+        count: u64,
+        [min, max, mean]: [&[f64]; 3],
+        emit: &mut impl FnMut(Bucket) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let start = bucket_start(timestamp, self.period);
+        if self
+            .bucket
+            .as_ref()
+            .is_some_and(|bucket| bucket.start != start)
+        {
+            self.finish(emit)?;
+        }
+        let bucket = self
+            .bucket
+            .get_or_insert_with(|| Bucket::empty(start, self.len));
+        bucket.count += count;
+        for i in 0..self.len {
+            bucket.min[i] = bucket.min[i].min(min[i]);
+            bucket.max[i] = bucket.max[i].max(max[i]);
+            self.sum[i] += mean[i] * count as f64;
+        }
+        Ok(())
+    }
+
+    /// Folds the raw samples of `time_series` in `range`
+    fn push_raw(
+        &mut self,
+        time_series: &TimeSeries,
+        schema: &ComponentSchema,
+        range: Range<Timestamp>,
+        emit: &mut impl FnMut(Bucket) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let (timestamps, data) = time_series.slice(range);
+        let mut values = Vec::with_capacity(self.len);
+        for (timestamp, buf) in timestamps.iter().zip(data.chunks_exact(schema.size())) {
+            let (_, view) = schema.parse_value(buf)?;
+            values.clear();
+            values.extend((0..).map_while(|i| view.get(i)).map(|x| x.as_f64()));
+            self.push(*timestamp, 1, [&values[..]; 3], emit)?;
+        }
+        Ok(())
+    }
+
+    /// Emits the open bucket, if there is one
+    fn finish(&mut self, emit: &mut impl FnMut(Bucket) -> Result<(), Error>) -> Result<(), Error> {
+        let Some(mut bucket) = self.bucket.take() else {
+            return Ok(());
+        };
+        for (mean, sum) in bucket.mean.iter_mut().zip(&mut self.sum) {
+            *mean = *sum / bucket.count as f64;
+            *sum = 0.0;
+        }
+        emit(bucket)
+    }
+}
+
+/// One tier of a component's rollups
+#[derive(Clone)]
+pub struct Rollup {
+    /// The bucket period in microseconds
+    period: i64,
+    time_series: TimeSeries,
+}
+
+impl Rollup {
+    /// The end of the last stored bucket, which is where the next one starts
+    fn end(&self) -> Option<Timestamp> {
+        let (start, _) = self.time_series.latest()?;
+        Some(Timestamp(start.0 + self.period))
+    }
+
+    /// The stored buckets that start in `range`
+    pub fn buckets(&self, range: Range<Timestamp>) -> impl Iterator<Item = Bucket> + '_ {
+        let (timestamps, data) = self.time_series.slice(range);
+        let size = self.time_series.element_size();
+        timestamps
+            .iter()
+            .zip(data.chunks_exact(size))
+            .map(|(start, buf)| Bucket::decode(*start, buf))
+    }
+}
+
+/// The rollup tiers of a component's current schema version
+///
+/// Components of bytes or strings have no tiers.
+#[derive(Clone, Default)]
+pub struct Rollups {
+    tiers: Vec<Rollup>,
+}
+
+impl Rollups {
+    /// Opens the tiers in a component's version directory, creating any that don't exist yet
+    pub fn open(
+        path: &Path,
+        schema: &ComponentSchema,
+        start_timestamp: Timestamp,
+    ) -> Result<Self, Error> {
+        if matches!(schema.prim_type, PrimType::Bytes | PrimType::String) || schema.size() == 0 {
+            return Ok(Rollups::default());
+        }
+        let element_size = Bucket::size(schema.dim.iter().product());
+        let tiers = TIERS
+            .iter()
+            .map(|(name, period)| {
+                let path = path.join(name);
+                let time_series = if path.join("index").exists() {
+                    TimeSeries::open_expecting(&path, element_size)?
+                } else {
+                    TimeSeries::create(&path, start_timestamp, element_size)?
+                };
+                Ok(Rollup {
+                    period: period.as_micros() as i64,
+                    time_series,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Rollups { tiers })
+    }
+
+    /// Writes every closed bucket that isn't in a tier yet, returning how many were written
+    ///
+    /// A bucket is closed once `raw` has a sample past it. The first tier is folded from `raw` and each later one from
+    /// the tier before it, which the periods dividing each other keeps exact.
+    pub fn update(&self, raw: &TimeSeries, schema: &ComponentSchema) -> Result<usize, Error> {
+        let (Some(&first), Some(&last)) = (raw.timestamps().first(), raw.timestamps().last())
+        else {
+            return Ok(0);
+        };
+        let len = schema.dim.iter().product();
+        let mut written = 0;
+        let mut buf = vec![];
+        for (i, tier) in self.tiers.iter().enumerate() {
+            let finer = i.checked_sub(1).map(|i| &self.tiers[i]);
+            let start = tier
+                .end()
+                .unwrap_or_else(|| bucket_start(first, tier.period));
+            let end = bucket_start(last, tier.period);
+            if start >= end {
+                continue;
+            }
+            let mut emit = |bucket: Bucket| -> Result<(), Error> {
+                bucket.encode(&mut buf);
+                tier.time_series.push_buf(bucket.start, &buf)?;
+                written += 1;
+                Ok(())
+            };
+            let mut folder = Folder::new(tier.period, len);
+            match finer {
+                None => folder.push_raw(raw, schema, start..end, &mut emit)?,
+                Some(finer) => {
+                    for bucket in finer.buckets(start..end) {
+                        let stats = [&bucket.min[..], &bucket.max[..], &bucket.mean[..]];
+                        folder.push(bucket.start, bucket.count, stats, &mut emit)?;
+                    }
+                }
+            }
+            folder.finish(&mut emit)?;
+        }
+        Ok(written)
+    }
+
+    /// Downsamples the samples of `raw` in `range` to at most `limit` buckets, or returns `None` if there are no tiers
+    ///
+    /// The finest tier that fits in `limit` is used, and if none do, the first `limit` buckets of the coarsest. The
+    /// buckets at either end of the range and the ones the tier doesn't have yet are folded from `raw`, so only the
+    /// samples in `range` are counted. The first bucket is reported at the first sample rather than at its start.
+    pub fn downsample(
+        &self,
+        raw: &TimeSeries,
+        schema: &ComponentSchema,
+        range: Range<Timestamp>,
+        limit: usize,
+    ) -> Result<Option<Vec<Bucket>>, Error> {
+        // the tiers are trimmed by retention like the raw samples, which the caller pins
+        let _pins = self
+            .tiers
+            .iter()
+            .map(|tier| tier.time_series.pin())
+            .collect::<Vec<_>>();
+        let (timestamps, _) = raw.slice(range.clone());
+        let (Some(&first), Some(&last)) = (timestamps.first(), timestamps.last()) else {
+            return Ok(Some(vec![]));
+        };
+        // the stored buckets that lie entirely within the samples
+        let stored_range = |tier: &Rollup| {
+            let start = bucket_start(
+                Timestamp(first.0.saturating_add(tier.period - 1)),
+                tier.period,
+            );
+            let end = tier
+                .end()
+                .unwrap_or(start)
+                .min(bucket_start(last, tier.period))
+                .max(start);
+            start..end
+        };
+        let Some(tier) = self
+            .tiers
+            .iter()
+            .find(|tier| {
+                let stored = stored_range(tier);
+                let head = usize::from(first < stored.start);
+                let tail = if last >= stored.end {
+                    ((last.0 - stored.end.0) / tier.period + 1) as usize
+                } else {
+                    0
+                };
+                head + tier.time_series.slice(stored).0.len() + tail <= limit
+            })
+            .or(self.tiers.last())
+        else {
+            return Ok(None);
+        };
+        let stored = stored_range(tier);
+        let mut buckets = vec![];
+        let mut emit = |bucket: Bucket| -> Result<(), Error> {
+            buckets.push(bucket);
+            Ok(())
+        };
+        let mut folder = Folder::new(tier.period, schema.dim.iter().product());
+        folder.push_raw(raw, schema, first..stored.start, &mut emit)?;
+        folder.finish(&mut emit)?;
+        for bucket in tier.buckets(stored.clone()).take(limit) {
+            emit(bucket)?;
+        }
+        folder.push_raw(raw, schema, stored.end..range.end, &mut emit)?;
+        folder.finish(&mut emit)?;
+        buckets.truncate(limit);
+        if let Some(bucket) = buckets.first_mut() {
+            bucket.start = bucket.start.max(first);
+        }
+        Ok(Some(buckets))
+    }
+
+    /// Drops the buckets older than the policy's `max_age`
+    ///
+    /// The sample and byte limits are sized for the raw samples, so they don't apply to the tiers.
+    pub fn apply_retention(
+        &self,
+        policy: &RetentionPolicy,
+        latest: Timestamp,
+    ) -> Result<CompactionReport, Error> {
+        let policy = RetentionPolicy {
+            max_age: policy.max_age,
+            ..Default::default()
+        };
+        let mut report = CompactionReport::default();
+        for tier in &self.tiers {
+            report += tier.time_series.apply_retention(&policy, latest)?;
+        }
+        Ok(report)
+    }
+
+    /// Each tier's time series, along with the directory it's stored in
+    pub fn time_series(&self) -> impl Iterator<Item = (&'static str, &TimeSeries)> {
+        TIERS
+            .iter()
+            .zip(&self.tiers)
+            .map(|((name, _), tier)| (*name, &tier.time_series))
+    }
+
+    /// Flushes every tier to disk
+    pub fn sync(&self) -> Result<(), Error> {
+        self.tiers
+            .iter()
+            .try_for_each(|tier| tier.time_series.sync())
+    }
+}
+
+/// The mean of each bucket, cast back to the component's type, along with the bucket starts
+pub fn means(buckets: &[Bucket], prim_type: PrimType) -> (Vec<Timestamp>, Vec<u8>) {
+    let timestamps = buckets.iter().map(|bucket| bucket.start).collect();
+    let mut data = Vec::with_capacity(buckets.len() * buckets.first().map_or(0, |b| b.mean.len()));
+    for mean in buckets.iter().flat_map(|bucket| &bucket.mean) {
+        data.extend_from_slice(
+            prim_type
+                .saturating_cast(ElementValue::F64(*mean))
+                .as_bytes(),
+        );
+    }
+    (timestamps, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::IntoBytes;
+
+    use super::*;
+
+    #[test]
+    fn test_rollups() {
+        let path =
+            std::env::temp_dir().join(format!("elodin_db_rollup_test_{}", fastrand::u64(..)));
+        let schema = ComponentSchema::new(PrimType::F64, &[2]);
+        let raw = TimeSeries::create(&path, Timestamp(0), 16).unwrap();
+        let rollups = Rollups::open(&path, &schema, Timestamp(0)).unwrap();
+        // 2.5 minutes of 100 Hz samples, the second element counting down
+        for i in 0..15_000i64 {
+            let value = [i as f64, -i as f64];
+            raw.push_buf(Timestamp(i * 10_000), value.as_bytes())
+                .unwrap();
+        }
+
+        // the last second and minute are still open
+        assert_eq!(rollups.update(&raw, &schema).unwrap(), 149 + 2);
+        assert_eq!(rollups.update(&raw, &schema).unwrap(), 0);
+        let second = rollups.tiers[0]
+            .buckets(Timestamp(1_000_000)..Timestamp(2_000_000))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            second,
+            [Bucket {
+                start: Timestamp(1_000_000),
+                count: 100,
+                min: vec![100.0, -199.0],
+                max: vec![199.0, -100.0],
+                mean: vec![149.5, -149.5],
+            }]
+        );
+        let minute = rollups.tiers[1]
+            .buckets(Timestamp(60_000_000)..Timestamp(120_000_000))
+            .collect::<Vec<_>>();
+        assert_eq!(minute[0].count, 6_000);
+        assert_eq!(minute[0].min, [6_000.0, -11_999.0]);
+        assert_eq!(minute[0].mean, [8_999.5, -8_999.5]);
+
+        // the open minute is folded from the raw samples, and the first bucket is reported at the first sample
+        let buckets = rollups
+            .downsample(
+                &raw,
+                &schema,
+                Timestamp(30_000_000)..Timestamp(i64::MAX),
+                10,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            buckets.iter().map(|b| b.start).collect::<Vec<_>>(),
+            [30_000_000, 60_000_000, 120_000_000].map(Timestamp)
+        );
+        assert_eq!(buckets[0].count, 3_000);
+        assert_eq!(buckets[2].count, 3_000);
+        assert_eq!(buckets[2].max, [14_999.0, -12_000.0]);
+        // a short range fits in the 1s tier
+        let buckets = rollups
+            .downsample(&raw, &schema, Timestamp(0)..Timestamp(5_000_000), 10)
+            .unwrap()
+            .unwrap();
+        assert_eq!(buckets.len(), 5);
+        assert_eq!(buckets[4].mean, [449.5, -449.5]);
+        let (timestamps, data) = means(&buckets[..1], PrimType::F64);
+        assert_eq!(timestamps, [Timestamp(0)]);
+        assert_eq!(data, [49.5f64, -49.5].as_bytes());
+        drop(rollups);
+
+        // the tiers are reopened where they left off
+        let rollups = Rollups::open(&path, &schema, Timestamp(0)).unwrap();
+        raw.push_buf(Timestamp(180_000_000), [0.0f64, 0.0].as_bytes())
+            .unwrap();
+        assert_eq!(rollups.update(&raw, &schema).unwrap(), 1 + 1);
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
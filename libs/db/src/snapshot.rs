@@ -0,0 +1,152 @@
+//! Point-in-time copies of a db, see [`impeller2_wkt::CreateSnapshot`]
+//!
+//! A snapshot is a db directory of its own. The lengths of every log are read with the state locked, which also
+//! holds the metadata still while it's copied, and the logs are then copied up to those lengths with the lock released.
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use tracing::info;
+
+use crate::{Component, DB, Error, MetadataExt, msg_log::MsgLog, time_series::TimeSeries};
+
+/// The append logs of time series and msg logs, which are copied by their owners rather than as plain files
+const LOG_FILES: [&str; 5] = ["index", "data", "timestamps", "offsets", "data_log"];
+
+impl DB {
+    /// Writes a consistent copy of the db to `path`, which must not exist yet and can't be inside the db
+    pub fn snapshot(&self, path: &Path) -> Result<(), Error> {
+        // a compaction would zero data the copy still needs
+        let _compaction = self
+            .compaction_lock
+            .lock()
+            .expect("compaction lock poisoned");
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::create_dir(path)?;
+        if path.canonicalize()?.starts_with(self.path.canonicalize()?) {
+            std::fs::remove_dir(path)?;
+            return Err(Error::SnapshotInsideDb);
+        }
+
+        let (time_series, msg_logs) = self.with_state(|state| {
+            copy_files(&self.path, path)?;
+            let mut time_series: Vec<(TimeSeries, PathBuf, usize)> = vec![];
+            for component in state.components.values() {
+                for version in component.versions() {
+                    let version_path =
+                        Component::version_path(path, component.component_id, version.version);
+                    let series = &version.time_series;
+                    time_series.push((series.clone(), version_path, series.end_position()));
+                }
+                let current_path =
+                    Component::version_path(path, component.component_id, component.version);
+                for (name, series) in component.rollups.time_series() {
+                    time_series.push((
+                        series.clone(),
+                        current_path.join(name),
+                        series.end_position(),
+                    ));
+                }
+            }
+            let msg_logs = state
+                .msg_logs
+                .iter()
+                .map(|(id, msg_log)| {
+                    let msg_path = path.join("msgs").join(u16::from_le_bytes(*id).to_string());
+                    (msg_log.clone(), msg_path, msg_log.timestamps().len())
+                })
+                .collect::<Vec<(MsgLog, PathBuf, usize)>>();
+            Ok::<_, Error>((time_series, msg_logs))
+        })?;
+
+        for (time_series, path, end) in time_series {
+            time_series.snapshot(path, end)?;
+        }
+        for (msg_log, path, end) in msg_logs {
+            msg_log.snapshot(path, end)?;
+        }
+        info!(?path, "created snapshot");
+        Ok(())
+    }
+
+    /// Replaces the db's contents with a copy of the snapshot at `path`, see [`impeller2_wkt::RestoreSnapshot`]
+    ///
+    /// The snapshot is copied and opened next to the db before the db's directory is swapped for it, so a failed copy
+    /// leaves the db as it was and the state lock is only held for the swap. The vtables, streams and config of the
+    /// running db are kept.
+    pub fn restore_snapshot(&self, path: &Path) -> Result<(), Error> {
+        let staging = sibling(&self.path, "restoring");
+        if staging.exists() {
+            // left behind by a restore that failed
+            std::fs::remove_dir_all(&staging)?;
+        }
+        DB::open(path.to_path_buf())?.snapshot(&staging)?;
+        let restored = DB::open(staging.clone())?;
+
+        let _compaction = self
+            .compaction_lock
+            .lock()
+            .expect("compaction lock poisoned");
+        let replaced = sibling(&self.path, "replaced");
+        self.with_state_mut(|state| {
+            std::fs::rename(&self.path, &replaced)?;
+            if let Err(err) = std::fs::rename(&staging, &self.path) {
+                std::fs::rename(&replaced, &self.path)?;
+                return Err(err.into());
+            }
+            // the restored files stay mapped across the rename, only the msg logs remember where they live
+            let mut restored_state = restored.state.into_inner().expect("state lock poisoned");
+            for (id, msg_log) in &mut restored_state.msg_logs {
+                msg_log.relocate(
+                    self.path
+                        .join("msgs")
+                        .join(u16::from_le_bytes(*id).to_string()),
+                );
+            }
+            state.components = restored_state.components;
+            state.component_metadata = restored_state.component_metadata;
+            state.component_metadata_history = restored_state.component_metadata_history;
+            state.retention = restored_state.retention;
+            state.msg_logs = restored_state.msg_logs;
+            self.last_updated.store(restored.last_updated.latest());
+            // the restored db_state is replaced by the running config
+            state.db_config.write(self.path.join("db_state"))
+        })?;
+        std::fs::remove_dir_all(&replaced)?;
+        info!(?path, "restored snapshot");
+        Ok(())
+    }
+}
+
+/// Copies the files in `src` that aren't append logs into `dst`, creating every directory along the way
+fn copy_files(src: &Path, dst: &Path) -> Result<(), Error> {
+    std::fs::create_dir_all(dst)?;
+    for elem in std::fs::read_dir(src)? {
+        let elem = elem?;
+        let (src, dst) = (elem.path(), dst.join(elem.file_name()));
+        if elem.file_type()?.is_dir() {
+            copy_files(&src, &dst)?;
+            continue;
+        }
+        match elem.file_name().to_str() {
+            Some(name) if LOG_FILES.contains(&name) => {}
+            // schemas are only written when their directory is created, so a link can't see a later change
+            Some("schema") if std::fs::hard_link(&src, &dst).is_ok() => {}
+            _ => {
+                std::fs::copy(&src, &dst)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A path next to `path`, with `suffix` appended to its name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(format!(".{suffix}"));
+    path.with_file_name(name)
+}
@@ -1,17 +1,28 @@
-use std::{ops::Range, path::Path, sync::Arc};
+use std::{ops::Range, path::Path, sync::Arc, time::Duration};
 
 use impeller2::types::Timestamp;
+use impeller2_wkt::{CompactionReport, RetentionPolicy};
 use stellarator::sync::WaitQueue;
 use tracing::warn;
 use zerocopy::FromBytes;
 
-use crate::{Error, append_log::AppendLog};
+use crate::{
+    Error,
+    append_log::{AppendLog, ReadPin},
+};
+
+/// Pins the index and data of a [`TimeSeries`], see [`TimeSeries::pin`]
+pub struct TimeSeriesPin {
+    _index: ReadPin,
+    _data: ReadPin,
+}
 
 #[derive(Clone)]
 pub struct TimeSeries {
     index: AppendLog<Timestamp>,
     data: AppendLog<u64>,
     data_waker: Arc<WaitQueue>,
+    strictly_increasing: bool,
 }
 
 impl TimeSeries {
@@ -29,6 +40,7 @@ impl TimeSeries {
             index,
             data,
             data_waker: data_waker.clone(),
+            strictly_increasing: false,
         };
         Ok(time_series)
     }
@@ -42,21 +54,90 @@ impl TimeSeries {
             index,
             data,
             data_waker: data_waker.clone(),
+            strictly_increasing: false,
         };
         Ok(time_series)
     }
 
+    /// Opens an existing time series, failing with [`Error::SchemaMismatch`] if it was
+    /// created with a different element size.
+    pub fn open_expecting(path: impl AsRef<Path>, element_size: u64) -> Result<Self, Error> {
+        let time_series = Self::open(path)?;
+        let stored_size = *time_series.data.extra();
+        if stored_size != element_size {
+            warn!(stored_size, element_size, "element size mismatch");
+            return Err(Error::SchemaMismatch);
+        }
+        Ok(time_series)
+    }
+
+    /// Rejects samples whose timestamp equals the last one with [`Error::DuplicateTimestamp`]
+    ///
+    /// By default equal timestamps are accepted, but lookups by timestamp then return an arbitrary one of the
+    /// duplicates.
+    pub fn with_strictly_increasing(mut self, strictly_increasing: bool) -> Self {
+        self.strictly_increasing = strictly_increasing;
+        self
+    }
+
+    /// Returns each timestamp that appears more than once in the index
+    pub fn find_duplicates(&self) -> Vec<Timestamp> {
+        let mut duplicates: Vec<Timestamp> = vec![];
+        for pair in self.timestamps().windows(2) {
+            if pair[0] == pair[1] && duplicates.last() != Some(&pair[0]) {
+                duplicates.push(pair[0]);
+            }
+        }
+        duplicates
+    }
+
     pub fn start_timestamp(&self) -> Timestamp {
         let index_ts = *self.index.extra();
         match self.timestamps().first() {
+            // once the front is trimmed the creation time no longer bounds the data
+            Some(first_ts) if self.index.trimmed_len() > 0 => *first_ts,
             Some(first_ts) => index_ts.min(*first_ts),
             None => index_ts,
         }
     }
 
-    fn timestamps(&self) -> &[Timestamp] {
-        <[Timestamp]>::ref_from_bytes(self.index.get(..).expect("couldn't get full range"))
-            .expect("mmep unaligned")
+    /// Keeps every sample that's readable now from being reclaimed until dropped, even if it's trimmed, see
+    /// [`AppendLog::pin`]
+    ///
+    /// Readers that can race a compaction take it before reading, and hold it for as long as they use what they read.
+    pub fn pin(&self) -> TimeSeriesPin {
+        TimeSeriesPin {
+            _index: self.index.pin(),
+            _data: self.data.pin(),
+        }
+    }
+
+    pub(crate) fn timestamps(&self) -> &[Timestamp] {
+        self.retained().1
+    }
+
+    /// The timestamps that haven't been trimmed, along with the position of the first one
+    ///
+    /// Positions count from the first sample ever written, so they stay valid when the front is trimmed, and the data
+    /// of the sample at position `i` is always at `i * element_size`.
+    fn retained(&self) -> (usize, &[Timestamp]) {
+        let first = self.index.trimmed_len() as usize / size_of::<Timestamp>();
+        let index = self
+            .index
+            .get(first * size_of::<Timestamp>()..)
+            .expect("couldn't get full range");
+        let timestamps = <[Timestamp]>::ref_from_bytes(index).expect("mmep unaligned");
+        (first, timestamps)
+    }
+
+    /// The position of the first sample that hasn't been trimmed, see [`Self::trim_front`]
+    pub fn first_position(&self) -> usize {
+        self.retained().0
+    }
+
+    /// The position after the last sample, see [`Self::first_position`]
+    pub fn end_position(&self) -> usize {
+        self.index.len() as usize / size_of::<Timestamp>()
     }
 
     pub fn element_size(&self) -> usize {
@@ -64,67 +145,103 @@ impl TimeSeries {
     }
 
     pub fn get(&self, timestamp: Timestamp) -> Option<&[u8]> {
-        let timestamps = self.timestamps();
-        let index = timestamps.binary_search(&timestamp).ok()?;
+        let (first, timestamps) = self.retained();
+        let index = first + timestamps.binary_search(&timestamp).ok()?;
         let element_size = self.element_size();
         let i = index * element_size;
         self.data.get(i..i + element_size)
     }
 
     pub fn get_nearest(&self, timestamp: Timestamp) -> Option<(Timestamp, &[u8])> {
-        let timestamps = self.timestamps();
+        let (first, timestamps) = self.retained();
         let index = match timestamps.binary_search(&timestamp) {
             Ok(i) => i,
             Err(i) => i.saturating_sub(1),
         };
         let element_size = self.element_size();
         let timestamp = timestamps.get(index)?;
-        let i = index * element_size;
+        let i = (first + index) * element_size;
         let buf = self.data.get(i..i + element_size)?;
         Some((*timestamp, buf))
     }
 
     pub fn get_range(&self, range: Range<Timestamp>) -> Option<(&[Timestamp], &[u8])> {
-        let timestamps = self.timestamps();
-
-        let start = range.start;
-        let end = range.end;
-        let start_index = match timestamps.binary_search(&start) {
-            Ok(i) => i,
-            Err(i) => i,
-        };
+        let (first, timestamps) = self.retained();
 
-        let end_index = match timestamps.binary_search(&end) {
-            Ok(i) => i,
-            Err(i) => i.saturating_sub(1),
-        };
+        // partition points rather than binary searches, so samples sharing a timestamp with either end are all kept
+        let start_index = timestamps.partition_point(|timestamp| *timestamp < range.start);
+        let end_index = timestamps
+            .partition_point(|timestamp| *timestamp <= range.end)
+            .saturating_sub(1);
 
         let timestamps = timestamps.get(start_index..=end_index)?;
         let element_size = self.element_size();
-        let data = self
-            .data
-            .get(start_index * element_size..end_index.saturating_add(1) * element_size)?;
+        let data = self.data.get(
+            (first + start_index) * element_size
+                ..(first + end_index).saturating_add(1) * element_size,
+        )?;
 
         Some((timestamps, data))
     }
 
+    /// The samples with timestamps in `range`, which unlike [`Self::get_range`] excludes the end
+    pub fn slice(&self, range: Range<Timestamp>) -> (&[Timestamp], &[u8]) {
+        let (first, timestamps) = self.retained();
+        let start = timestamps.partition_point(|timestamp| *timestamp < range.start);
+        let end = timestamps
+            .partition_point(|timestamp| *timestamp < range.end)
+            .max(start);
+        let element_size = self.element_size();
+        let data = self
+            .data
+            .get((first + start) * element_size..(first + end) * element_size)
+            .expect("couldn't get full range");
+        (&timestamps[start..end], data)
+    }
+
     pub async fn wait(&self) {
         let _ = self.data_waker.wait().await;
     }
 
+    /// Waits for new data for at most `timeout`, returning `false` if none arrived
+    ///
+    /// Lets an idle stream task wake up periodically, e.g. to check whether it was cancelled.
+    pub async fn wait_timeout(&self, timeout: Duration) -> bool {
+        futures_lite::future::or(
+            async {
+                self.wait().await;
+                true
+            },
+            async {
+                stellarator::sleep(timeout).await;
+                false
+            },
+        )
+        .await
+    }
+
     pub fn waiter(&self) -> Arc<WaitQueue> {
         self.data_waker.clone()
     }
 
     pub fn latest(&self) -> Option<(&Timestamp, &[u8])> {
-        let index = self.index.len() as usize / size_of::<Timestamp>() - 1;
+        let (first, timestamps) = self.retained();
+        let index = timestamps.len().checked_sub(1)?;
         let element_size = self.element_size();
-        let i = index * element_size;
+        let i = (first + index) * element_size;
         let data = self.data.get(i..i + element_size)?;
-        let timestamp = self.timestamps().get(index)?;
+        let timestamp = timestamps.get(index)?;
         Some((timestamp, data))
     }
 
+    /// Returns the latest sample only if it is newer than `after`
+    ///
+    /// Lets a polling consumer check for new data without re-reading a sample it has already seen.
+    pub fn latest_since(&self, after: Timestamp) -> Option<(Timestamp, &[u8])> {
+        let (timestamp, data) = self.latest()?;
+        (*timestamp > after).then_some((*timestamp, data))
+    }
+
     pub(crate) fn data(&self) -> &AppendLog<u64> {
         &self.data
     }
@@ -133,25 +250,112 @@ impl TimeSeries {
         &self.index
     }
 
+    /// Flushes the index and data to disk
+    pub fn sync(&self) -> Result<(), Error> {
+        self.data.sync()?;
+        self.index.sync()
+    }
+
+    /// Copies the samples before position `end` to a new time series at `path`, see [`AppendLog::snapshot`]
+    ///
+    /// `end` should be read with [`Self::end_position`] before the copy. The data is always written before the index,
+    /// so every sample the index had then has its data.
+    pub fn snapshot(&self, path: impl AsRef<Path>, end: usize) -> Result<(), Error> {
+        let path = path.as_ref();
+        std::fs::create_dir_all(path)?;
+        self.index
+            .snapshot(path.join("index"), (end * size_of::<Timestamp>()) as u64)?;
+        self.data
+            .snapshot(path.join("data"), (end * self.element_size()) as u64)
+    }
+
     pub fn push_buf(&self, timestamp: Timestamp, buf: &[u8]) -> Result<(), Error> {
-        let len = self.index.len() as usize;
+        self.append(timestamp, buf)?;
+        self.data_waker.wake_all();
+        Ok(())
+    }
+
+    /// Appends every sample in `samples`, waking subscribers once at the end rather than
+    /// once per sample.
+    ///
+    /// Returns the number of samples written. If a sample fails to append, the samples
+    /// before it remain written and subscribers are still woken.
+    pub fn push_batch<'a>(
+        &self,
+        samples: impl IntoIterator<Item = (Timestamp, &'a [u8])>,
+    ) -> Result<usize, Error> {
+        let mut written = 0;
+        let res = samples.into_iter().try_for_each(|(timestamp, buf)| {
+            self.append(timestamp, buf)?;
+            written += 1;
+            Ok(())
+        });
+        if written > 0 {
+            self.data_waker.wake_all();
+        }
+        res.map(|_| written)
+    }
+
+    /// Drops every sample before `position`, see [`Self::first_position`]
+    ///
+    /// The latest sample is always kept. The index is trimmed before the data, so a sample that's still in the index
+    /// always has its data.
+    pub fn trim_front(&self, position: usize) -> Result<CompactionReport, Error> {
+        let (first, timestamps) = self.retained();
+        let position = position.min(first + timestamps.len().saturating_sub(1));
+        if position <= first {
+            return Ok(CompactionReport::default());
+        }
+        let index_bytes = self
+            .index
+            .trim_front((position * size_of::<Timestamp>()) as u64)?;
+        let data_bytes = self
+            .data
+            .trim_front((position * self.element_size()) as u64)?;
+        Ok(CompactionReport {
+            samples_dropped: index_bytes / size_of::<Timestamp>() as u64,
+            bytes_reclaimed: index_bytes + data_bytes,
+        })
+    }
+
+    /// Drops the samples `policy` doesn't keep, measuring `max_age` back from `latest`
+    ///
+    /// `latest` is passed in so every schema version of a component can be aged from the component's newest sample.
+    pub fn apply_retention(
+        &self,
+        policy: &RetentionPolicy,
+        latest: Timestamp,
+    ) -> Result<CompactionReport, Error> {
+        let (first, timestamps) = self.retained();
+        let len = timestamps.len();
+        let mut drop = 0;
+        if let Some(max_samples) = policy.max_samples {
+            drop = drop.max(len.saturating_sub(max_samples as usize));
+        }
+        if let Some(max_bytes) = policy.max_bytes {
+            let sample_size = (self.element_size() + size_of::<Timestamp>()) as u64;
+            drop = drop.max(len.saturating_sub((max_bytes / sample_size) as usize));
+        }
+        if let Some(max_age) = policy.max_age {
+            let max_age = i64::try_from(max_age.as_micros()).unwrap_or(i64::MAX);
+            let cutoff = Timestamp(latest.0.saturating_sub(max_age));
+            drop = drop.max(timestamps.partition_point(|timestamp| *timestamp < cutoff));
+        }
+        self.trim_front(first + drop)
+    }
 
+    fn append(&self, timestamp: Timestamp, buf: &[u8]) -> Result<(), Error> {
         // check if timestamp is greater than the last timestamp
         // to ensure index is ordered
-        if len > 0 {
-            let last_timestamp = self
-                .index
-                .get(len - size_of::<i64>()..len)
-                .expect("couldn't find last timestamp");
-            let last_timestamp = Timestamp::from_le_bytes(
-                last_timestamp
-                    .try_into()
-                    .expect("last_timestamp was wrong size"),
-            );
+        if let Some(&last_timestamp) = self.timestamps().last() {
             if last_timestamp > timestamp {
                 warn!(?last_timestamp, ?timestamp, "time travel");
                 return Err(Error::TimeTravel);
             }
+            if self.strictly_increasing && last_timestamp == timestamp {
+                warn!(?timestamp, "duplicate timestamp");
+                return Err(Error::DuplicateTimestamp(timestamp));
+            }
         }
 
         // write new data to head of data writer
@@ -159,8 +363,257 @@ impl TimeSeries {
 
         // always write index last so we get consistent reads
         self.index.write(&timestamp.to_le_bytes())?;
-
-        self.data_waker.wake_all();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[stellarator::test]
+    async fn test_push_batch_single_wake() {
+        let path =
+            std::env::temp_dir().join(format!("elodin_db_time_series_test_{}", fastrand::u64(..)));
+        let time_series = TimeSeries::create(&path, Timestamp(0), 8).unwrap();
+        let samples = (0..1000i64)
+            .map(|i| (Timestamp(i), i.to_le_bytes()))
+            .collect::<Vec<_>>();
+
+        let writer = time_series.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            let written = writer
+                .push_batch(samples.iter().map(|(ts, buf)| (*ts, &buf[..])))
+                .unwrap();
+            assert_eq!(written, 1000);
+        });
+
+        time_series.wait().await;
+        handle.join().unwrap();
+        let (timestamps, data) = time_series.get_range(Timestamp(0)..Timestamp(999)).unwrap();
+        assert_eq!(timestamps.len(), 1000);
+        assert_eq!(data.len(), 1000 * 8);
+        assert_eq!(time_series.latest().unwrap().0, &Timestamp(999));
+
+        let err = time_series.push_batch([
+            (Timestamp(1000), &[0u8; 8][..]),
+            (Timestamp(10), &[0u8; 8][..]),
+        ]);
+        assert!(matches!(err, Err(Error::TimeTravel)));
+        assert_eq!(time_series.latest().unwrap().0, &Timestamp(1000));
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[stellarator::test]
+    async fn test_wait_timeout() {
+        let path =
+            std::env::temp_dir().join(format!("elodin_db_time_series_test_{}", fastrand::u64(..)));
+        let time_series = TimeSeries::create(&path, Timestamp(0), 8).unwrap();
+        assert!(!time_series.wait_timeout(Duration::from_millis(10)).await);
+
+        let writer = time_series.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            writer.push_buf(Timestamp(1), &[0u8; 8]).unwrap();
+        });
+        assert!(time_series.wait_timeout(Duration::from_secs(5)).await);
+        handle.join().unwrap();
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_timestamps() {
+        let path =
+            std::env::temp_dir().join(format!("elodin_db_time_series_test_{}", fastrand::u64(..)));
+        let time_series = TimeSeries::create(path.join("permissive"), Timestamp(0), 8).unwrap();
+        for timestamp in [1, 2, 2, 2, 3, 4, 4] {
+            time_series
+                .push_buf(Timestamp(timestamp), &[0u8; 8])
+                .unwrap();
+        }
+        assert_eq!(
+            time_series.find_duplicates(),
+            vec![Timestamp(2), Timestamp(4)]
+        );
+
+        let time_series = TimeSeries::create(path.join("strict"), Timestamp(0), 8)
+            .unwrap()
+            .with_strictly_increasing(true);
+        time_series.push_buf(Timestamp(1), &[0u8; 8]).unwrap();
+        assert!(matches!(
+            time_series.push_buf(Timestamp(1), &[1u8; 8]),
+            Err(Error::DuplicateTimestamp(Timestamp(1)))
+        ));
+        assert!(matches!(
+            time_series.push_buf(Timestamp(0), &[1u8; 8]),
+            Err(Error::TimeTravel)
+        ));
+        time_series.push_buf(Timestamp(2), &[0u8; 8]).unwrap();
+        assert_eq!(time_series.get(Timestamp(1)), Some(&[0u8; 8][..]));
+        assert!(time_series.find_duplicates().is_empty());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_latest_since() {
+        let path =
+            std::env::temp_dir().join(format!("elodin_db_time_series_test_{}", fastrand::u64(..)));
+        let time_series = TimeSeries::create(&path, Timestamp(0), 8).unwrap();
+        assert_eq!(time_series.latest_since(Timestamp(i64::MIN)), None);
+
+        time_series.push_buf(Timestamp(5), &[5u8; 8]).unwrap();
+        assert_eq!(
+            time_series.latest_since(Timestamp(0)),
+            Some((Timestamp(5), &[5u8; 8][..]))
+        );
+        // nothing has changed since the last poll
+        assert_eq!(time_series.latest_since(Timestamp(5)), None);
+
+        time_series.push_buf(Timestamp(6), &[6u8; 8]).unwrap();
+        assert_eq!(
+            time_series.latest_since(Timestamp(5)),
+            Some((Timestamp(6), &[6u8; 8][..]))
+        );
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_open_expecting_element_size() {
+        let path =
+            std::env::temp_dir().join(format!("elodin_db_time_series_test_{}", fastrand::u64(..)));
+        let time_series = TimeSeries::create(&path, Timestamp(0), 8).unwrap();
+        time_series.push_buf(Timestamp(1), &[0u8; 8]).unwrap();
+        drop(time_series);
+
+        let time_series = TimeSeries::open_expecting(&path, 8).unwrap();
+        assert_eq!(time_series.element_size(), 8);
+        drop(time_series);
+        assert!(matches!(
+            TimeSeries::open_expecting(&path, 16),
+            Err(Error::SchemaMismatch)
+        ));
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_retention() {
+        use zerocopy::IntoBytes;
+
+        let path =
+            std::env::temp_dir().join(format!("elodin_db_time_series_test_{}", fastrand::u64(..)));
+        let time_series = TimeSeries::create(&path, Timestamp(0), 8).unwrap();
+        // enough samples that trimming crosses whole pages
+        for i in 0..20_000i64 {
+            time_series
+                .push_buf(Timestamp(i * 10), &i.to_le_bytes())
+                .unwrap();
+        }
+
+        let policy = RetentionPolicy {
+            max_samples: Some(15_000),
+            ..Default::default()
+        };
+        let report = time_series
+            .apply_retention(&policy, Timestamp(199_990))
+            .unwrap();
+        assert_eq!(report.samples_dropped, 5_000);
+        assert_eq!(report.bytes_reclaimed, 5_000 * 16);
+        assert_eq!(time_series.first_position(), 5_000);
+        assert_eq!(time_series.start_timestamp(), Timestamp(50_000));
+        assert_eq!(time_series.get(Timestamp(40_000)), None);
+        assert_eq!(
+            time_series.get(Timestamp(50_000)),
+            Some(&5_000i64.to_le_bytes()[..])
+        );
+        let (timestamps, data) = time_series
+            .get_range(Timestamp(0)..Timestamp(50_010))
+            .unwrap();
+        assert_eq!(timestamps, [Timestamp(50_000), Timestamp(50_010)]);
+        assert_eq!(data, [5_000i64, 5_001].as_bytes());
+
+        // the age is measured back from the latest sample, and the tighter limit wins
+        let policy = RetentionPolicy {
+            max_age: Some(Duration::from_micros(1_000)),
+            max_bytes: Some(16 * 1_000),
+            ..Default::default()
+        };
+        let report = time_series
+            .apply_retention(&policy, Timestamp(199_990))
+            .unwrap();
+        assert_eq!(report.samples_dropped, 14_899);
+        assert_eq!(time_series.start_timestamp(), Timestamp(198_990));
+
+        // nothing more to drop, and the latest sample survives a policy that keeps nothing
+        let policy = RetentionPolicy {
+            max_samples: Some(0),
+            ..Default::default()
+        };
+        time_series
+            .apply_retention(&policy, Timestamp(199_990))
+            .unwrap();
+        assert_eq!(
+            time_series.latest(),
+            Some((&Timestamp(199_990), &19_999i64.to_le_bytes()[..]))
+        );
+        time_series
+            .push_buf(Timestamp(200_000), &20_000i64.to_le_bytes())
+            .unwrap();
+        assert!(matches!(
+            time_series.push_buf(Timestamp(0), &[0u8; 8]),
+            Err(Error::TimeTravel)
+        ));
+        drop(time_series);
+
+        // the trim is persisted in the header
+        let time_series = TimeSeries::open(&path).unwrap();
+        assert_eq!(time_series.first_position(), 19_999);
+        assert_eq!(
+            time_series
+                .get_range(Timestamp(0)..Timestamp(200_000))
+                .unwrap()
+                .0
+                .len(),
+            2
+        );
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_pinned_read_survives_trim() {
+        use std::os::unix::fs::MetadataExt;
+
+        let path =
+            std::env::temp_dir().join(format!("elodin_db_time_series_test_{}", fastrand::u64(..)));
+        let time_series = TimeSeries::create(&path, Timestamp(0), 8).unwrap();
+        for i in 0..100_000i64 {
+            time_series
+                .push_buf(Timestamp(i), &i.to_le_bytes())
+                .unwrap();
+        }
+        time_series.sync().unwrap();
+        let blocks = || std::fs::metadata(path.join("data")).unwrap().blocks();
+        let written = blocks();
+
+        let pin = time_series.pin();
+        let (_, data) = time_series
+            .get_range(Timestamp(0)..Timestamp(100_000))
+            .unwrap();
+        time_series.trim_front(90_000).unwrap();
+        // the pages behind the trimmed samples are kept while the read is pinned
+        assert_eq!(&data[50_000 * 8..50_001 * 8], 50_000i64.to_le_bytes());
+        assert_eq!(blocks(), written);
+
+        drop(pin);
+        #[cfg(target_os = "linux")]
+        assert!(blocks() < written);
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
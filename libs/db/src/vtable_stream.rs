@@ -92,7 +92,10 @@ pub async fn handle_vtable_stream<A: AsyncWrite + 'static>(
                         .ok_or(Error::ComponentNotFound(component_id))?
                         .clone();
 
-                    let state = db.get_or_insert_fixed_rate_state(stream_id, behavior);
+                    // the stage only plays back this component, so only its rate sets the timestep
+                    let filter = [component_id].into_iter().collect();
+                    let state =
+                        db.get_or_insert_fixed_rate_state(stream_id, behavior, Some(&filter));
                     plan.insert(
                         0,
                         StreamStage::FixedRate(FixedRateStage {
@@ -277,6 +280,7 @@ impl FixedRateStage {
             return Ok(true);
         }
         let current_timestamp = self.state.current_timestamp();
+        let _pin = self.component.time_series.pin();
         let Some((timestamp, buf)) = self.component.time_series.get_nearest(current_timestamp)
         else {
             return Ok(true);
@@ -314,12 +318,13 @@ impl RealTimeStage {
             "real time stage waiting"
         );
         self.component.time_series.wait().await;
-        let Some((&timestamp, buf)) = self.component.time_series.latest() else {
+        let _pin = self.component.time_series.pin();
+        let Some((timestamp, buf)) = self.component.latest() else {
             return Ok(true);
         };
         shard
             .with_buf(|shard| {
-                shard.copy_from_slice(buf);
+                shard.copy_from_slice(&buf);
             })
             .await;
         if let Some(timestamp_shard) = timestamp_shard {
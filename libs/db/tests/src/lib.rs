@@ -2,15 +2,18 @@
 mod tests {
 
     use arrow::{array::AsArray, datatypes::Float64Type};
-    use elodin_db::{DB, Error, Server};
+    use elodin_db::{DB, Error, Follower, Server};
     use impeller2::{
-        types::{ComponentId, IntoLenPacket, LenPacket, Msg, PrimType, Timestamp},
+        types::{
+            ComponentId, ComponentView, IntoLenPacket, LenPacket, Msg, OwnedPacket, PrimType,
+            Q15_16, Timestamp, write_var_len,
+        },
         vtable::builder::{component, raw_field, raw_table, schema, timestamp, vtable},
     };
-    use impeller2_stellar::Client;
+    use impeller2_stellar::{Client, PacketSink, PacketStream};
     use postcard_schema::{Schema, schema::owned::OwnedNamedType};
     use std::{net::SocketAddr, sync::Arc, time::Duration};
-    use stellarator::{net::TcpListener, sleep, spawn, struc_con::stellar, test};
+    use stellarator::{io::SplitExt, net::TcpListener, sleep, spawn, struc_con::stellar, test};
     use zerocopy::FromBytes;
     use zerocopy::IntoBytes;
 
@@ -72,6 +75,62 @@ mod tests {
         })
     }
 
+    #[test]
+    async fn test_compression() {
+        let (addr, db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+        let codec = client
+            .negotiate_compression(&[Compression::Zstd, Compression::Lz4])
+            .await
+            .unwrap();
+        assert_eq!(codec, Some(Compression::Zstd));
+
+        let component_id = ComponentId::new("spectrum");
+        let vtable_id = 1u16.to_le_bytes();
+        let vtable = vtable([raw_field(
+            0,
+            1024,
+            schema(PrimType::F64, &[128], component(component_id)),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: vtable_id,
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        // repetitive enough to compress, so both the table and the reply below are sent compressed
+        let floats = (0..128).map(|i| (i / 16) as f64).collect::<Vec<_>>();
+        let mut pkt = LenPacket::table(vtable_id, 1024);
+        pkt.extend_aligned(&floats);
+        client.send(pkt).await.0.unwrap();
+        let FlushAck = client.request(&Flush).await.unwrap();
+        db.with_state(|state| {
+            let (_, data) = state
+                .get_component(component_id)
+                .expect("missing component")
+                .time_series
+                .latest()
+                .expect("missing latest value");
+            assert_eq!(data, floats.as_bytes());
+        });
+
+        let time_series = client
+            .request(&GetTimeSeries {
+                id: vtable_id,
+                range: Timestamp(i64::MIN)..Timestamp(i64::MAX),
+                component_id,
+                limit: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(time_series.data().unwrap(), floats.as_bytes());
+
+        let codec = client.negotiate_compression(&[]).await.unwrap();
+        assert_eq!(codec, None);
+    }
+
     #[test]
     async fn test_vtable_stream() {
         let (addr, _db) = setup_test_db().await.unwrap();
@@ -117,6 +176,66 @@ mod tests {
         }
     }
 
+    #[test]
+    async fn test_get_vtable() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+        let vtable_id = 1u16.to_le_bytes();
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            schema(PrimType::F64, &[1], component("pressure")),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: vtable_id,
+                vtable: vtable.clone(),
+            })
+            .await
+            .0
+            .unwrap();
+        let FlushAck = client.request(&Flush).await.unwrap();
+
+        let reply = client.request(&GetVTable { id: vtable_id }).await.unwrap();
+        assert_eq!(reply.id, vtable_id);
+        assert_eq!(format!("{:?}", reply.vtable), format!("{vtable:?}"));
+        assert!(
+            client
+                .request(&GetVTable {
+                    id: 2u16.to_le_bytes()
+                })
+                .await
+                .is_err()
+        );
+
+        // vtables the db generates for a stream can be fetched too
+        let mut pkt = LenPacket::table(vtable_id, 8);
+        pkt.extend_aligned(&[1.0f64]);
+        client.send(pkt).await.0.unwrap();
+        let FlushAck = client.request(&Flush).await.unwrap();
+        let mut rx_client = Client::connect(addr).await.unwrap();
+        let mut sub = rx_client
+            .stream(&Stream {
+                behavior: StreamBehavior::RealTime,
+                id: 1,
+            })
+            .await
+            .unwrap();
+        let StreamReply::VTable(stream_vtable) = sub.next().await.unwrap() else {
+            panic!("unexpected reply type");
+        };
+        let reply = client
+            .request(&GetVTable {
+                id: stream_vtable.id,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", reply.vtable),
+            format!("{:?}", stream_vtable.vtable)
+        );
+    }
+
     #[test]
     async fn test_dump_metadata() {
         let (addr, _db) = setup_test_db().await.unwrap();
@@ -145,6 +264,61 @@ mod tests {
         )
     }
 
+    #[test]
+    async fn test_query_metadata() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+        for (id, unit) in [
+            ("imu.accel", "m/s^2"),
+            ("imu.gyro", "rad/s"),
+            ("gps.speed", "m/s"),
+        ] {
+            let metadata = SetComponentMetadata::new(id, id).metadata(
+                [("unit".to_string(), unit.to_string())]
+                    .into_iter()
+                    .collect(),
+            );
+            client.send(&metadata).await.0.unwrap();
+        }
+        sleep(Duration::from_millis(50)).await;
+
+        let names = |resp: QueryMetadataResp| {
+            resp.component_metadata
+                .into_iter()
+                .map(|m| m.name)
+                .collect::<Vec<_>>()
+        };
+        let resp = client
+            .request(&QueryMetadata {
+                name_glob: Some("imu*".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(names(resp), ["imu.accel", "imu.gyro"]);
+
+        let resp = client
+            .request(&QueryMetadata {
+                metadata_filters: vec![MetadataFilter {
+                    key: "unit".to_string(),
+                    value: Some("m/s*".to_string()),
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(names(resp), ["gps.speed", "imu.accel"]);
+
+        let resp = client
+            .request(&QueryMetadata {
+                kind: Some(MetadataKind::Msg),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(resp.component_metadata.is_empty());
+    }
+
     #[test]
     async fn test_sql_query() {
         let (addr, _db) = setup_test_db().await.unwrap();
@@ -207,6 +381,51 @@ mod tests {
         assert_eq!(arr.values(), &[0.0, 10.0, 20.0, 30.0, 40.0]);
     }
 
+    #[test]
+    async fn test_sql_projection_order() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("projection_order");
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            schema(PrimType::F64, &[1], component(component_id)),
+        )]);
+        client
+            .send(&SetComponentMetadata::new(component_id, "projection_order"))
+            .await
+            .0
+            .unwrap();
+        client
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        let mut pkt = LenPacket::table(1u16.to_le_bytes(), 8);
+        pkt.extend_aligned(&[1.0f64]);
+        client.send(pkt).await.0.unwrap();
+        sleep(Duration::from_millis(100)).await;
+
+        let sql = "SELECT projection_order AS c, time AS a, time AS b FROM projection_order";
+        let mut stream = client.stream(&SQLQuery(sql.to_string())).await.unwrap();
+        let msg = stream.next().await.unwrap();
+        let batch = msg.batch.expect("missing batch");
+        let mut decoder = arrow::ipc::reader::StreamDecoder::new();
+        let mut buffer = arrow::buffer::Buffer::from(batch.into_owned());
+        let batch = decoder.decode(&mut buffer).unwrap().unwrap();
+        let names = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["c", "a", "b"]);
+    }
+
     #[test]
     async fn test_get_time_series() {
         let (addr, _db) = setup_test_db().await.unwrap();
@@ -311,7 +530,10 @@ mod tests {
 
         sleep(Duration::from_millis(50)).await;
 
-        let get_metadata = GetComponentMetadata { component_id };
+        let get_metadata = GetComponentMetadata {
+            component_id,
+            at: None,
+        };
         let component_metadata = client.request(&get_metadata).await.unwrap();
 
         assert_eq!(component_metadata.component_id, component_id);
@@ -319,6 +541,144 @@ mod tests {
         assert_eq!(component_metadata.metadata.get("unit").unwrap(), "celsius");
     }
 
+    #[test]
+    async fn test_fixed_rate_timestep_follows_streamed_components() {
+        let (addr, db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let fast = ComponentId::new("imu");
+        let slow = ComponentId::new("gps");
+        client
+            .send(&SetComponentMetadata::new(fast, "IMU").rate(1000.0))
+            .await
+            .0
+            .unwrap();
+        client
+            .send(&SetComponentMetadata::new(slow, "GPS").rate(10.0))
+            .await
+            .0
+            .unwrap();
+        sleep(Duration::from_millis(50)).await;
+
+        let all = db.with_state(|s| db.fixed_rate_timestep(s, 0, None));
+        assert_eq!(all, Duration::from_millis(1));
+        // a stream limited to the slow component doesn't tick at the fast one's rate
+        let filter = [slow].into_iter().collect();
+        let filtered = db.with_state(|s| db.fixed_rate_timestep(s, 0, Some(&filter)));
+        assert_eq!(filtered, Duration::from_millis(100));
+    }
+
+    #[test]
+    async fn test_msg_with_ack() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("arm");
+        client
+            .send_with_ack(&SetComponentMetadata::new(component_id, "Arm Command"))
+            .await
+            .unwrap();
+
+        // the client hands acks to its sink, so they're read off a raw connection instead
+        let (rx, tx) = stellarator::net::TcpStream::connect(addr)
+            .await
+            .unwrap()
+            .split();
+        let mut rx = PacketStream::new(rx);
+        let tx = PacketSink::new(tx);
+
+        // a second copy with the same sequence number is acked again, but not handled
+        for name in ["First Copy", "Second Copy"] {
+            let seq = 7;
+            let bytes =
+                postcard::to_allocvec(&SetComponentMetadata::new(component_id, name)).unwrap();
+            let mut pkt = LenPacket::msg_with_ack(SetComponentMetadata::ID, seq, bytes.len());
+            pkt.extend_from_slice(&bytes);
+            tx.send(pkt).await.0.unwrap();
+            let OwnedPacket::Msg(m) = rx.next(vec![0; 64]).await.unwrap() else {
+                panic!("expected an ack");
+            };
+            assert_eq!(m.parse::<Ack>().unwrap(), Ack { seq });
+        }
+
+        // a msg that fails to be handled is never acked, so the sender retransmits it
+        let mut pkt = LenPacket::msg_with_ack(SetComponentMetadata::ID, 8, 1);
+        pkt.extend_from_slice(&[0xff]);
+        tx.send(pkt).await.0.unwrap();
+        let OwnedPacket::Msg(m) = rx.next(vec![0; 256]).await.unwrap() else {
+            panic!("expected an error");
+        };
+        assert_eq!(m.id, ErrorResponse::ID);
+
+        let metadata = client
+            .request(&GetComponentMetadata {
+                component_id,
+                at: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(metadata.name, "First Copy");
+    }
+
+    #[test]
+    async fn test_versioned_component_metadata() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        // metadata set before versioning is enabled stays in effect until the first versioned change
+        let component_id = ComponentId::new("sensor");
+        client
+            .send(&SetComponentMetadata::new(component_id, "First Name"))
+            .await
+            .0
+            .unwrap();
+
+        let mut config = SetDbConfig::default();
+        config
+            .metadata
+            .insert("metadata.versioned".to_string(), "true".to_string());
+        client.send(&config).await.0.unwrap();
+
+        for (timestamp, name) in [(Timestamp(10), "Old Name"), (Timestamp(20), "New Name")] {
+            let msg = SetComponentMetadata::new(component_id, name);
+            let bytes = postcard::to_allocvec(&msg).unwrap();
+            let mut pkt =
+                LenPacket::msg_with_timestamp(SetComponentMetadata::ID, timestamp, bytes.len());
+            pkt.extend_from_slice(&bytes);
+            client.send(pkt).await.0.unwrap();
+        }
+        sleep(Duration::from_millis(50)).await;
+
+        for (at, name) in [
+            (Some(Timestamp(5)), "First Name"),
+            (Some(Timestamp(15)), "Old Name"),
+            (Some(Timestamp(25)), "New Name"),
+            (None, "New Name"),
+        ] {
+            let metadata = client
+                .request(&GetComponentMetadata { component_id, at })
+                .await
+                .unwrap();
+            assert_eq!(metadata.name, name);
+        }
+
+        // with versioning disabled again the history is ignored
+        let mut config = SetDbConfig::default();
+        config
+            .metadata
+            .insert("metadata.versioned".to_string(), "false".to_string());
+        client.send(&config).await.0.unwrap();
+        sleep(Duration::from_millis(50)).await;
+        let metadata = client
+            .request(&GetComponentMetadata {
+                component_id,
+                at: Some(Timestamp(15)),
+            })
+            .await
+            .unwrap();
+        assert_eq!(metadata.name, "New Name");
+    }
+
     #[test]
     async fn test_dump_schema() {
         let (addr, _db) = setup_test_db().await.unwrap();
@@ -374,6 +734,46 @@ mod tests {
         assert_eq!(schema2.shape(), &[2, 2]);
     }
 
+    #[test]
+    async fn test_dump_metadata_order() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+        for name in ["gyro", "accel", "mag", "baro"] {
+            let component_id = ComponentId::new(name);
+            client
+                .send(&SetComponentMetadata::new(component_id, name))
+                .await
+                .0
+                .unwrap();
+        }
+        for name in ["log", "cmd", "event"] {
+            let msg_metadata = SetMsgMetadata {
+                id: impeller2::types::msg_id(name),
+                metadata: MsgMetadata {
+                    name: name.to_string(),
+                    schema: u32::SCHEMA.into(),
+                    metadata: Default::default(),
+                },
+            };
+            client.send(&msg_metadata).await.0.unwrap();
+        }
+        let FlushAck = client.request(&Flush).await.unwrap();
+
+        let response = client.request(&DumpMetadata).await.unwrap();
+        let names: Vec<_> = response
+            .component_metadata
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["accel", "baro", "gyro", "mag"]);
+        let names: Vec<_> = response
+            .msg_metadata
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(names, ["cmd", "event", "log"]);
+    }
+
     #[test]
     async fn test_msg_metadata_and_get_msgs() {
         let (addr, _db) = setup_test_db().await.unwrap();
@@ -426,6 +826,26 @@ mod tests {
             let msg: Msg = postcard::from_bytes(data).unwrap();
             assert_eq!(msg.i, i as u32);
         }
+
+        let mut stream = client
+            .stream(&GetMsgsStream {
+                msg_id: Msg::ID,
+                range: Timestamp(0)..Timestamp(i64::MAX),
+                limit: Some(3),
+            })
+            .await
+            .unwrap();
+        for i in 0..3 {
+            let MsgEntry {
+                msg: Some((_, data)),
+            } = stream.next().await.unwrap()
+            else {
+                panic!("stream ended early");
+            };
+            let msg: Msg = postcard::from_bytes(&data).unwrap();
+            assert_eq!(msg.i, i);
+        }
+        assert!(stream.next().await.unwrap().msg.is_none());
     }
 
     #[test]
@@ -510,6 +930,47 @@ mod tests {
         sub.next().await.expect_err("sql query didnt return err");
     }
 
+    #[test]
+    async fn test_reused_query_id() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let query = |query: &str| SQLQueryStream {
+            id: 7,
+            query: query.to_string(),
+        };
+        let long = client
+            .send_req(&query("SELECT value FROM generate_series(1, 100000000)"))
+            .await
+            .unwrap();
+        let first: ArrowIPC<'static> = client.recv(long).await.unwrap();
+        assert!(first.batch.is_some());
+
+        // the running query keeps its id
+        let reused = client.send_req(&query("SELECT 1")).await.unwrap();
+        client
+            .recv::<ArrowIPC<'static>>(reused)
+            .await
+            .expect_err("reused a running query's id");
+
+        // and can still be cancelled by it
+        client.send(&CancelQuery { query_id: 7 }).await.0.unwrap();
+        let mut batches = 1;
+        loop {
+            let msg: ArrowIPC<'static> = client.recv(long).await.unwrap();
+            if msg.batch.is_none() {
+                break;
+            }
+            batches += 1;
+        }
+        assert!(batches < 100_000_000 / 8192);
+
+        // the id is free again once the query is done
+        let reused = client.send_req(&query("SELECT 1")).await.unwrap();
+        let msg: ArrowIPC<'static> = client.recv(reused).await.unwrap();
+        assert!(msg.batch.is_some());
+    }
+
     #[test]
     async fn test_get_time_series_not_found() {
         let (addr, _db) = setup_test_db().await.unwrap();
@@ -590,6 +1051,7 @@ mod tests {
 
         let get_metadata = GetComponentMetadata {
             component_id: non_existent_component_id,
+            at: None,
         };
 
         let result = client.request(&get_metadata).await.unwrap_err();
@@ -792,7 +1254,10 @@ mod tests {
         sleep(Duration::from_millis(100)).await;
 
         let component_metadata = new_client
-            .request(&GetComponentMetadata { component_id })
+            .request(&GetComponentMetadata {
+                component_id,
+                at: None,
+            })
             .await
             .unwrap();
         assert_eq!(component_metadata.name, "Restart Test Component");
@@ -1091,10 +1556,13 @@ mod tests {
         let Err(impeller2_stellar::Error::Response(err)) = client.recv::<()>(42).await else {
             panic!("invalid response");
         };
-        assert_eq!(
-            elodin_db::Error::SchemaMismatch.to_string(),
-            err.description
-        );
+        let expected = elodin_db::Error::from(impeller2::error::Error::SchemaMismatch {
+            component_id,
+            expected: impeller2::schema::schema_hash(PrimType::F64, &[1]),
+            found: impeller2::schema::schema_hash(PrimType::F32, &[1]),
+        });
+        assert_eq!(expected.to_string(), err.description);
+        assert!(err.description.contains(&component_id.to_string()));
     }
 
     #[test]
@@ -1138,6 +1606,1468 @@ mod tests {
         assert_eq!(elodin_db::Error::TimeTravel.to_string(), err.description);
     }
 
+    #[test]
+    async fn test_get_server_time() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let before = Timestamp::now();
+        let ServerTime(server_time) = client.request(&GetServerTime).await.unwrap();
+        let after = Timestamp::now();
+        assert!(before <= server_time && server_time <= after);
+    }
+
+    #[test]
+    async fn test_flush() {
+        let (addr, db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("flush_test");
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            schema(PrimType::F64, &[1], component(component_id)),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        let mut pkt = LenPacket::table(1u16.to_le_bytes(), 8);
+        pkt.extend_aligned(&[42.0f64]);
+        client.send(pkt).await.0.unwrap();
+
+        // no sleep needed, the ack is only sent once the table before it has been written and synced
+        let FlushAck = client.request(&Flush).await.unwrap();
+        db.with_state(|state| {
+            let component = state.get_component(component_id).unwrap();
+            let (_, buf) = component.time_series.latest().unwrap();
+            assert_eq!(buf, 42.0f64.to_le_bytes());
+        });
+    }
+
+    #[test]
+    async fn test_retention() {
+        let (addr, db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("retention_test");
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            timestamp(
+                raw_table(8, 8),
+                schema(PrimType::F64, &[1], component(component_id)),
+            ),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        for i in 1..=100 {
+            let mut pkt = LenPacket::table(1u16.to_le_bytes(), 16);
+            pkt.extend_aligned(&[i as f64]);
+            pkt.push_aligned(Timestamp(i));
+            client.send(pkt).await.0.unwrap();
+        }
+
+        let policy = RetentionPolicy {
+            max_samples: Some(10),
+            ..Default::default()
+        };
+        client
+            .send(&SetRetention {
+                component_id,
+                policy,
+            })
+            .await
+            .0
+            .unwrap();
+        // packets are handled in order, so the policy is set before the compaction runs
+        let report = client.request(&Compact).await.unwrap();
+        assert_eq!(
+            report,
+            CompactionReport {
+                samples_dropped: 90,
+                bytes_reclaimed: 90 * 16,
+            }
+        );
+        // the policy is saved alongside the component, so it survives a restart
+        assert!(
+            db.path
+                .join(component_id.to_string())
+                .join("retention")
+                .exists()
+        );
+
+        let time_series = client
+            .request(&GetTimeSeries {
+                id: 2u16.to_le_bytes(),
+                range: Timestamp(i64::MIN)..Timestamp(i64::MAX),
+                component_id,
+                limit: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            time_series.timestamps().unwrap(),
+            (91..=100).map(Timestamp).collect::<Vec<_>>()
+        );
+
+        let report = client.request(&Compact).await.unwrap();
+        assert_eq!(report, CompactionReport::default());
+
+        client
+            .send(
+                SetRetention {
+                    component_id: ComponentId::new("missing"),
+                    policy,
+                }
+                .with_request_id(42),
+            )
+            .await
+            .0
+            .unwrap();
+        let Err(impeller2_stellar::Error::Response(err)) = client.recv::<()>(42).await else {
+            panic!("invalid response");
+        };
+        assert_eq!(
+            Error::ComponentNotFound(ComponentId::new("missing")).to_string(),
+            err.description
+        );
+    }
+
+    #[test]
+    async fn test_get_time_series_rollup() {
+        let (addr, db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("rollup_test");
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            timestamp(
+                raw_table(8, 8),
+                schema(PrimType::F64, &[1], component(component_id)),
+            ),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        // 3 seconds of 10 Hz samples
+        for i in 0..30 {
+            let mut pkt = LenPacket::table(1u16.to_le_bytes(), 16);
+            pkt.extend_aligned(&[i as f64]);
+            pkt.push_aligned(Timestamp(i * 100_000));
+            client.send(pkt).await.0.unwrap();
+        }
+
+        let mut query = GetTimeSeries {
+            id: 2u16.to_le_bytes(),
+            range: Timestamp(i64::MIN)..Timestamp(i64::MAX),
+            component_id,
+            limit: Some(10),
+        };
+        // too many samples for the limit, so each second is replied with its mean
+        let time_series = client.request(&query).await.unwrap();
+        assert_eq!(
+            time_series.timestamps().unwrap(),
+            [0, 1_000_000, 2_000_000].map(Timestamp)
+        );
+        assert_eq!(time_series.data().unwrap(), [4.5f64, 14.5, 24.5].as_bytes());
+
+        // the same once the closed seconds are rolled up, which the server may have already done
+        db.update_rollups().unwrap();
+        let time_series = client.request(&query).await.unwrap();
+        assert_eq!(time_series.data().unwrap(), [4.5f64, 14.5, 24.5].as_bytes());
+        assert!(
+            db.path
+                .join(component_id.to_string())
+                .join("rollup_1s")
+                .exists()
+        );
+
+        // a range that fits is replied with the raw samples
+        query.range = Timestamp(0)..Timestamp(500_000);
+        let time_series = client.request(&query).await.unwrap();
+        assert_eq!(time_series.timestamps().unwrap().len(), 6);
+        query.range = Timestamp(i64::MIN)..Timestamp(i64::MAX);
+        query.limit = None;
+        let time_series = client.request(&query).await.unwrap();
+        assert_eq!(time_series.timestamps().unwrap().len(), 30);
+
+        // pages are never downsampled
+        let page = client
+            .request(&GetTimeSeriesPage {
+                id: 2u16.to_le_bytes(),
+                range: Timestamp(i64::MIN)..Timestamp(i64::MAX),
+                component_id,
+                limit: 10,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            page.data().unwrap(),
+            (0..10).map(|i| i as f64).collect::<Vec<_>>().as_bytes()
+        );
+    }
+
+    #[test]
+    async fn test_snapshot() {
+        let (addr, db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("snapshot_test");
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            timestamp(
+                raw_table(8, 8),
+                schema(PrimType::F64, &[1], component(component_id)),
+            ),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        client
+            .send(&SetComponentMetadata::new(component_id, "Snapshot Test"))
+            .await
+            .0
+            .unwrap();
+        let table = |i: i64| {
+            let mut pkt = LenPacket::table(1u16.to_le_bytes(), 16);
+            pkt.extend_aligned(&[i as f64]);
+            pkt.push_aligned(Timestamp(i));
+            pkt
+        };
+        for i in 1..=5 {
+            client.send(table(i)).await.0.unwrap();
+        }
+
+        let path = std::env::temp_dir().join(format!("elodin_db_snapshot_{}", fastrand::u64(..)));
+        let created = client
+            .request(&CreateSnapshot { path: path.clone() })
+            .await
+            .unwrap();
+        assert_eq!(created.path, path);
+        assert!(
+            path.join(component_id.to_string())
+                .join("metadata")
+                .exists()
+        );
+        // an existing path isn't overwritten
+        assert!(
+            client
+                .request(&CreateSnapshot { path: path.clone() })
+                .await
+                .is_err()
+        );
+        client
+            .send(
+                CreateSnapshot {
+                    path: db.path.join("snapshot"),
+                }
+                .with_request_id(42),
+            )
+            .await
+            .0
+            .unwrap();
+        let Err(impeller2_stellar::Error::Response(err)) = client.recv::<()>(42).await else {
+            panic!("invalid response");
+        };
+        assert_eq!(Error::SnapshotInsideDb.to_string(), err.description);
+
+        for i in 6..=10 {
+            client.send(table(i)).await.0.unwrap();
+        }
+        client
+            .request(&RestoreSnapshot { path: path.clone() })
+            .await
+            .unwrap();
+        // the vtable outlives the restore, so writes carry on from the snapshot
+        client.send(table(11)).await.0.unwrap();
+        let time_series = client
+            .request(&GetTimeSeries {
+                id: 2u16.to_le_bytes(),
+                range: Timestamp(i64::MIN)..Timestamp(i64::MAX),
+                component_id,
+                limit: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            time_series.timestamps().unwrap(),
+            [1, 2, 3, 4, 5, 11].map(Timestamp)
+        );
+        let metadata = client
+            .request(&GetComponentMetadata {
+                component_id,
+                at: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(metadata.name, "Snapshot Test");
+        // the snapshot itself is left as it was
+        assert!(DB::open(path.clone()).is_ok());
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    /// Polls the follower until it has `len` samples and msgs, returning the sample timestamps
+    async fn wait_for_follower(
+        client: &mut Client,
+        component_id: ComponentId,
+        msg_id: [u8; 2],
+        len: usize,
+    ) -> Vec<Timestamp> {
+        for _ in 0..200 {
+            let time_series = client
+                .request(&GetTimeSeries {
+                    id: 2u16.to_le_bytes(),
+                    range: Timestamp(i64::MIN)..Timestamp(i64::MAX),
+                    component_id,
+                    limit: None,
+                })
+                .await;
+            let msgs = client
+                .request(&GetMsgs {
+                    msg_id,
+                    range: Timestamp(i64::MIN)..Timestamp(i64::MAX),
+                    limit: None,
+                })
+                .await;
+            if let (Ok(time_series), Ok(msgs)) = (time_series, msgs) {
+                let timestamps = time_series.timestamps().unwrap().to_vec();
+                if timestamps.len() >= len && msgs.data.len() >= len {
+                    return timestamps;
+                }
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        panic!("follower never caught up");
+    }
+
+    #[test]
+    async fn test_follower() {
+        let (leader_addr, _leader_db) = setup_test_db().await.unwrap();
+        let (follower_addr, follower_db) = setup_test_db().await.unwrap();
+        let mut leader = Client::connect(leader_addr).await.unwrap();
+        let mut follower = Client::connect(follower_addr).await.unwrap();
+
+        let component_id = ComponentId::new("follower_test");
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            timestamp(
+                raw_table(8, 8),
+                schema(PrimType::F64, &[1], component(component_id)),
+            ),
+        )]);
+        leader
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        leader
+            .send(&SetComponentMetadata::new(component_id, "Follower Test"))
+            .await
+            .0
+            .unwrap();
+        let table = |i: i64| {
+            let mut pkt = LenPacket::table(1u16.to_le_bytes(), 16);
+            pkt.extend_aligned(&[i as f64]);
+            pkt.push_aligned(Timestamp(i));
+            pkt
+        };
+        let msg_id = 4321u16.to_le_bytes();
+        let msg = |i: i64| {
+            let mut pkt = LenPacket::msg_with_timestamp(msg_id, Timestamp(i), 8);
+            pkt.extend_from_slice(&i.to_le_bytes());
+            pkt
+        };
+        for i in 1..=5 {
+            leader.send(table(i)).await.0.unwrap();
+            leader.send(msg(i)).await.0.unwrap();
+        }
+
+        spawn(
+            Follower::new(leader_addr, follower_db)
+                .with_interval(Duration::from_millis(10))
+                .run(),
+        );
+        assert_eq!(
+            wait_for_follower(&mut follower, component_id, msg_id, 5).await,
+            [1, 2, 3, 4, 5].map(Timestamp)
+        );
+
+        // later writes are picked up from where the follower left off, including ones sharing its latest timestamp
+        for i in [5, 6, 7, 8] {
+            leader.send(table(i)).await.0.unwrap();
+            leader.send(msg(i)).await.0.unwrap();
+        }
+        assert_eq!(
+            wait_for_follower(&mut follower, component_id, msg_id, 9).await,
+            [1, 2, 3, 4, 5, 5, 6, 7, 8].map(Timestamp)
+        );
+
+        let metadata = follower
+            .request(&GetComponentMetadata {
+                component_id,
+                at: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(metadata.name, "Follower Test");
+        let MsgBatch { data } = follower
+            .request(&GetMsgs {
+                msg_id,
+                range: Timestamp(i64::MIN)..Timestamp(i64::MAX),
+                limit: None,
+            })
+            .await
+            .unwrap();
+        let expected = [1, 2, 3, 4, 5, 5, 6, 7, 8]
+            .map(|i: i64| (Timestamp(i), i.to_le_bytes().to_vec()))
+            .into_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    async fn test_string_component() {
+        let (addr, db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("mode");
+        let vtable = vtable([raw_field(
+            0,
+            16,
+            schema(PrimType::String, &[16], component(component_id)),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        let mut slot = [0u8; 16];
+        write_var_len(b"armed", &mut slot).unwrap();
+        let mut pkt = LenPacket::table(1u16.to_le_bytes(), 16);
+        pkt.extend_aligned(&slot);
+        client.send(pkt).await.0.unwrap();
+
+        let FlushAck = client.request(&Flush).await.unwrap();
+        db.with_state(|state| {
+            let component = state.get_component(component_id).unwrap();
+            assert_eq!(component.schema.prim_type, PrimType::String);
+            let (_, buf) = component.time_series.latest().unwrap();
+            let view = ComponentView::try_from_bytes_shape(buf, &[16], PrimType::String).unwrap();
+            assert_eq!(view.as_str(), Some("armed"));
+        });
+    }
+
+    #[test]
+    async fn test_migrate_component() {
+        let (addr, db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("speed");
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            schema(PrimType::F64, &[], component(component_id)),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        let mut pkt = LenPacket::table(1u16.to_le_bytes(), 8);
+        pkt.extend_aligned(&[1.0f64]);
+        client.send(pkt).await.0.unwrap();
+
+        let new_schema = impeller2::schema::Schema::<Vec<u64>>::new(PrimType::F64, [2u64]).unwrap();
+        let SchemaMsg(migrated) = client
+            .request(&MigrateComponent {
+                component_id,
+                schema: new_schema.clone(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(migrated.version(), 1);
+        assert_eq!(migrated.dim(), &[2]);
+
+        let vtable = vtable([raw_field(
+            0,
+            16,
+            schema(PrimType::F64, &[2], component(component_id)),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 2u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        let mut pkt = LenPacket::table(2u16.to_le_bytes(), 16);
+        pkt.extend_aligned(&[2.0f64, 3.0]);
+        client.send(pkt).await.0.unwrap();
+
+        let SchemaMsg(current) = client.request(&GetSchema { component_id }).await.unwrap();
+        assert_eq!(current, migrated);
+        let SchemaMsg(unchanged) = client
+            .request(&MigrateComponent {
+                component_id,
+                schema: new_schema,
+            })
+            .await
+            .unwrap();
+        assert_eq!(unchanged.version(), 1);
+
+        let FlushAck = client.request(&Flush).await.unwrap();
+        let check = |db: &DB| {
+            db.with_state(|state| {
+                let component = state.get_component(component_id).unwrap();
+                assert_eq!(component.version, 1);
+                let (_, buf) = component.time_series.latest().unwrap();
+                assert_eq!(<[f64]>::ref_from_bytes(buf).unwrap(), &[2.0, 3.0]);
+                let [epoch] = &component.epochs[..] else {
+                    panic!("expected one epoch");
+                };
+                assert_eq!(epoch.version, 0);
+                assert!(epoch.schema.dim.is_empty());
+                let (_, buf) = epoch.time_series.latest().unwrap();
+                assert_eq!(<[f64]>::ref_from_bytes(buf).unwrap(), &[1.0]);
+            });
+        };
+        check(&db);
+        let reopened = DB::open(db.path.clone()).unwrap();
+        check(&reopened);
+
+        let ctx = reopened.as_session_context().unwrap();
+        assert!(ctx.table_exist("speed").unwrap());
+        assert!(ctx.table_exist("speed_v0").unwrap());
+        assert!(ctx.table_exist("speed_v1").unwrap());
+    }
+
+    #[test]
+    async fn test_component_enum() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("flight_mode");
+        let vtable = vtable([raw_field(
+            0,
+            1,
+            schema(PrimType::U8, &[], component(component_id)),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        for mode in [0u8, 1, 7] {
+            let mut pkt = LenPacket::table(1u16.to_le_bytes(), 1);
+            pkt.extend_aligned(&[mode]);
+            client.send(pkt).await.0.unwrap();
+            sleep(Duration::from_millis(1)).await;
+        }
+        let values = [(0, "IDLE".to_string()), (1, "ARMED".to_string())]
+            .into_iter()
+            .collect::<std::collections::BTreeMap<_, _>>();
+        client
+            .send(&SetComponentEnum {
+                component_id,
+                values: values.clone(),
+            })
+            .await
+            .0
+            .unwrap();
+
+        let metadata = client
+            .request(&GetComponentMetadata {
+                component_id,
+                at: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(metadata.enum_values(), Some(values));
+
+        let FlushAck = client.request(&Flush).await.unwrap();
+        let sql = "SELECT flight_mode_name FROM flight_mode ORDER BY time";
+        let mut stream = client.stream(&SQLQuery(sql.to_string())).await.unwrap();
+        let mut names = vec![];
+        loop {
+            let msg = stream.next().await.unwrap();
+            let Some(batch) = msg.batch else {
+                break;
+            };
+            let mut decoder = arrow::ipc::reader::StreamDecoder::new();
+            let mut buffer = arrow::buffer::Buffer::from(batch.into_owned());
+            if let Some(batch) = decoder.decode(&mut buffer).unwrap() {
+                names.extend(
+                    batch
+                        .column(0)
+                        .as_string::<i32>()
+                        .iter()
+                        .map(|name| name.map(str::to_string)),
+                );
+            }
+        }
+        assert_eq!(
+            names,
+            vec![Some("IDLE".to_string()), Some("ARMED".to_string()), None]
+        );
+    }
+
+    #[test]
+    async fn test_component_bits() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("status");
+        let vtable = vtable([raw_field(
+            0,
+            2,
+            schema(PrimType::U16, &[], component(component_id)),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        for status in [0b0000u16, 0b0001, 0b1001] {
+            let mut pkt = LenPacket::table(1u16.to_le_bytes(), 2);
+            pkt.extend_aligned(&[status]);
+            client.send(pkt).await.0.unwrap();
+            sleep(Duration::from_millis(1)).await;
+        }
+        let bits = [(0, "ARMED".to_string()), (3, "GPS_LOCK".to_string())]
+            .into_iter()
+            .collect::<std::collections::BTreeMap<_, _>>();
+        client
+            .send(&SetComponentBits {
+                component_id,
+                bits: bits.clone(),
+            })
+            .await
+            .0
+            .unwrap();
+
+        let metadata = client
+            .request(&GetComponentMetadata {
+                component_id,
+                at: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(metadata.bit_names(), Some(bits));
+
+        let FlushAck = client.request(&Flush).await.unwrap();
+        let sql = "SELECT status_armed, status_gps_lock FROM status ORDER BY time";
+        let mut stream = client.stream(&SQLQuery(sql.to_string())).await.unwrap();
+        let mut flags = vec![];
+        loop {
+            let msg = stream.next().await.unwrap();
+            let Some(batch) = msg.batch else {
+                break;
+            };
+            let mut decoder = arrow::ipc::reader::StreamDecoder::new();
+            let mut buffer = arrow::buffer::Buffer::from(batch.into_owned());
+            if let Some(batch) = decoder.decode(&mut buffer).unwrap() {
+                let armed = batch.column(0).as_boolean();
+                let gps_lock = batch.column(1).as_boolean();
+                flags.extend((0..batch.num_rows()).map(|i| (armed.value(i), gps_lock.value(i))));
+            }
+        }
+        assert_eq!(flags, vec![(false, false), (true, false), (true, true)]);
+    }
+
+    #[test]
+    async fn test_fixed_point_widened_in_sql() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("motor_current");
+        let vtable = vtable([raw_field(
+            0,
+            4,
+            schema(PrimType::Q15_16, &[], component(component_id)),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 1u16.to_le_bytes(),
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        for amps in [1.5, -0.25, 300.0] {
+            let mut pkt = LenPacket::table(1u16.to_le_bytes(), 4);
+            pkt.extend_aligned(&[Q15_16::from_f64(amps)]);
+            client.send(pkt).await.0.unwrap();
+            sleep(Duration::from_millis(1)).await;
+        }
+
+        let FlushAck = client.request(&Flush).await.unwrap();
+        let sql = "SELECT motor_current FROM motor_current ORDER BY time";
+        let mut stream = client.stream(&SQLQuery(sql.to_string())).await.unwrap();
+        let mut values = vec![];
+        loop {
+            let msg = stream.next().await.unwrap();
+            let Some(batch) = msg.batch else {
+                break;
+            };
+            let mut decoder = arrow::ipc::reader::StreamDecoder::new();
+            let mut buffer = arrow::buffer::Buffer::from(batch.into_owned());
+            if let Some(batch) = decoder.decode(&mut buffer).unwrap() {
+                values.extend_from_slice(batch.column(0).as_primitive::<Float64Type>().values());
+            }
+        }
+        assert_eq!(values, vec![1.5, -0.25, 300.0]);
+    }
+
+    #[test]
+    async fn test_set_component_recording() {
+        let (addr, db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+
+        let component_id = ComponentId::new("noisy_debug");
+        let vtable_id = 1u16.to_le_bytes();
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            timestamp(
+                raw_table(8, 8),
+                schema(PrimType::F64, &[], component(component_id)),
+            ),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: vtable_id,
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        let value_table = |value: f64, timestamp: Timestamp| {
+            let mut pkt = LenPacket::table(vtable_id, 16);
+            pkt.extend_aligned(&[value]);
+            pkt.extend_aligned(&[timestamp.0]);
+            pkt
+        };
+
+        client
+            .send(value_table(1.0, Timestamp(1000)))
+            .await
+            .0
+            .unwrap();
+        client
+            .send(&SetComponentRecording {
+                component_id,
+                recording: false,
+            })
+            .await
+            .0
+            .unwrap();
+        client
+            .send(value_table(2.0, Timestamp(2000)))
+            .await
+            .0
+            .unwrap();
+        let FlushAck = client.request(&Flush).await.unwrap();
+
+        db.with_state(|state| {
+            let component = state.get_component(component_id).unwrap();
+            assert!(!component.is_recording());
+            let (timestamp, buf) = component.time_series.latest().unwrap();
+            assert_eq!(*timestamp, Timestamp(1000));
+            assert_eq!(buf, 1.0f64.to_le_bytes());
+            let (timestamp, buf) = component.latest().unwrap();
+            assert_eq!(timestamp, Timestamp(2000));
+            assert_eq!(&buf[..], 2.0f64.to_le_bytes());
+        });
+
+        let time_series = client
+            .request(&GetLatest {
+                id: 2u16.to_le_bytes(),
+                component_id,
+            })
+            .await
+            .unwrap();
+        assert_eq!(time_series.timestamps().unwrap(), &[Timestamp(2000)]);
+
+        client
+            .send(&SetComponentRecording {
+                component_id,
+                recording: true,
+            })
+            .await
+            .0
+            .unwrap();
+        client
+            .send(value_table(3.0, Timestamp(3000)))
+            .await
+            .0
+            .unwrap();
+        let FlushAck = client.request(&Flush).await.unwrap();
+        db.with_state(|state| {
+            let component = state.get_component(component_id).unwrap();
+            let (timestamps, _) = component
+                .time_series
+                .get_range(Timestamp(0)..Timestamp(10000))
+                .unwrap();
+            assert_eq!(timestamps, &[Timestamp(1000), Timestamp(3000)]);
+        });
+    }
+
+    #[test]
+    async fn test_mock_server_records_packets() {
+        let server = elodin_db::mock::MockServer::bind().unwrap();
+        let mut client = Client::connect(server.addr()).await.unwrap();
+
+        let layout = vtable([raw_field(
+            0,
+            8,
+            schema(PrimType::F64, &[], component("producer")),
+        )]);
+        client
+            .send(&VTableMsg {
+                id: 7u16.to_le_bytes(),
+                vtable: layout.clone(),
+            })
+            .await
+            .0
+            .unwrap();
+        let mut pkt = LenPacket::table(7u16.to_le_bytes(), 8);
+        pkt.extend_aligned(&[1.5f64]);
+        client.send(pkt).await.0.unwrap();
+
+        server.wait_for_packets(2).await;
+        let id = server.vtable_id(&layout).unwrap();
+        assert_eq!(id, 7u16.to_le_bytes());
+        assert_eq!(server.tables(id), [1.5f64.to_le_bytes().to_vec()]);
+    }
+
+    async fn next_stream_timestamp(client: &mut Client, req_id: u8) -> Timestamp {
+        loop {
+            match client.recv(req_id).await.unwrap() {
+                impeller2_stellar::ResilientReply::Timestamp(ts) => return ts.timestamp,
+                _ => continue,
+            }
+        }
+    }
+
+    #[test]
+    async fn test_cli_subscribe() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut tx_client = Client::connect(addr).await.unwrap();
+        let component_id = ComponentId::new("subscribe_test");
+        let vtable_id = 1u16.to_le_bytes();
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            schema(PrimType::F64, &[1], component(component_id)),
+        )]);
+        tx_client
+            .send(&VTableMsg {
+                id: vtable_id,
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        let FlushAck = tx_client.request(&Flush).await.unwrap();
+
+        let mut cli = impeller2_cli::Client::connect(addr).await.unwrap();
+        let stream = Stream {
+            behavior: StreamBehavior::RealTime,
+            id: 0,
+        };
+        let mut sub = cli.subscribe(stream).await.unwrap();
+        spawn(async move {
+            for i in 1..=3 {
+                sleep(Duration::from_millis(50)).await;
+                let mut pkt = LenPacket::table(vtable_id, 8);
+                pkt.extend_aligned(&[i as f64]);
+                tx_client.send(pkt).await.0.unwrap();
+            }
+        });
+
+        let mut seen = vec![];
+        while seen.last() != Some(&3.0) {
+            let value = sub.next().await.unwrap();
+            assert_eq!(value.component_id, component_id);
+            assert_eq!(value.schema.prim_type(), PrimType::F64);
+            assert_eq!(value.schema.shape(), &[1]);
+            seen.push(f64::read_from_bytes(&value.data).unwrap());
+        }
+        assert!(seen.ends_with(&[1.0, 2.0, 3.0]));
+    }
+
+    #[test]
+    async fn test_decimated_stream() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut tx_client = Client::connect(addr).await.unwrap();
+        let component_id = ComponentId::new("decimated");
+        let vtable_id = 1u16.to_le_bytes();
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            schema(PrimType::F64, &[1], component(component_id)),
+        )]);
+        tx_client
+            .send(&VTableMsg {
+                id: vtable_id,
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        let FlushAck = tx_client.request(&Flush).await.unwrap();
+
+        let mut cli = impeller2_cli::Client::connect(addr).await.unwrap();
+        let stream = Stream {
+            behavior: StreamBehavior::Decimated {
+                max_rate: 10,
+                method: DecimationMethod::Max,
+            },
+            id: 0,
+        };
+        let mut sub = cli.subscribe(stream).await.unwrap();
+        spawn(async move {
+            for i in 1..=50 {
+                sleep(Duration::from_millis(5)).await;
+                let mut pkt = LenPacket::table(vtable_id, 8);
+                pkt.extend_aligned(&[i as f64]);
+                tx_client.send(pkt).await.0.unwrap();
+            }
+        });
+
+        let mut seen = vec![];
+        while seen.last() != Some(&50.0) {
+            let value = sub.next().await.unwrap();
+            assert_eq!(value.component_id, component_id);
+            seen.push(f64::read_from_bytes(&value.data).unwrap());
+        }
+        // a 10 Hz subscriber to a 200 Hz component sees a handful of maxima rather than every sample
+        assert!(seen.len() < 25, "{seen:?}");
+        assert!(seen.is_sorted(), "{seen:?}");
+    }
+
+    #[test]
+    async fn test_cli_filtered_stream() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut tx_client = Client::connect(addr).await.unwrap();
+        let names = ["imu.accel", "imu.gyro", "gps.alt"];
+        let mut vtable_ids = vec![];
+        for (i, name) in names.into_iter().enumerate() {
+            let component_id = ComponentId::new(name);
+            tx_client
+                .send(&SetComponentMetadata::new(component_id, name))
+                .await
+                .0
+                .unwrap();
+            let vtable_id = (i as u16 + 1).to_le_bytes();
+            let vtable = vtable([raw_field(
+                0,
+                8,
+                schema(PrimType::F64, &[1], component(component_id)),
+            )]);
+            tx_client
+                .send(&VTableMsg {
+                    id: vtable_id,
+                    vtable,
+                })
+                .await
+                .0
+                .unwrap();
+            let mut pkt = LenPacket::table(vtable_id, 8);
+            pkt.extend_aligned(&[0.0f64]);
+            tx_client.send(pkt).await.0.unwrap();
+            vtable_ids.push(vtable_id);
+        }
+        let FlushAck = tx_client.request(&Flush).await.unwrap();
+
+        let mut cli = impeller2_cli::Client::connect(addr).await.unwrap();
+        let mut component_ids = cli.match_components("imu.*").await.unwrap();
+        component_ids.sort();
+        let mut expected = vec![ComponentId::new("imu.accel"), ComponentId::new("imu.gyro")];
+        expected.sort();
+        assert_eq!(component_ids, expected);
+        assert!(cli.match_components("baro.*").await.is_err());
+
+        let stream = impeller2_cli::StreamRequest {
+            stream: Stream {
+                behavior: StreamBehavior::RealTime,
+                id: 0,
+            },
+            component_ids: Some(component_ids.clone()),
+        };
+        let mut sub = cli.subscribe(stream).await.unwrap();
+        spawn(async move {
+            for i in 1..=3 {
+                sleep(Duration::from_millis(50)).await;
+                for vtable_id in &vtable_ids {
+                    let mut pkt = LenPacket::table(*vtable_id, 8);
+                    pkt.extend_aligned(&[i as f64]);
+                    tx_client.send(pkt).await.0.unwrap();
+                }
+            }
+        });
+
+        let mut seen = std::collections::HashSet::new();
+        while seen.len() < 2 * 3 {
+            let value = sub.next().await.unwrap();
+            assert!(component_ids.contains(&value.component_id));
+            let sample = f64::read_from_bytes(&value.data).unwrap();
+            if sample > 0.0 {
+                seen.insert((value.component_id, sample as u64));
+            }
+        }
+    }
+
+    #[test]
+    async fn test_cli_mirror() {
+        let (src_addr, _src_db) = setup_test_db().await.unwrap();
+        let (dest_addr, _dest_db) = setup_test_db().await.unwrap();
+        let mut tx_client = Client::connect(src_addr).await.unwrap();
+        let component_id = ComponentId::new("mirrored");
+        let vtable_id = 1u16.to_le_bytes();
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            schema(PrimType::F64, &[1], component(component_id)),
+        )]);
+        tx_client
+            .send(&VTableMsg {
+                id: vtable_id,
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        let mut pkt = LenPacket::table(vtable_id, 8);
+        pkt.extend_aligned(&[0.0f64]);
+        tx_client.send(pkt).await.0.unwrap();
+        let FlushAck = tx_client.request(&Flush).await.unwrap();
+
+        spawn(async move {
+            for i in 1..=3 {
+                sleep(Duration::from_millis(50)).await;
+                let mut pkt = LenPacket::table(vtable_id, 8);
+                pkt.extend_aligned(&[i as f64]);
+                tx_client.send(pkt).await.0.unwrap();
+            }
+        });
+        let mut src = impeller2_cli::Client::connect(src_addr).await.unwrap();
+        let mut dest = impeller2_cli::Client::connect(dest_addr).await.unwrap();
+        let forwarded = src
+            .mirror(&mut dest, None, Some(Duration::from_millis(500)))
+            .await
+            .unwrap();
+        // one vtable and three tables
+        assert_eq!(forwarded, 4);
+        dest.flush().await.unwrap();
+
+        let mut client = Client::connect(dest_addr).await.unwrap();
+        let time_series = client
+            .request(&GetTimeSeries {
+                id: vtable_id,
+                range: Timestamp(i64::MIN)..Timestamp(i64::MAX),
+                component_id,
+                limit: None,
+            })
+            .await
+            .unwrap();
+        let data = <[f64]>::ref_from_bytes(time_series.data().unwrap()).unwrap();
+        assert_eq!(data, &[1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    async fn test_cli_send_batch() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let flag = ComponentId::new("batch_flag");
+        let position = ComponentId::new("batch_position");
+        let mut cli = impeller2_cli::Client::connect(addr).await.unwrap();
+        for i in 0..2 {
+            // the u8 column leaves the f64 column misaligned unless the batch pads it
+            let columns = vec![
+                impeller2_cli::BatchColumn {
+                    component_id: flag,
+                    prim_type: PrimType::U8,
+                    shape: vec![],
+                    data: vec![i],
+                },
+                impeller2_cli::BatchColumn {
+                    component_id: position,
+                    prim_type: PrimType::F64,
+                    shape: vec![2],
+                    data: [i as f64, -(i as f64)]
+                        .iter()
+                        .flat_map(|v| v.to_le_bytes())
+                        .collect(),
+                },
+            ];
+            cli.send_batch(columns).await.unwrap();
+            sleep(Duration::from_millis(10)).await;
+        }
+        let short = impeller2_cli::BatchColumn {
+            component_id: position,
+            prim_type: PrimType::F64,
+            shape: vec![2],
+            data: vec![0; 8],
+        };
+        assert!(cli.send_batch(vec![short]).await.is_err());
+        cli.flush().await.unwrap();
+
+        let mut client = Client::connect(addr).await.unwrap();
+        let range = Timestamp(i64::MIN)..Timestamp(i64::MAX);
+        let time_series = client
+            .request(&GetTimeSeries {
+                id: 1u16.to_le_bytes(),
+                range: range.clone(),
+                component_id: flag,
+                limit: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(time_series.data().unwrap(), &[0, 1]);
+        let time_series = client
+            .request(&GetTimeSeries {
+                id: 2u16.to_le_bytes(),
+                range,
+                component_id: position,
+                limit: None,
+            })
+            .await
+            .unwrap();
+        let data = <[f64]>::ref_from_bytes(time_series.data().unwrap()).unwrap();
+        assert_eq!(data, &[0.0, -0.0, 1.0, -1.0]);
+    }
+
+    #[test]
+    async fn test_cli_set_with_units() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let component_id = ComponentId::new("heading");
+        let mut client = Client::connect(addr).await.unwrap();
+        // heading is stored as hundredths of a degree
+        let metadata = SetComponentMetadata::new(component_id, "heading").metadata(
+            [
+                ("unit".to_string(), "deg".to_string()),
+                ("scale".to_string(), "0.01".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        client.send(&metadata).await.0.unwrap();
+        let FlushAck = client.request(&Flush).await.unwrap();
+        let mut cli = impeller2_cli::Client::connect(addr).await.unwrap();
+        cli.send_batch(vec![impeller2_cli::BatchColumn {
+            component_id,
+            prim_type: PrimType::I32,
+            shape: vec![],
+            data: 0i32.to_le_bytes().to_vec(),
+        }])
+        .await
+        .unwrap();
+        cli.flush().await.unwrap();
+
+        sleep(Duration::from_millis(10)).await;
+        cli.set(component_id, &[(std::f64::consts::FRAC_PI_2, Some("rad"))])
+            .await
+            .unwrap();
+        assert!(cli.set(component_id, &[(1.0, Some("m"))]).await.is_err());
+        assert!(
+            cli.set(component_id, &[(1.0, None), (2.0, None)])
+                .await
+                .is_err()
+        );
+        cli.flush().await.unwrap();
+
+        let time_series = client
+            .request(&GetTimeSeries {
+                id: 1u16.to_le_bytes(),
+                range: Timestamp(i64::MIN)..Timestamp(i64::MAX),
+                component_id,
+                limit: None,
+            })
+            .await
+            .unwrap();
+        let data = <[i32]>::ref_from_bytes(time_series.data().unwrap()).unwrap();
+        assert_eq!(data, &[0, 9000]);
+    }
+
+    #[test]
+    async fn test_cli_relative_time_range() {
+        use impeller2_cli::time_range::TimeRange;
+
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let vtable_id = 1u16.to_le_bytes();
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            timestamp(
+                raw_table(8, 8),
+                schema(PrimType::F64, &[], component(ComponentId::new("ranged"))),
+            ),
+        )]);
+        let mut client = Client::connect(addr).await.unwrap();
+        client
+            .send(&VTableMsg {
+                id: vtable_id,
+                vtable,
+            })
+            .await
+            .0
+            .unwrap();
+        for timestamp in [1_000_000i64, 5_000_000] {
+            let mut pkt = LenPacket::table(vtable_id, 16);
+            pkt.extend_aligned(&[1.0f64]);
+            pkt.extend_aligned(&[timestamp]);
+            client.send(pkt).await.0.unwrap();
+        }
+        let FlushAck = client.request(&Flush).await.unwrap();
+
+        let mut cli = impeller2_cli::Client::connect(addr).await.unwrap();
+        assert_eq!(cli.last_updated().await.unwrap(), Timestamp(5_000_000));
+        let range = TimeRange::parse("last 1s").unwrap();
+        assert_eq!(
+            cli.resolve_time_range(&range).await.unwrap(),
+            Timestamp(4_000_000)..Timestamp(i64::MAX)
+        );
+        let earliest = cli.earliest_timestamp().await.unwrap();
+        let range = TimeRange::parse("first 2s").unwrap();
+        assert_eq!(
+            cli.resolve_time_range(&range).await.unwrap(),
+            Timestamp(i64::MIN)..Timestamp(earliest.0 + 2_000_000)
+        );
+        // the subscription used for last_updated must not leak replies onto the main connection
+        assert_eq!(cli.earliest_timestamp().await.unwrap(), earliest);
+    }
+
+    #[test]
+    async fn test_cli_asset_round_trip() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let dir = std::env::temp_dir().join(format!("assets_{}", fastrand::u64(..)));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut glb = b"glTF".to_vec();
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&20u32.to_le_bytes());
+        glb.extend_from_slice(&[7; 8]);
+        std::fs::write(dir.join("model.glb"), &glb).unwrap();
+
+        let mut cli = impeller2_cli::Client::connect(addr).await.unwrap();
+        let glb_id = cli
+            .put_asset_file(None, &dir.join("model.glb"))
+            .await
+            .unwrap();
+        assert_eq!(glb_id, impeller2::types::msg_id("model.glb"));
+        let material = impeller2_cli::AssetValue::Material(Material::color(1.0, 0.5, 0.0));
+        let material_id = impeller2::types::msg_id("paint");
+        cli.put_asset(material_id, "paint".to_string(), &material)
+            .await
+            .unwrap();
+        cli.flush().await.unwrap();
+
+        cli.get_asset_to_file(glb_id, &dir.join("out.glb"))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(dir.join("out.glb")).unwrap(), glb);
+        cli.get_asset_to_file(material_id, &dir.join("paint.json"))
+            .await
+            .unwrap();
+        let json: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.join("paint.json")).unwrap()).unwrap();
+        assert_eq!(json, serde_json::to_value(&material).unwrap());
+
+        // the written json uploads again as the same asset
+        let id = cli
+            .put_asset_file(None, &dir.join("paint.json"))
+            .await
+            .unwrap();
+        assert_eq!(id, impeller2::types::msg_id("paint.json"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    async fn test_cli_replay_capture() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let component_id = ComponentId::new("replayed");
+        let vtable_id = 1u16.to_le_bytes();
+        let vtable = vtable([raw_field(
+            0,
+            8,
+            timestamp(
+                raw_table(8, 8),
+                schema(PrimType::F64, &[], component(component_id)),
+            ),
+        )]);
+
+        let path = std::env::temp_dir().join(format!("capture_{}.imp", fastrand::u64(..)));
+        let mut writer = impeller2_cli::CaptureWriter::create(&path).unwrap();
+        let vtable_msg = VTableMsg {
+            id: vtable_id,
+            vtable,
+        };
+        writer
+            .write(Duration::ZERO, &(&vtable_msg).into_len_packet())
+            .unwrap();
+        for i in 1..=3i64 {
+            let mut pkt = LenPacket::table(vtable_id, 16);
+            pkt.extend_aligned(&[i as f64 * 10.0]);
+            pkt.extend_aligned(&[i * 1000]);
+            writer
+                .write(Duration::from_millis(i as u64 * 10), &pkt)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let sent = impeller2_cli::replay(&path, addr, 2.0).await.unwrap();
+        assert_eq!(sent, 4);
+        sleep(Duration::from_millis(100)).await;
+
+        let mut client = Client::connect(addr).await.unwrap();
+        let time_series = client
+            .request(&GetTimeSeries {
+                id: vtable_id,
+                range: Timestamp(0)..Timestamp(10000),
+                component_id,
+                limit: None,
+            })
+            .await
+            .unwrap();
+        let data = <[f64]>::ref_from_bytes(time_series.data().unwrap()).unwrap();
+        assert_eq!(data, &[10.0, 20.0, 30.0]);
+        assert_eq!(
+            time_series.timestamps().unwrap(),
+            &[Timestamp(1000), Timestamp(2000), Timestamp(3000)]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    async fn test_cli_exec_json() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut client = Client::connect(addr).await.unwrap();
+        client
+            .send(&SetComponentMetadata::new("exec_test", "exec_test"))
+            .await
+            .0
+            .unwrap();
+        let FlushAck = client.request(&Flush).await.unwrap();
+
+        let args = impeller2_cli::ExecArgs {
+            addr: addr.to_string(),
+            sql: false,
+            json: true,
+            code: "return client:dump_metadata()".to_string(),
+        };
+        let json = impeller2_cli::exec_json(&args).await.unwrap();
+        let names = json["component_metadata"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|m| m["name"].as_str())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"exec_test"));
+
+        let args = impeller2_cli::ExecArgs {
+            sql: true,
+            code: "select 1 as one".to_string(),
+            ..args
+        };
+        let json = impeller2_cli::exec_json(&args).await.unwrap();
+        assert_eq!(json[0]["one"], 1);
+
+        let args = impeller2_cli::ExecArgs {
+            sql: false,
+            code: "error('boom')".to_string(),
+            ..args
+        };
+        assert!(impeller2_cli::exec_json(&args).await.is_err());
+    }
+
+    #[test]
+    async fn test_set_stream_state_batch() {
+        let (addr, _db) = setup_test_db().await.unwrap();
+        let mut control = Client::connect(addr).await.unwrap();
+        let mut clients = vec![];
+        for id in [1, 2] {
+            let mut client = Client::connect(addr).await.unwrap();
+            let stream = Stream {
+                behavior: StreamBehavior::FixedRate(FixedRateBehavior {
+                    initial_timestamp: InitialTimestamp::Manual(Timestamp(100)),
+                    timestep: 1,
+                    frequency: 1,
+                }),
+                id,
+            };
+            client.send((&stream).with_request_id(1)).await.0.unwrap();
+            assert_eq!(next_stream_timestamp(&mut client, 1).await, Timestamp(100));
+            clients.push(client);
+        }
+
+        let scrub = |id| SetStreamState {
+            id,
+            playing: Some(false),
+            timestamp: Some(Timestamp(150)),
+            time_step: None,
+            frequency: None,
+        };
+
+        // an unknown stream id rejects the whole batch
+        control
+            .send((&SetStreamStateBatch(vec![scrub(1), scrub(99)])).with_request_id(7))
+            .await
+            .0
+            .unwrap();
+        let Err(impeller2_stellar::Error::Response(err)) = control.recv::<()>(7).await else {
+            panic!("invalid response");
+        };
+        assert_eq!(Error::StreamNotFound(99).to_string(), err.description);
+
+        control
+            .send(&SetStreamStateBatch(vec![scrub(1), scrub(2)]))
+            .await
+            .0
+            .unwrap();
+        for client in &mut clients {
+            assert_eq!(next_stream_timestamp(client, 1).await, Timestamp(150));
+        }
+    }
+
     #[test]
     async fn test_db_reopen() {
         let temp_dir =
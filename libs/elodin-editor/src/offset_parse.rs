@@ -29,7 +29,7 @@ peg::parser! {
             = "-" _ span:span()  {? span_to_duration(span).map(Offset::Latest).or(Err("invalid duration")) }
 
         rule fixed() -> Offset
-            = "=" _ epoch:epoch()  { Offset::Fixed(Timestamp::from(epoch)) }
+            = "=" _ epoch:epoch()  {? Timestamp::try_from(epoch).map(Offset::Fixed).or(Err("epoch out of range")) }
 
         pub rule offset() -> Offset
             = start() / end() / fixed()
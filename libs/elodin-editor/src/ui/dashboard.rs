@@ -568,6 +568,10 @@ impl ComponentValueExt for ComponentValue {
             ComponentValue::Bool(a) => a.buf.as_buf().first().map(|&v| if v { 1.0 } else { 0.0 }),
             ComponentValue::F32(array) => array.buf.as_buf().first().copied(),
             ComponentValue::F64(array) => array.buf.as_buf().first().map(|&v| v as f32),
+            ComponentValue::F16(array) => array.buf.as_buf().first().map(|v| v.to_f32()),
+            ComponentValue::BF16(array) => array.buf.as_buf().first().map(|v| v.to_f32()),
+            ComponentValue::Q15_16(array) => array.buf.as_buf().first().map(|v| v.to_f32()),
+            ComponentValue::Bytes(_) | ComponentValue::String(_) => None,
         }
     }
 }
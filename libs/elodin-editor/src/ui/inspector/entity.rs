@@ -7,7 +7,10 @@ use bevy::ecs::{
 use bevy::prelude::{Children, Resource};
 use bevy_egui::egui::{self, Align, Color32, Layout, RichText, emath};
 use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
-use impeller2::types::ComponentId;
+use impeller2::{
+    bits::Bits,
+    types::{ComponentId, Q15_16, bf16, f16},
+};
 use impeller2_bevy::{
     ComponentMetadataRegistry, ComponentPath, ComponentPathRegistry, ComponentValue,
     ComponentValueExt, ElementValueMut,
@@ -156,6 +159,10 @@ impl WidgetSystem for InspectorEntity<'_, '_> {
                 width,
             );
 
+            if let Some(bit_names) = metadata.bit_names() {
+                inspector_bits(ui, &bit_names, &component_value, width);
+            }
+
             if create_graph {
                 let values = default_component_values(component_id, &component_value);
                 let component_path = path_reg
@@ -208,6 +215,24 @@ fn inspector_item_value_ui(
                 ElementValueMut::F64(n) => comp_drag_value(ui, n),
                 ElementValueMut::F32(n) => comp_drag_value(ui, n),
                 ElementValueMut::Bool(b) => ui.checkbox(b, ""),
+                ElementValueMut::F16(n) => {
+                    let mut v = n.to_f32();
+                    let res = comp_drag_value(ui, &mut v);
+                    *n = f16::from_f32(v);
+                    res
+                }
+                ElementValueMut::BF16(n) => {
+                    let mut v = n.to_f32();
+                    let res = comp_drag_value(ui, &mut v);
+                    *n = bf16::from_f32(v);
+                    res
+                }
+                ElementValueMut::Q15_16(n) => {
+                    let mut v = n.to_f64();
+                    let res = comp_drag_value(ui, &mut v);
+                    *n = Q15_16::from_f64(v);
+                    res
+                }
             }
         },
     )
@@ -299,6 +324,32 @@ fn inspector_item_multi(
     }
 }
 
+/// Shows the named bits of a scalar flags component as read-only checkboxes
+fn inspector_bits(
+    ui: &mut egui::Ui,
+    bit_names: &BTreeMap<u8, String>,
+    value: &ComponentValue,
+    line_width: f32,
+) {
+    if value.shape().iter().product::<usize>() != 1 {
+        return;
+    }
+    let Some(bits) = value.get(0).and_then(Bits::from_element) else {
+        return;
+    };
+    ui.vertical(|ui| {
+        ui.set_width(line_width);
+        ui.add_space(8.0);
+        ui.horizontal_wrapped(|ui| {
+            ui.style_mut().spacing.item_spacing = egui::vec2(8.0, 8.0);
+            for (&bit, name) in bit_names {
+                let mut set = bits.is_set(bit as u32);
+                ui.add_enabled(false, egui::Checkbox::new(&mut set, name));
+            }
+        });
+    });
+}
+
 pub struct DimIndexFormat(SmallVec<[usize; 4]>);
 impl Display for DimIndexFormat {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -128,6 +128,15 @@ impl WidgetSystem for MonitorWidget<'_, '_> {
                                                 impeller2_bevy::ElementValueMut::Bool(v) => {
                                                     v.to_string()
                                                 }
+                                                impeller2_bevy::ElementValueMut::F16(v) => {
+                                                    format!("{:.8}", v.to_f32())
+                                                }
+                                                impeller2_bevy::ElementValueMut::BF16(v) => {
+                                                    format!("{:.8}", v.to_f32())
+                                                }
+                                                impeller2_bevy::ElementValueMut::Q15_16(v) => {
+                                                    format!("{:.8}", v.to_f64())
+                                                }
                                             };
                                             ui.add_space(8.0);
                                             let value = RichText::new(value).monospace().size(18.);
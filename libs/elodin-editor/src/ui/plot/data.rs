@@ -9,7 +9,9 @@ use bevy::{
 };
 use bevy_render::render_resource::{Buffer, BufferDescriptor, BufferSlice, BufferUsages};
 use bevy_render::renderer::{RenderDevice, RenderQueue};
-use impeller2::types::{ComponentId, ComponentView, OwnedPacket, PrimType, Timestamp};
+use impeller2::types::{
+    ComponentId, ComponentView, OwnedPacket, PrimType, Q15_16, Timestamp, bf16, f16,
+};
 use impeller2_bevy::{
     CommandsExt, ComponentSchemaRegistry, ComponentValueMap, CurrentStreamId, EntityMap,
     PacketGrantR, PacketHandlerInput, PacketHandlers,
@@ -334,6 +336,32 @@ pub fn handle_time_series(
                     &mut lines,
                     earliest_timestamp.0,
                 ),
+                PrimType::F16 => process_time_series::<f16>(
+                    buf,
+                    timestamps,
+                    len,
+                    plot_data,
+                    &mut lines,
+                    earliest_timestamp.0,
+                ),
+                PrimType::BF16 => process_time_series::<bf16>(
+                    buf,
+                    timestamps,
+                    len,
+                    plot_data,
+                    &mut lines,
+                    earliest_timestamp.0,
+                ),
+                PrimType::Q15_16 => process_time_series::<Q15_16>(
+                    buf,
+                    timestamps,
+                    len,
+                    plot_data,
+                    &mut lines,
+                    earliest_timestamp.0,
+                ),
+                // byte and string slots have nothing to plot
+                PrimType::Bytes | PrimType::String => return,
             }
             let Some(last_timestamp) = timestamps.last() else {
                 return;
@@ -634,6 +662,24 @@ impl AsF32 for bool {
     }
 }
 
+impl AsF32 for f16 {
+    fn as_f32(&self) -> f32 {
+        self.to_f32()
+    }
+}
+
+impl AsF32 for bf16 {
+    fn as_f32(&self) -> f32 {
+        self.to_f32()
+    }
+}
+
+impl AsF32 for Q15_16 {
+    fn as_f32(&self) -> f32 {
+        self.to_f32()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SharedBuffer<T, const N: usize> {
     cpu: Vec<T>,
@@ -0,0 +1,63 @@
+//! Compares decoding a column of samples with a [`TypedDecoder`] against matching the prim type for every sample
+//!
+//! Run with `cargo bench -p impeller2 --bench typed_decoder`.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use impeller2::{
+    schema::{Schema, TypedDecoder},
+    types::{ComponentView, PrimType},
+};
+use zerocopy::IntoBytes;
+
+const SAMPLES: usize = 100_000;
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    for shape in [[1u64], [3], [16]] {
+        let schema = Schema::<Vec<u64>>::new(PrimType::F64, shape).unwrap();
+        let data = (0..SAMPLES * shape[0] as usize)
+            .map(|i| i as f64)
+            .collect::<Vec<_>>();
+        let bytes = data.as_bytes();
+        let size = schema.size();
+
+        group.bench_with_input(
+            BenchmarkId::new("per_sample_match", shape[0]),
+            bytes,
+            |b, bytes| {
+                b.iter(|| {
+                    for chunk in bytes.chunks(size) {
+                        black_box(
+                            ComponentView::try_from_bytes_shape(
+                                chunk,
+                                schema.shape(),
+                                schema.prim_type(),
+                            )
+                            .unwrap(),
+                        );
+                    }
+                })
+            },
+        );
+
+        let decoder = TypedDecoder::new(schema.clone()).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("typed_decoder", shape[0]),
+            bytes,
+            |b, bytes| {
+                b.iter(|| {
+                    for chunk in bytes.chunks(size) {
+                        black_box(decoder.decode(chunk).unwrap());
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -751,6 +751,23 @@ impl ComponentValueExt for ComponentValue {
                     .indexed_iter_mut()
                     .map(|(i, x)| (i, ElementValueMut::F64(x))),
             ),
+            ComponentValue::F16(array) => Box::new(
+                array
+                    .indexed_iter_mut()
+                    .map(|(i, x)| (i, ElementValueMut::F16(x))),
+            ),
+            ComponentValue::BF16(array) => Box::new(
+                array
+                    .indexed_iter_mut()
+                    .map(|(i, x)| (i, ElementValueMut::BF16(x))),
+            ),
+            ComponentValue::Q15_16(array) => Box::new(
+                array
+                    .indexed_iter_mut()
+                    .map(|(i, x)| (i, ElementValueMut::Q15_16(x))),
+            ),
+            // slots are replaced whole, editing single bytes could corrupt the length prefix
+            ComponentValue::Bytes(_) | ComponentValue::String(_) => Box::new(std::iter::empty()),
         }
     }
 }
@@ -0,0 +1,124 @@
+//! Flag words, like status registers, decoded bit by bit
+//!
+//! Any scalar `u8`, `u16` or `u32` component can be read as [`Bits`]. The names of its bits live in the component's
+//! metadata, so they don't change how the component is stored or sent.
+
+use crate::{
+    com_de::FromComponentView,
+    error::Error,
+    types::{ComponentView, ElementValue},
+};
+
+/// The value of a scalar unsigned integer component, viewed as a set of flags
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bits {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+}
+
+impl Bits {
+    /// Reads an element as bits, or `None` if it isn't a `u8`, `u16` or `u32`
+    pub fn from_element(value: ElementValue) -> Option<Self> {
+        match value {
+            ElementValue::U8(x) => Some(Bits::U8(x)),
+            ElementValue::U16(x) => Some(Bits::U16(x)),
+            ElementValue::U32(x) => Some(Bits::U32(x)),
+            _ => None,
+        }
+    }
+
+    /// How many bits the value has
+    pub const fn width(&self) -> u32 {
+        match self {
+            Bits::U8(_) => u8::BITS,
+            Bits::U16(_) => u16::BITS,
+            Bits::U32(_) => u32::BITS,
+        }
+    }
+
+    /// The value widened to a `u32`
+    pub const fn value(&self) -> u32 {
+        match *self {
+            Bits::U8(x) => x as u32,
+            Bits::U16(x) => x as u32,
+            Bits::U32(x) => x,
+        }
+    }
+
+    /// Whether bit `bit` is set, counting from the least significant bit
+    ///
+    /// Bits past [`Self::width`] are never set.
+    pub const fn is_set(&self, bit: u32) -> bool {
+        bit < self.width() && self.value() & (1 << bit) != 0
+    }
+
+    /// The indices of the set bits, least significant first
+    pub fn iter_set(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.width()).filter(|&bit| self.is_set(bit))
+    }
+}
+
+impl FromComponentView for Bits {
+    fn from_component_view(view: ComponentView<'_>) -> Result<Self, Error> {
+        if view.shape().iter().product::<usize>() != 1 {
+            return Err(Error::ShapeMismatch);
+        }
+        let value = view.get(0).ok_or(Error::BufferUnderflow)?;
+        Bits::from_element(value).ok_or(Error::InvalidComponentData)
+    }
+}
+
+/// Implements [`FromComponentView`] for a bitflags-style type, one with a `from_bits_retain` constructor over `$bits`
+///
+/// Bits without a flag are kept, so a newer producer setting a bit an older consumer doesn't know about isn't lost.
+///
+/// ```ignore
+/// impeller2::impl_bits_component!(StatusFlags, u16);
+/// ```
+#[macro_export]
+macro_rules! impl_bits_component {
+    ($ty:ty, $bits:ty) => {
+        impl $crate::com_de::FromComponentView for $ty {
+            fn from_component_view(
+                view: $crate::types::ComponentView<'_>,
+            ) -> Result<Self, $crate::error::Error> {
+                let bits = <$bits as $crate::com_de::FromComponentView>::from_component_view(view)?;
+                Ok(<$ty>::from_bits_retain(bits))
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::com_de::AsComponentView;
+
+    #[test]
+    fn test_bits() {
+        let bits = Bits::from_component_view(0b1010_0001u16.as_component_view()).unwrap();
+        assert_eq!(bits, Bits::U16(0b1010_0001));
+        assert_eq!(bits.width(), 16);
+        assert!(bits.is_set(0) && bits.is_set(5) && bits.is_set(7));
+        assert!(!bits.is_set(1) && !bits.is_set(40));
+        assert_eq!(bits.iter_set().collect::<Vec<_>>(), [0, 5, 7]);
+        assert!(Bits::from_component_view(1.0f32.as_component_view()).is_err());
+    }
+
+    #[test]
+    fn test_bitflags_style() {
+        #[derive(Debug, PartialEq)]
+        struct Status(u8);
+        impl Status {
+            fn from_bits_retain(bits: u8) -> Self {
+                Status(bits)
+            }
+        }
+        crate::impl_bits_component!(Status, u8);
+
+        let status = Status::from_component_view(0x81u8.as_component_view()).unwrap();
+        assert_eq!(status, Status(0x81));
+        assert!(Status::from_component_view(0x81u16.as_component_view()).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! An optional CRC-32 trailer for packets sent over links that can corrupt data, like UDP or a serial port
+//!
+//! A packet with a trailer has [`CHECKSUM_FLAG`] set in its packet type byte, and ends with the little endian CRC-32
+//! (IEEE) of everything before it, header included. The length prefix counts the trailer.
+
+use crate::error::Error;
+
+/// Set in the packet type byte when the packet ends with a checksum
+pub const CHECKSUM_FLAG: u8 = 1 << 5;
+
+pub const CHECKSUM_LEN: usize = size_of::<u32>();
+
+/// Computes the CRC-32 (IEEE) of a buffer
+///
+/// Targets with a CRC unit can implement this over it, everyone else can use [`SoftwareCrc32`].
+pub trait Crc32 {
+    fn crc32(&self, buf: &[u8]) -> u32;
+}
+
+/// A table driven [`Crc32`]
+#[cfg(feature = "crc")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SoftwareCrc32;
+
+#[cfg(feature = "crc")]
+impl Crc32 for SoftwareCrc32 {
+    fn crc32(&self, buf: &[u8]) -> u32 {
+        const CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+        CRC.checksum(buf)
+    }
+}
+
+/// Whether `packet`, without its length prefix, ends with a checksum
+pub fn has_checksum(packet: &[u8]) -> bool {
+    packet.first().is_some_and(|ty| ty & CHECKSUM_FLAG != 0)
+}
+
+/// Checks the trailer of `packet`, without its length prefix, returning the packet's length without the trailer
+pub fn verify(packet: &[u8], crc: &impl Crc32) -> Result<usize, Error> {
+    let (body, trailer) = packet
+        .split_last_chunk::<CHECKSUM_LEN>()
+        .ok_or(Error::BufferUnderflow)?;
+    if crc.crc32(body) != u32::from_le_bytes(*trailer) {
+        return Err(Error::ChecksumMismatch);
+    }
+    Ok(body.len())
+}
+
+#[cfg(all(test, feature = "crc"))]
+mod tests {
+    use super::*;
+    use crate::types::{LenPacket, OwnedPacket};
+
+    #[test]
+    fn test_check_value() {
+        assert_eq!(SoftwareCrc32.crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut pkt = LenPacket::table([1, 2], 8);
+        pkt.extend_aligned(&[1.0f64, 2.0]);
+        let original = pkt.inner.clone();
+
+        pkt.append_checksum(&SoftwareCrc32);
+        assert_eq!(pkt.inner.len(), original.len() + CHECKSUM_LEN);
+        assert!(has_checksum(&pkt.inner[4..]));
+        let len = verify(&pkt.inner[4..], &SoftwareCrc32).unwrap();
+        assert_eq!(len + 4, original.len());
+
+        pkt.remove_checksum();
+        assert_eq!(pkt.inner, original);
+        assert!(OwnedPacket::parse(pkt.inner).is_ok());
+    }
+
+    #[test]
+    fn test_corruption() {
+        let mut pkt = LenPacket::table([1, 2], 8);
+        pkt.extend_aligned(&[1.0f64, 2.0]);
+        pkt.append_checksum(&SoftwareCrc32);
+        pkt.inner[10] ^= 0x01;
+        assert!(matches!(
+            verify(&pkt.inner[4..], &SoftwareCrc32),
+            Err(Error::ChecksumMismatch)
+        ));
+    }
+}
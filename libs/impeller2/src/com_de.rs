@@ -6,9 +6,10 @@
 
 use crate::{
     error::Error,
-    types::{ComponentId, ComponentView, Timestamp},
+    types::{ComponentId, ComponentView, Q15_16, Timestamp},
 };
 use core::{convert::Infallible, slice};
+use half::{bf16, f16};
 use nox_array::ArrayView;
 
 pub trait Componentize {
@@ -158,6 +159,11 @@ pub trait AsComponentView {
     fn as_component_view(&self) -> ComponentView<'_>;
 }
 
+/// A primitive that can be the element type of a [`ComponentView`]
+pub trait ComponentViewElem: Sized {
+    fn component_view<'a>(view: ArrayView<'a, Self>) -> ComponentView<'a>;
+}
+
 macro_rules! impl_component_view {
     ($ty:tt, $prim:tt) => {
         impl FromComponentView for $ty {
@@ -179,6 +185,12 @@ macro_rules! impl_component_view {
                 ))
             }
         }
+
+        impl ComponentViewElem for $ty {
+            fn component_view<'a>(view: ArrayView<'a, Self>) -> ComponentView<'a> {
+                ComponentView::$prim(view)
+            }
+        }
     };
 }
 
@@ -193,3 +205,6 @@ impl_component_view!(i8, I8);
 impl_component_view!(f64, F64);
 impl_component_view!(f32, F32);
 impl_component_view!(bool, Bool);
+impl_component_view!(f16, F16);
+impl_component_view!(bf16, BF16);
+impl_component_view!(Q15_16, Q15_16);
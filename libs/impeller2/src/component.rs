@@ -18,8 +18,9 @@ pub trait Component {
     fn schema() -> Schema<Vec<u64>>;
 }
 
+/// A Rust type stored as a [`PrimType`](crate::types::PrimType)
 #[cfg(feature = "alloc")]
-pub(crate) trait PrimTypeElem {
+pub trait PrimTypeElem {
     const PRIM_TYPE: crate::types::PrimType;
 }
 
@@ -42,6 +43,10 @@ impl_prim_type_element!(u8, U8);
 impl_prim_type_element!(u16, U16);
 impl_prim_type_element!(u32, U32);
 impl_prim_type_element!(u64, U64);
+impl_prim_type_element!(half::f16, F16);
+impl_prim_type_element!(half::bf16, BF16);
+impl_prim_type_element!(crate::fixed::Q15_16, Q15_16);
+impl_prim_type_element!(bool, Bool);
 
 pub trait Asset: DeserializeOwned + Serialize {
     const NAME: &'static str;
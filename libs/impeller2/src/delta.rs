@@ -0,0 +1,231 @@
+//! Delta tables, which only carry the fields that changed since the last table sent with the same vtable
+//!
+//! Boolean and status components rarely change, so sending them in every table wastes most of its bytes. The body of
+//! a [`PacketTy::DeltaTable`](crate::types::PacketTy::DeltaTable) packet is laid out as:
+//! - a bitmask with one bit per vtable field, set when the field is present, zero padded to a multiple of 8 bytes
+//! - every timestamp the vtable reads from the table, in op order, since those change with every table
+//! - each present field in field order, padded to its prim type's alignment
+//!
+//! Encoding needs the last table sent, which [`DeltaEncoder`] keeps. Decoding is stateless, [`VTable::apply_delta`]
+//! sinks just the fields that are present.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use zerocopy::IntoBytes;
+
+use crate::{
+    buf::Buf,
+    com_de::Decomponentize,
+    error::Error,
+    types::LenPacket,
+    vtable::{Field, Op, VTable},
+};
+
+/// Encodes tables for one vtable as deltas against the table encoded before them
+#[derive(Default)]
+pub struct DeltaEncoder {
+    prev: Option<Vec<u8>>,
+}
+
+impl DeltaEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the last table, so the next one is sent in full, e.g. after reconnecting
+    pub fn reset(&mut self) {
+        self.prev = None;
+    }
+
+    /// Appends the delta between `table` and the last table encoded to `pkt`, which should be a
+    /// [`LenPacket::delta_table`]
+    pub fn encode<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>>(
+        &mut self,
+        vtable: &VTable<Ops, Data, Fields>,
+        table: &[u8],
+        pkt: &mut LenPacket,
+    ) -> Result<(), Error> {
+        let columns = vtable.columns()?;
+        let prev = self
+            .prev
+            .as_deref()
+            .filter(|prev| prev.len() == table.len());
+        let mut body = vec![0u8; mask_len(columns.len())];
+        for range in vtable.table_timestamp_ranges()? {
+            body.extend_from_slice(table.get(range).ok_or(Error::BufferUnderflow)?);
+        }
+        for (i, column) in columns.iter().enumerate() {
+            let range = column.offset..column.offset + column.len;
+            let value = table.get(range.clone()).ok_or(Error::BufferUnderflow)?;
+            if prev.is_some_and(|prev| prev[range] == *value) {
+                continue;
+            }
+            body[i / 8] |= 1 << (i % 8);
+            body.resize(body.len() + column.ty.padding(body.len()), 0);
+            body.extend_from_slice(value);
+        }
+        pkt.extend_from_slice(&body);
+
+        let prev = self.prev.get_or_insert_with(Vec::new);
+        prev.clear();
+        prev.extend_from_slice(table);
+        Ok(())
+    }
+}
+
+impl<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> VTable<Ops, Data, Fields> {
+    /// Parses a delta table, applying only the fields it carries to the sink
+    pub fn apply_delta<D: Decomponentize>(
+        &self,
+        delta: &[u8],
+        sink: &mut D,
+    ) -> Result<Result<(), D::Error>, Error> {
+        let columns = self.columns()?;
+        let timestamps = self.table_timestamp_ranges()?;
+        let table_len = self.table_len()?;
+        // rebuilt in a u64 buffer so every field is as aligned as it is in a regular table
+        let mut words = vec![0u64; table_len.div_ceil(8)];
+        let table = &mut words.as_mut_bytes()[..table_len];
+
+        let mask = delta
+            .get(..columns.len().div_ceil(8))
+            .ok_or(Error::BufferUnderflow)?;
+        let present = |i: usize| mask[i / 8] & (1 << (i % 8)) != 0;
+        let mut offset = mask_len(columns.len());
+        for range in timestamps {
+            let value = delta
+                .get(offset..offset + range.len())
+                .ok_or(Error::BufferUnderflow)?;
+            offset += range.len();
+            table[range].copy_from_slice(value);
+        }
+        for (i, column) in columns.iter().enumerate() {
+            if !present(i) {
+                continue;
+            }
+            offset += column.ty.padding(offset);
+            let value = delta
+                .get(offset..offset + column.len)
+                .ok_or(Error::BufferUnderflow)?;
+            offset += column.len;
+            table[column.offset..column.offset + column.len].copy_from_slice(value);
+        }
+
+        for (i, field) in self.fields.iter().enumerate() {
+            if !present(i) {
+                continue;
+            }
+            if let Err(err) = self.apply_field(field, table, sink)? {
+                return Ok(Err(err));
+            }
+        }
+        Ok(Ok(()))
+    }
+}
+
+/// The length of the field bitmask, padded so what follows it is 8 byte aligned
+fn mask_len(fields: usize) -> usize {
+    fields.div_ceil(8).next_multiple_of(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ComponentId, ComponentView, OwnedPacket, PrimType, Timestamp};
+    use crate::vtable::builder::{component, raw_field, raw_table, schema, timestamp, vtable};
+
+    fn table(time: i64, speed: f64, armed: bool) -> Vec<u8> {
+        let mut table = time.to_le_bytes().to_vec();
+        table.extend_from_slice(&speed.to_le_bytes());
+        table.push(armed as u8);
+        table
+    }
+
+    fn decode(
+        vtable: &VTable<Vec<Op>, Vec<u8>, Vec<Field>>,
+        delta: &[u8],
+    ) -> Vec<(ComponentId, Vec<u8>, Option<Timestamp>)> {
+        let mut values = vec![];
+        let mut sink = |id: ComponentId, view: ComponentView<'_>, time: Option<Timestamp>| {
+            values.push((id, view.as_bytes().to_vec(), time));
+        };
+        vtable.apply_delta(delta, &mut sink).unwrap().unwrap();
+        values
+    }
+
+    #[test]
+    fn test_only_changed_fields_are_sent() {
+        let time = raw_table(0, 8);
+        let vtable = vtable([
+            raw_field(
+                8,
+                8,
+                schema(
+                    PrimType::F64,
+                    &[1],
+                    timestamp(time.clone(), component("speed")),
+                ),
+            ),
+            raw_field(
+                16,
+                1,
+                schema(PrimType::Bool, &[1], timestamp(time, component("armed"))),
+            ),
+        ]);
+        let mut encoder = DeltaEncoder::new();
+
+        let mut pkt = LenPacket::delta_table([1, 0], 32);
+        encoder
+            .encode(&vtable, &table(1, 2.0, true), &mut pkt)
+            .unwrap();
+        let OwnedPacket::Table(first) = OwnedPacket::parse_with_offset(pkt.inner, 4).unwrap()
+        else {
+            panic!("expected a table");
+        };
+        assert!(first.delta);
+        assert_eq!(
+            decode(&vtable, &first.buf[..]),
+            vec![
+                (
+                    ComponentId::new("speed"),
+                    2.0f64.to_le_bytes().to_vec(),
+                    Some(Timestamp(1))
+                ),
+                (ComponentId::new("armed"), vec![1], Some(Timestamp(1))),
+            ]
+        );
+
+        let mut pkt = LenPacket::delta_table([1, 0], 32);
+        encoder
+            .encode(&vtable, &table(2, 3.0, true), &mut pkt)
+            .unwrap();
+        // mask, timestamp and speed, without the unchanged flag
+        assert_eq!(pkt.inner.len(), 8 + 8 + 8 + 8);
+        assert_eq!(
+            decode(&vtable, &pkt.inner[8..]),
+            vec![(
+                ComponentId::new("speed"),
+                3.0f64.to_le_bytes().to_vec(),
+                Some(Timestamp(2))
+            )]
+        );
+
+        encoder.reset();
+        let mut pkt = LenPacket::delta_table([1, 0], 32);
+        encoder
+            .encode(&vtable, &table(3, 3.0, true), &mut pkt)
+            .unwrap();
+        assert_eq!(decode(&vtable, &pkt.inner[8..]).len(), 2);
+    }
+
+    #[test]
+    fn test_truncated_delta() {
+        let vtable = vtable([raw_field(0, 8, schema(PrimType::F64, &[1], component("x")))]);
+        let mut pkt = LenPacket::delta_table([1, 0], 16);
+        DeltaEncoder::new()
+            .encode(&vtable, &1.0f64.to_le_bytes(), &mut pkt)
+            .unwrap();
+        let delta = &pkt.inner[8..pkt.inner.len() - 1];
+        assert!(vtable.apply_delta(delta, &mut ()).is_err());
+    }
+}
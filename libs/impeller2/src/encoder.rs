@@ -0,0 +1,161 @@
+//! A transport-agnostic packet encoder that works without `std` or `alloc`
+//!
+//! [`PacketEncoder`] writes length-prefixed packets (the same layout as [`crate::types::LenPacket`]) into any
+//! [`Buf<u8>`], so firmware can hand the encoded bytes to a UART, USB endpoint, or any other byte sink.
+
+use serde::Serialize;
+
+use crate::{
+    buf::Buf,
+    error::Error,
+    types::{Msg, PACKET_HEADER_LEN, PacketId, PacketTy, RequestId},
+    vtable::{Field, Op, VTable},
+};
+
+/// The packet id of `impeller2_wkt::VTableMsg`
+pub const VTABLE_MSG_ID: PacketId =
+    const_fnv1a_hash::fnv1a_hash_str_16_xor("VTableMsg").to_le_bytes();
+
+const LEN_PREFIX: usize = size_of::<u32>();
+
+/// Encodes msgs, vtables, and tables into a reusable buffer
+///
+/// Each `encode_*` call clears the buffer and returns the full encoded packet, including the `u32` length prefix.
+pub struct PacketEncoder<B: Buf<u8> = heapless::Vec<u8, 1024>> {
+    buf: B,
+    req_id: RequestId,
+}
+
+impl<B: Buf<u8>> Default for PacketEncoder<B> {
+    fn default() -> Self {
+        Self::new(B::default())
+    }
+}
+
+impl<B: Buf<u8>> PacketEncoder<B> {
+    pub fn new(buf: B) -> Self {
+        Self { buf, req_id: 0 }
+    }
+
+    /// Sets the request id written into subsequent packets
+    pub fn set_request_id(&mut self, req_id: RequestId) {
+        self.req_id = req_id;
+    }
+
+    /// The most recently encoded packet
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buf.as_slice()
+    }
+
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+
+    /// Encodes `msg` as a postcard msg packet
+    pub fn encode_msg<M: Msg>(&mut self, msg: &M) -> Result<&[u8], Error> {
+        self.encode_serialized(M::ID, msg)
+    }
+
+    /// Encodes a `VTableMsg` registering `vtable` under `id`
+    pub fn encode_vtable<O: Buf<Op>, D: Buf<u8>, F: Buf<Field>>(
+        &mut self,
+        id: PacketId,
+        vtable: &VTable<O, D, F>,
+    ) -> Result<&[u8], Error> {
+        // postcard encodes a struct as its fields in order, so this matches `VTableMsg { id, vtable }`
+        self.encode_serialized(VTABLE_MSG_ID, &(id, vtable))
+    }
+
+    /// Encodes a table packet for the vtable registered under `id`
+    pub fn encode_table(&mut self, id: PacketId, table: &[u8]) -> Result<&[u8], Error> {
+        self.start(PacketTy::Table, id)?;
+        self.buf.extend_from_slice(table)?;
+        self.finish()
+    }
+
+    fn encode_serialized(&mut self, id: PacketId, msg: &impl Serialize) -> Result<&[u8], Error> {
+        self.start(PacketTy::Msg, id)?;
+        postcard::serialize_with_flavor(msg, BufFlavor(&mut self.buf))?;
+        self.finish()
+    }
+
+    fn start(&mut self, ty: PacketTy, id: PacketId) -> Result<(), Error> {
+        self.buf.clear();
+        self.buf.extend_from_slice(&[0; LEN_PREFIX])?;
+        self.buf.push(ty as u8)?;
+        self.buf.extend_from_slice(&id)?;
+        self.buf.push(self.req_id)
+    }
+
+    fn finish(&mut self) -> Result<&[u8], Error> {
+        let len = u32::try_from(self.buf.len() - LEN_PREFIX).map_err(|_| Error::OffsetOverflow)?;
+        debug_assert!(len as usize >= PACKET_HEADER_LEN);
+        self.buf.as_mut_slice()[..LEN_PREFIX].copy_from_slice(&len.to_le_bytes());
+        Ok(self.buf.as_slice())
+    }
+}
+
+struct BufFlavor<'a, B>(&'a mut B);
+
+impl<B: Buf<u8>> postcard::ser_flavors::Flavor for BufFlavor<'_, B> {
+    type Output = ();
+
+    fn try_push(&mut self, data: u8) -> postcard::Result<()> {
+        self.0
+            .push(data)
+            .map_err(|_| postcard::Error::SerializeBufferFull)
+    }
+
+    fn try_extend(&mut self, data: &[u8]) -> postcard::Result<()> {
+        self.0
+            .extend_from_slice(data)
+            .map_err(|_| postcard::Error::SerializeBufferFull)
+    }
+
+    fn finalize(self) -> postcard::Result<Self::Output> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::types::{IntoLenPacket, LenPacket, OwnedPacket};
+
+    #[test]
+    fn test_encoder_matches_len_packet() {
+        let mut encoder = PacketEncoder::<heapless::Vec<u8, 64>>::default();
+        let table = [1u8, 2, 3, 4, 5, 6, 7, 8];
+        let mut expected = LenPacket::table([1, 0], 8);
+        expected.extend_from_slice(&table);
+        assert_eq!(
+            encoder.encode_table([1, 0], &table).unwrap(),
+            &expected.inner[..]
+        );
+
+        #[derive(Serialize, postcard_schema::Schema)]
+        struct Ping {
+            seq: u32,
+        }
+        let expected = (&Ping { seq: 7 }).with_request_id(3);
+        encoder.set_request_id(3);
+        assert_eq!(
+            encoder.encode_msg(&Ping { seq: 7 }).unwrap(),
+            &expected.inner[..]
+        );
+
+        let OwnedPacket::Msg(msg) =
+            OwnedPacket::parse_with_offset(encoder.as_bytes().to_vec(), LEN_PREFIX).unwrap()
+        else {
+            panic!("expected msg packet");
+        };
+        assert_eq!(msg.id, Ping::ID);
+        assert_eq!(msg.req_id, 3);
+    }
+
+    #[test]
+    fn test_encoder_overflow() {
+        let mut encoder = PacketEncoder::<heapless::Vec<u8, 8>>::default();
+        assert!(encoder.encode_table([1, 0], &[0; 16]).is_err());
+    }
+}
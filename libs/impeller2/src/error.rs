@@ -80,12 +80,94 @@ pub enum Error {
     )]
     InvalidOp,
 
+    #[error("component not found")]
+    #[cfg_attr(
+        feature = "std",
+        diagnostic(
+            code(impeller::component_not_found),
+            help("the vtable has no field for the component")
+        )
+    )]
+    ComponentNotFound,
+
     #[error("schema not found")]
     #[cfg_attr(
         feature = "std",
         diagnostic(code(impeller::schema_not_found), help("schema not found"))
     )]
     SchemaNotFound,
+
+    #[error("field size mismatch")]
+    #[cfg_attr(
+        feature = "std",
+        diagnostic(
+            code(impeller::field_size_mismatch),
+            help("a vtable field's length didn't match the size of its schema")
+        )
+    )]
+    FieldSizeMismatch,
+
+    #[error("timestamp out of range")]
+    #[cfg_attr(
+        feature = "std",
+        diagnostic(
+            code(impeller::timestamp_out_of_range),
+            help("time could not be represented as microseconds since the unix epoch")
+        )
+    )]
+    TimestampOutOfRange,
+
+    #[error("shape mismatch")]
+    #[cfg_attr(
+        feature = "std",
+        diagnostic(
+            code(impeller::shape_mismatch),
+            help("the number of elements didn't match the product of the shape")
+        )
+    )]
+    ShapeMismatch,
+
+    #[error("checksum mismatch")]
+    #[cfg_attr(
+        feature = "std",
+        diagnostic(
+            code(impeller::checksum_mismatch),
+            help(
+                "the packet's crc32 trailer didn't match its contents, it was likely corrupted in transit"
+            )
+        )
+    )]
+    ChecksumMismatch,
+
+    #[error("big endian field")]
+    #[cfg_attr(
+        feature = "std",
+        diagnostic(
+            code(impeller::byte_order),
+            help(
+                "the field is big endian, so it can't be viewed in place, use VTable::apply or VTable::parse_table_unaligned"
+            )
+        )
+    )]
+    ByteOrder,
+
+    #[error(
+        "schema mismatch for component {component_id}: expected hash {expected:#018x}, found {found:#018x}"
+    )]
+    #[cfg_attr(
+        feature = "std",
+        diagnostic(
+            code(impeller::schema_mismatch),
+            help(
+                "the vtable's schema for the component differs from the registered one, the producer was likely built against an older schema"
+            )
+        )
+    )]
+    SchemaMismatch {
+        component_id: crate::types::ComponentId,
+        expected: u64,
+        found: u64,
+    },
 }
 
 impl<A, B: ?Sized> From<zerocopy::CastError<A, B>> for Error {
@@ -0,0 +1,130 @@
+//! Q-format fixed-point numbers, for telemetry that's produced as scaled integers
+
+use core::{
+    fmt,
+    ops::{Add, AddAssign, Sub},
+};
+
+use serde::{Deserialize, Serialize};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+/// A signed fixed-point number with 15 integer bits and 16 fractional bits, stored in an `i32`
+///
+/// The value is the raw `i32` divided by 2^16, so it covers roughly ±32768 in steps of about 1.5e-5. Arithmetic
+/// saturates rather than wrapping.
+#[derive(
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Debug,
+    Serialize,
+    Deserialize,
+    FromBytes,
+    IntoBytes,
+    Immutable,
+    KnownLayout,
+)]
+#[repr(transparent)]
+pub struct Q15_16(i32);
+
+impl Q15_16 {
+    /// The number of fractional bits
+    pub const FRAC_BITS: u32 = 16;
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(1 << Self::FRAC_BITS);
+    pub const MIN: Self = Self(i32::MIN);
+    pub const MAX: Self = Self(i32::MAX);
+
+    const SCALE: f64 = (1u32 << Self::FRAC_BITS) as f64;
+
+    pub const fn from_bits(bits: i32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    /// Converts `value`, rounding to the nearest step and saturating at [`Q15_16::MIN`] and [`Q15_16::MAX`]
+    ///
+    /// NaN becomes zero.
+    pub fn from_f64(value: f64) -> Self {
+        let scaled = value * Self::SCALE;
+        // `as` saturates and maps NaN to 0; adding a half before flooring rounds to nearest without needing std
+        let rounded = scaled + 0.5;
+        let floored = rounded as i64 - (rounded < (rounded as i64) as f64) as i64;
+        Self(floored.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+
+    pub fn from_f32(value: f32) -> Self {
+        Self::from_f64(value as f64)
+    }
+
+    /// Widens to an `f64`, which represents every value exactly
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE
+    }
+
+    /// Widens to an `f32`, which rounds values with more than 24 significant bits
+    pub fn to_f32(self) -> f32 {
+        self.to_f64() as f32
+    }
+}
+
+impl fmt::Display for Q15_16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_f64(), f)
+    }
+}
+
+impl From<Q15_16> for f64 {
+    fn from(value: Q15_16) -> Self {
+        value.to_f64()
+    }
+}
+
+impl Add for Q15_16 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl AddAssign for Q15_16 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Q15_16 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_q15_16_round_trip() {
+        assert_eq!(Q15_16::from_f64(1.0), Q15_16::ONE);
+        assert_eq!(Q15_16::from_f64(-2.5).to_bits(), -5 << 15);
+        assert_eq!(Q15_16::from_f64(-2.5).to_f64(), -2.5);
+        // rounds to the nearest step
+        assert_eq!(Q15_16::from_f64(1.4 / 65536.0).to_bits(), 1);
+        assert_eq!(Q15_16::from_f64(-1.6 / 65536.0).to_bits(), -2);
+        assert_eq!(Q15_16::from_f64(1e9), Q15_16::MAX);
+        assert_eq!(Q15_16::from_f64(-1e9), Q15_16::MIN);
+        assert_eq!(Q15_16::from_f64(f64::NAN), Q15_16::ZERO);
+        assert_eq!(Q15_16::MAX + Q15_16::ONE, Q15_16::MAX);
+    }
+}
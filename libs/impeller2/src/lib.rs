@@ -38,12 +38,20 @@
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
+pub mod bits;
 pub mod buf;
+pub mod checksum;
 pub mod com_de;
 pub mod component;
+#[cfg(feature = "alloc")]
+pub mod delta;
+pub mod encoder;
 pub mod error;
+pub mod fixed;
 pub mod registry;
 pub mod schema;
+#[cfg(feature = "alloc")]
+pub mod sink;
 pub mod types;
 pub mod util;
 pub mod vtable;
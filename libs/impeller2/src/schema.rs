@@ -1,7 +1,12 @@
 use serde::{Deserialize, Serialize};
-use zerocopy::{FromBytes, IntoBytes};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, TryFromBytes};
 
-use crate::{buf::Buf, error::Error, types::PrimType};
+use crate::{
+    buf::Buf,
+    com_de::ComponentViewElem,
+    error::Error,
+    types::{ComponentView, PrimType, VAR_LEN_PREFIX_LEN},
+};
 
 #[cfg(feature = "alloc")]
 pub type DefaultSizeBuf = Vec<u64>;
@@ -13,9 +18,12 @@ pub struct Schema<S: Buf<u64> = DefaultSizeBuf> {
     prim_type: PrimType,
     #[serde(bound(deserialize = ""))]
     shape: S,
+    /// Part of the wire form, so a schema encoded by a peer from before versioning doesn't decode
+    version: u16,
 }
 
 impl<D: Buf<u64>> Schema<D> {
+    /// Bytes and string schemas must have a single dimension, the slot length, with room for the length prefix
     pub fn new<T, I>(prim_type: PrimType, shape: I) -> Result<Self, Error>
     where
         T: DimElem,
@@ -27,12 +35,28 @@ impl<D: Buf<u64>> Schema<D> {
         for dim in shape {
             data.push(dim.into_u64())?;
         }
+        if prim_type.is_var_len()
+            && !matches!(data.as_slice(), &[len] if len >= VAR_LEN_PREFIX_LEN as u64)
+        {
+            return Err(Error::ShapeMismatch);
+        }
         Ok(Self {
             shape: data,
             prim_type,
+            version: 0,
         })
     }
 
+    /// Sets the schema version, which is bumped each time a component's shape or prim type is migrated
+    pub fn with_version(mut self, version: u16) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
     pub fn prim_type(&self) -> PrimType {
         self.prim_type
     }
@@ -49,6 +73,38 @@ impl<D: Buf<u64>> Schema<D> {
     pub fn size(&self) -> usize {
         self.shape.as_slice().iter().copied().product::<u64>() as usize * self.prim_type.size()
     }
+
+    /// A hash of the schema's prim type and shape, see [`schema_hash`]
+    pub fn stable_hash(&self) -> u64 {
+        schema_hash(self.prim_type, self.shape.as_slice())
+    }
+}
+
+/// Hashes a prim type and shape with 64 bit FNV-1a
+///
+/// The hash only depends on the prim type's discriminant and the little endian bytes of each dim, so it's the same
+/// across platforms and releases, and can be sent alongside a vtable to check that both ends agree on a component's
+/// layout. The schema version isn't hashed, since a migration that doesn't change the layout doesn't change the bytes.
+pub const fn schema_hash(prim_type: PrimType, dim: &[u64]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    const fn write(mut hash: u64, bytes: [u8; 8]) -> u64 {
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(PRIME);
+            i += 1;
+        }
+        hash
+    }
+    let mut hash = write(OFFSET_BASIS, (prim_type as u64).to_le_bytes());
+    hash = write(hash, (dim.len() as u64).to_le_bytes());
+    let mut i = 0;
+    while i < dim.len() {
+        hash = write(hash, dim[i].to_le_bytes());
+        i += 1;
+    }
+    hash
 }
 
 pub trait DimElem {
@@ -72,3 +128,160 @@ impl<T: DimElem + Copy> DimElem for &'_ T {
         (*self).into_u64()
     }
 }
+
+/// Decodes a sample of a known [`PrimType`] from its bytes, shape and element count
+pub(crate) type DecodeFn =
+    for<'a> fn(&'a [u8], &'a [usize], usize) -> Result<ComponentView<'a>, Error>;
+
+/// Decodes samples of a single component
+///
+/// The [`PrimType`] is matched once when the decoder is built, so hot loops that decode many samples of the same
+/// component skip the per-sample dispatch that [`ComponentView::try_from_bytes_shape`] does. The `typed_decoder`
+/// bench compares the two.
+#[derive(Clone)]
+pub struct TypedDecoder<S: Buf<u64> = DefaultSizeBuf> {
+    schema: Schema<S>,
+    len: usize,
+    decode: DecodeFn,
+}
+
+impl<S: Buf<u64>> TypedDecoder<S> {
+    pub fn new(schema: Schema<S>) -> Result<Self, Error> {
+        let len = elem_count(schema.shape())?;
+        let decode = decode_fn(schema.prim_type());
+        Ok(Self {
+            schema,
+            len,
+            decode,
+        })
+    }
+
+    pub fn schema(&self) -> &Schema<S> {
+        &self.schema
+    }
+
+    /// The size of one sample in bytes
+    pub fn size(&self) -> usize {
+        self.len * self.schema.prim_type().size()
+    }
+
+    /// Decodes the sample at the start of `bytes`
+    pub fn decode<'a>(&'a self, bytes: &'a [u8]) -> Result<ComponentView<'a>, Error> {
+        (self.decode)(bytes, self.schema.shape(), self.len)
+    }
+}
+
+/// The number of elements in a sample of `shape`
+pub(crate) fn elem_count(shape: &[usize]) -> Result<usize, Error> {
+    shape.iter().try_fold(1usize, |x, &xs| {
+        x.checked_mul(xs).ok_or(Error::OffsetOverflow)
+    })
+}
+
+/// Matches `prim_type` to its decoder, so a loop over many samples of one type dispatches once
+pub(crate) fn decode_fn(prim_type: PrimType) -> DecodeFn {
+    match prim_type {
+        PrimType::U8 => decode_as::<u8>,
+        PrimType::U16 => decode_as::<u16>,
+        PrimType::U32 => decode_as::<u32>,
+        PrimType::U64 => decode_as::<u64>,
+        PrimType::I8 => decode_as::<i8>,
+        PrimType::I16 => decode_as::<i16>,
+        PrimType::I32 => decode_as::<i32>,
+        PrimType::I64 => decode_as::<i64>,
+        PrimType::Bool => decode_as::<bool>,
+        PrimType::F32 => decode_as::<f32>,
+        PrimType::F64 => decode_as::<f64>,
+        PrimType::F16 => decode_as::<half::f16>,
+        PrimType::BF16 => decode_as::<half::bf16>,
+        PrimType::Q15_16 => decode_as::<crate::fixed::Q15_16>,
+        PrimType::Bytes => decode_bytes,
+        PrimType::String => decode_string,
+    }
+}
+
+fn decode_as<'a, T: ComponentViewElem + TryFromBytes + KnownLayout + Immutable>(
+    bytes: &'a [u8],
+    shape: &'a [usize],
+    len: usize,
+) -> Result<ComponentView<'a>, Error> {
+    let (buf, _) = <[T]>::try_ref_from_prefix_with_elems(bytes, len)?;
+    Ok(T::component_view(
+        nox_array::ArrayView::from_buf_shape_unchecked(buf, shape),
+    ))
+}
+
+fn decode_bytes<'a>(
+    bytes: &'a [u8],
+    shape: &'a [usize],
+    _len: usize,
+) -> Result<ComponentView<'a>, Error> {
+    ComponentView::try_from_bytes_shape(bytes, shape, PrimType::Bytes)
+}
+
+fn decode_string<'a>(
+    bytes: &'a [u8],
+    shape: &'a [usize],
+    _len: usize,
+) -> Result<ComponentView<'a>, Error> {
+    ComponentView::try_from_bytes_shape(bytes, shape, PrimType::String)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_decoder() {
+        let schema = Schema::<Vec<u64>>::new(PrimType::F64, [3u64]).unwrap();
+        let decoder = TypedDecoder::new(schema).unwrap();
+        assert_eq!(decoder.size(), 24);
+
+        let samples = [1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let bytes = samples.as_bytes();
+        for (chunk, expected) in bytes.chunks(decoder.size()).zip(samples.chunks(3)) {
+            let view = decoder.decode(chunk).unwrap();
+            let ComponentView::F64(view) = view else {
+                panic!("expected f64 view");
+            };
+            assert_eq!(view.buf(), expected);
+            assert_eq!(view.shape(), &[3]);
+            let slow = ComponentView::try_from_bytes_shape(chunk, &[3], PrimType::F64).unwrap();
+            assert_eq!(slow.as_bytes(), chunk);
+        }
+
+        assert!(decoder.decode(&bytes[..16]).is_err());
+
+        let schema = Schema::<Vec<u64>>::new(PrimType::Bool, [0u64; 0]).unwrap();
+        let decoder = TypedDecoder::new(schema).unwrap();
+        assert!(matches!(decoder.decode(&[1]), Ok(ComponentView::Bool(_))));
+        assert!(decoder.decode(&[2]).is_err());
+
+        let schema = Schema::<Vec<u64>>::new(PrimType::String, [8u64]).unwrap();
+        let decoder = TypedDecoder::new(schema).unwrap();
+        assert_eq!(decoder.size(), 8);
+        let view = decoder.decode(&[2, 0, 0, 0, b'o', b'k', 0, 0]).unwrap();
+        assert_eq!(view.as_str(), Some("ok"));
+        assert!(Schema::<Vec<u64>>::new(PrimType::String, [2u64]).is_err());
+        assert!(Schema::<Vec<u64>>::new(PrimType::Bytes, [8u64, 2]).is_err());
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        let schema = Schema::<Vec<u64>>::new(PrimType::F64, [3u64]).unwrap();
+        // pinned so the hash can't drift between releases
+        assert_eq!(schema.stable_hash(), 0x9665ff5b485ae86d);
+        assert_eq!(
+            schema.clone().with_version(2).stable_hash(),
+            schema.stable_hash()
+        );
+        for other in [
+            Schema::<Vec<u64>>::new(PrimType::F64, [4u64]).unwrap(),
+            Schema::<Vec<u64>>::new(PrimType::F32, [3u64]).unwrap(),
+            Schema::<Vec<u64>>::new(PrimType::F64, [3u64, 1]).unwrap(),
+            Schema::<Vec<u64>>::new(PrimType::F64, [0u64; 0]).unwrap(),
+        ] {
+            assert_ne!(other.stable_hash(), schema.stable_hash());
+        }
+    }
+}
@@ -0,0 +1,517 @@
+//! Reusable [`Decomponentize`] implementations for checking and summarizing component data.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::{
+    com_de::Decomponentize,
+    types::{ComponentId, ComponentView, PrimType, Timestamp},
+};
+
+/// What a [`SchemaGuardSink`] does when a component's schema changes between samples
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SchemaGuardPolicy {
+    /// Panic with a description of the mismatch
+    Panic,
+    /// Return [`SchemaGuardError::Mismatch`] from `apply_value`
+    #[default]
+    Error,
+    /// Silently drop the offending sample, counting it in [`SchemaGuardSink::mismatches`]
+    Skip,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum SchemaGuardError<E> {
+    #[error("schema mismatch for {component_id:?}: expected {expected:?}, found {found:?}")]
+    Mismatch {
+        component_id: ComponentId,
+        expected: (PrimType, Vec<usize>),
+        found: (PrimType, Vec<usize>),
+    },
+    #[error("{0}")]
+    Inner(E),
+}
+
+/// A sink that checks every component keeps the same primitive type and shape across samples
+///
+/// The first `(prim_type, shape)` seen for each component is recorded, and any later sample that disagrees is handled
+/// according to the [`SchemaGuardPolicy`] before it reaches the inner sink.
+pub struct SchemaGuardSink<S> {
+    inner: S,
+    policy: SchemaGuardPolicy,
+    schemas: BTreeMap<ComponentId, (PrimType, Vec<usize>)>,
+    mismatches: usize,
+}
+
+impl<S> SchemaGuardSink<S> {
+    pub fn new(inner: S, policy: SchemaGuardPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            schemas: BTreeMap::new(),
+            mismatches: 0,
+        }
+    }
+
+    /// The number of samples that didn't match their component's schema
+    pub fn mismatches(&self) -> usize {
+        self.mismatches
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Decomponentize> Decomponentize for SchemaGuardSink<S> {
+    type Error = SchemaGuardError<S::Error>;
+
+    fn apply_value(
+        &mut self,
+        component_id: ComponentId,
+        value: ComponentView<'_>,
+        timestamp: Option<Timestamp>,
+    ) -> Result<(), Self::Error> {
+        let prim_type = value.prim_type();
+        let shape = value.shape();
+        let (expected_ty, expected_shape) = self
+            .schemas
+            .entry(component_id)
+            .or_insert_with(|| (prim_type, shape.to_vec()));
+        if *expected_ty != prim_type || expected_shape.as_slice() != shape {
+            self.mismatches += 1;
+            let expected = (*expected_ty, expected_shape.clone());
+            let found = (prim_type, shape.to_vec());
+            match self.policy {
+                SchemaGuardPolicy::Panic => panic!(
+                    "schema mismatch for {component_id:?}: expected {expected:?}, found {found:?}"
+                ),
+                SchemaGuardPolicy::Error => {
+                    return Err(SchemaGuardError::Mismatch {
+                        component_id,
+                        expected,
+                        found,
+                    });
+                }
+                SchemaGuardPolicy::Skip => return Ok(()),
+            }
+        }
+        self.inner
+            .apply_value(component_id, value, timestamp)
+            .map_err(SchemaGuardError::Inner)
+    }
+}
+
+/// A sink that folds every sample into a 64-bit FNV-1a hash, for checking that two runs produced identical data
+///
+/// Each sample's component id, timestamp, prim type, shape and bytes are hashed. An ordered sink chains those hashes,
+/// so it only matches if samples arrive in the same order; an unordered sink sums them, so any interleaving of the
+/// same samples produces the same hash.
+#[derive(Clone, Copy, Debug)]
+pub struct HashSink {
+    state: u64,
+    ordered: bool,
+    count: usize,
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+impl HashSink {
+    pub fn ordered() -> Self {
+        Self {
+            state: FNV_OFFSET,
+            ordered: true,
+            count: 0,
+        }
+    }
+
+    pub fn unordered() -> Self {
+        Self {
+            state: 0,
+            ordered: false,
+            count: 0,
+        }
+    }
+
+    /// The number of samples hashed so far
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+impl Decomponentize for HashSink {
+    type Error = core::convert::Infallible;
+
+    fn apply_value(
+        &mut self,
+        component_id: ComponentId,
+        value: ComponentView<'_>,
+        timestamp: Option<Timestamp>,
+    ) -> Result<(), Self::Error> {
+        let mut hash = fnv1a(FNV_OFFSET, &component_id.0.to_le_bytes());
+        hash = match timestamp {
+            Some(timestamp) => fnv1a(fnv1a(hash, &[1]), &timestamp.to_le_bytes()),
+            None => fnv1a(hash, &[0]),
+        };
+        hash = fnv1a(hash, value.prim_type().as_str().as_bytes());
+        for &dim in value.shape() {
+            hash = fnv1a(hash, &(dim as u64).to_le_bytes());
+        }
+        hash = fnv1a(hash, value.as_bytes());
+        self.state = if self.ordered {
+            fnv1a(self.state, &hash.to_le_bytes())
+        } else {
+            self.state.wrapping_add(hash)
+        };
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// Running mean and covariance of one component, accumulated with Welford's algorithm
+///
+/// Every element of the component is treated as one dimension of a vector sample, so a `[3]` component yields a 3x3
+/// covariance and a `[2, 2]` component a 4x4 one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ComponentStats {
+    count: u64,
+    mean: Vec<f64>,
+    m2: Vec<f64>,
+}
+
+impl ComponentStats {
+    fn push(&mut self, sample: &[f64]) {
+        let dim = sample.len();
+        if self.mean.len() != dim {
+            *self = ComponentStats {
+                count: 0,
+                mean: alloc::vec![0.0; dim],
+                m2: alloc::vec![0.0; dim * dim],
+            };
+        }
+        self.count += 1;
+        let n = self.count as f64;
+        let delta: Vec<f64> = sample.iter().zip(&self.mean).map(|(x, m)| x - m).collect();
+        for (mean, d) in self.mean.iter_mut().zip(&delta) {
+            *mean += d / n;
+        }
+        for (i, d) in delta.iter().enumerate() {
+            for (j, (x, mean)) in sample.iter().zip(&self.mean).enumerate() {
+                self.m2[i * dim + j] += d * (x - mean);
+            }
+        }
+    }
+
+    /// The number of samples accumulated
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The number of elements in each sample
+    pub fn dim(&self) -> usize {
+        self.mean.len()
+    }
+
+    pub fn mean(&self) -> &[f64] {
+        &self.mean
+    }
+
+    /// The unbiased sample covariance as a row-major `dim x dim` matrix, or `None` with fewer than two samples
+    pub fn covariance(&self) -> Option<Vec<f64>> {
+        if self.count < 2 {
+            return None;
+        }
+        let denom = (self.count - 1) as f64;
+        Some(self.m2.iter().map(|m| m / denom).collect())
+    }
+}
+
+/// A sink that accumulates the online mean and covariance of every component it sees
+///
+/// Elements are widened to `f64`. If a component's element count changes, its statistics restart from the new sample.
+#[derive(Clone, Debug, Default)]
+pub struct StatsSink {
+    stats: BTreeMap<ComponentId, ComponentStats>,
+    sample: Vec<f64>,
+}
+
+impl StatsSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self, component_id: ComponentId) -> Option<&ComponentStats> {
+        self.stats.get(&component_id)
+    }
+
+    pub fn mean(&self, component_id: ComponentId) -> Option<&[f64]> {
+        self.stats(component_id).map(ComponentStats::mean)
+    }
+
+    pub fn covariance(&self, component_id: ComponentId) -> Option<Vec<f64>> {
+        self.stats(component_id)?.covariance()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ComponentId, &ComponentStats)> {
+        self.stats.iter()
+    }
+}
+
+impl Decomponentize for StatsSink {
+    type Error = core::convert::Infallible;
+
+    fn apply_value(
+        &mut self,
+        component_id: ComponentId,
+        value: ComponentView<'_>,
+        _timestamp: Option<Timestamp>,
+    ) -> Result<(), Self::Error> {
+        self.sample.clear();
+        self.sample
+            .extend((0..).map_while(|i| value.get(i)).map(|x| x.as_f64()));
+        self.stats
+            .entry(component_id)
+            .or_default()
+            .push(&self.sample);
+        Ok(())
+    }
+}
+
+/// A sink that forwards every value to each of a runtime-chosen list of sinks
+///
+/// This is the dynamic counterpart to combining sinks in a tuple. Sinks are called in order, and the first error stops
+/// the value from reaching the sinks after it.
+pub struct TeeSink<E>(pub Vec<Box<dyn Decomponentize<Error = E>>>);
+
+impl<E> Default for TeeSink<E> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<E> TeeSink<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, sink: impl Decomponentize<Error = E> + 'static) {
+        self.0.push(Box::new(sink));
+    }
+
+    pub fn with(mut self, sink: impl Decomponentize<Error = E> + 'static) -> Self {
+        self.push(sink);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<E> Decomponentize for TeeSink<E> {
+    type Error = E;
+
+    fn apply_value(
+        &mut self,
+        component_id: ComponentId,
+        value: ComponentView<'_>,
+        timestamp: Option<Timestamp>,
+    ) -> Result<(), Self::Error> {
+        for sink in &mut self.0 {
+            sink.apply_value(component_id, value, timestamp)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nox_array::ArrayView;
+
+    #[test]
+    fn test_schema_guard_shape_change() {
+        let mut count = 0;
+        let mut sink = SchemaGuardSink::new(
+            |_: ComponentId, _: ComponentView<'_>, _: Option<Timestamp>| count += 1,
+            SchemaGuardPolicy::Error,
+        );
+        let id = ComponentId::new("test");
+        let vec3 = ComponentView::F64(ArrayView::from_buf_shape_unchecked(&[1.0, 2.0, 3.0], &[3]));
+        let mat2 = ComponentView::F64(ArrayView::from_buf_shape_unchecked(
+            &[1.0, 2.0, 3.0, 4.0],
+            &[2, 2],
+        ));
+        sink.apply_value(id, vec3, None).unwrap();
+        sink.apply_value(id, vec3, None).unwrap();
+        let err = sink.apply_value(id, mat2, None).unwrap_err();
+        assert_eq!(
+            err,
+            SchemaGuardError::Mismatch {
+                component_id: id,
+                expected: (PrimType::F64, vec![3]),
+                found: (PrimType::F64, vec![2, 2]),
+            }
+        );
+        assert_eq!(sink.mismatches(), 1);
+        drop(sink);
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_hash_sink() {
+        let a = ComponentId::new("a");
+        let b = ComponentId::new("b");
+        let vec3 = ComponentView::F64(ArrayView::from_buf_shape_unchecked(&[1.0, 2.0, 3.0], &[3]));
+        let other = ComponentView::F64(ArrayView::from_buf_shape_unchecked(&[1.0, 2.0, 4.0], &[3]));
+        let hash = |mut sink: HashSink, samples: &[(ComponentId, ComponentView<'_>, i64)]| {
+            for &(id, view, ts) in samples {
+                sink.apply_value(id, view, Some(Timestamp(ts))).unwrap();
+            }
+            sink.finish()
+        };
+        let samples = [(a, vec3, 0), (b, vec3, 0), (a, vec3, 1)];
+        let shuffled = [(a, vec3, 1), (a, vec3, 0), (b, vec3, 0)];
+        let changed = [(a, vec3, 0), (b, other, 0), (a, vec3, 1)];
+
+        assert_eq!(
+            hash(HashSink::ordered(), &samples),
+            hash(HashSink::ordered(), &samples)
+        );
+        assert_ne!(
+            hash(HashSink::ordered(), &samples),
+            hash(HashSink::ordered(), &shuffled)
+        );
+        assert_ne!(
+            hash(HashSink::ordered(), &samples),
+            hash(HashSink::ordered(), &changed)
+        );
+        assert_eq!(
+            hash(HashSink::unordered(), &samples),
+            hash(HashSink::unordered(), &shuffled)
+        );
+        assert_ne!(
+            hash(HashSink::unordered(), &samples),
+            hash(HashSink::unordered(), &changed)
+        );
+    }
+
+    #[test]
+    fn test_stats_sink_gaussian() {
+        // xorshift + Box-Muller so the sample is deterministic
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut uniform = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let mut normal = move || {
+            let u1 = uniform().max(f64::MIN_POSITIVE);
+            let u2 = uniform();
+            (-2.0 * u1.ln()).sqrt() * (2.0 * core::f64::consts::PI * u2).cos()
+        };
+
+        // x = mean + L z, where L is the cholesky factor of [[4.0, 1.2], [1.2, 1.0]]
+        let mean = [1.0, -2.0];
+        let l = [[2.0, 0.0], [0.6, 0.8]];
+        let id = ComponentId::new("accel");
+        let mut sink = StatsSink::new();
+        let mut samples = vec![];
+        for _ in 0..20_000 {
+            let z = [normal(), normal()];
+            let x = [
+                mean[0] + l[0][0] * z[0],
+                mean[1] + l[1][0] * z[0] + l[1][1] * z[1],
+            ];
+            samples.push(x);
+            let view = ComponentView::F64(ArrayView::from_buf_shape_unchecked(&x, &[2]));
+            sink.apply_value(id, view, None).unwrap();
+        }
+
+        // two-pass statistics over the same sample
+        let n = samples.len() as f64;
+        let expected_mean = [0, 1].map(|i| samples.iter().map(|x| x[i]).sum::<f64>() / n);
+        let expected_cov = [(0, 0), (0, 1), (1, 0), (1, 1)].map(|(i, j)| {
+            samples
+                .iter()
+                .map(|x| (x[i] - expected_mean[i]) * (x[j] - expected_mean[j]))
+                .sum::<f64>()
+                / (n - 1.0)
+        });
+
+        let stats = sink.stats(id).unwrap();
+        assert_eq!(stats.count(), 20_000);
+        assert_eq!(stats.dim(), 2);
+        for (a, b) in stats.mean().iter().zip(expected_mean) {
+            assert!((a - b).abs() < 1e-9, "{a} != {b}");
+        }
+        let cov = sink.covariance(id).unwrap();
+        for (a, b) in cov.iter().zip(expected_cov) {
+            assert!((a - b).abs() < 1e-9, "{a} != {b}");
+        }
+
+        // and both should land near the true distribution
+        for (a, b) in stats.mean().iter().zip(mean) {
+            assert!((a - b).abs() < 0.05, "{a} != {b}");
+        }
+        for (a, b) in cov.iter().zip([4.0, 1.2, 1.2, 1.0]) {
+            assert!((a - b).abs() < 0.15, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn test_tee_sink_reaches_every_sink() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut tee = TeeSink::new();
+        for i in 0..3 {
+            let seen = seen.clone();
+            tee.push(
+                move |id: ComponentId, view: ComponentView<'_>, ts: Option<Timestamp>| {
+                    seen.borrow_mut()
+                        .push((i, id, view.as_bytes().to_vec(), ts));
+                },
+            );
+        }
+        assert_eq!(tee.len(), 3);
+
+        let id = ComponentId::new("accel");
+        let x = [1.0, 2.0, 3.0];
+        let view = ComponentView::F64(ArrayView::from_buf_shape_unchecked(&x, &[3]));
+        tee.apply_value(id, view, Some(Timestamp(7))).unwrap();
+
+        let seen = seen.borrow();
+        assert_eq!(seen.len(), 3);
+        for (i, (sink, seen_id, bytes, ts)) in seen.iter().enumerate() {
+            assert_eq!(*sink, i);
+            assert_eq!(*seen_id, id);
+            assert_eq!(bytes.as_slice(), view.as_bytes());
+            assert_eq!(*ts, Some(Timestamp(7)));
+        }
+    }
+}
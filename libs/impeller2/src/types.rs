@@ -5,6 +5,8 @@ use core::{
     time::Duration,
 };
 
+pub use crate::fixed::Q15_16;
+pub use half::{bf16, f16};
 use nox_array::ArrayView;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, TryFromBytes, Unaligned};
@@ -128,6 +130,16 @@ pub enum PrimType {
     Bool,
     F32,
     F64,
+    F16,
+    #[serde(rename = "bf16")]
+    BF16,
+    /// Bytes in a fixed-capacity slot, see [`var_len_payload`]
+    Bytes,
+    /// UTF-8 text in a fixed-capacity slot, see [`var_len_payload`]
+    String,
+    /// Signed fixed-point with 16 fractional bits, see [`Q15_16`]
+    #[serde(rename = "q15_16")]
+    Q15_16,
 }
 
 impl PrimType {
@@ -150,6 +162,10 @@ impl PrimType {
             PrimType::Bool => mem::align_of::<bool>(),
             PrimType::F32 => mem::align_of::<f32>(),
             PrimType::F64 => mem::align_of::<f64>(),
+            PrimType::F16 => mem::align_of::<f16>(),
+            PrimType::BF16 => mem::align_of::<bf16>(),
+            PrimType::Bytes | PrimType::String => mem::align_of::<u32>(),
+            PrimType::Q15_16 => mem::align_of::<Q15_16>(),
         }
     }
 
@@ -166,6 +182,10 @@ impl PrimType {
             PrimType::Bool => mem::size_of::<bool>(),
             PrimType::F32 => mem::size_of::<f32>(),
             PrimType::F64 => mem::size_of::<f64>(),
+            PrimType::F16 => mem::size_of::<f16>(),
+            PrimType::BF16 => mem::size_of::<bf16>(),
+            PrimType::Bytes | PrimType::String => mem::size_of::<u8>(),
+            PrimType::Q15_16 => mem::size_of::<Q15_16>(),
         }
     }
 
@@ -182,6 +202,165 @@ impl PrimType {
             PrimType::Bool => "bool",
             PrimType::F32 => "f32",
             PrimType::F64 => "f64",
+            PrimType::F16 => "f16",
+            PrimType::BF16 => "bf16",
+            PrimType::Bytes => "bytes",
+            PrimType::String => "string",
+            PrimType::Q15_16 => "q15_16",
+        }
+    }
+
+    /// Whether values of this type are length-prefixed in a slot rather than an array of elements
+    pub const fn is_var_len(self) -> bool {
+        matches!(self, PrimType::Bytes | PrimType::String)
+    }
+
+    /// The smallest value representable by this type
+    pub const fn min_value(self) -> ElementValue {
+        match self {
+            PrimType::U8 => ElementValue::U8(u8::MIN),
+            PrimType::U16 => ElementValue::U16(u16::MIN),
+            PrimType::U32 => ElementValue::U32(u32::MIN),
+            PrimType::U64 => ElementValue::U64(u64::MIN),
+            PrimType::I8 => ElementValue::I8(i8::MIN),
+            PrimType::I16 => ElementValue::I16(i16::MIN),
+            PrimType::I32 => ElementValue::I32(i32::MIN),
+            PrimType::I64 => ElementValue::I64(i64::MIN),
+            PrimType::Bool => ElementValue::Bool(false),
+            PrimType::F32 => ElementValue::F32(f32::MIN),
+            PrimType::F64 => ElementValue::F64(f64::MIN),
+            PrimType::F16 => ElementValue::F16(f16::MIN),
+            PrimType::BF16 => ElementValue::BF16(bf16::MIN),
+            PrimType::Bytes | PrimType::String => ElementValue::U8(u8::MIN),
+            PrimType::Q15_16 => ElementValue::Q15_16(Q15_16::MIN),
+        }
+    }
+
+    /// The largest value representable by this type
+    pub const fn max_value(self) -> ElementValue {
+        match self {
+            PrimType::U8 => ElementValue::U8(u8::MAX),
+            PrimType::U16 => ElementValue::U16(u16::MAX),
+            PrimType::U32 => ElementValue::U32(u32::MAX),
+            PrimType::U64 => ElementValue::U64(u64::MAX),
+            PrimType::I8 => ElementValue::I8(i8::MAX),
+            PrimType::I16 => ElementValue::I16(i16::MAX),
+            PrimType::I32 => ElementValue::I32(i32::MAX),
+            PrimType::I64 => ElementValue::I64(i64::MAX),
+            PrimType::Bool => ElementValue::Bool(true),
+            PrimType::F32 => ElementValue::F32(f32::MAX),
+            PrimType::F64 => ElementValue::F64(f64::MAX),
+            PrimType::F16 => ElementValue::F16(f16::MAX),
+            PrimType::BF16 => ElementValue::BF16(bf16::MAX),
+            PrimType::Bytes | PrimType::String => ElementValue::U8(u8::MAX),
+            PrimType::Q15_16 => ElementValue::Q15_16(Q15_16::MAX),
+        }
+    }
+
+    /// Casts `value` to this type, returning `None` if it falls outside the type's range
+    ///
+    /// Floats cast to integer types are truncated towards zero, and NaN is never in range for an integer. Only `0` and
+    /// `1` cast to `bool`. Infinities and NaN pass through to float types, but a finite value too large for an `f32`
+    /// is rejected rather than becoming infinite. Fixed-point values are rounded to the nearest step, and NaN and infinities
+    /// are out of range. The elements of a bytes or string slot are `u8`s.
+    pub fn checked_cast(self, value: ElementValue) -> Option<ElementValue> {
+        macro_rules! int {
+            ($variant:ident, $ty:ty) => {
+                match value.as_i128() {
+                    Some(v) => <$ty>::try_from(v).ok(),
+                    None => {
+                        let v = value.as_f64();
+                        // `MAX as f64` can round up past the real maximum, so compare against the exclusive
+                        // power-of-two bound instead
+                        let upper = (<$ty>::MAX / 2 + 1) as f64 * 2.0;
+                        (v >= <$ty>::MIN as f64 && v < upper).then_some(v as $ty)
+                    }
+                }
+                .map(ElementValue::$variant)
+            };
+        }
+        match self {
+            PrimType::U8 | PrimType::Bytes | PrimType::String => int!(U8, u8),
+            PrimType::U16 => int!(U16, u16),
+            PrimType::U32 => int!(U32, u32),
+            PrimType::U64 => int!(U64, u64),
+            PrimType::I8 => int!(I8, i8),
+            PrimType::I16 => int!(I16, i16),
+            PrimType::I32 => int!(I32, i32),
+            PrimType::I64 => int!(I64, i64),
+            PrimType::Bool => {
+                let v = value.as_f64();
+                (v == 0.0 || v == 1.0).then_some(ElementValue::Bool(v == 1.0))
+            }
+            PrimType::F32 => {
+                let v = value.as_f64();
+                (!v.is_finite() || (-f32::MAX as f64..=f32::MAX as f64).contains(&v))
+                    .then_some(ElementValue::F32(v as f32))
+            }
+            PrimType::F64 => Some(ElementValue::F64(value.as_f64())),
+            PrimType::F16 => {
+                let v = value.as_f64();
+                (!v.is_finite() || v.abs() <= f16::MAX.to_f64())
+                    .then_some(ElementValue::F16(f16::from_f64(v)))
+            }
+            PrimType::BF16 => {
+                let v = value.as_f64();
+                (!v.is_finite() || v.abs() <= bf16::MAX.to_f64())
+                    .then_some(ElementValue::BF16(bf16::from_f64(v)))
+            }
+            PrimType::Q15_16 => {
+                let v = value.as_f64();
+                (Q15_16::MIN.to_f64()..=Q15_16::MAX.to_f64())
+                    .contains(&v)
+                    .then_some(ElementValue::Q15_16(Q15_16::from_f64(v)))
+            }
+        }
+    }
+
+    /// Casts `value` to this type, clamping it to the type's range
+    ///
+    /// NaN becomes `0` for integer and fixed-point types, and any non-zero value becomes `true` for `bool`.
+    pub fn saturating_cast(self, value: ElementValue) -> ElementValue {
+        macro_rules! int {
+            ($variant:ident, $ty:ty) => {
+                ElementValue::$variant(match value.as_i128() {
+                    Some(v) => v.clamp(<$ty>::MIN as i128, <$ty>::MAX as i128) as $ty,
+                    None => value.as_f64() as $ty,
+                })
+            };
+        }
+        match self {
+            PrimType::U8 | PrimType::Bytes | PrimType::String => int!(U8, u8),
+            PrimType::U16 => int!(U16, u16),
+            PrimType::U32 => int!(U32, u32),
+            PrimType::U64 => int!(U64, u64),
+            PrimType::I8 => int!(I8, i8),
+            PrimType::I16 => int!(I16, i16),
+            PrimType::I32 => int!(I32, i32),
+            PrimType::I64 => int!(I64, i64),
+            PrimType::Bool => ElementValue::Bool(value.as_f64() != 0.0),
+            PrimType::F32 => {
+                let v = value.as_f64();
+                if v.is_finite() {
+                    ElementValue::F32(v.clamp(-f32::MAX as f64, f32::MAX as f64) as f32)
+                } else {
+                    ElementValue::F32(v as f32)
+                }
+            }
+            PrimType::F64 => ElementValue::F64(value.as_f64()),
+            PrimType::F16 => {
+                let v = value.as_f64();
+                let max = f16::MAX.to_f64();
+                let v = if v.is_finite() { v.clamp(-max, max) } else { v };
+                ElementValue::F16(f16::from_f64(v))
+            }
+            PrimType::BF16 => {
+                let v = value.as_f64();
+                let max = bf16::MAX.to_f64();
+                let v = if v.is_finite() { v.clamp(-max, max) } else { v };
+                ElementValue::BF16(bf16::from_f64(v))
+            }
+            PrimType::Q15_16 => ElementValue::Q15_16(Q15_16::from_f64(value.as_f64())),
         }
     }
 }
@@ -200,6 +379,11 @@ impl core::fmt::Display for PrimType {
             PrimType::Bool => "bool",
             PrimType::F32 => "f32",
             PrimType::F64 => "f64",
+            PrimType::F16 => "f16",
+            PrimType::BF16 => "bf16",
+            PrimType::Bytes => "bytes",
+            PrimType::String => "string",
+            PrimType::Q15_16 => "q15_16",
         };
         core::fmt::Display::fmt(s, f)
     }
@@ -218,6 +402,41 @@ pub enum ComponentView<'a> {
     Bool(ArrayView<'a, bool>),
     F32(ArrayView<'a, f32>),
     F64(ArrayView<'a, f64>),
+    F16(ArrayView<'a, f16>),
+    BF16(ArrayView<'a, bf16>),
+    /// A whole [`PrimType::Bytes`] slot, length prefix included
+    Bytes(ArrayView<'a, u8>),
+    /// A whole [`PrimType::String`] slot, length prefix included
+    String(ArrayView<'a, u8>),
+    Q15_16(ArrayView<'a, Q15_16>),
+}
+
+/// The length of the `u32` prefix at the start of a [`PrimType::Bytes`] or [`PrimType::String`] slot
+pub const VAR_LEN_PREFIX_LEN: usize = size_of::<u32>();
+
+/// The payload of a [`PrimType::Bytes`] or [`PrimType::String`] slot
+///
+/// A slot is the component's whole buffer, as long as its schema's shape. It starts with the payload's length as a
+/// little endian `u32`, followed by the payload and then zeros up to the end of the slot. Slots keep tables and time
+/// series fixed-size, so the shape caps how long a value can get.
+pub fn var_len_payload(slot: &[u8]) -> Result<&[u8], Error> {
+    let (len, rest) = slot
+        .split_first_chunk::<VAR_LEN_PREFIX_LEN>()
+        .ok_or(Error::BufferUnderflow)?;
+    let len = u32::from_le_bytes(*len) as usize;
+    rest.get(..len).ok_or(Error::InvalidComponentData)
+}
+
+/// Writes `payload` into `slot` in the layout [`var_len_payload`] reads, zeroing the rest of the slot
+pub fn write_var_len(payload: &[u8], slot: &mut [u8]) -> Result<(), Error> {
+    let (len, rest) = slot
+        .split_first_chunk_mut::<VAR_LEN_PREFIX_LEN>()
+        .ok_or(Error::BufferOverflow)?;
+    let dest = rest.get_mut(..payload.len()).ok_or(Error::BufferOverflow)?;
+    dest.copy_from_slice(payload);
+    *len = (payload.len() as u32).to_le_bytes();
+    rest[payload.len()..].fill(0);
+    Ok(())
 }
 
 impl<'a> From<ComponentView<'a>> for i64 {
@@ -295,6 +514,28 @@ impl core::fmt::Display for ComponentView<'_> {
                 f.write_str("f64")?;
                 core::fmt::Display::fmt(array, f)
             }
+            ComponentView::F16(array) => {
+                f.write_str("f16")?;
+                core::fmt::Display::fmt(array, f)
+            }
+            ComponentView::BF16(array) => {
+                f.write_str("bf16")?;
+                core::fmt::Display::fmt(array, f)
+            }
+            ComponentView::Bytes(_) => {
+                f.write_str("bytes[")?;
+                for byte in self.payload().unwrap_or_default() {
+                    write!(f, "{byte:02x}")?;
+                }
+                f.write_str("]")
+            }
+            ComponentView::String(_) => {
+                write!(f, "string{:?}", self.as_str().unwrap_or_default())
+            }
+            ComponentView::Q15_16(array) => {
+                f.write_str("q15_16")?;
+                core::fmt::Display::fmt(array, f)
+            }
         }
     }
 }
@@ -313,6 +554,10 @@ impl<'a> ComponentView<'a> {
             Self::Bool(ref view) => view.shape(),
             Self::F32(ref view) => view.shape(),
             Self::F64(ref view) => view.shape(),
+            Self::F16(ref view) => view.shape(),
+            Self::BF16(ref view) => view.shape(),
+            Self::Bytes(ref view) | Self::String(ref view) => view.shape(),
+            Self::Q15_16(ref view) => view.shape(),
         }
     }
 
@@ -329,7 +574,51 @@ impl<'a> ComponentView<'a> {
             Self::Bool(_) => PrimType::Bool,
             Self::F32(_) => PrimType::F32,
             Self::F64(_) => PrimType::F64,
+            Self::F16(_) => PrimType::F16,
+            Self::BF16(_) => PrimType::BF16,
+            Self::Bytes(_) => PrimType::Bytes,
+            Self::String(_) => PrimType::String,
+            Self::Q15_16(_) => PrimType::Q15_16,
+        }
+    }
+
+    /// The payload of a bytes or string slot, or `None` for other types
+    pub fn payload(&self) -> Option<&'a [u8]> {
+        match *self {
+            Self::Bytes(view) | Self::String(view) => var_len_payload(view.buf).ok(),
+            _ => None,
+        }
+    }
+
+    /// The text in a string slot, or `None` for other types
+    pub fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            Self::String(_) => core::str::from_utf8(self.payload()?).ok(),
+            _ => None,
+        }
+    }
+
+    /// The value of a scalar `u8`, `u16` or `u32` component as flags, or `None` for other components
+    pub fn as_bits(&self) -> Option<crate::bits::Bits> {
+        use crate::com_de::FromComponentView;
+        crate::bits::Bits::from_component_view(*self).ok()
+    }
+
+    /// Views `data` as a component with the given shape, failing with [`Error::ShapeMismatch`] if `data` doesn't
+    /// hold exactly as many elements as the shape describes
+    pub fn from_slice<T: crate::com_de::ComponentViewElem>(
+        data: &'a [T],
+        shape: &'a [usize],
+    ) -> Result<Self, Error> {
+        let len = shape.iter().try_fold(1usize, |x, &xs| {
+            x.checked_mul(xs).ok_or(Error::OffsetOverflow)
+        })?;
+        if len != data.len() {
+            return Err(Error::ShapeMismatch);
         }
+        Ok(T::component_view(ArrayView::from_buf_shape_unchecked(
+            data, shape,
+        )))
     }
 
     pub fn try_from_bytes_shape(
@@ -386,8 +675,43 @@ impl<'a> ComponentView<'a> {
                 let (buf, _) = <[f64]>::ref_from_prefix_with_elems(buf, len)?;
                 Ok(Self::F64(ArrayView::from_buf_shape_unchecked(buf, shape)))
             }
+            PrimType::F16 => {
+                let (buf, _) = <[f16]>::ref_from_prefix_with_elems(buf, len)?;
+                Ok(Self::F16(ArrayView::from_buf_shape_unchecked(buf, shape)))
+            }
+            PrimType::BF16 => {
+                let (buf, _) = <[bf16]>::ref_from_prefix_with_elems(buf, len)?;
+                Ok(Self::BF16(ArrayView::from_buf_shape_unchecked(buf, shape)))
+            }
+            PrimType::Bytes => {
+                let (buf, _) = <[u8]>::ref_from_prefix_with_elems(buf, len)?;
+                var_len_payload(buf)?;
+                Ok(Self::Bytes(ArrayView::from_buf_shape_unchecked(buf, shape)))
+            }
+            PrimType::String => {
+                let (buf, _) = <[u8]>::ref_from_prefix_with_elems(buf, len)?;
+                core::str::from_utf8(var_len_payload(buf)?)
+                    .map_err(|_| Error::InvalidComponentData)?;
+                Ok(Self::String(ArrayView::from_buf_shape_unchecked(
+                    buf, shape,
+                )))
+            }
+            PrimType::Q15_16 => {
+                let (buf, _) = <[Q15_16]>::ref_from_prefix_with_elems(buf, len)?;
+                Ok(Self::Q15_16(ArrayView::from_buf_shape_unchecked(
+                    buf, shape,
+                )))
+            }
         }
     }
+
+    /// The length in bytes of the view's underlying element buffer
+    pub fn byte_len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    /// The view's underlying element buffer, which can be passed back to [`ComponentView::try_from_bytes_shape`]
+    /// along with [`ComponentView::shape`] and [`ComponentView::prim_type`]
     pub fn as_bytes(&self) -> &[u8] {
         match *self {
             Self::U8(ref view) => view.as_bytes(),
@@ -401,6 +725,10 @@ impl<'a> ComponentView<'a> {
             Self::Bool(ref view) => view.as_bytes(),
             Self::F32(ref view) => view.as_bytes(),
             Self::F64(ref view) => view.as_bytes(),
+            Self::F16(ref view) => view.as_bytes(),
+            Self::BF16(ref view) => view.as_bytes(),
+            Self::Bytes(ref view) | Self::String(ref view) => view.as_bytes(),
+            Self::Q15_16(ref view) => view.as_bytes(),
         }
     }
 
@@ -420,6 +748,14 @@ impl<'a> ComponentView<'a> {
             }
             ComponentView::F32(f32) => Box::new(f32.buf().iter().map(|&x| ElementValue::F32(x))),
             ComponentView::F64(f64) => Box::new(f64.buf().iter().map(|&x| ElementValue::F64(x))),
+            ComponentView::F16(f16) => Box::new(f16.buf().iter().map(|&x| ElementValue::F16(x))),
+            ComponentView::BF16(bf16) => {
+                Box::new(bf16.buf().iter().map(|&x| ElementValue::BF16(x)))
+            }
+            ComponentView::Bytes(slot) | ComponentView::String(slot) => {
+                Box::new(slot.buf().iter().map(|&x| ElementValue::U8(x)))
+            }
+            ComponentView::Q15_16(q) => Box::new(q.buf().iter().map(|&x| ElementValue::Q15_16(x))),
         }
     }
 
@@ -436,7 +772,46 @@ impl<'a> ComponentView<'a> {
             Self::Bool(x) => x.buf().get(i).map(|&x| ElementValue::Bool(x)),
             Self::F32(x) => x.buf().get(i).map(|&x| ElementValue::F32(x)),
             Self::F64(x) => x.buf().get(i).map(|&x| ElementValue::F64(x)),
+            Self::F16(x) => x.buf().get(i).map(|&x| ElementValue::F16(x)),
+            Self::BF16(x) => x.buf().get(i).map(|&x| ElementValue::BF16(x)),
+            Self::Bytes(x) | Self::String(x) => x.buf().get(i).map(|&x| ElementValue::U8(x)),
+            Self::Q15_16(x) => x.buf().get(i).map(|&x| ElementValue::Q15_16(x)),
+        }
+    }
+}
+
+/// Converts a component into lua, scalars become plain values and arrays become nested tables, one level per dimension
+///
+/// Bytes and string slots become lua strings holding just their payload.
+#[cfg(feature = "mlua")]
+impl mlua::IntoLua for ComponentView<'_> {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        if self.prim_type().is_var_len() {
+            let payload = self
+                .payload()
+                .ok_or_else(|| mlua::Error::runtime("invalid bytes or string slot"))?;
+            return lua.create_string(payload).map(mlua::Value::String);
+        }
+        fn nest(
+            lua: &mlua::Lua,
+            view: &ComponentView<'_>,
+            shape: &[usize],
+            offset: usize,
+        ) -> mlua::Result<mlua::Value> {
+            let Some((&len, rest)) = shape.split_first() else {
+                let elem = view
+                    .get(offset)
+                    .ok_or_else(|| mlua::Error::runtime("component view index out of bounds"))?;
+                return mlua::IntoLua::into_lua(elem, lua);
+            };
+            let stride = rest.iter().product::<usize>();
+            let table = lua.create_table_with_capacity(len, 0)?;
+            for i in 0..len {
+                table.raw_push(nest(lua, view, rest, offset + i * stride)?)?;
+            }
+            Ok(mlua::Value::Table(table))
         }
+        nest(lua, &self, self.shape(), 0)
     }
 }
 
@@ -453,9 +828,51 @@ pub enum ElementValue {
     F64(f64),
     F32(f32),
     Bool(bool),
+    F16(f16),
+    BF16(bf16),
+    Q15_16(Q15_16),
 }
 
 impl ElementValue {
+    /// The value as an `i128` if it's an integer or bool, which holds every integer type losslessly
+    pub fn as_i128(&self) -> Option<i128> {
+        match *self {
+            ElementValue::U8(x) => Some(x as i128),
+            ElementValue::U16(x) => Some(x as i128),
+            ElementValue::U32(x) => Some(x as i128),
+            ElementValue::U64(x) => Some(x as i128),
+            ElementValue::I8(x) => Some(x as i128),
+            ElementValue::I16(x) => Some(x as i128),
+            ElementValue::I32(x) => Some(x as i128),
+            ElementValue::I64(x) => Some(x as i128),
+            ElementValue::Bool(x) => Some(x as i128),
+            ElementValue::F32(_)
+            | ElementValue::F64(_)
+            | ElementValue::F16(_)
+            | ElementValue::BF16(_)
+            | ElementValue::Q15_16(_) => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            ElementValue::U8(x) => x.as_bytes(),
+            ElementValue::U16(x) => x.as_bytes(),
+            ElementValue::U32(x) => x.as_bytes(),
+            ElementValue::U64(x) => x.as_bytes(),
+            ElementValue::I8(x) => x.as_bytes(),
+            ElementValue::I16(x) => x.as_bytes(),
+            ElementValue::I32(x) => x.as_bytes(),
+            ElementValue::I64(x) => x.as_bytes(),
+            ElementValue::Bool(x) => x.as_bytes(),
+            ElementValue::F32(x) => x.as_bytes(),
+            ElementValue::F64(x) => x.as_bytes(),
+            ElementValue::F16(x) => x.as_bytes(),
+            ElementValue::BF16(x) => x.as_bytes(),
+            ElementValue::Q15_16(x) => x.as_bytes(),
+        }
+    }
+
     pub fn as_f64(&self) -> f64 {
         match *self {
             ElementValue::U8(x) => x as f64,
@@ -468,6 +885,9 @@ impl ElementValue {
             ElementValue::I64(x) => x as f64,
             ElementValue::F64(x) => x,
             ElementValue::F32(x) => x as f64,
+            ElementValue::F16(x) => x.to_f64(),
+            ElementValue::BF16(x) => x.to_f64(),
+            ElementValue::Q15_16(x) => x.to_f64(),
             ElementValue::Bool(x) => {
                 if x {
                     1.0
@@ -490,6 +910,9 @@ impl ElementValue {
             ElementValue::I64(x) => x as f32,
             ElementValue::F32(x) => x,
             ElementValue::F64(x) => x as f32,
+            ElementValue::F16(x) => x.to_f32(),
+            ElementValue::BF16(x) => x.to_f32(),
+            ElementValue::Q15_16(x) => x.to_f32(),
             ElementValue::Bool(x) => {
                 if x {
                     1.0
@@ -501,6 +924,29 @@ impl ElementValue {
     }
 }
 
+#[cfg(feature = "mlua")]
+impl mlua::IntoLua for ElementValue {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        use mlua::IntoLua;
+        match self {
+            ElementValue::U8(v) => v.into_lua(lua),
+            ElementValue::U16(v) => v.into_lua(lua),
+            ElementValue::U32(v) => v.into_lua(lua),
+            ElementValue::U64(v) => v.into_lua(lua),
+            ElementValue::I8(v) => v.into_lua(lua),
+            ElementValue::I16(v) => v.into_lua(lua),
+            ElementValue::I32(v) => v.into_lua(lua),
+            ElementValue::I64(v) => v.into_lua(lua),
+            ElementValue::F64(v) => v.into_lua(lua),
+            ElementValue::F32(v) => v.into_lua(lua),
+            ElementValue::Bool(v) => v.into_lua(lua),
+            ElementValue::F16(v) => v.to_f64().into_lua(lua),
+            ElementValue::BF16(v) => v.to_f64().into_lua(lua),
+            ElementValue::Q15_16(v) => v.to_f64().into_lua(lua),
+        }
+    }
+}
+
 #[derive(
     TryFromBytes, Unaligned, Immutable, KnownLayout, PartialEq, Debug, Clone, Copy, IntoBytes,
 )]
@@ -510,6 +956,10 @@ pub enum PacketTy {
     Table = 1,
     TimeSeries = 2,
     MsgWithTimestamp = 3,
+    /// A table carrying only the fields that changed, see [`crate::delta`]
+    DeltaTable = 4,
+    /// A msg the receiver acknowledges by echoing its sequence number, see [`LenPacket::msg_with_ack`]
+    MsgWithAck = 5,
 }
 
 pub type PacketId = [u8; 2];
@@ -616,7 +1066,18 @@ impl LenPacket {
 
     pub fn msg_with_timestamp(id: PacketId, timestamp: Timestamp, cap: usize) -> Self {
         let mut pkt = Self::new(PacketTy::MsgWithTimestamp, id, cap + size_of::<Timestamp>());
-        pkt.extend_from_slice(timestamp.as_bytes());
+        pkt.extend_from_slice(&timestamp.to_le_bytes());
+        pkt
+    }
+
+    /// A msg whose body starts with the little endian sequence number `seq`
+    ///
+    /// The receiver replies with an `Ack` carrying the same `seq`, letting the sender retransmit msgs that must not be
+    /// dropped, like arm and disarm commands, over lossy links. Retransmits reuse `seq`, so receivers can drop copies
+    /// of a msg they have already handled.
+    pub fn msg_with_ack(id: PacketId, seq: u32, cap: usize) -> Self {
+        let mut pkt = Self::new(PacketTy::MsgWithAck, id, cap + size_of::<u32>());
+        pkt.extend_from_slice(&seq.to_le_bytes());
         pkt
     }
 
@@ -624,6 +1085,10 @@ impl LenPacket {
         Self::new(PacketTy::Table, id, cap)
     }
 
+    pub fn delta_table(id: PacketId, cap: usize) -> Self {
+        Self::new(PacketTy::DeltaTable, id, cap)
+    }
+
     pub fn time_series(id: PacketId, cap: usize) -> Self {
         Self::new(PacketTy::TimeSeries, id, cap)
     }
@@ -698,6 +1163,27 @@ impl LenPacket {
         .expect("len packet was not a valid `Packet`")
     }
 
+    /// Ends the packet with a checksum of everything in it, see [`crate::checksum`]
+    ///
+    /// Nothing can be added to the packet afterwards until [`Self::remove_checksum`] is called.
+    pub fn append_checksum(&mut self, crc: &impl crate::checksum::Crc32) {
+        self.inner[4] |= crate::checksum::CHECKSUM_FLAG;
+        let checksum = crc.crc32(&self.inner[4..]);
+        self.extend_from_slice(&checksum.to_le_bytes());
+    }
+
+    /// Undoes [`Self::append_checksum`], leaving packets without a checksum untouched
+    pub fn remove_checksum(&mut self) {
+        if !crate::checksum::has_checksum(&self.inner[4..]) {
+            return;
+        }
+        self.inner[4] &= !crate::checksum::CHECKSUM_FLAG;
+        let len = self.pkt_len() - crate::checksum::CHECKSUM_LEN as u32;
+        self.inner
+            .truncate(self.inner.len() - crate::checksum::CHECKSUM_LEN);
+        self.inner[..4].copy_from_slice(&len.to_le_bytes());
+    }
+
     pub fn clear(&mut self) {
         self.inner[..4].copy_from_slice(&(PACKET_HEADER_LEN as u32).to_le_bytes());
         self.inner.truncate(PACKET_HEADER_LEN + 4);
@@ -749,12 +1235,13 @@ impl<B: IoBuf> OwnedPacket<B> {
                 req_id,
                 buf,
                 timestamp: None,
+                ack_seq: None,
             }),
             PacketTy::MsgWithTimestamp => {
                 let timestamp_buf = buf
                     .get(..size_of::<Timestamp>())
                     .ok_or(Error::InvalidPacket)?;
-                let timestamp = Timestamp::read_from_bytes(timestamp_buf)?;
+                let timestamp = Timestamp::try_from(timestamp_buf)?;
                 let buf = buf
                     .into_inner()
                     .try_slice(PACKET_HEADER_LEN + offset + size_of::<Timestamp>()..)
@@ -764,9 +1251,36 @@ impl<B: IoBuf> OwnedPacket<B> {
                     req_id,
                     buf,
                     timestamp: Some(timestamp),
+                    ack_seq: None,
+                })
+            }
+            PacketTy::MsgWithAck => {
+                let seq_buf = buf.get(..size_of::<u32>()).ok_or(Error::InvalidPacket)?;
+                let seq = u32::from_le_bytes(seq_buf.try_into().map_err(|_| Error::InvalidPacket)?);
+                let buf = buf
+                    .into_inner()
+                    .try_slice(PACKET_HEADER_LEN + offset + size_of::<u32>()..)
+                    .ok_or(Error::InvalidPacket)?;
+                OwnedPacket::Msg(MsgBuf {
+                    id,
+                    req_id,
+                    buf,
+                    timestamp: None,
+                    ack_seq: Some(seq),
                 })
             }
-            PacketTy::Table => OwnedPacket::Table(OwnedTable { id, req_id, buf }),
+            PacketTy::Table => OwnedPacket::Table(OwnedTable {
+                id,
+                req_id,
+                buf,
+                delta: false,
+            }),
+            PacketTy::DeltaTable => OwnedPacket::Table(OwnedTable {
+                id,
+                req_id,
+                buf,
+                delta: true,
+            }),
             PacketTy::TimeSeries => {
                 let time_series = TimeSeries::try_ref_from_bytes(&buf)?;
                 let len = time_series.len()?;
@@ -797,6 +1311,8 @@ pub struct OwnedTable<B: IoBuf> {
     pub id: PacketId,
     pub req_id: RequestId,
     pub buf: Slice<B>,
+    /// Whether `buf` is a delta table, holding only the fields that changed, see [`crate::delta`]
+    pub delta: bool,
 }
 
 impl<B: IoBuf> OwnedTable<B> {
@@ -806,7 +1322,14 @@ impl<B: IoBuf> OwnedTable<B> {
         sink: &mut D,
     ) -> Result<Result<(), D::Error>, Error> {
         let vtable = registry.get(&self.id).ok_or(Error::VTableNotFound)?;
-        vtable.apply(stellarator_buf::deref(&self.buf), sink)
+        let buf = stellarator_buf::deref(&self.buf);
+        if self.delta {
+            #[cfg(feature = "alloc")]
+            return vtable.apply_delta(buf, sink);
+            #[cfg(not(feature = "alloc"))]
+            return Err(Error::InvalidPacket);
+        }
+        vtable.apply(buf, sink)
     }
 }
 
@@ -815,6 +1338,8 @@ pub struct MsgBuf<B: IoBuf> {
     pub id: PacketId,
     pub req_id: RequestId,
     pub timestamp: Option<Timestamp>,
+    /// The sequence number to acknowledge, for msgs sent as a [`PacketTy::MsgWithAck`]
+    pub ack_seq: Option<u32>,
     pub buf: Slice<B>,
 }
 
@@ -892,14 +1417,24 @@ pub struct Timestamp(pub i64);
 #[cfg(feature = "hifitime")]
 impl From<Timestamp> for hifitime::Epoch {
     fn from(val: Timestamp) -> Self {
-        hifitime::Epoch::from_unix_milliseconds((val.0 as f64) / 1000.0)
+        let micros = hifitime::Duration::from_total_nanoseconds(val.0 as i128 * 1000);
+        hifitime::Epoch::from_unix_duration(micros)
     }
 }
 
 #[cfg(feature = "hifitime")]
-impl From<hifitime::Epoch> for Timestamp {
-    fn from(epoch: hifitime::Epoch) -> Self {
-        Timestamp((epoch.to_unix_milliseconds() * 1000.0) as i64)
+impl TryFrom<hifitime::Epoch> for Timestamp {
+    type Error = Error;
+
+    /// Converts an epoch into a timestamp, truncating any sub-microsecond precision towards the past.
+    ///
+    /// Fails with [`Error::TimestampOutOfRange`] if the epoch can't be represented as an `i64` number of
+    /// microseconds since the unix epoch.
+    fn try_from(epoch: hifitime::Epoch) -> Result<Self, Self::Error> {
+        let nanos = epoch.to_unix_duration().total_nanoseconds();
+        i64::try_from(nanos.div_euclid(1000))
+            .map(Timestamp)
+            .map_err(|_| Error::TimestampOutOfRange)
     }
 }
 
@@ -921,6 +1456,15 @@ impl Timestamp {
     }
 }
 
+impl TryFrom<&[u8]> for Timestamp {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let bytes: [u8; 8] = bytes.try_into().map_err(|_| Error::BufferUnderflow)?;
+        Ok(Timestamp::from_le_bytes(bytes))
+    }
+}
+
 impl Add<Duration> for Timestamp {
     type Output = Timestamp;
 
@@ -979,6 +1523,10 @@ impl From<std::time::SystemTime> for Timestamp {
     }
 }
 
+/// A message that expects a reply
+///
+/// Requests are sent with a [`RequestId`] in their header (see [`IntoLenPacket::with_request_id`]), and every reply,
+/// including an `ErrorResponse`, echoes it back. That id is what lets one connection have several requests in flight.
 pub trait Request {
     type Reply<B: IoBuf + Clone>: TryFromPacket<B>;
 }
@@ -1037,6 +1585,52 @@ impl<R: Request> Request for &'_ R {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_prim_type_serde_names() {
+        use serde::de::{IntoDeserializer, value};
+
+        for prim_type in [
+            PrimType::U8,
+            PrimType::U16,
+            PrimType::U32,
+            PrimType::U64,
+            PrimType::I8,
+            PrimType::I16,
+            PrimType::I32,
+            PrimType::I64,
+            PrimType::Bool,
+            PrimType::F32,
+            PrimType::F64,
+            PrimType::F16,
+            PrimType::BF16,
+            PrimType::Bytes,
+            PrimType::String,
+            PrimType::Q15_16,
+        ] {
+            let name: value::StrDeserializer<'_, value::Error> =
+                prim_type.as_str().into_deserializer();
+            assert_eq!(PrimType::deserialize(name), Ok(prim_type));
+        }
+    }
+
+    #[test]
+    fn test_component_view_from_slice() {
+        let data = [1.0f64, 2.0, 3.0];
+        let view = ComponentView::from_slice(&data, &[3]).unwrap();
+        assert_eq!(view.prim_type(), PrimType::F64);
+        assert_eq!(view.shape(), &[3]);
+        assert_eq!(view.as_bytes(), data.as_bytes());
+
+        assert!(matches!(
+            ComponentView::from_slice(&data, &[2, 2]),
+            Err(Error::ShapeMismatch)
+        ));
+        assert!(matches!(
+            ComponentView::from_slice(&[7u8], &[]),
+            Ok(ComponentView::U8(_))
+        ));
+    }
+
     #[test]
     fn test_padding() {
         assert_eq!(PrimType::F64.padding(1), 7);
@@ -1048,4 +1642,203 @@ mod tests {
         assert_eq!(PrimType::U16.padding(12), 0);
         assert_eq!(PrimType::U16.padding(11), 1);
     }
+
+    #[test]
+    fn test_checked_cast_overflow() {
+        assert_eq!(PrimType::U8.checked_cast(ElementValue::U64(256)), None);
+        assert_eq!(
+            PrimType::U8.checked_cast(ElementValue::U64(255)),
+            Some(ElementValue::U8(255))
+        );
+        assert_eq!(PrimType::U32.checked_cast(ElementValue::I64(-1)), None);
+        assert_eq!(PrimType::I8.checked_cast(ElementValue::F64(-129.0)), None);
+        assert_eq!(
+            PrimType::I8.checked_cast(ElementValue::F64(-128.5)),
+            Some(ElementValue::I8(-128))
+        );
+        assert_eq!(
+            PrimType::U64.checked_cast(ElementValue::F64(u64::MAX as f64)),
+            None
+        );
+        assert_eq!(
+            PrimType::I64.checked_cast(ElementValue::I64(i64::MAX)),
+            Some(ElementValue::I64(i64::MAX))
+        );
+        assert_eq!(
+            PrimType::I16.checked_cast(ElementValue::F64(f64::NAN)),
+            None
+        );
+        assert_eq!(PrimType::F32.checked_cast(ElementValue::F64(1e39)), None);
+        assert_eq!(
+            PrimType::F32.checked_cast(ElementValue::F64(f64::INFINITY)),
+            Some(ElementValue::F32(f32::INFINITY))
+        );
+        assert_eq!(PrimType::Bool.checked_cast(ElementValue::I64(2)), None);
+        assert_eq!(
+            PrimType::Bool.checked_cast(ElementValue::I64(1)),
+            Some(ElementValue::Bool(true))
+        );
+    }
+
+    #[test]
+    fn test_half_prim_types() {
+        assert_eq!(PrimType::F16.size(), 2);
+        assert_eq!(PrimType::BF16.size(), 2);
+        assert_eq!(PrimType::F16.padding(3), 1);
+        let buf = [f16::from_f32(1.5), f16::from_f32(-2.0)];
+        let view =
+            ComponentView::try_from_bytes_shape(buf.as_bytes(), &[2], PrimType::F16).unwrap();
+        assert_eq!(view.prim_type(), PrimType::F16);
+        assert_eq!(view.get(1).map(|v| v.as_f64()), Some(-2.0));
+        assert_eq!(PrimType::F16.checked_cast(ElementValue::F64(1e5)), None);
+        assert_eq!(
+            PrimType::BF16.checked_cast(ElementValue::F64(1e5)),
+            Some(ElementValue::BF16(bf16::from_f64(1e5)))
+        );
+    }
+
+    #[test]
+    fn test_fixed_prim_type() {
+        assert_eq!(PrimType::Q15_16.size(), 4);
+        assert_eq!(PrimType::Q15_16.padding(2), 2);
+        let buf = [Q15_16::from_f64(0.25), Q15_16::from_f64(-3.0)];
+        let view =
+            ComponentView::try_from_bytes_shape(buf.as_bytes(), &[2], PrimType::Q15_16).unwrap();
+        assert_eq!(view.prim_type(), PrimType::Q15_16);
+        assert_eq!(view.get(0).map(|v| v.as_f64()), Some(0.25));
+        assert_eq!(view.get(1).map(|v| v.as_f32()), Some(-3.0));
+        assert_eq!(PrimType::Q15_16.checked_cast(ElementValue::F64(4e4)), None);
+        assert_eq!(
+            PrimType::Q15_16.checked_cast(ElementValue::F64(f64::NAN)),
+            None
+        );
+        assert_eq!(
+            PrimType::Q15_16.saturating_cast(ElementValue::F64(-4e4)),
+            ElementValue::Q15_16(Q15_16::MIN)
+        );
+    }
+
+    #[test]
+    fn test_var_len_prim_types() {
+        assert!(PrimType::String.is_var_len() && !PrimType::U8.is_var_len());
+        assert_eq!(PrimType::Bytes.padding(2), 2);
+        let mut slot = [0xffu8; 16];
+        write_var_len(b"armed", &mut slot).unwrap();
+        assert_eq!(&slot[..4], &5u32.to_le_bytes());
+        assert_eq!(&slot[9..], &[0; 7]);
+        assert_eq!(var_len_payload(&slot).unwrap(), b"armed");
+
+        let view = ComponentView::try_from_bytes_shape(&slot, &[16], PrimType::String).unwrap();
+        assert_eq!(view.prim_type(), PrimType::String);
+        assert_eq!(view.as_str(), Some("armed"));
+        assert_eq!(view.as_bytes(), &slot);
+        let view = ComponentView::try_from_bytes_shape(&slot, &[16], PrimType::Bytes).unwrap();
+        assert_eq!(view.payload(), Some(&b"armed"[..]));
+        assert_eq!(view.as_str(), None);
+
+        assert!(write_var_len(&[0; 13], &mut slot).is_err());
+        slot[..4].copy_from_slice(&13u32.to_le_bytes());
+        assert!(ComponentView::try_from_bytes_shape(&slot, &[16], PrimType::Bytes).is_err());
+        write_var_len(&[0xff, 0xfe], &mut slot).unwrap();
+        assert!(ComponentView::try_from_bytes_shape(&slot, &[16], PrimType::String).is_err());
+    }
+
+    #[test]
+    fn test_saturating_cast() {
+        assert_eq!(
+            PrimType::U8.saturating_cast(ElementValue::U64(1000)),
+            PrimType::U8.max_value()
+        );
+        assert_eq!(
+            PrimType::U16.saturating_cast(ElementValue::I32(-5)),
+            PrimType::U16.min_value()
+        );
+        assert_eq!(
+            PrimType::I32.saturating_cast(ElementValue::F64(1e12)),
+            ElementValue::I32(i32::MAX)
+        );
+        assert_eq!(
+            PrimType::F32.saturating_cast(ElementValue::F64(-1e39)),
+            ElementValue::F32(f32::MIN)
+        );
+        assert_eq!(
+            PrimType::I64.saturating_cast(ElementValue::U64(u64::MAX)),
+            ElementValue::I64(i64::MAX)
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_msg_timestamp_little_endian() {
+        let ts = Timestamp(0x0102_0304_0506_0708);
+        let pkt = LenPacket::msg_with_timestamp([1, 2], ts, 0);
+        assert_eq!(
+            &pkt.inner[4 + PACKET_HEADER_LEN..][..8],
+            &[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]
+        );
+        let OwnedPacket::Msg(msg) = OwnedPacket::parse_with_offset(pkt.inner, 4).unwrap() else {
+            panic!("expected msg packet");
+        };
+        assert_eq!(msg.timestamp, Some(ts));
+        assert!(Timestamp::try_from(&[0u8; 4][..]).is_err());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_msg_with_ack() {
+        let mut pkt = LenPacket::msg_with_ack([1, 2], 0x0102_0304, 2).with_request_id(7);
+        pkt.extend_from_slice(&[9, 9]);
+        assert_eq!(
+            &pkt.inner[4 + PACKET_HEADER_LEN..],
+            &[0x04, 0x03, 0x02, 0x01, 9, 9]
+        );
+        let OwnedPacket::Msg(msg) = OwnedPacket::parse_with_offset(pkt.inner, 4).unwrap() else {
+            panic!("expected msg packet");
+        };
+        assert_eq!(msg.ack_seq, Some(0x0102_0304));
+        assert_eq!((msg.id, msg.req_id), ([1, 2], 7));
+        assert_eq!(stellarator_buf::deref(&msg.buf), &[9, 9]);
+    }
+
+    #[test]
+    fn test_component_view_bytes_round_trip() {
+        let views = [
+            ComponentView::F64(ArrayView::from_buf_shape_unchecked(
+                &[1.0, 2.0, 3.0, 4.0],
+                &[2, 2],
+            )),
+            ComponentView::U16(ArrayView::from_buf_shape_unchecked(&[1, 2, 3], &[3])),
+            ComponentView::Bool(ArrayView::from_buf_shape_unchecked(&[true, false], &[2])),
+        ];
+        for view in views {
+            assert_eq!(
+                view.byte_len(),
+                view.shape().iter().product::<usize>() * view.prim_type().size()
+            );
+            let copy = ComponentView::try_from_bytes_shape(
+                view.as_bytes(),
+                view.shape(),
+                view.prim_type(),
+            )
+            .unwrap();
+            assert_eq!(copy.prim_type(), view.prim_type());
+            assert_eq!(copy.shape(), view.shape());
+            assert_eq!(copy.as_bytes(), view.as_bytes());
+        }
+    }
+
+    #[cfg(feature = "hifitime")]
+    #[test]
+    fn test_timestamp_epoch_round_trip() {
+        for ts in [
+            Timestamp(0),
+            Timestamp(1),
+            Timestamp(-1),
+            Timestamp(1_718_000_000_123_457),
+            Timestamp(-62_135_596_800_000_001),
+        ] {
+            let epoch = hifitime::Epoch::from(ts);
+            assert_eq!(Timestamp::try_from(epoch).unwrap(), ts);
+        }
+    }
 }
@@ -49,13 +49,14 @@
 use core::ops::Range;
 
 use serde::{Deserialize, Serialize};
-use zerocopy::{FromBytes, IntoBytes, TryFromBytes};
+use zerocopy::{FromBytes, IntoBytes, KnownLayout, TryFromBytes};
 
 use crate::{
     buf::Buf,
     com_de::Decomponentize,
     error::Error,
-    types::{ComponentId, ComponentView, PacketId, PrimType, Timestamp},
+    schema::{decode_fn, elem_count, schema_hash},
+    types::{ComponentId, ComponentView, Msg, PacketId, PrimType, Timestamp},
 };
 
 /// Operations that can be performed in a VTable
@@ -104,6 +105,29 @@ pub struct Field {
     pub arg: OpRef,
 }
 
+/// The byte order of a field's values, attached to a field with [`builder::endian`]
+///
+/// Fields without one are little endian. Big endian fields can't be viewed in place, so [`VTable::apply`] copies and
+/// swaps them, and [`VTable::realize_fields`] fails on them with [`Error::ByteOrder`].
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, postcard_schema::Schema,
+)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+/// The [`stable_hash`](crate::schema::Schema::stable_hash) of a field's schema, attached with
+/// [`builder::hashed_schema`]
+///
+/// Realizing a field fails with [`Error::SchemaMismatch`] if the hash doesn't match the schema it wraps, and
+/// [`VTable::verify_schemas`] checks each field's schema against the ones a consumer already has registered.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, postcard_schema::Schema,
+)]
+pub struct SchemaHash(pub u64);
+
 const _ASSERT_OP_SIZE: () = const {
     assert!(core::mem::size_of::<Op>() <= 8);
 };
@@ -157,6 +181,9 @@ type DefaultFields = alloc::vec::Vec<Field>;
 #[cfg(not(feature = "alloc"))]
 type DefaultFields = heapless::Vec<Field, 32>;
 
+/// How deeply [`VTable::realize`] follows the ops an op refers to, which is far more than any real vtable needs
+const MAX_OP_DEPTH: usize = 16;
+
 /// A description of the layout of a table
 #[derive(Debug, Serialize, Deserialize, Clone, Default, postcard_schema::Schema)]
 pub struct VTable<
@@ -223,7 +250,22 @@ pub struct RealizedField<'a> {
     pub shape: &'a [usize],
     pub ty: PrimType,
     pub view: Option<ComponentView<'a>>,
+    /// The timestamp of the field, or of its first row if it has several
     pub timestamp: Option<Timestamp>,
+    pub endian: Endian,
+    /// How many values of the schema the field holds back to back, each with its own timestamp, see
+    /// [`builder::row_timestamps`]
+    pub rows: usize,
+    /// Where in the table the field's timestamps are, if they're read from the table
+    pub timestamp_range: Option<Range<usize>>,
+    /// The [`schema_hash`] of the field's schema
+    pub schema_hash: u64,
+}
+
+/// The bytes of a realized field, and of its row timestamps if it has more than one row
+struct FieldData<'a> {
+    data: &'a [u8],
+    timestamps: &'a [u8],
 }
 
 impl<'a> RealizedOp<'a> {
@@ -287,6 +329,18 @@ impl<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> VTable<Ops, Data, Fields>
         op_ref: OpRef,
         table: Option<&'a [u8]>,
     ) -> Result<RealizedOp<'a>, Error> {
+        self.realize_nested(op_ref, table, MAX_OP_DEPTH)
+    }
+
+    /// Realizes `op_ref`, following at most `depth` nested ops so a cyclic vtable fails rather than overflowing the
+    /// stack
+    fn realize_nested<'a>(
+        &'a self,
+        op_ref: OpRef,
+        table: Option<&'a [u8]>,
+        depth: usize,
+    ) -> Result<RealizedOp<'a>, Error> {
+        let depth = depth.checked_sub(1).ok_or(Error::InvalidOp)?;
         let op = self.get_op(op_ref)?;
         match op {
             Op::Data { offset, len } => {
@@ -310,29 +364,32 @@ impl<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> VTable<Ops, Data, Fields>
             }
             Op::Component { component_id } => {
                 let component_id = self
-                    .realize(*component_id, table)?
+                    .realize_nested(*component_id, table, depth)?
                     .as_component_id()
                     .ok_or(Error::InvalidOp)?;
                 Ok(RealizedOp::Component(RealizedComponent { component_id }))
             }
             Op::Schema { ty, dim, arg } => {
                 let ty = self
-                    .realize(*ty, table)?
+                    .realize_nested(*ty, table, depth)?
                     .as_prim_ty()
                     .ok_or(Error::InvalidOp)?;
                 let dim = self
-                    .realize(*dim, table)?
+                    .realize_nested(*dim, table, depth)?
                     .as_slice()
                     .ok_or(Error::InvalidOp)?;
                 let dim = <[u64]>::try_ref_from_bytes(dim)?;
                 Ok(RealizedOp::Schema(RealizedSchema { ty, dim, arg: *arg }))
             }
             Op::Timestamp { source, arg } => {
-                let source = self.realize(*source, table)?;
-                let timestamp = if let Some(data) = source.as_slice() {
-                    Some(Timestamp::read_from_bytes(data)?)
-                } else {
-                    None
+                let source = self.realize_nested(*source, table, depth)?;
+                let timestamp = match source.as_slice() {
+                    // a region of several timestamps has one per row of the field, this is the first
+                    Some(data) if data.len() > size_of::<Timestamp>() => {
+                        Some(Timestamp::try_from(&data[..size_of::<Timestamp>()])?)
+                    }
+                    Some(data) => Some(Timestamp::try_from(data)?),
+                    None => None,
                 };
                 Ok(RealizedOp::Timestamp(RealizedTimestamp {
                     timestamp,
@@ -343,7 +400,7 @@ impl<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> VTable<Ops, Data, Fields>
             Op::None => Ok(RealizedOp::None),
             Op::Ext { arg, id, data } => {
                 let data = self
-                    .realize(*data, table)?
+                    .realize_nested(*data, table, depth)?
                     .as_slice()
                     .ok_or(Error::InvalidOp)?;
                 Ok(RealizedOp::Ext(RealizedExt {
@@ -360,57 +417,182 @@ impl<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> VTable<Ops, Data, Fields>
     /// `realized_fields` loops through each field, turning each [`Offset`] into a reference, and evaluating any [`Op`]
     /// Evaluates each field in the VTable, returning an iterator of [`RealizedField`]s
     ///
-    /// This turns each [`Offset`] into a reference and evaluates any [`Op`]s
+    /// This turns each [`Offset`] into a reference and evaluates any [`Op`]s. Fields with several rows are viewed at
+    /// their first row.
     pub fn realize_fields<'a>(
         &'a self,
         table: Option<&'a [u8]>,
     ) -> impl Iterator<Item = Result<RealizedField<'a>, Error>> + 'a {
         self.fields.iter().map(move |field| {
-            let mut realized_op = self.realize(field.arg, table)?;
-            let mut timestamp: Option<RealizedTimestamp> = None;
-            let mut schema: Option<RealizedSchema<'_>> = None;
-            loop {
-                match realized_op {
-                    RealizedOp::Component(ref component) => {
-                        let RealizedComponent { component_id } = *component;
-
-                        let schema = schema.as_ref().ok_or(Error::SchemaNotFound)?;
-                        // NOTE(sphw): bogan version of zerocopy::transmute_ref
-                        // In the future this will need to also support 32 bit systems
-                        // remove when https://github.com/google/zerocopy/pull/2428 is merged and released
-                        let shape: &[usize] = <[usize]>::ref_from_bytes(schema.dim.as_bytes())?;
-                        let view = if let Some(table) = table {
-                            let offset = field.offset.to_index();
-                            let data = table
-                                .get(offset..offset + field.len as usize)
-                                .ok_or(Error::BufferUnderflow)?;
-                            Some(ComponentView::try_from_bytes_shape(data, shape, schema.ty)?)
-                        } else {
-                            None
-                        };
-                        return Ok(RealizedField {
-                            component_id,
-                            view,
-                            timestamp: timestamp.and_then(|t| t.timestamp),
-                            shape,
-                            ty: schema.ty,
-                        });
-                    }
-                    RealizedOp::Schema(s) => {
-                        let s = schema.insert(s);
-                        realized_op = self.realize(s.arg, table)?;
-                    }
-                    RealizedOp::Timestamp(t) => {
-                        let t = timestamp.insert(t);
-                        realized_op = self.realize(t.arg, table)?;
+            let (mut realized, data) = self.realize_field(field, table)?;
+            if let Some(FieldData { data, .. }) = data {
+                if realized.endian == Endian::Big && needs_swap(realized.ty) {
+                    return Err(Error::ByteOrder);
+                }
+                realized.view = Some(ComponentView::try_from_bytes_shape(
+                    data,
+                    realized.shape,
+                    realized.ty,
+                )?);
+            }
+            Ok(realized)
+        })
+    }
+
+    /// Realizes a field without viewing its value, returning the field's bytes when there's a table
+    ///
+    /// Timestamps read from the table are already swapped to native order for big endian fields.
+    fn realize_field<'a>(
+        &'a self,
+        field: &Field,
+        table: Option<&'a [u8]>,
+    ) -> Result<(RealizedField<'a>, Option<FieldData<'a>>), Error> {
+        let mut realized_op = self.realize(field.arg, table)?;
+        let mut timestamp: Option<RealizedTimestamp> = None;
+        let mut schema: Option<RealizedSchema<'_>> = None;
+        let mut endian = Endian::Little;
+        let mut expected_hash: Option<SchemaHash> = None;
+        // an acyclic chain visits each op at most once, so anything longer is a cycle
+        for _ in 0..=self.ops.as_slice().len() {
+            match realized_op {
+                RealizedOp::Component(ref component) => {
+                    let RealizedComponent { component_id } = *component;
+
+                    let schema = schema.as_ref().ok_or(Error::SchemaNotFound)?;
+                    let schema_hash = schema_hash(schema.ty, schema.dim);
+                    if let Some(SchemaHash(expected)) = expected_hash {
+                        if expected != schema_hash {
+                            return Err(Error::SchemaMismatch {
+                                component_id,
+                                expected,
+                                found: schema_hash,
+                            });
+                        }
                     }
-                    RealizedOp::Ext(e) => {
-                        realized_op = self.realize(e.arg, table)?;
+                    // NOTE(sphw): bogan version of zerocopy::transmute_ref
+                    // In the future this will need to also support 32 bit systems
+                    // remove when https://github.com/google/zerocopy/pull/2428 is merged and released
+                    let shape: &[usize] = <[usize]>::ref_from_bytes(schema.dim.as_bytes())?;
+                    let timestamp_range = timestamp.as_ref().and_then(|t| t.range.clone());
+                    let rows = timestamp_range
+                        .as_ref()
+                        .map_or(1, |range| (range.len() / size_of::<Timestamp>()).max(1));
+                    let data = if let Some(table) = table {
+                        let offset = field.offset.to_index();
+                        let data = table
+                            .get(offset..offset + field.len as usize)
+                            .ok_or(Error::BufferUnderflow)?;
+                        let timestamps = match timestamp_range.clone() {
+                            Some(range) if rows > 1 => {
+                                table.get(range).ok_or(Error::BufferUnderflow)?
+                            }
+                            _ => &[] as &[u8],
+                        };
+                        Some(FieldData { data, timestamps })
+                    } else {
+                        None
+                    };
+                    let timestamp = timestamp.and_then(|t| match t.timestamp {
+                        Some(ts) if endian == Endian::Big && t.range.is_some() => {
+                            Some(Timestamp(ts.0.swap_bytes()))
+                        }
+                        ts => ts,
+                    });
+                    let realized = RealizedField {
+                        component_id,
+                        view: None,
+                        timestamp,
+                        shape,
+                        ty: schema.ty,
+                        endian,
+                        rows,
+                        timestamp_range,
+                        schema_hash,
+                    };
+                    return Ok((realized, data));
+                }
+                RealizedOp::Schema(s) => {
+                    let s = schema.insert(s);
+                    realized_op = self.realize(s.arg, table)?;
+                }
+                RealizedOp::Timestamp(t) => {
+                    let t = timestamp.insert(t);
+                    realized_op = self.realize(t.arg, table)?;
+                }
+                RealizedOp::Ext(e) if e.id == Endian::ID => {
+                    endian = postcard::from_bytes(e.data)?;
+                    realized_op = self.realize(e.arg, table)?;
+                }
+                RealizedOp::Ext(e) if e.id == SchemaHash::ID => {
+                    expected_hash = Some(postcard::from_bytes(e.data)?);
+                    realized_op = self.realize(e.arg, table)?;
+                }
+                RealizedOp::Ext(e) => {
+                    realized_op = self.realize(e.arg, table)?;
+                }
+                _ => return Err(Error::InvalidOp),
+            }
+        }
+        Err(Error::InvalidOp)
+    }
+
+    /// Checks that the VTable is self-consistent, without needing a table to apply it to
+    ///
+    /// Every field is realized, and its length is checked against the size of its schema times its rows. Any timestamp
+    /// sourced from the table must also be a whole number of [`Timestamp`]s.
+    pub fn validate(&self) -> Result<(), Error> {
+        for (field, res) in self.fields.iter().zip(self.realize_fields(None)) {
+            let RealizedField {
+                shape, ty, rows, ..
+            } = res?;
+            let size = shape
+                .iter()
+                .try_fold(ty.size(), |size, &dim| size.checked_mul(dim))
+                .and_then(|size| size.checked_mul(rows))
+                .ok_or(Error::OffsetOverflow)?;
+            if size != field.len as usize {
+                return Err(Error::FieldSizeMismatch);
+            }
+        }
+        for op in self.ops.iter() {
+            if let Op::Timestamp { source, .. } = op {
+                let source = self.realize(*source, None)?;
+                if let Some(range) = source.as_table_range() {
+                    if range.is_empty() || range.len() % size_of::<Timestamp>() != 0 {
+                        return Err(Error::InvalidOp);
                     }
-                    _ => return Err(Error::InvalidOp),
                 }
             }
-        })
+        }
+        Ok(())
+    }
+
+    /// Checks each field's schema against the schema hash a consumer has registered for its component
+    ///
+    /// `registered` returns the [`schema_hash`] of the schema already known for a component, or `None` if the
+    /// component is new. The first field that disagrees fails with [`Error::SchemaMismatch`], naming the component, so
+    /// a producer built against an old schema is rejected instead of having its bytes misparsed.
+    pub fn verify_schemas(
+        &self,
+        mut registered: impl FnMut(ComponentId) -> Option<u64>,
+    ) -> Result<(), Error> {
+        for res in self.realize_fields(None) {
+            let RealizedField {
+                component_id,
+                schema_hash,
+                ..
+            } = res?;
+            if let Some(expected) = registered(component_id) {
+                if expected != schema_hash {
+                    return Err(Error::SchemaMismatch {
+                        component_id,
+                        expected,
+                        found: schema_hash,
+                    });
+                }
+            }
+        }
+        Ok(())
     }
 
     /// Parses the passed in table, and sinks the values into the sink
@@ -418,25 +600,522 @@ impl<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> VTable<Ops, Data, Fields>
     ///
     /// This evaluates each field in the VTable against the provided table data and
     /// applies the resulting values to the sink
+    ///
+    /// Fields that can't be viewed in place, because they're big endian or misaligned, are copied into an aligned
+    /// buffer first when the `alloc` feature is enabled. Each row of a field with several rows is applied separately,
+    /// with its own timestamp.
     pub fn apply<D: Decomponentize>(
         &self,
         table: &[u8],
         sink: &mut D,
     ) -> Result<Result<(), D::Error>, Error> {
-        for res in self.realize_fields(Some(table)) {
-            let RealizedField {
-                component_id,
-                view,
-                timestamp,
-                ..
-            } = res?;
-            let view = view.expect("table not found");
-            if let Err(err) = sink.apply_value(component_id, view, timestamp) {
+        for field in self.fields.iter() {
+            if let Err(err) = self.apply_field(field, table, sink)? {
                 return Ok(Err(err));
             }
         }
         Ok(Ok(()))
     }
+
+    /// Parses a table by copying every field, for tables from big endian hardware or packed C structs
+    ///
+    /// Unlike [`VTable::apply`] this never views the table in place, so it works on any buffer at the cost of a copy
+    /// per field.
+    #[cfg(feature = "alloc")]
+    pub fn parse_table_unaligned<D: Decomponentize>(
+        &self,
+        table: &[u8],
+        sink: &mut D,
+    ) -> Result<Result<(), D::Error>, Error> {
+        let mut scratch = alloc::vec::Vec::new();
+        for field in self.fields.iter() {
+            let (realized, data) = self.realize_field(field, Some(table))?;
+            let data = data.expect("table not found");
+            for row in 0..realized.rows {
+                let (row, timestamp) = data.row(&realized, row)?;
+                if let Err(err) = apply_copied(&realized, row, timestamp, &mut scratch, sink)? {
+                    return Ok(Err(err));
+                }
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    /// Lays out `value`'s components in `buf` as this vtable describes, returning how many bytes of `buf` were written
+    ///
+    /// Nothing is allocated, so a table can be built straight into a DMA buffer every tick. Each component is copied to
+    /// its field, swapped to the field's byte order, along with its timestamp if the field reads one from the table.
+    /// Fields `value` doesn't sink are left as they were. A component that isn't in the vtable, or whose type or size
+    /// doesn't match its field, is an error.
+    pub fn write_table_into(
+        &self,
+        buf: &mut [u8],
+        value: &impl crate::com_de::Componentize,
+    ) -> Result<usize, Error> {
+        let mut sink = TableSink {
+            vtable: self,
+            buf,
+            len: 0,
+            res: Ok(()),
+        };
+        value.sink_columns(&mut sink);
+        sink.res?;
+        Ok(sink.len)
+    }
+
+    /// Applies a single field of `table`, falling back to a copy if it can't be viewed in place
+    pub(crate) fn apply_field<D: Decomponentize>(
+        &self,
+        field: &Field,
+        table: &[u8],
+        sink: &mut D,
+    ) -> Result<Result<(), D::Error>, Error> {
+        let (realized, data) = self.realize_field(field, Some(table))?;
+        let data = data.expect("table not found");
+        // every row shares the field's schema, so the prim type is only matched once
+        let decode = decode_fn(realized.ty);
+        let len = elem_count(realized.shape)?;
+        for row in 0..realized.rows {
+            let (row, timestamp) = data.row(&realized, row)?;
+            let view = if realized.endian == Endian::Big && needs_swap(realized.ty) {
+                Err(Error::ByteOrder)
+            } else {
+                decode(row, realized.shape, len)
+            };
+            let res = match view {
+                Ok(view) => sink.apply_value(realized.component_id, view, timestamp),
+                #[cfg(feature = "alloc")]
+                Err(Error::Alignment | Error::ByteOrder) => {
+                    apply_copied(&realized, row, timestamp, &mut alloc::vec::Vec::new(), sink)?
+                }
+                Err(err) => return Err(err),
+            };
+            if let Err(err) = res {
+                return Ok(Err(err));
+            }
+        }
+        Ok(Ok(()))
+    }
+}
+
+impl<'a> FieldData<'a> {
+    /// The bytes and timestamp of one of the field's rows
+    fn row(
+        &self,
+        realized: &RealizedField<'_>,
+        row: usize,
+    ) -> Result<(&'a [u8], Option<Timestamp>), Error> {
+        if realized.rows == 1 {
+            return Ok((self.data, realized.timestamp));
+        }
+        let row_len = self.data.len() / realized.rows;
+        let data = self
+            .data
+            .get(row * row_len..(row + 1) * row_len)
+            .ok_or(Error::BufferUnderflow)?;
+        let timestamp = self
+            .timestamps
+            .get(row * size_of::<Timestamp>()..(row + 1) * size_of::<Timestamp>())
+            .ok_or(Error::BufferUnderflow)?;
+        let timestamp = Timestamp::try_from(timestamp)?;
+        let timestamp = match realized.endian {
+            Endian::Little => timestamp,
+            Endian::Big => Timestamp(timestamp.0.swap_bytes()),
+        };
+        Ok((data, Some(timestamp)))
+    }
+}
+
+/// A sink that copies each component into its field of a table, see [`VTable::write_table_into`]
+struct TableSink<'a, 'v, Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> {
+    vtable: &'v VTable<Ops, Data, Fields>,
+    buf: &'a mut [u8],
+    len: usize,
+    /// The first error, kept since `Componentize::sink_columns` can't return one
+    res: Result<(), Error>,
+}
+
+impl<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> TableSink<'_, '_, Ops, Data, Fields> {
+    fn write(
+        &mut self,
+        component_id: ComponentId,
+        value: ComponentView<'_>,
+        timestamp: Option<Timestamp>,
+    ) -> Result<(), Error> {
+        for field in self.vtable.fields.iter() {
+            let (realized, _) = self.vtable.realize_field(field, None)?;
+            if realized.component_id != component_id {
+                continue;
+            }
+            if realized.ty != value.prim_type() {
+                return Err(Error::InvalidComponentData);
+            }
+            let data = value.as_bytes();
+            if data.len() != field.len as usize {
+                return Err(Error::FieldSizeMismatch);
+            }
+            let swap = realized.endian == Endian::Big && needs_swap(realized.ty);
+            if swap && realized.ty.is_var_len() {
+                return Err(Error::ByteOrder);
+            }
+
+            let offset = field.offset.to_index();
+            let end = offset + data.len();
+            let out = self.buf.get_mut(offset..end).ok_or(Error::BufferOverflow)?;
+            out.copy_from_slice(data);
+            if swap {
+                for elem in out.chunks_exact_mut(realized.ty.size()) {
+                    elem.reverse();
+                }
+            }
+            self.len = self.len.max(end);
+
+            if let (Some(timestamp), Some(range)) = (timestamp, realized.timestamp_range) {
+                let bytes = match realized.endian {
+                    Endian::Little => timestamp.to_le_bytes(),
+                    Endian::Big => timestamp.0.to_be_bytes(),
+                };
+                let end = range.end;
+                let out = self.buf.get_mut(range).ok_or(Error::BufferOverflow)?;
+                for slot in out.chunks_exact_mut(size_of::<Timestamp>()) {
+                    slot.copy_from_slice(&bytes);
+                }
+                self.len = self.len.max(end);
+            }
+            return Ok(());
+        }
+        Err(Error::ComponentNotFound)
+    }
+}
+
+impl<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> Decomponentize
+    for TableSink<'_, '_, Ops, Data, Fields>
+{
+    type Error = Error;
+
+    fn apply_value(
+        &mut self,
+        component_id: ComponentId,
+        value: ComponentView<'_>,
+        timestamp: Option<Timestamp>,
+    ) -> Result<(), Self::Error> {
+        if self.res.is_ok() {
+            self.res = self.write(component_id, value, timestamp);
+        }
+        self.res.clone()
+    }
+}
+
+/// Whether a big endian value of `ty` differs from its little endian form
+fn needs_swap(ty: PrimType) -> bool {
+    ty.size() > 1 || ty.is_var_len()
+}
+
+/// Copies `data` into `scratch` in native order, and applies it to the sink
+#[cfg(feature = "alloc")]
+fn apply_copied<D: Decomponentize>(
+    realized: &RealizedField<'_>,
+    data: &[u8],
+    timestamp: Option<Timestamp>,
+    scratch: &mut alloc::vec::Vec<u64>,
+    sink: &mut D,
+) -> Result<Result<(), D::Error>, Error> {
+    scratch.clear();
+    scratch.resize(data.len().div_ceil(8), 0);
+    let buf = &mut scratch.as_mut_bytes()[..data.len()];
+    buf.copy_from_slice(data);
+    if realized.endian == Endian::Big {
+        if realized.ty.is_var_len() {
+            for slot in buf.chunks_exact_mut(realized.shape.iter().product::<usize>().max(1)) {
+                if let Some(prefix) = slot.get_mut(..crate::types::VAR_LEN_PREFIX_LEN) {
+                    prefix.reverse();
+                }
+            }
+        } else {
+            for elem in buf.chunks_exact_mut(realized.ty.size()) {
+                elem.reverse();
+            }
+        }
+    }
+    let view = ComponentView::try_from_bytes_shape(buf, realized.shape, realized.ty)?;
+    Ok(sink.apply_value(realized.component_id, view, timestamp))
+}
+
+/// The layout of a single column in a [`VTable`], used by [`VTable::diff`]
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnLayout {
+    pub component_id: ComponentId,
+    pub ty: PrimType,
+    pub shape: alloc::vec::Vec<usize>,
+    pub offset: usize,
+    pub len: usize,
+}
+
+/// A difference in one column between two [`VTable`]s
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VTableDiff {
+    /// The column is only in the other vtable
+    Added(ColumnLayout),
+    /// The column is only in this vtable
+    Removed(ColumnLayout),
+    /// The column's type, shape, or position in the table changed
+    Changed {
+        old: ColumnLayout,
+        new: ColumnLayout,
+    },
+}
+
+#[cfg(feature = "alloc")]
+impl<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> VTable<Ops, Data, Fields> {
+    /// Returns the layout of every column, in field order
+    pub fn columns(&self) -> Result<alloc::vec::Vec<ColumnLayout>, Error> {
+        self.column_iter().collect()
+    }
+
+    /// Returns the layout of each column in field order, with an error for each column that can't be realized
+    ///
+    /// Unlike [`VTable::columns`], a column with a malformed schema doesn't hide the columns after it, so callers can
+    /// skip bad entries from untrusted vtables.
+    pub fn column_iter(&self) -> impl Iterator<Item = Result<ColumnLayout, Error>> + '_ {
+        self.fields
+            .iter()
+            .zip(self.realize_fields(None))
+            .map(|(field, res)| {
+                let RealizedField {
+                    component_id,
+                    shape,
+                    ty,
+                    ..
+                } = res?;
+                Ok(ColumnLayout {
+                    component_id,
+                    ty,
+                    shape: shape.to_vec(),
+                    offset: field.offset.to_index(),
+                    len: field.len as usize,
+                })
+            })
+    }
+
+    /// The number of bytes a table needs to hold every field and timestamp this vtable reads from it
+    pub fn table_len(&self) -> Result<usize, Error> {
+        let columns = self
+            .column_iter()
+            .map(|col| col.map(|col| col.offset + col.len));
+        let timestamps = self
+            .table_timestamp_ranges()?
+            .into_iter()
+            .map(|range| Ok(range.end));
+        columns
+            .chain(timestamps)
+            .try_fold(0, |len, end| Ok(len.max(end?)))
+    }
+
+    /// Checks that `table` can be parsed by this vtable, on top of the checks in [`VTable::validate`]
+    ///
+    /// The table has to be at least [`VTable::table_len`] bytes, and every field has to be aligned for its type and
+    /// hold a valid value.
+    pub fn validate_table(&self, table: &[u8]) -> Result<(), Error> {
+        self.validate()?;
+        if table.len() < self.table_len()? {
+            return Err(Error::BufferUnderflow);
+        }
+        for res in self.realize_fields(Some(table)) {
+            res?;
+        }
+        Ok(())
+    }
+
+    /// Each distinct part of the table that a timestamp op reads from, in op order
+    pub(crate) fn table_timestamp_ranges(&self) -> Result<alloc::vec::Vec<Range<usize>>, Error> {
+        let mut ranges: alloc::vec::Vec<Range<usize>> = alloc::vec::Vec::new();
+        for op in self.ops.iter() {
+            let Op::Timestamp { source, .. } = op else {
+                continue;
+            };
+            if let Some(range) = self.realize(*source, None)?.as_table_range() {
+                if !ranges.contains(&range) {
+                    ranges.push(range);
+                }
+            }
+        }
+        Ok(ranges)
+    }
+
+    /// Compares the columns of two vtables by component id
+    ///
+    /// Removed and changed columns are reported in this vtable's field order, followed by added columns in `other`'s
+    /// field order. This is useful for working out why a table recorded with one vtable won't parse with another.
+    pub fn diff<O: Buf<Op>, D: Buf<u8>, F: Buf<Field>>(
+        &self,
+        other: &VTable<O, D, F>,
+    ) -> Result<alloc::vec::Vec<VTableDiff>, Error> {
+        let old = self.columns()?;
+        let new = other.columns()?;
+        let mut diffs = alloc::vec::Vec::new();
+        for old_col in &old {
+            match new
+                .iter()
+                .find(|col| col.component_id == old_col.component_id)
+            {
+                None => diffs.push(VTableDiff::Removed(old_col.clone())),
+                Some(new_col) if new_col != old_col => diffs.push(VTableDiff::Changed {
+                    old: old_col.clone(),
+                    new: new_col.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for new_col in &new {
+            if !old
+                .iter()
+                .any(|col| col.component_id == new_col.component_id)
+            {
+                diffs.push(VTableDiff::Added(new_col.clone()));
+            }
+        }
+        Ok(diffs)
+    }
+}
+
+/// Builds tables for a [`VTable`], handing out typed slices for each column so the layout can't be gotten wrong
+///
+/// The table is zeroed to start with, and backed by `u64`s so every field is aligned the same way it will be in a
+/// packet.
+#[cfg(feature = "alloc")]
+pub struct TableWriter<
+    'a,
+    Ops: Buf<Op> = DefaultOps,
+    Data: Buf<u8> = DefaultData,
+    Fields: Buf<Field> = DefaultFields,
+> {
+    vtable: &'a VTable<Ops, Data, Fields>,
+    columns: alloc::vec::Vec<ColumnLayout>,
+    timestamps: alloc::vec::Vec<Range<usize>>,
+    words: alloc::vec::Vec<u64>,
+    len: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> TableWriter<'a, Ops, Data, Fields> {
+    /// Validates `vtable` and checks every field is aligned for its type
+    pub fn new(vtable: &'a VTable<Ops, Data, Fields>) -> Result<Self, Error> {
+        vtable.validate()?;
+        let columns = vtable.columns()?;
+        if columns
+            .iter()
+            .any(|col| col.offset % col.ty.alignment() != 0)
+        {
+            return Err(Error::Alignment);
+        }
+        let len = vtable.table_len()?;
+        Ok(Self {
+            vtable,
+            columns,
+            timestamps: vtable.table_timestamp_ranges()?,
+            words: alloc::vec![0; len.div_ceil(8)],
+            len,
+        })
+    }
+
+    /// The values of `component_id`'s column, which must be stored as `T`
+    pub fn column_mut<T>(&mut self, component_id: impl Into<ComponentId>) -> Result<&mut [T], Error>
+    where
+        T: crate::component::PrimTypeElem + TryFromBytes + IntoBytes + KnownLayout,
+    {
+        let component_id = component_id.into();
+        let col = self
+            .columns
+            .iter()
+            .find(|col| col.component_id == component_id)
+            .ok_or(Error::ComponentNotFound)?;
+        if col.ty != T::PRIM_TYPE {
+            return Err(Error::InvalidComponentData);
+        }
+        let range = col.offset..col.offset + col.len;
+        let bytes = &mut self.words.as_mut_bytes()[range];
+        Ok(<[T]>::try_mut_from_bytes(bytes)?)
+    }
+
+    /// Sets every timestamp the vtable reads from the table, including each row's
+    pub fn set_timestamp(&mut self, timestamp: Timestamp) {
+        let bytes = self.words.as_mut_bytes();
+        for range in &self.timestamps {
+            for slot in bytes[range.clone()].chunks_exact_mut(size_of::<Timestamp>()) {
+                slot.copy_from_slice(&timestamp.to_le_bytes());
+            }
+        }
+    }
+
+    pub fn vtable(&self) -> &'a VTable<Ops, Data, Fields> {
+        self.vtable
+    }
+
+    /// The table, [`VTable::table_len`] bytes long
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.words.as_bytes()[..self.len]
+    }
+}
+
+/// Prints the layout of each field, one per line, e.g. `field 0: table[8..32] f64[3] timestamp=table[0..8] component=42`
+impl<Ops: Buf<Op>, Data: Buf<u8>, Fields: Buf<Field>> core::fmt::Display
+    for VTable<Ops, Data, Fields>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (i, field) in self.fields.iter().enumerate() {
+            let start = field.offset.to_index();
+            write!(
+                f,
+                "field {i}: table[{start}..{}]",
+                start + field.len as usize
+            )?;
+            let mut op_ref = field.arg;
+            let mut remaining = self.ops.as_slice().len() + 1;
+            loop {
+                let Some(left) = remaining.checked_sub(1) else {
+                    write!(f, " <cycle>")?;
+                    break;
+                };
+                remaining = left;
+                match self.realize(op_ref, None) {
+                    Ok(RealizedOp::Schema(schema)) => {
+                        write!(f, " {}{:?}", schema.ty, schema.dim)?;
+                        op_ref = schema.arg;
+                    }
+                    Ok(RealizedOp::Timestamp(timestamp)) => {
+                        match (timestamp.range, timestamp.timestamp) {
+                            (Some(range), _) => {
+                                write!(f, " timestamp=table[{}..{}]", range.start, range.end)?
+                            }
+                            (None, Some(timestamp)) => write!(f, " timestamp={}", timestamp.0)?,
+                            (None, None) => write!(f, " timestamp=?")?,
+                        }
+                        op_ref = timestamp.arg;
+                    }
+                    Ok(RealizedOp::Ext(ext)) => {
+                        write!(f, " ext={:?}", ext.id)?;
+                        op_ref = ext.arg;
+                    }
+                    Ok(RealizedOp::Component(component)) => {
+                        write!(f, " component={}", component.component_id)?;
+                        break;
+                    }
+                    Ok(_) => {
+                        write!(f, " <invalid op>")?;
+                        break;
+                    }
+                    Err(err) => {
+                        write!(f, " <{err:?}>")?;
+                        break;
+                    }
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(feature = "alloc")]
@@ -485,6 +1164,21 @@ pub mod builder {
         arg: Arc<OpBuilder>,
     }
 
+    impl FieldBuilder {
+        /// Moves the field `offset` bytes further into the table, for laying out a struct inside another
+        ///
+        /// Only the field's own offset moves, so timestamps read from the table keep pointing where they did.
+        pub fn offset_by(mut self, offset: u16) -> Result<Self, Error> {
+            let offset = self
+                .offset
+                .0
+                .checked_add(offset)
+                .ok_or(Error::OffsetOverflow)?;
+            self.offset = Offset(offset);
+            Ok(self)
+        }
+    }
+
     /// Creates a data operation builder from the provided data
     pub fn data<T: IntoBytes + Immutable + ?Sized>(data: &T) -> Arc<OpBuilder> {
         let align = core::mem::align_of_val(data);
@@ -516,11 +1210,36 @@ pub mod builder {
         Arc::new(OpBuilder::Schema { ty, dim, arg })
     }
 
+    /// Creates a schema operation builder wrapped in its [`SchemaHash`], so consumers can detect a schema that drifted
+    ///
+    /// Parsers that don't know the extension skip it, so it can be used in place of [`schema`] anywhere.
+    pub fn hashed_schema(ty: PrimType, dim: &[u64], arg: Arc<OpBuilder>) -> Arc<OpBuilder> {
+        ext(SchemaHash(schema_hash(ty, dim)), schema(ty, dim, arg))
+    }
+
     /// Creates a timestamp operation builder from a timestamp source and an argument
     pub fn timestamp(timestamp: Arc<OpBuilder>, arg: Arc<OpBuilder>) -> Arc<OpBuilder> {
         Arc::new(OpBuilder::Timestamp { timestamp, arg })
     }
 
+    /// Creates a timestamp operation builder that reads a `[Timestamp; rows]` region of the table
+    ///
+    /// The field it's applied to holds `rows` values of its schema back to back, and each is sunk with the matching
+    /// timestamp, so samples taken at slightly different times can share a table without sharing a timestamp. Rows are
+    /// written to the time series in order, so their timestamps shouldn't decrease.
+    ///
+    /// Fails with [`Error::OffsetOverflow`] if the timestamps are too long for a table region, i.e. `rows >= 8192`.
+    pub fn row_timestamps(
+        offset: impl Into<Offset>,
+        rows: u16,
+        arg: Arc<OpBuilder>,
+    ) -> Result<Arc<OpBuilder>, Error> {
+        let len = rows
+            .checked_mul(size_of::<Timestamp>() as u16)
+            .ok_or(Error::OffsetOverflow)?;
+        Ok(timestamp(raw_table(offset, len), arg))
+    }
+
     /// Creates an extension operation builder from a message and an argument
     ///
     ///  Extensions are used to attache extra metadata to a field.
@@ -535,6 +1254,13 @@ pub mod builder {
         })
     }
 
+    /// Creates an extension operation builder that sets the byte order of a field's values
+    ///
+    /// It's usually wrapped around the field's schema, e.g. `endian(Endian::Big, schema(...))`.
+    pub fn endian(endian: Endian, arg: Arc<OpBuilder>) -> Arc<OpBuilder> {
+        ext(endian, arg)
+    }
+
     /// Creates a field builder with the specified offset, length, and argument
     pub fn raw_field(offset: impl Into<Offset>, len: u16, arg: Arc<OpBuilder>) -> FieldBuilder {
         FieldBuilder {
@@ -771,4 +1497,637 @@ mod tests {
         assert_eq!(bar.buf.as_buf(), &[5.0]);
         assert_eq!(sink.timestamp, Some(foo.timestamp));
     }
+
+    #[test]
+    fn test_display() {
+        use super::builder::*;
+
+        let time = raw_table(0, 8);
+        let v = vtable([raw_field(
+            8,
+            24,
+            schema(PrimType::F64, &[3], timestamp(time, component("test"))),
+        )]);
+        assert_eq!(
+            v.to_string(),
+            format!(
+                "field 0: table[8..32] f64[3] timestamp=table[0..8] component={}\n",
+                ComponentId::new("test")
+            )
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        use super::builder::*;
+
+        let time = raw_table(0, 8);
+        let old = vtable([
+            raw_field(
+                8,
+                24,
+                schema(PrimType::F64, &[3], timestamp(time.clone(), component("a"))),
+            ),
+            raw_field(
+                32,
+                8,
+                schema(PrimType::F64, &[1], timestamp(time.clone(), component("b"))),
+            ),
+        ]);
+        let new = vtable([
+            raw_field(
+                8,
+                32,
+                schema(PrimType::F64, &[4], timestamp(time.clone(), component("a"))),
+            ),
+            raw_field(
+                40,
+                8,
+                schema(PrimType::F64, &[1], timestamp(time, component("b"))),
+            ),
+        ]);
+        assert!(old.diff(&old).unwrap().is_empty());
+        assert_eq!(
+            old.diff(&new).unwrap(),
+            vec![
+                VTableDiff::Changed {
+                    old: ColumnLayout {
+                        component_id: ComponentId::new("a"),
+                        ty: PrimType::F64,
+                        shape: vec![3],
+                        offset: 8,
+                        len: 24,
+                    },
+                    new: ColumnLayout {
+                        component_id: ComponentId::new("a"),
+                        ty: PrimType::F64,
+                        shape: vec![4],
+                        offset: 8,
+                        len: 32,
+                    },
+                },
+                VTableDiff::Changed {
+                    old: ColumnLayout {
+                        component_id: ComponentId::new("b"),
+                        ty: PrimType::F64,
+                        shape: vec![1],
+                        offset: 32,
+                        len: 8,
+                    },
+                    new: ColumnLayout {
+                        component_id: ComponentId::new("b"),
+                        ty: PrimType::F64,
+                        shape: vec![1],
+                        offset: 40,
+                        len: 8,
+                    },
+                },
+            ]
+        );
+
+        let single = vtable([raw_field(
+            8,
+            24,
+            schema(
+                PrimType::F64,
+                &[3],
+                timestamp(raw_table(0, 8), component("a")),
+            ),
+        )]);
+        let diff = single.diff(&old).unwrap();
+        assert_eq!(diff.len(), 1);
+        assert!(
+            matches!(&diff[0], VTableDiff::Added(col) if col.component_id == ComponentId::new("b"))
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        use super::builder::*;
+        use crate::error::Error;
+
+        let time = raw_table(0, 8);
+        let v = vtable([raw_field(
+            8,
+            24,
+            schema(PrimType::F64, &[3], timestamp(time, component("test"))),
+        )]);
+        v.validate().unwrap();
+
+        let v = vtable([raw_field(
+            0,
+            16,
+            schema(PrimType::F64, &[3], component("test")),
+        )]);
+        assert!(matches!(v.validate(), Err(Error::FieldSizeMismatch)));
+
+        let time = raw_table(0, 4);
+        let v = vtable([raw_field(
+            8,
+            24,
+            schema(PrimType::F64, &[3], timestamp(time, component("test"))),
+        )]);
+        assert!(matches!(v.validate(), Err(Error::InvalidOp)));
+    }
+
+    #[test]
+    fn test_table_writer() {
+        use super::TableWriter;
+        use super::builder::*;
+        use crate::error::Error;
+
+        let time = raw_table(0, 8);
+        let v = vtable([
+            raw_field(
+                8,
+                24,
+                schema(
+                    PrimType::F64,
+                    &[3],
+                    timestamp(time.clone(), component("gyro")),
+                ),
+            ),
+            raw_field(
+                32,
+                1,
+                schema(PrimType::Bool, &[1], timestamp(time, component("armed"))),
+            ),
+        ]);
+        assert_eq!(v.table_len().unwrap(), 33);
+
+        let mut writer = TableWriter::new(&v).unwrap();
+        writer.set_timestamp(Timestamp(7));
+        writer
+            .column_mut::<f64>("gyro")
+            .unwrap()
+            .copy_from_slice(&[1.0, 2.0, 3.0]);
+        writer.column_mut::<bool>("armed").unwrap()[0] = true;
+        assert!(matches!(
+            writer.column_mut::<f32>("gyro"),
+            Err(Error::InvalidComponentData)
+        ));
+        assert!(matches!(
+            writer.column_mut::<f64>("missing"),
+            Err(Error::ComponentNotFound)
+        ));
+
+        let table = writer.as_bytes().to_vec();
+        v.validate_table(&table).unwrap();
+        assert!(matches!(
+            v.validate_table(&table[..32]),
+            Err(Error::BufferUnderflow)
+        ));
+        assert_eq!(&table[..8], &7i64.to_le_bytes());
+        assert_eq!(&table[8..16], &1.0f64.to_le_bytes());
+        assert_eq!(table[32], 1);
+
+        let misaligned = vtable([raw_field(4, 8, schema(PrimType::F64, &[1], component("x")))]);
+        assert!(matches!(
+            TableWriter::new(&misaligned),
+            Err(Error::Alignment)
+        ));
+    }
+
+    #[test]
+    fn test_write_table_into() {
+        use super::builder::*;
+        use crate::com_de::{AsComponentView, Componentize};
+        use crate::error::Error;
+
+        struct Imu {
+            time: Timestamp,
+            gyro: [f64; 3],
+            temp: f32,
+        }
+
+        impl Componentize for Imu {
+            fn sink_columns(&self, output: &mut impl Decomponentize) {
+                let gyro =
+                    ComponentView::try_from_bytes_shape(self.gyro.as_bytes(), &[3], PrimType::F64)
+                        .unwrap();
+                let _ = output.apply_value(ComponentId::new("gyro"), gyro, Some(self.time));
+                let _ = output.apply_value(
+                    ComponentId::new("temp"),
+                    self.temp.as_component_view(),
+                    Some(self.time),
+                );
+            }
+        }
+
+        let time = raw_table(0, 8);
+        let v = vtable([
+            raw_field(
+                8,
+                24,
+                schema(
+                    PrimType::F64,
+                    &[3],
+                    timestamp(time.clone(), component("gyro")),
+                ),
+            ),
+            raw_field(
+                32,
+                4,
+                schema(PrimType::F32, &[1], timestamp(time, component("temp"))),
+            ),
+        ]);
+        let imu = Imu {
+            time: Timestamp(42),
+            gyro: [1.0, 2.0, 3.0],
+            temp: 21.5,
+        };
+
+        let mut buf = [0u64; 8];
+        let len = v.write_table_into(buf.as_mut_bytes(), &imu).unwrap();
+        assert_eq!(len, 36);
+        let mut sink = TestSink::default();
+        v.apply(&buf.as_bytes()[..len], &mut sink).unwrap().unwrap();
+        assert_eq!(sink.timestamp, Some(Timestamp(42)));
+        assert_eq!(
+            sink.f64_components[&ComponentId::new("gyro")].buf.as_buf(),
+            &[1.0, 2.0, 3.0]
+        );
+        assert_eq!(
+            sink.f32_components[&ComponentId::new("temp")].buf.as_buf(),
+            &[21.5]
+        );
+
+        assert!(matches!(
+            v.write_table_into(&mut [0; 32], &imu),
+            Err(Error::BufferOverflow)
+        ));
+        let narrow = vtable([raw_field(
+            0,
+            12,
+            schema(PrimType::F32, &[3], component("gyro")),
+        )]);
+        assert!(matches!(
+            narrow.write_table_into(&mut [0; 64], &imu),
+            Err(Error::InvalidComponentData)
+        ));
+    }
+
+    #[test]
+    fn test_big_endian_and_packed() {
+        use super::Endian;
+        use super::builder::*;
+        use crate::error::Error;
+
+        let v = vtable([
+            raw_field(
+                8,
+                4,
+                endian(
+                    Endian::Big,
+                    schema(
+                        PrimType::F32,
+                        &[],
+                        timestamp(raw_table(0, 8), component("a")),
+                    ),
+                ),
+            ),
+            // packed right after the f32, so it's misaligned
+            raw_field(12, 8, schema(PrimType::F64, &[], component("b"))),
+        ]);
+        v.validate().unwrap();
+
+        let mut words = [0u64; 3];
+        let buf = words.as_mut_bytes();
+        buf[..8].copy_from_slice(&1000i64.to_be_bytes());
+        buf[8..12].copy_from_slice(&1.5f32.to_be_bytes());
+        buf[12..20].copy_from_slice(&2.5f64.to_le_bytes());
+        let table = &buf[..20];
+
+        let mut fields = v.realize_fields(Some(table));
+        assert!(matches!(fields.next(), Some(Err(Error::ByteOrder))));
+        assert!(matches!(fields.next(), Some(Err(Error::Alignment))));
+
+        let mut sink = TestSink::default();
+        v.apply(table, &mut sink).unwrap().unwrap();
+        let mut unaligned = TestSink::default();
+        v.parse_table_unaligned(table, &mut unaligned)
+            .unwrap()
+            .unwrap();
+        for sink in [sink, unaligned] {
+            let a = sink.f32_components.get(&ComponentId::new("a")).unwrap();
+            assert_eq!(a.buf.as_buf(), &[1.5]);
+            let b = sink.f64_components.get(&ComponentId::new("b")).unwrap();
+            assert_eq!(b.buf.as_buf(), &[2.5]);
+            assert_eq!(sink.timestamp, Some(Timestamp(1000)));
+        }
+    }
+
+    #[test]
+    fn test_row_timestamps() {
+        use super::builder::*;
+        use crate::error::Error;
+
+        // three gyro samples, each taken at its own time
+        let v = vtable([raw_field(
+            24,
+            24,
+            schema(
+                PrimType::F64,
+                &[],
+                row_timestamps(0, 3, component("gyro_x")).unwrap(),
+            ),
+        )]);
+        v.validate().unwrap();
+
+        let mut table = [0i64; 6];
+        table[..3].copy_from_slice(&[100, 105, 111]);
+        let values = [1.0f64, 2.0, 3.0];
+        table[3..].copy_from_slice(&values.map(|x| x.to_bits() as i64));
+
+        let mut rows = vec![];
+        let mut sink = |_: ComponentId, view: ComponentView<'_>, time: Option<Timestamp>| {
+            let ComponentView::F64(view) = view else {
+                panic!("expected f64");
+            };
+            rows.push((view.buf()[0], time));
+        };
+        v.apply(table.as_bytes(), &mut sink).unwrap().unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (1.0, Some(Timestamp(100))),
+                (2.0, Some(Timestamp(105))),
+                (3.0, Some(Timestamp(111))),
+            ]
+        );
+
+        let field = v.realize_fields(None).next().unwrap().unwrap();
+        assert_eq!(field.rows, 3);
+        let short = vtable([raw_field(
+            24,
+            16,
+            schema(
+                PrimType::F64,
+                &[],
+                row_timestamps(0, 3, component("gyro_x")).unwrap(),
+            ),
+        )]);
+        assert!(short.validate().is_err());
+
+        assert!(row_timestamps(0, 8191, component("gyro_x")).is_ok());
+        assert!(matches!(
+            row_timestamps(0, 8192, component("gyro_x")),
+            Err(Error::OffsetOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_timestamp_little_endian() {
+        use super::builder::*;
+
+        let v = vtable([raw_field(
+            8,
+            8,
+            schema(
+                PrimType::F64,
+                &[],
+                timestamp(raw_table(0, 8), component("altitude")),
+            ),
+        )]);
+        let mut table = [0u8; 16];
+        table[..8].copy_from_slice(&[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]);
+        table[8..].copy_from_slice(&12.5f64.to_le_bytes());
+        let mut sink = TestSink::default();
+        v.apply(&table, &mut sink).unwrap().unwrap();
+        assert_eq!(sink.timestamp, Some(Timestamp(0x0102_0304_0506_0708)));
+        let altitude = sink
+            .f64_components
+            .get(&ComponentId::new("altitude"))
+            .unwrap();
+        assert_eq!(altitude.buf.as_buf(), &[12.5]);
+    }
+
+    #[test]
+    fn test_column_iter_corrupt_shape() {
+        use super::builder::*;
+        use super::{Offset, Op};
+        use crate::error::Error;
+
+        let mut v = vtable([
+            raw_field(0, 8, schema(PrimType::F64, &[], component("good"))),
+            raw_field(8, 24, schema(PrimType::F64, &[3], component("bad"))),
+        ]);
+        let dim = v
+            .ops
+            .iter()
+            .rev()
+            .find_map(|op| match op {
+                Op::Schema { dim, .. } => Some(*dim),
+                _ => None,
+            })
+            .unwrap();
+        v.ops[dim.to_index()] = Op::Data {
+            offset: Offset(1000),
+            len: 8,
+        };
+
+        let columns = v.column_iter().collect::<Vec<_>>();
+        assert_eq!(columns.len(), 2);
+        assert_eq!(
+            columns[0].as_ref().unwrap().component_id,
+            ComponentId::new("good")
+        );
+        assert!(matches!(columns[1], Err(Error::BufferOverflow)));
+        assert!(v.columns().is_err());
+    }
+
+    #[test]
+    fn test_cyclic_vtable_is_rejected() {
+        use super::*;
+
+        // a component whose id is itself
+        let v = VTable {
+            ops: vec![Op::Component {
+                component_id: OpRef(0),
+            }],
+            fields: vec![Field {
+                offset: Offset(0),
+                len: 8,
+                arg: OpRef(0),
+            }],
+            data: vec![],
+        };
+        assert!(matches!(v.validate(), Err(Error::InvalidOp)));
+
+        // a schema that wraps itself
+        let mut data = PrimType::F64.as_bytes().to_vec();
+        data.extend_from_slice(&[0; 8]);
+        let v = VTable {
+            ops: vec![
+                Op::Schema {
+                    ty: OpRef(1),
+                    dim: OpRef(2),
+                    arg: OpRef(0),
+                },
+                Op::Data {
+                    offset: Offset(0),
+                    len: 8,
+                },
+                Op::Data {
+                    offset: Offset(8),
+                    len: 0,
+                },
+            ],
+            fields: vec![Field {
+                offset: Offset(0),
+                len: 8,
+                arg: OpRef(0),
+            }],
+            data,
+        };
+        assert!(matches!(v.apply(&[0; 8], &mut ()), Err(Error::InvalidOp)));
+        assert!(v.to_string().ends_with("<cycle>"));
+    }
+
+    mod fuzz {
+        use super::super::*;
+        use proptest::prelude::*;
+
+        fn op_ref() -> impl Strategy<Value = OpRef> {
+            // a few past the end of the ops, so dangling refs are covered too
+            (0u16..10).prop_map(OpRef)
+        }
+
+        fn offset() -> impl Strategy<Value = Offset> {
+            (0u16..72).prop_map(Offset)
+        }
+
+        fn arb_op() -> impl Strategy<Value = Op> {
+            prop_oneof![
+                (offset(), 0u16..32).prop_map(|(offset, len)| Op::Data { offset, len }),
+                (offset(), 0u16..32).prop_map(|(offset, len)| Op::Table { offset, len }),
+                Just(Op::None),
+                op_ref().prop_map(|component_id| Op::Component { component_id }),
+                (op_ref(), op_ref(), op_ref()).prop_map(|(ty, dim, arg)| Op::Schema {
+                    ty,
+                    dim,
+                    arg
+                }),
+                (op_ref(), op_ref()).prop_map(|(source, arg)| Op::Timestamp { source, arg }),
+                (op_ref(), any::<PacketId>(), op_ref()).prop_map(|(arg, id, data)| Op::Ext {
+                    arg,
+                    id,
+                    data
+                }),
+            ]
+        }
+
+        fn arb_vtable() -> impl Strategy<Value = VTable> {
+            let field = (offset(), 0u16..64, op_ref()).prop_map(|(offset, len, arg)| Field {
+                offset,
+                len,
+                arg,
+            });
+            (
+                prop::collection::vec(arb_op(), 0..8),
+                prop::collection::vec(field, 0..4),
+                // small words are valid prim types, dims, and component ids, so realizing gets past the first op
+                prop::collection::vec(0u64..16, 0..8),
+            )
+                .prop_map(|(ops, fields, data)| VTable {
+                    ops,
+                    fields,
+                    data: data.as_bytes().to_vec(),
+                })
+        }
+
+        fn exercise(vtable: &VTable, table: &[u8]) {
+            let _ = vtable.validate();
+            let _ = vtable.columns();
+            let _ = vtable.to_string();
+            let _ = vtable.apply(
+                table,
+                &mut |_: ComponentId, view: ComponentView<'_>, _: Option<Timestamp>| {
+                    let _ = (view.shape(), view.as_bytes());
+                },
+            );
+        }
+
+        proptest! {
+            #[test]
+            fn test_arbitrary_vtables_never_panic(
+                vtable in arb_vtable(),
+                table in prop::collection::vec(any::<u8>(), 0..128),
+            ) {
+                exercise(&vtable, &table);
+            }
+
+            #[test]
+            fn test_mutated_vtables_never_panic(
+                mutations in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+                table in prop::collection::vec(any::<u8>(), 0..64),
+            ) {
+                use crate::vtable::builder::*;
+                let valid = vtable([raw_field(
+                    8,
+                    24,
+                    schema(PrimType::F64, &[3], timestamp(raw_table(0, 8), component("test"))),
+                )]);
+                let mut bytes = postcard::to_allocvec(&valid).unwrap();
+                for (index, byte) in mutations {
+                    let i = index.index(bytes.len());
+                    bytes[i] = byte;
+                }
+                if let Ok(vtable) = postcard::from_bytes::<VTable>(&bytes) {
+                    exercise(&vtable, &table);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_schemas() {
+        use super::builder::*;
+        use super::{Endian, SchemaHash};
+        use crate::error::Error;
+        use crate::schema::schema_hash;
+
+        let gyro = ComponentId::new("gyro");
+        let v = vtable([raw_field(
+            0,
+            24,
+            hashed_schema(PrimType::F64, &[3], component(gyro)),
+        )]);
+        v.validate().unwrap();
+        let hash = schema_hash(PrimType::F64, &[3]);
+        let field = v.realize_fields(None).next().unwrap().unwrap();
+        assert_eq!(field.schema_hash, hash);
+
+        v.verify_schemas(|_| None).unwrap();
+        v.verify_schemas(|id| (id == gyro).then_some(hash)).unwrap();
+        let stale = schema_hash(PrimType::F64, &[4]);
+        let err = v.verify_schemas(|_| Some(stale)).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::SchemaMismatch { component_id, expected, found }
+                if component_id == gyro && expected == stale && found == hash
+        ));
+
+        // a hash that disagrees with the schema it wraps is caught when the field is realized
+        let v = vtable([raw_field(
+            0,
+            24,
+            ext(
+                SchemaHash(stale),
+                schema(PrimType::F64, &[3], component(gyro)),
+            ),
+        )]);
+        assert!(matches!(v.validate(), Err(Error::SchemaMismatch { .. })));
+
+        let v = vtable([raw_field(
+            0,
+            24,
+            endian(
+                Endian::Big,
+                hashed_schema(PrimType::F64, &[3], component(gyro)),
+            ),
+        )]);
+        v.validate().unwrap();
+        v.verify_schemas(|_| Some(hash)).unwrap();
+    }
 }
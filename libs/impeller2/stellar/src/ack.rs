@@ -0,0 +1,169 @@
+//! Reliable delivery for msgs that must not be silently dropped, like arm and disarm commands
+//!
+//! [`PacketSink::send_with_ack`](crate::PacketSink::send_with_ack) frames a msg as a [`PacketTy::MsgWithAck`] and
+//! retransmits it until the receiver replies with an [`Ack`], or gives up after a configurable number of retransmits.
+//! A sink never reads, so whatever reads the link hands the acks it receives to the sink's [`AckTracker`]. Receivers
+//! ack a msg once it's handled, ack every later copy, and use an [`AckWindow`] to only handle the first one.
+//!
+//! [`PacketTy::MsgWithAck`]: impeller2::types::PacketTy::MsgWithAck
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{
+        Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use impeller2_wkt::Ack;
+use stellarator::sync::WaitQueue;
+
+/// How long a sink waits for an ack before retransmitting
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How many times a sink retransmits an unacknowledged msg before giving up
+pub const DEFAULT_MAX_RETRANSMITS: u32 = 5;
+
+/// How many sequence numbers an [`AckWindow`] remembers by default
+pub const DEFAULT_ACK_WINDOW_LEN: usize = 64;
+
+/// The sequence numbers a sink is waiting to have acknowledged
+pub struct AckTracker {
+    next_seq: AtomicU32,
+    pending: Mutex<HashSet<u32>>,
+    acked: WaitQueue,
+}
+
+impl Default for AckTracker {
+    fn default() -> Self {
+        // a restarted sender shouldn't reuse the sequence numbers its receiver still remembers from before
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|time| time.subsec_nanos())
+            .unwrap_or_default();
+        Self {
+            next_seq: AtomicU32::new(seed),
+            pending: Mutex::new(HashSet::new()),
+            acked: WaitQueue::new(),
+        }
+    }
+}
+
+impl AckTracker {
+    /// Marks the msg `ack` refers to as delivered, returning `false` if nothing was waiting on it
+    ///
+    /// Acks for msgs that were already acknowledged, or that were given up on, are ignored.
+    pub fn ack(&self, ack: &Ack) -> bool {
+        let acked = self.pending.lock().unwrap().remove(&ack.seq);
+        if acked {
+            self.acked.wake_all();
+        }
+        acked
+    }
+
+    /// Hands out a fresh sequence number and starts waiting for its ack
+    pub(crate) fn register(&self) -> u32 {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        self.pending.lock().unwrap().insert(seq);
+        seq
+    }
+
+    /// Stops waiting for the ack of `seq`
+    pub(crate) fn forget(&self, seq: u32) {
+        self.pending.lock().unwrap().remove(&seq);
+    }
+
+    fn is_pending(&self, seq: u32) -> bool {
+        self.pending.lock().unwrap().contains(&seq)
+    }
+
+    /// Waits up to `timeout` for `seq` to be acknowledged, returning whether it was
+    pub(crate) async fn wait_for(&self, seq: u32, timeout: Duration) -> bool {
+        futures_lite::future::or(
+            async {
+                loop {
+                    // created before the check, so an ack landing in between still wakes it
+                    let acked = self.acked.wait();
+                    if !self.is_pending(seq) {
+                        return true;
+                    }
+                    let _ = acked.await;
+                }
+            },
+            async {
+                stellarator::sleep(timeout).await;
+                false
+            },
+        )
+        .await
+    }
+}
+
+/// The sequence numbers of the most recently received msgs, so a receiver can drop retransmits of a msg it already
+/// handled
+pub struct AckWindow {
+    seen: VecDeque<u32>,
+    len: usize,
+}
+
+impl Default for AckWindow {
+    fn default() -> Self {
+        Self::new(DEFAULT_ACK_WINDOW_LEN)
+    }
+}
+
+impl AckWindow {
+    /// A window remembering the last `len` sequence numbers
+    pub fn new(len: usize) -> Self {
+        Self {
+            seen: VecDeque::with_capacity(len),
+            len: len.max(1),
+        }
+    }
+
+    /// Whether `seq` was already received
+    pub fn contains(&self, seq: u32) -> bool {
+        self.seen.contains(&seq)
+    }
+
+    /// Records `seq`, returning `false` if it's a retransmit of a msg that was already received
+    pub fn insert(&mut self, seq: u32) -> bool {
+        if self.contains(seq) {
+            return false;
+        }
+        if self.seen.len() >= self.len {
+            self.seen.pop_front();
+        }
+        self.seen.push_back(seq);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ack_window() {
+        let mut window = AckWindow::new(2);
+        assert!(window.insert(1));
+        assert!(!window.insert(1));
+        assert!(window.insert(2));
+        assert!(window.insert(3));
+        // 1 fell out of the window
+        assert!(window.insert(1));
+        assert!(!window.insert(3));
+    }
+
+    #[test]
+    fn test_tracker_ignores_unknown_acks() {
+        let tracker = AckTracker::default();
+        let seq = tracker.register();
+        assert!(!tracker.ack(&Ack {
+            seq: seq.wrapping_add(1)
+        }));
+        assert!(tracker.ack(&Ack { seq }));
+        assert!(!tracker.ack(&Ack { seq }));
+    }
+}
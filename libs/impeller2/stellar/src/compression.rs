@@ -0,0 +1,127 @@
+//! Per-packet compression, negotiated with [`impeller2_wkt::NegotiateCompression`]
+
+use impeller2::types::{LenPacket, PACKET_HEADER_LEN};
+use impeller2_wkt::Compression;
+
+use crate::Error;
+
+const CODEC_SHIFT: u8 = 6;
+const PACKET_TY_MASK: u8 = (1 << CODEC_SHIFT) - 1;
+
+/// Bodies shorter than this aren't worth compressing
+const MIN_COMPRESS_LEN: usize = 64;
+
+/// The largest body a compressed packet may claim to expand to, so a corrupt length can't exhaust memory
+const MAX_DECOMPRESSED_LEN: usize = 64 * 1024 * 1024;
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// Every codec this build supports, in order of preference
+pub const SUPPORTED_CODECS: &[Compression] = &[Compression::Lz4, Compression::Zstd];
+
+/// The codec a packet was compressed with, from its packet type byte
+pub fn packet_codec(packet: &[u8]) -> Result<Option<Compression>, Error> {
+    let ty = *packet.first().ok_or(Error::Decompress)?;
+    match ty >> CODEC_SHIFT {
+        0 => Ok(None),
+        1 => Ok(Some(Compression::Lz4)),
+        2 => Ok(Some(Compression::Zstd)),
+        _ => Err(Error::Decompress),
+    }
+}
+
+/// `packet` with its body compressed with `codec`, or `None` if compression wouldn't make it smaller
+pub fn compress(packet: &LenPacket, codec: Compression) -> Option<LenPacket> {
+    let body_start = size_of::<u32>() + PACKET_HEADER_LEN;
+    let body = &packet.inner[body_start..];
+    if body.len() < MIN_COMPRESS_LEN {
+        return None;
+    }
+    let compressed = match codec {
+        Compression::Lz4 => lz4_flex::block::compress(body),
+        Compression::Zstd => zstd::bulk::compress(body, ZSTD_LEVEL).ok()?,
+    };
+    if compressed.len() + size_of::<u32>() >= body.len() {
+        return None;
+    }
+    let len = PACKET_HEADER_LEN + size_of::<u32>() + compressed.len();
+    let mut inner = Vec::with_capacity(size_of::<u32>() + len);
+    inner.extend_from_slice(&(len as u32).to_le_bytes());
+    inner.push(packet.inner[4] | (codec as u8) << CODEC_SHIFT);
+    inner.extend_from_slice(&packet.inner[5..body_start]);
+    inner.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    inner.extend_from_slice(&compressed);
+    Some(LenPacket { inner })
+}
+
+/// Decompresses `packet`, a compressed packet without its length prefix, into `out` with the codec bits cleared
+pub fn decompress(packet: &[u8], codec: Compression, out: &mut Vec<u8>) -> Result<(), Error> {
+    let (header, body) = packet
+        .split_at_checked(PACKET_HEADER_LEN)
+        .ok_or(Error::Decompress)?;
+    let (len, compressed) = body.split_first_chunk::<4>().ok_or(Error::Decompress)?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if len > MAX_DECOMPRESSED_LEN {
+        return Err(Error::Decompress);
+    }
+    out.clear();
+    out.extend_from_slice(header);
+    out[0] &= PACKET_TY_MASK;
+    out.resize(PACKET_HEADER_LEN + len, 0);
+    let dest = &mut out[PACKET_HEADER_LEN..];
+    let written = match codec {
+        Compression::Lz4 => lz4_flex::block::decompress_into(compressed, dest).ok(),
+        Compression::Zstd => zstd::bulk::decompress_to_buffer(compressed, dest).ok(),
+    };
+    if written != Some(len) {
+        return Err(Error::Decompress);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use impeller2::types::OwnedPacket;
+
+    #[test]
+    fn test_round_trip() {
+        let floats = (0..256).map(|i| (i / 8) as f64).collect::<Vec<_>>();
+        for codec in SUPPORTED_CODECS {
+            let mut pkt = LenPacket::table([3, 4], 0);
+            pkt.extend_aligned(&floats);
+            let pkt = pkt.with_request_id(9);
+            let original = pkt.inner.clone();
+
+            let compressed = compress(&pkt, *codec).unwrap();
+            assert!(compressed.inner.len() < original.len());
+            let packet = &compressed.inner[4..];
+            assert_eq!(packet_codec(packet).unwrap(), Some(*codec));
+
+            let mut out = vec![];
+            decompress(packet, *codec, &mut out).unwrap();
+            assert_eq!(out, original[4..]);
+            let OwnedPacket::Table(table) = OwnedPacket::parse(out).unwrap() else {
+                panic!("expected a table");
+            };
+            assert_eq!((table.id, table.req_id), ([3, 4], 9));
+        }
+    }
+
+    #[test]
+    fn test_small_packets_stay_uncompressed() {
+        let mut pkt = LenPacket::table([3, 4], 0);
+        pkt.extend_aligned(&[1.0f64]);
+        assert!(compress(&pkt, Compression::Lz4).is_none());
+        assert_eq!(packet_codec(&pkt.inner[4..]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_corrupt_length() {
+        let mut pkt = LenPacket::table([3, 4], 0);
+        pkt.extend_aligned(&[0u64; 64]);
+        let mut pkt = compress(&pkt, Compression::Lz4).unwrap();
+        pkt.inner[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decompress(&pkt.inner[4..], Compression::Lz4, &mut vec![]).is_err());
+    }
+}
@@ -1,25 +1,44 @@
 use std::{
+    collections::VecDeque,
     marker::PhantomData,
     net::SocketAddr,
     ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+use impeller2::checksum::{self, SoftwareCrc32};
 use impeller2::types::{
-    IntoLenPacket, LenPacket, Msg, OwnedPacket, Request, RequestId, TryFromPacket,
+    IntoLenPacket, LenPacket, Msg, OwnedPacket, PacketHeader, PacketId, PacketTy, Request,
+    RequestId, TryFromPacket,
 };
-use impeller2_wkt::ErrorResponse;
+use impeller2_wkt::{Ack, Compression, ErrorResponse, NegotiateCompression};
 use stellarator::{
     BufResult,
-    buf::{IoBufMut, Slice},
+    buf::{IoBuf, IoBufMut, Slice},
     io::{AsyncRead, AsyncWrite, GrowableBuf, LengthDelReader, OwnedReader, OwnedWriter, SplitExt},
     net::TcpStream,
 };
+use zerocopy::TryFromBytes;
 
+pub mod ack;
+pub mod compression;
+pub mod observe;
+mod pool;
 #[cfg(feature = "queue")]
 pub mod queue;
+mod resilient;
+
+pub use ack::{AckTracker, AckWindow};
+pub use observe::{PacketEvent, PacketObserver};
+pub use pool::*;
+pub use resilient::*;
 
 pub struct PacketStream<R: AsyncRead> {
     reader: LengthDelReader<R>,
+    decompressed: Vec<u8>,
+    observer: Option<Arc<dyn PacketObserver>>,
 }
 
 impl<R: AsyncRead> PacketStream<R> {
@@ -28,11 +47,25 @@ impl<R: AsyncRead> PacketStream<R> {
         Self::from_reader(reader)
     }
     pub fn from_reader(reader: LengthDelReader<R>) -> Self {
-        Self { reader }
+        Self {
+            reader,
+            decompressed: vec![],
+            observer: None,
+        }
+    }
+
+    /// Reports every packet received from now on to `observer`, or stops reporting them if `None`
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn PacketObserver>>) {
+        self.observer = observer;
     }
 
     pub async fn next<B: IoBufMut>(&mut self, buf: B) -> Result<OwnedPacket<Slice<B>>, Error> {
+        let start = Instant::now();
         let packet_buf = self.reader.recv(buf).await?;
+        let len = packet_buf.len();
+        let packet_buf = strip_checksum(packet_buf)?;
+        let packet_buf = self.decompress(packet_buf, |_, _| {})?;
+        self.observe(&packet_buf, len, start);
         OwnedPacket::parse(packet_buf).map_err(Error::from)
     }
 
@@ -40,32 +73,264 @@ impl<R: AsyncRead> PacketStream<R> {
         &mut self,
         buf: B,
     ) -> Result<OwnedPacket<Slice<B>>, Error> {
+        let start = Instant::now();
         let packet_buf = self.reader.recv_growable(buf).await?;
+        let len = packet_buf.len();
+        let packet_buf = strip_checksum(packet_buf)?;
+        let packet_buf = self.decompress(packet_buf, B::grow)?;
+        self.observe(&packet_buf, len, start);
         OwnedPacket::parse(packet_buf).map_err(Error::from)
     }
+
+    /// Reports a received packet to the observer, `len` being its size on the wire without the length prefix
+    fn observe(&self, packet: &[u8], len: usize, start: Instant) {
+        let Some(observer) = &self.observer else {
+            return;
+        };
+        // malformed packets are reported by the parse that follows
+        let Ok((header, _)) = PacketHeader::try_read_from_prefix(packet) else {
+            return;
+        };
+        observer.observe(&PacketEvent {
+            direction: observe::Direction::Received,
+            id: header.id,
+            ty: header.packet_ty,
+            len: len + size_of::<u32>(),
+            latency: start.elapsed(),
+        });
+    }
+
+    /// Replaces a compressed packet in `packet_buf` with its decompressed contents, calling `grow` to make room
+    fn decompress<B: IoBufMut>(
+        &mut self,
+        packet_buf: Slice<B>,
+        grow: impl FnOnce(&mut B, usize),
+    ) -> Result<Slice<B>, Error> {
+        let Some(codec) = compression::packet_codec(&packet_buf)? else {
+            return Ok(packet_buf);
+        };
+        compression::decompress(&packet_buf, codec, &mut self.decompressed)?;
+        let start = packet_buf.range().start;
+        let end = start + self.decompressed.len();
+        let mut buf = packet_buf.into_inner();
+        grow(&mut buf, end);
+        if end > buf.total_len() {
+            return Err(impeller2::error::Error::BufferOverflow.into());
+        }
+        // Safety: `end` is within the buffer's capacity, and every byte up to it is initialized, either by the read
+        // that filled the compressed packet or by the copy here
+        unsafe {
+            let ptr = buf.stable_mut_ptr().as_ptr() as *mut u8;
+            std::ptr::copy_nonoverlapping(
+                self.decompressed.as_ptr(),
+                ptr.add(start),
+                self.decompressed.len(),
+            );
+            buf.set_init(end.max(buf.init_len()));
+        }
+        buf.try_slice(start..end)
+            .ok_or(impeller2::error::Error::BufferOverflow.into())
+    }
+}
+
+/// Verifies and removes the checksum trailer from `packet_buf`, if it has one
+fn strip_checksum<B: IoBufMut>(packet_buf: Slice<B>) -> Result<Slice<B>, Error> {
+    if !checksum::has_checksum(&packet_buf) {
+        return Ok(packet_buf);
+    }
+    let len = checksum::verify(&packet_buf, &SoftwareCrc32)?;
+    let start = packet_buf.range().start;
+    let mut buf = packet_buf.into_inner();
+    // Safety: `start` is the packet type byte, which the read that filled the packet initialized
+    unsafe {
+        let ptr = buf.stable_mut_ptr().as_ptr() as *mut u8;
+        *ptr.add(start) &= !checksum::CHECKSUM_FLAG;
+    }
+    buf.try_slice(start..start + len)
+        .ok_or(impeller2::error::Error::BufferOverflow.into())
 }
 
 pub struct PacketSink<W: AsyncWrite> {
     writer: W,
+    compression: Option<Compression>,
+    checksum: bool,
+    acks: Arc<AckTracker>,
+    ack_timeout: Duration,
+    max_retransmits: u32,
+    observer: Option<Arc<dyn PacketObserver>>,
 }
 
 impl<W: AsyncWrite> PacketSink<W> {
     pub fn new(writer: W) -> Self {
-        Self { writer }
+        Self {
+            writer,
+            compression: None,
+            checksum: false,
+            acks: Arc::default(),
+            ack_timeout: ack::DEFAULT_ACK_TIMEOUT,
+            max_retransmits: ack::DEFAULT_MAX_RETRANSMITS,
+            observer: None,
+        }
+    }
+
+    /// Reports every packet sent from now on to `observer`, or stops reporting them if `None`
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn PacketObserver>>) {
+        self.observer = observer;
+    }
+
+    /// How long [`Self::send_with_ack`] waits for an ack before retransmitting
+    pub fn set_ack_timeout(&mut self, timeout: Duration) {
+        self.ack_timeout = timeout;
+    }
+
+    pub fn ack_timeout(&self) -> Duration {
+        self.ack_timeout
+    }
+
+    /// How many times [`Self::send_with_ack`] retransmits a msg before giving up
+    pub fn set_max_retransmits(&mut self, max_retransmits: u32) {
+        self.max_retransmits = max_retransmits;
+    }
+
+    pub fn max_retransmits(&self) -> u32 {
+        self.max_retransmits
+    }
+
+    /// The acks [`Self::send_with_ack`] is waiting on, which whatever reads the link should hand received [`Ack`]s to
+    pub fn acks(&self) -> Arc<AckTracker> {
+        self.acks.clone()
+    }
+
+    /// Ends every packet sent from now on with a CRC-32 trailer, so the receiver can detect corruption
+    pub fn set_checksum(&mut self, checksum: bool) {
+        self.checksum = checksum;
+    }
+
+    pub fn checksum(&self) -> bool {
+        self.checksum
+    }
+
+    /// Compresses every packet sent from now on with `codec`, or stops compressing if `None`
+    pub fn set_compression(&mut self, codec: Option<Compression>) {
+        self.compression = codec;
+    }
+
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// Shuts down the connection the sink writes to, e.g. once a send was dropped partway through a packet and the
+    /// link can no longer be framed
+    pub fn shutdown(&self) -> std::io::Result<()> {
+        self.writer.shutdown()
     }
 
     pub async fn send(&self, packet: impl IntoLenPacket) -> BufResult<(), LenPacket> {
-        let packet = packet.into_len_packet();
+        let mut packet = packet.into_len_packet();
+        let header = &packet.as_packet().header;
+        let (id, ty) = (header.id, header.packet_ty);
+        let start = Instant::now();
+        // the uncompressed packet is handed back either way, so callers keep reusing its buffer
+        if let Some(mut compressed) = self
+            .compression
+            .and_then(|codec| compression::compress(&packet, codec))
+        {
+            if self.checksum {
+                compressed.append_checksum(&SoftwareCrc32);
+            }
+            let len = compressed.inner.len();
+            let (res, _) = self.writer.write_all(compressed.inner).await;
+            self.observe(&res, id, ty, len, start);
+            return (res, packet);
+        }
+        if self.checksum {
+            packet.append_checksum(&SoftwareCrc32);
+        }
+        let len = packet.inner.len();
         let (res, inner) = self.writer.write_all(packet.inner).await;
-        (res, LenPacket { inner })
+        self.observe(&res, id, ty, len, start);
+        let mut packet = LenPacket { inner };
+        packet.remove_checksum();
+        (res, packet)
+    }
+
+    /// Reports a sent packet to the observer, if it was written
+    fn observe<T, E>(
+        &self,
+        res: &Result<T, E>,
+        id: PacketId,
+        ty: PacketTy,
+        len: usize,
+        start: Instant,
+    ) {
+        if let (Some(observer), Ok(_)) = (&self.observer, res) {
+            observer.observe(&PacketEvent {
+                direction: observe::Direction::Sent,
+                id,
+                ty,
+                len,
+                latency: start.elapsed(),
+            });
+        }
+    }
+
+    /// Sends `msg` as a [`PacketTy::MsgWithAck`], retransmitting it until it's acknowledged
+    ///
+    /// Fails with [`Error::AckTimeout`] once [`Self::max_retransmits`] retransmits have gone unacknowledged. The ack
+    /// only arrives if something hands it to [`Self::acks`], see [`Client::send_with_ack`] for a client that does.
+    pub async fn send_with_ack(&self, msg: impl IntoLenPacket) -> Result<(), Error> {
+        let msg = msg.into_len_packet();
+        let msg = msg.as_packet();
+        if msg.header.packet_ty != PacketTy::Msg {
+            return Err(Error::InvalidPacketType);
+        }
+        let seq = self.acks.register();
+        let mut packet = LenPacket::msg_with_ack(msg.header.id, seq, msg.body.len())
+            .with_request_id(msg.header.req_id);
+        packet.extend_from_slice(&msg.body);
+        for _ in 0..=self.max_retransmits {
+            let (res, sent) = self.send(packet).await;
+            packet = sent;
+            if let Err(err) = res {
+                self.acks.forget(seq);
+                return Err(err.into());
+            }
+            if self.acks.wait_for(seq, self.ack_timeout).await {
+                return Ok(());
+            }
+        }
+        self.acks.forget(seq);
+        Err(Error::AckTimeout(seq))
     }
 }
 
+/// The most replies [`Client`] will hold on to for requests that aren't currently being waited on
+const MAX_PENDING_REPLIES: usize = 64;
+
+type TcpPacketStream = PacketStream<OwnedReader<TcpStream>>;
+
+/// A read that owns the stream it reads from, handing it back along with the packet
+type Read =
+    Pin<Box<dyn Future<Output = (TcpPacketStream, Result<OwnedPacket<Slice<Vec<u8>>>, Error>)>>>;
+
 pub struct Client {
-    resp_buf: Option<Vec<u8>>,
     pub tx: PacketSink<OwnedWriter<TcpStream>>,
-    pub rx: PacketStream<OwnedReader<TcpStream>>,
-    next_req_id: u8,
+    rx: Receiver,
+    next_req_id: RequestId,
+}
+
+/// The reading half of a [`Client`]
+///
+/// A read in progress is kept here rather than in the future waiting on it, so a wait that's dropped part way through,
+/// like one raced against a timeout or Ctrl-C, never leaves a packet half-read. The next wait picks the same read back
+/// up.
+struct Receiver {
+    stream: Option<TcpPacketStream>,
+    read: Option<Read>,
+    /// An observer set while a read was in progress, applied once the stream is handed back
+    observer: Option<Option<Arc<dyn PacketObserver>>>,
+    resp_buf: Option<Vec<u8>>,
+    pending: VecDeque<OwnedPacket<Slice<Vec<u8>>>>,
 }
 
 impl Client {
@@ -73,15 +338,40 @@ impl Client {
         let stream = TcpStream::connect(addr).await?;
         let (rx, tx) = stream.split();
         let tx = PacketSink::new(tx);
-        let rx = PacketStream::new(rx);
+        let rx = Receiver {
+            stream: Some(PacketStream::new(rx)),
+            read: None,
+            observer: None,
+            resp_buf: Some(vec![0u8; 256]),
+            pending: VecDeque::new(),
+        };
         Ok(Client {
             tx,
             rx,
             next_req_id: 0,
-            resp_buf: Some(vec![0u8; 256]),
         })
     }
 
+    /// Reports every packet sent and received from now on to `observer`, or stops reporting them if `None`
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn PacketObserver>>) {
+        self.tx.set_observer(observer.clone());
+        match &mut self.rx.stream {
+            Some(stream) => stream.set_observer(observer),
+            None => self.rx.observer = Some(observer),
+        }
+    }
+
+    /// Hands out the next request id, skipping 0 since that is what packets sent outside of a request carry
+    ///
+    /// Ids wrap around, so replies still held for an earlier request with the same id are dropped rather than handed
+    /// to the new one.
+    pub fn next_request_id(&mut self) -> RequestId {
+        self.next_req_id = self.next_req_id.wrapping_add(1).max(1);
+        let req_id = self.next_req_id;
+        self.rx.pending.retain(|pkt| pkt.req_id() != req_id);
+        req_id
+    }
+
     pub async fn send(&mut self, packet: impl IntoLenPacket) -> BufResult<(), LenPacket> {
         let len_pkt = packet.into_len_packet();
         self.tx.send(len_pkt).await
@@ -91,44 +381,86 @@ impl Client {
         &mut self,
         req: R,
     ) -> Result<R::Reply<Slice<Vec<u8>>>, Error> {
-        let req_id = self.next_req_id.wrapping_add(1);
-        self.send(req.with_request_id(req_id)).await.0?;
+        let req_id = self.send_req(req).await?;
         self.recv(req_id).await
     }
 
+    /// Sends `req` tagged with a fresh request id without waiting for the reply
+    ///
+    /// Pass the returned id to [`Client::recv`] to get the reply. Several requests can be in flight at once, replies
+    /// that arrive while waiting on a different id are held until they're asked for.
+    pub async fn send_req<R: Request + IntoLenPacket>(
+        &mut self,
+        req: R,
+    ) -> Result<RequestId, Error> {
+        let req_id = self.next_request_id();
+        self.send(req.with_request_id(req_id)).await.0?;
+        Ok(req_id)
+    }
+
+    /// Waits for the reply to `req_id`
+    ///
+    /// Dropping the returned future part way through is safe, a packet that was being read is picked back up by the
+    /// next call.
     pub async fn recv<O: TryFromPacket<Slice<Vec<u8>>>>(
         &mut self,
         req_id: RequestId,
     ) -> Result<O, Error> {
+        if let Some(pkt) = self.rx.take_held(req_id) {
+            return parse_reply(&pkt);
+        }
+        let acks = self.tx.acks();
         loop {
-            let buf = self.resp_buf.take().unwrap_or(vec![0u8; 256]);
-            let pkt = self.rx.next_grow(buf).await?;
+            let pkt = self.rx.next(&acks).await?;
             if pkt.req_id() != req_id {
-                println!("skipping msg because of mismatched req_id");
-                self.resp_buf = Some(pkt.into_buf().into_inner());
+                self.rx.hold(pkt);
                 continue;
             }
-            let res = match &pkt {
-                OwnedPacket::Msg(m) if m.id == ErrorResponse::ID => {
-                    match postcard::from_bytes::<ErrorResponse>(&m.buf) {
-                        Ok(e) => Err(Error::Response(e)),
-                        Err(e) => Err(Error::Postcard(e)),
-                    }
-                }
-                pkt => O::try_from_packet(pkt).map_err(Error::from),
-            };
-
-            self.resp_buf = Some(pkt.into_buf().into_inner());
+            let res = parse_reply(&pkt);
+            self.rx.resp_buf = Some(pkt.into_buf().into_inner());
             return res;
         }
     }
 
+    /// Sends `msg` with [`PacketSink::send_with_ack`], reading from the connection until it's acknowledged
+    ///
+    /// Replies that arrive in the meantime are held for [`Client::recv`].
+    pub async fn send_with_ack(&mut self, msg: impl IntoLenPacket) -> Result<(), Error> {
+        let acks = self.tx.acks();
+        let Client { tx, rx, .. } = self;
+        // the reads are dropped once the ack arrives, which is safe since `rx` keeps a read in progress
+        futures_lite::future::or(tx.send_with_ack(msg), async {
+            loop {
+                let pkt = rx.next(&acks).await?;
+                rx.hold(pkt);
+            }
+        })
+        .await
+    }
+
+    /// Asks the db to compress what it sends with the first of `codecs` it supports, and compresses what this client
+    /// sends with the same codec
+    ///
+    /// Returns the codec both sides settled on, or `None` if there wasn't one in common.
+    pub async fn negotiate_compression(
+        &mut self,
+        codecs: &[Compression],
+    ) -> Result<Option<Compression>, Error> {
+        let codecs = codecs
+            .iter()
+            .copied()
+            .filter(|codec| compression::SUPPORTED_CODECS.contains(codec))
+            .collect();
+        let accepted = self.request(&NegotiateCompression { codecs }).await?;
+        self.tx.set_compression(accepted.codec);
+        Ok(accepted.codec)
+    }
+
     pub async fn stream<R: impeller2::types::Request + IntoLenPacket>(
         &mut self,
         req: R,
     ) -> Result<SubStream<'_, R::Reply<Slice<Vec<u8>>>>, Error> {
-        let req_id = self.next_req_id.wrapping_add(1);
-        self.send(req.with_request_id(req_id)).await.0?;
+        let req_id = self.send_req(req).await?;
         Ok(SubStream {
             req_id,
             client: self,
@@ -137,6 +469,67 @@ impl Client {
     }
 }
 
+impl Receiver {
+    /// Takes the reply to `req_id` if it arrived while waiting on a different one
+    fn take_held(&mut self, req_id: RequestId) -> Option<OwnedPacket<Slice<Vec<u8>>>> {
+        let i = self.pending.iter().position(|pkt| pkt.req_id() == req_id)?;
+        self.pending.remove(i)
+    }
+
+    /// Holds a reply nobody is waiting on yet, dropping the oldest one once there are too many
+    fn hold(&mut self, pkt: OwnedPacket<Slice<Vec<u8>>>) {
+        if self.pending.len() >= MAX_PENDING_REPLIES {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(pkt);
+    }
+
+    /// Reads the next packet, handing acks to `acks` rather than returning them
+    async fn next(&mut self, acks: &AckTracker) -> Result<OwnedPacket<Slice<Vec<u8>>>, Error> {
+        loop {
+            let read = self.read.get_or_insert_with(|| {
+                let mut stream = self
+                    .stream
+                    .take()
+                    .expect("stream missing with no read in progress");
+                let buf = self.resp_buf.take().unwrap_or(vec![0u8; 256]);
+                Box::pin(async move {
+                    let res = stream.next_grow(buf).await;
+                    (stream, res)
+                })
+            });
+            let (mut stream, res) = read.await;
+            self.read = None;
+            if let Some(observer) = self.observer.take() {
+                stream.set_observer(observer);
+            }
+            self.stream = Some(stream);
+            let pkt = res?;
+            match &pkt {
+                OwnedPacket::Msg(m) if m.id == Ack::ID => {
+                    acks.ack(&m.parse::<Ack>()?);
+                    self.resp_buf = Some(pkt.into_buf().into_inner());
+                }
+                _ => return Ok(pkt),
+            }
+        }
+    }
+}
+
+fn parse_reply<O: TryFromPacket<Slice<Vec<u8>>>>(
+    pkt: &OwnedPacket<Slice<Vec<u8>>>,
+) -> Result<O, Error> {
+    match pkt {
+        OwnedPacket::Msg(m) if m.id == ErrorResponse::ID => {
+            match postcard::from_bytes::<ErrorResponse>(&m.buf) {
+                Ok(e) => Err(Error::Response(e)),
+                Err(e) => Err(Error::Postcard(e)),
+            }
+        }
+        pkt => O::try_from_packet(pkt).map_err(Error::from),
+    }
+}
+
 pub struct SubStream<'a, R> {
     req_id: RequestId,
     client: &'a mut Client,
@@ -179,6 +572,10 @@ pub enum Error {
     Wait(stellarator::sync::wait_map::WaitError),
     #[error("rx handle closed")]
     RxHandleClosed,
+    #[error("invalid compressed packet")]
+    Decompress,
+    #[error("msg {0} was never acknowledged")]
+    AckTimeout(u32),
     #[error("{0}")]
     Response(ErrorResponse),
 }
@@ -0,0 +1,165 @@
+//! Hooks for watching the packets a [`PacketSink`](crate::PacketSink) sends and a
+//! [`PacketStream`](crate::PacketStream) receives, for debugging throughput problems in deployed systems
+//!
+//! Observers are called inline on every packet, so they should be cheap. [`TracingObserver`] emits a span per packet,
+//! and [`PacketCounters`] keeps running totals that can be sent to the db as components.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use impeller2::{
+    types::{ComponentId, LenPacket, PacketId, PacketTy, PrimType},
+    vtable::builder::{component, hashed_schema, raw_field, vtable},
+};
+use impeller2_wkt::{SetComponentMetadata, VTableMsg};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// A packet that went over the link
+#[derive(Clone, Copy, Debug)]
+pub struct PacketEvent {
+    pub direction: Direction,
+    pub id: PacketId,
+    pub ty: PacketTy,
+    /// The bytes on the wire, after compression and including the length prefix and any checksum
+    pub len: usize,
+    /// How long the write took for a sent packet, or how long the read waited for a received one
+    pub latency: Duration,
+}
+
+/// Called with every packet a sink sends or a stream receives, see [`crate::PacketSink::set_observer`]
+pub trait PacketObserver: Send + Sync {
+    fn observe(&self, event: &PacketEvent);
+}
+
+/// Emits a `packet` span at trace level for every packet, with the event's fields
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingObserver;
+
+impl PacketObserver for TracingObserver {
+    fn observe(&self, event: &PacketEvent) {
+        let span = tracing::trace_span!(
+            "packet",
+            direction = ?event.direction,
+            id = ?event.id,
+            ty = ?event.ty,
+            len = event.len,
+            latency_us = event.latency.as_micros() as u64,
+        );
+        span.in_scope(|| tracing::trace!("packet {:?}", event.direction));
+    }
+}
+
+/// Running totals of the packets seen, which can be written into the db as `u64` components
+///
+/// The components are named `{prefix}.packets_sent`, `{prefix}.bytes_sent`, `{prefix}.packets_received`,
+/// `{prefix}.bytes_received` and `{prefix}.max_latency_us`. The max latency is reset every time [`Self::table`] reads
+/// it, so it covers the time since the last table. The counters shouldn't observe the link their tables are sent over
+/// too, or every table counts itself.
+pub struct PacketCounters {
+    names: [String; PacketCounters::LEN],
+    values: [AtomicU64; PacketCounters::LEN],
+}
+
+impl PacketCounters {
+    const LEN: usize = 5;
+    const PACKETS_SENT: usize = 0;
+    const BYTES_SENT: usize = 1;
+    const PACKETS_RECEIVED: usize = 2;
+    const BYTES_RECEIVED: usize = 3;
+    const MAX_LATENCY_US: usize = 4;
+
+    pub fn new(prefix: &str) -> Self {
+        let names = [
+            "packets_sent",
+            "bytes_sent",
+            "packets_received",
+            "bytes_received",
+            "max_latency_us",
+        ]
+        .map(|name| format!("{prefix}.{name}"));
+        Self {
+            names,
+            values: Default::default(),
+        }
+    }
+
+    fn get(&self, i: usize) -> u64 {
+        self.values[i].load(Ordering::Relaxed)
+    }
+
+    pub fn packets_sent(&self) -> u64 {
+        self.get(Self::PACKETS_SENT)
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.get(Self::BYTES_SENT)
+    }
+
+    pub fn packets_received(&self) -> u64 {
+        self.get(Self::PACKETS_RECEIVED)
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.get(Self::BYTES_RECEIVED)
+    }
+
+    fn component_ids(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.names.iter().map(|name| ComponentId::new(name))
+    }
+
+    /// The vtable for [`Self::table`], to send before the first table
+    pub fn vtable_msg(&self, id: PacketId) -> VTableMsg {
+        let fields = self.component_ids().enumerate().map(|(i, component_id)| {
+            raw_field(
+                (i * size_of::<u64>()) as u16,
+                size_of::<u64>() as u16,
+                hashed_schema(PrimType::U64, &[], component(component_id)),
+            )
+        });
+        VTableMsg {
+            id,
+            vtable: vtable(fields),
+        }
+    }
+
+    /// Names each counter's component after its id
+    pub fn metadata(&self) -> impl Iterator<Item = SetComponentMetadata> + '_ {
+        self.component_ids()
+            .zip(&self.names)
+            .map(|(component_id, name)| SetComponentMetadata::new(component_id, name))
+    }
+
+    /// A table of the current counters, laid out by [`Self::vtable_msg`]
+    pub fn table(&self, id: PacketId) -> LenPacket {
+        let mut packet = LenPacket::table(id, Self::LEN * size_of::<u64>());
+        for (i, value) in self.values.iter().enumerate() {
+            let value = if i == Self::MAX_LATENCY_US {
+                value.swap(0, Ordering::Relaxed)
+            } else {
+                value.load(Ordering::Relaxed)
+            };
+            packet.extend_aligned(&[value]);
+        }
+        packet
+    }
+}
+
+impl PacketObserver for PacketCounters {
+    fn observe(&self, event: &PacketEvent) {
+        let (packets, bytes) = match event.direction {
+            Direction::Sent => (Self::PACKETS_SENT, Self::BYTES_SENT),
+            Direction::Received => (Self::PACKETS_RECEIVED, Self::BYTES_RECEIVED),
+        };
+        self.values[packets].fetch_add(1, Ordering::Relaxed);
+        self.values[bytes].fetch_add(event.len as u64, Ordering::Relaxed);
+        self.values[Self::MAX_LATENCY_US]
+            .fetch_max(event.latency.as_micros() as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    net::SocketAddr,
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+};
+
+use stellarator::sync::{Semaphore, semaphore::Permit};
+
+use crate::{Client, Error};
+
+/// A pool of [`Client`]s connected to a single db, capped at a fixed number of checked out clients
+///
+/// Once `max_in_flight` clients are checked out, [`ClientPool::get`] waits for one to be returned rather than opening
+/// another connection, so a burst of requests backs up in the caller instead of piling connections onto the db.
+/// Waiters are served in FIFO order, so a caller can't be starved by later ones.
+pub struct ClientPool {
+    addr: SocketAddr,
+    idle: Mutex<Vec<Client>>,
+    permits: Semaphore,
+    max_in_flight: usize,
+}
+
+impl ClientPool {
+    pub fn new(addr: SocketAddr, max_in_flight: usize) -> Self {
+        Self {
+            addr,
+            idle: Mutex::new(vec![]),
+            permits: Semaphore::new(max_in_flight),
+            max_in_flight,
+        }
+    }
+
+    /// Checks out a client, reusing an idle connection if there is one
+    pub async fn get(&self) -> Result<PooledClient<'_>, Error> {
+        let permit = self
+            .permits
+            .acquire(1)
+            .await
+            .expect("pool semaphore is never closed");
+        let idle = self.idle.lock().unwrap().pop();
+        let client = match idle {
+            Some(client) => client,
+            None => Client::connect(self.addr).await?,
+        };
+        Ok(PooledClient {
+            client: Some(client),
+            pool: self,
+            _permit: permit,
+        })
+    }
+
+    /// The number of clients currently checked out
+    pub fn in_flight(&self) -> usize {
+        self.max_in_flight - self.permits.available_permits()
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+}
+
+/// A [`Client`] checked out of a [`ClientPool`], returned to the pool on drop
+pub struct PooledClient<'a> {
+    client: Option<Client>,
+    pool: &'a ClientPool,
+    // declared last so the client is back in the pool before the permit is released
+    _permit: Permit<'a>,
+}
+
+impl PooledClient<'_> {
+    /// Closes the connection instead of returning it to the pool, e.g. after an io error
+    pub fn discard(mut self) {
+        self.client = None;
+    }
+}
+
+impl Deref for PooledClient<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        self.client.as_ref().expect("client taken before drop")
+    }
+}
+
+impl DerefMut for PooledClient<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.client.as_mut().expect("client taken before drop")
+    }
+}
+
+impl Drop for PooledClient<'_> {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            self.pool.idle.lock().unwrap().push(client);
+        }
+    }
+}
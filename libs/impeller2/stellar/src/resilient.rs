@@ -0,0 +1,141 @@
+use std::{net::SocketAddr, time::Duration};
+
+use impeller2::types::{IntoLenPacket, Msg, OwnedPacket, OwnedTable, Timestamp, TryFromPacket};
+use impeller2_wkt::{InitialTimestamp, Stream, StreamBehavior, StreamTimestamp, VTableMsg};
+use stellarator::buf::{IoBuf, Slice};
+
+use crate::{Client, Error};
+
+/// How a [`ResilientStream`] waits between reconnect attempts
+#[derive(Clone, Copy, Debug)]
+pub struct ReconnectPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up after this many consecutive failed attempts, or never if `None`
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            max_attempts: None,
+        }
+    }
+}
+
+/// A packet received on a [`ResilientStream`]
+pub enum ResilientReply<B: IoBuf> {
+    Table(OwnedTable<B>),
+    VTable(VTableMsg),
+    Timestamp(StreamTimestamp),
+}
+
+impl<B: IoBuf + Clone> TryFromPacket<B> for ResilientReply<B> {
+    fn try_from_packet(packet: &OwnedPacket<B>) -> Result<Self, impeller2::error::Error> {
+        match packet {
+            OwnedPacket::Msg(m) if m.id == VTableMsg::ID => Ok(Self::VTable(m.parse()?)),
+            OwnedPacket::Msg(m) if m.id == StreamTimestamp::ID => Ok(Self::Timestamp(m.parse()?)),
+            OwnedPacket::Table(table) => Ok(Self::Table(table.clone())),
+            _ => Err(impeller2::error::Error::InvalidPacket),
+        }
+    }
+}
+
+/// A [`Stream`] subscription that reconnects and resubscribes when the connection drops
+///
+/// The stream id is kept across reconnects. For fixed rate streams the last [`StreamTimestamp`] received is sent
+/// back as [`InitialTimestamp::Manual`], so playback resumes where it left off instead of restarting.
+pub struct ResilientStream {
+    addr: SocketAddr,
+    stream: Stream,
+    policy: ReconnectPolicy,
+    client: Option<Client>,
+    req_id: u8,
+    last_timestamp: Option<Timestamp>,
+}
+
+impl ResilientStream {
+    pub fn new(addr: SocketAddr, stream: Stream) -> Self {
+        Self {
+            addr,
+            stream,
+            policy: ReconnectPolicy::default(),
+            client: None,
+            req_id: 1,
+            last_timestamp: None,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The timestamp of the last tick received from a fixed rate stream
+    pub fn last_timestamp(&self) -> Option<Timestamp> {
+        self.last_timestamp
+    }
+
+    pub async fn next(&mut self) -> Result<ResilientReply<Slice<Vec<u8>>>, Error> {
+        loop {
+            if self.client.is_none() {
+                self.connect().await?;
+            }
+            let client = self.client.as_mut().expect("client was just connected");
+            match client.recv(self.req_id).await {
+                Ok(reply) => {
+                    if let ResilientReply::Timestamp(ts) = &reply {
+                        self.last_timestamp = Some(ts.timestamp);
+                    }
+                    return Ok(reply);
+                }
+                Err(Error::Stellar(_)) => {
+                    self.client = None;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn connect(&mut self) -> Result<(), Error> {
+        let mut stream = self.stream.clone();
+        if let (StreamBehavior::FixedRate(behavior), Some(last_timestamp)) =
+            (&mut stream.behavior, self.last_timestamp)
+        {
+            behavior.initial_timestamp = InitialTimestamp::Manual(last_timestamp);
+        }
+
+        let mut backoff = self.policy.initial_backoff;
+        let mut attempts = 0;
+        let client = loop {
+            let res = async {
+                let mut client = Client::connect(self.addr).await?;
+                client
+                    .send((&stream).with_request_id(self.req_id))
+                    .await
+                    .0?;
+                Ok::<_, Error>(client)
+            }
+            .await;
+            match res {
+                Ok(client) => break client,
+                Err(err) => {
+                    attempts += 1;
+                    if self
+                        .policy
+                        .max_attempts
+                        .is_some_and(|max_attempts| attempts >= max_attempts)
+                    {
+                        return Err(err);
+                    }
+                    stellarator::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.policy.max_backoff);
+                }
+            }
+        };
+        self.client = Some(client);
+        Ok(())
+    }
+}
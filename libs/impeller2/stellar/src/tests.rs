@@ -15,6 +15,10 @@ impl Msg for Foo {
     const ID: PacketId = [0x1, 0x2];
 }
 
+impl Request for Foo {
+    type Reply<B: IoBuf + Clone> = Foo;
+}
+
 #[stellarator::test]
 async fn test_packet_echo() {
     let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
@@ -34,3 +38,353 @@ async fn test_packet_echo() {
     let foo: Foo = m.parse().unwrap();
     assert_eq!(foo, Foo { bar: 0xBB });
 }
+
+#[stellarator::test]
+async fn test_resilient_stream_resumes() {
+    use impeller2::types::{LenPacket, Timestamp};
+    use impeller2_wkt::{
+        FixedRateBehavior, InitialTimestamp, Stream, StreamBehavior, StreamTimestamp,
+    };
+    use stellarator::io::SplitExt;
+
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let addr = listener.local_addr().unwrap();
+    stellarator::spawn(async move {
+        for timestamp in [Timestamp(5), Timestamp(6)] {
+            let (rx, tx) = listener.accept().await.unwrap().split();
+            let mut rx = PacketStream::new(rx);
+            let OwnedPacket::Msg(m) = rx.next(vec![0; 128]).await.unwrap() else {
+                panic!("non msg pkt");
+            };
+            let stream: Stream = m.parse().unwrap();
+            assert_eq!(stream.id, 7);
+            let StreamBehavior::FixedRate(behavior) = stream.behavior else {
+                panic!("expected fixed rate stream");
+            };
+            if timestamp == Timestamp(6) {
+                assert!(matches!(
+                    behavior.initial_timestamp,
+                    InitialTimestamp::Manual(Timestamp(5))
+                ));
+            }
+            let tx = PacketSink::new(tx);
+            let msg = StreamTimestamp {
+                timestamp,
+                stream_id: 7,
+            };
+            tx.send(msg.with_request_id(m.req_id)).await.0.unwrap();
+            let table = LenPacket::table([7, 0], 0).with_request_id(m.req_id);
+            tx.send(table).await.0.unwrap();
+        }
+    });
+
+    let mut stream = ResilientStream::new(
+        addr,
+        Stream {
+            behavior: StreamBehavior::FixedRate(FixedRateBehavior::default()),
+            id: 7,
+        },
+    );
+    for timestamp in [Timestamp(5), Timestamp(6)] {
+        let ResilientReply::Timestamp(ts) = stream.next().await.unwrap() else {
+            panic!("expected stream timestamp");
+        };
+        assert_eq!(ts.timestamp, timestamp);
+        assert_eq!(stream.last_timestamp(), Some(timestamp));
+        let ResilientReply::Table(_) = stream.next().await.unwrap() else {
+            panic!("expected table");
+        };
+    }
+}
+
+#[stellarator::test]
+async fn test_client_pool_caps_in_flight() {
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let addr = listener.local_addr().unwrap();
+    stellarator::spawn(async move {
+        let mut conns = vec![];
+        loop {
+            conns.push(listener.accept().await.unwrap());
+        }
+    });
+
+    let pool = ClientPool::new(addr, 2);
+    let a = pool.get().await.unwrap();
+    let b = pool.get().await.unwrap();
+    assert_eq!(pool.in_flight(), 2);
+
+    // the pool is saturated, so a third checkout waits until a client is returned
+    let waiting = futures_lite::future::or(async { Some(pool.get().await.unwrap()) }, async {
+        stellarator::sleep(std::time::Duration::from_millis(50)).await;
+        None
+    })
+    .await;
+    assert!(waiting.is_none());
+
+    drop(a);
+    assert_eq!(pool.in_flight(), 1);
+    let c = pool.get().await.unwrap();
+    assert_eq!(pool.in_flight(), 2);
+    drop((b, c));
+    assert_eq!(pool.in_flight(), 0);
+}
+
+#[stellarator::test]
+async fn test_checksum_trailer() {
+    use impeller2::checksum::SoftwareCrc32;
+
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let addr = listener.local_addr().unwrap();
+    stellarator::spawn(async move {
+        let mut sink = PacketSink::new(listener.accept().await.unwrap());
+        sink.set_checksum(true);
+        let (res, msg) = sink.send(&Foo { bar: 0xBB }).await;
+        res.unwrap();
+        // the sink hands back the packet as it was before the trailer was added
+        assert_eq!(msg.inner, Foo { bar: 0xBB }.into_len_packet().inner);
+
+        sink.set_checksum(false);
+        let mut corrupt = Foo { bar: 0xCC }.into_len_packet();
+        corrupt.append_checksum(&SoftwareCrc32);
+        corrupt.inner[8] ^= 0x01;
+        sink.send(corrupt).await.0.unwrap();
+    });
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut stream = PacketStream::new(stream);
+    let OwnedPacket::Msg(m) = stream.next(vec![0; 128]).await.unwrap() else {
+        panic!("non msg pkt");
+    };
+    assert_eq!(m.id, Foo::ID);
+    assert_eq!(m.parse::<Foo>().unwrap(), Foo { bar: 0xBB });
+    assert!(matches!(
+        stream.next(vec![0; 128]).await,
+        Err(Error::Impeller(impeller2::error::Error::ChecksumMismatch))
+    ));
+}
+
+#[stellarator::test]
+async fn test_out_of_order_replies() {
+    use stellarator::io::SplitExt;
+
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let addr = listener.local_addr().unwrap();
+    stellarator::spawn(async move {
+        let (rx, tx) = listener.accept().await.unwrap().split();
+        let mut rx = PacketStream::new(rx);
+        let tx = PacketSink::new(tx);
+        let mut reqs = vec![];
+        for _ in 0..2 {
+            let OwnedPacket::Msg(m) = rx.next(vec![0; 128]).await.unwrap() else {
+                panic!("non msg pkt");
+            };
+            reqs.push((m.req_id, m.parse::<Foo>().unwrap()));
+        }
+        // reply to the second request first, and fail the first one
+        let (req_id, foo) = &reqs[1];
+        tx.send((&Foo { bar: foo.bar + 1 }).with_request_id(*req_id))
+            .await
+            .0
+            .unwrap();
+        let err = ErrorResponse {
+            description: "nope".to_string(),
+        };
+        tx.send((&err).with_request_id(reqs[0].0)).await.0.unwrap();
+    });
+
+    let mut client = Client::connect(addr).await.unwrap();
+    let a = client.send_req(&Foo { bar: 1 }).await.unwrap();
+    let b = client.send_req(&Foo { bar: 2 }).await.unwrap();
+    assert_ne!(a, b);
+    assert!(matches!(
+        client.recv::<Foo>(a).await,
+        Err(Error::Response(ErrorResponse { description })) if description == "nope"
+    ));
+    assert_eq!(client.recv::<Foo>(b).await.unwrap(), Foo { bar: 3 });
+}
+
+#[stellarator::test]
+async fn test_reused_request_id_drops_stale_reply() {
+    use stellarator::io::SplitExt;
+
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let addr = listener.local_addr().unwrap();
+    stellarator::spawn(async move {
+        let (rx, tx) = listener.accept().await.unwrap().split();
+        let mut rx = PacketStream::new(rx);
+        let tx = PacketSink::new(tx);
+        loop {
+            let OwnedPacket::Msg(m) = rx.next(vec![0; 128]).await.unwrap() else {
+                panic!("non msg pkt");
+            };
+            let foo = m.parse::<Foo>().unwrap();
+            tx.send(Foo { bar: foo.bar + 1 }.with_request_id(m.req_id))
+                .await
+                .0
+                .unwrap();
+        }
+    });
+
+    let mut client = Client::connect(addr).await.unwrap();
+    // the reply to `stale` is held while waiting on the next request, and never asked for
+    let stale = client.send_req(&Foo { bar: 1 }).await.unwrap();
+    let next = client.send_req(&Foo { bar: 2 }).await.unwrap();
+    assert_eq!(client.recv::<Foo>(next).await.unwrap(), Foo { bar: 3 });
+    while client.next_req_id.wrapping_add(1).max(1) != stale {
+        client.next_request_id();
+    }
+    let reused = client.send_req(&Foo { bar: 10 }).await.unwrap();
+    assert_eq!(reused, stale);
+    assert_eq!(client.recv::<Foo>(reused).await.unwrap(), Foo { bar: 11 });
+}
+
+#[stellarator::test]
+async fn test_send_with_ack_retransmits() {
+    use impeller2_wkt::Ack;
+    use stellarator::io::SplitExt;
+
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let addr = listener.local_addr().unwrap();
+    stellarator::spawn(async move {
+        let (rx, tx) = listener.accept().await.unwrap().split();
+        let mut rx = PacketStream::new(rx);
+        let tx = PacketSink::new(tx);
+        let mut window = AckWindow::default();
+        let mut seqs = vec![];
+        // the first copy is "lost", so only the retransmit gets acked
+        for _ in 0..2 {
+            let OwnedPacket::Msg(m) = rx.next(vec![0; 128]).await.unwrap() else {
+                panic!("non msg pkt");
+            };
+            assert_eq!(m.id, Foo::ID);
+            assert_eq!(m.parse::<Foo>().unwrap(), Foo { bar: 0xAA });
+            seqs.push(m.ack_seq.unwrap());
+        }
+        assert_eq!(seqs[0], seqs[1]);
+        assert!(window.insert(seqs[0]));
+        assert!(!window.insert(seqs[1]));
+        tx.send(&Ack { seq: seqs[1] }).await.0.unwrap();
+        // a request sent after the acked msg is still answered
+        let OwnedPacket::Msg(m) = rx.next(vec![0; 128]).await.unwrap() else {
+            panic!("non msg pkt");
+        };
+        tx.send(Foo { bar: 0xBB }.with_request_id(m.req_id))
+            .await
+            .0
+            .unwrap();
+    });
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client
+        .tx
+        .set_ack_timeout(std::time::Duration::from_millis(50));
+    client.send_with_ack(&Foo { bar: 0xAA }).await.unwrap();
+    let reply = client.request(&Foo { bar: 0 }).await.unwrap();
+    assert_eq!(reply, Foo { bar: 0xBB });
+}
+
+#[stellarator::test]
+async fn test_dropped_recv_resumes_read() {
+    use stellarator::io::SplitExt;
+
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let addr = listener.local_addr().unwrap();
+    stellarator::spawn(async move {
+        let (rx, tx) = listener.accept().await.unwrap().split();
+        let mut rx = PacketStream::new(rx);
+        let OwnedPacket::Msg(m) = rx.next(vec![0; 128]).await.unwrap() else {
+            panic!("non msg pkt");
+        };
+        // the reply arrives in two halves, with the client giving up on it in between
+        let reply = Foo { bar: 0xBB }.with_request_id(m.req_id).inner;
+        let rest = reply[6..].to_vec();
+        tx.write_all(reply[..6].to_vec()).await.0.unwrap();
+        stellarator::sleep(std::time::Duration::from_millis(100)).await;
+        tx.write_all(rest).await.0.unwrap();
+    });
+
+    let mut client = Client::connect(addr).await.unwrap();
+    let req_id = client.send_req(&Foo { bar: 0 }).await.unwrap();
+    let gave_up = futures_lite::future::or(
+        async { client.recv::<Foo>(req_id).await.ok().is_none() },
+        async {
+            stellarator::sleep(std::time::Duration::from_millis(50)).await;
+            true
+        },
+    )
+    .await;
+    assert!(gave_up);
+    assert_eq!(client.recv::<Foo>(req_id).await.unwrap(), Foo { bar: 0xBB });
+}
+
+#[stellarator::test]
+async fn test_send_with_ack_gives_up() {
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let addr = listener.local_addr().unwrap();
+    stellarator::spawn(async move {
+        let mut rx = PacketStream::new(listener.accept().await.unwrap());
+        loop {
+            let _ = rx.next(vec![0; 128]).await.unwrap();
+        }
+    });
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client
+        .tx
+        .set_ack_timeout(std::time::Duration::from_millis(10));
+    client.tx.set_max_retransmits(2);
+    let res = client.send_with_ack(&Foo { bar: 0xAA }).await;
+    assert!(matches!(res, Err(Error::AckTimeout(_))));
+}
+
+#[stellarator::test]
+async fn test_packet_counters() {
+    use crate::observe::PacketCounters;
+    use impeller2::types::{ComponentId, ComponentView, Timestamp};
+
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let sent = Arc::new(PacketCounters::new("link"));
+    let sink_counters = sent.clone();
+    stellarator::spawn(async move {
+        let mut sink = PacketSink::new(listener.accept().await.unwrap());
+        sink.set_checksum(true);
+        sink.set_observer(Some(sink_counters));
+        for bar in 0..3 {
+            sink.send(Foo { bar }.into_len_packet()).await.0.unwrap();
+        }
+    });
+    let received = Arc::new(PacketCounters::new("link"));
+    let mut stream = PacketStream::new(TcpStream::connect(addr).await.unwrap());
+    stream.set_observer(Some(received.clone()));
+    for _ in 0..3 {
+        stream.next(vec![0; 128]).await.unwrap();
+    }
+    assert_eq!(sent.packets_sent(), 3);
+    assert_eq!(received.packets_received(), 3);
+    assert_eq!(received.bytes_received(), sent.bytes_sent());
+    assert_eq!(received.packets_sent(), 0);
+
+    let msg = received.vtable_msg([9, 0]);
+    let table = received.table([9, 0]);
+    let OwnedPacket::Table(table) = OwnedPacket::parse_with_offset(table.inner, 4).unwrap() else {
+        panic!("expected table packet");
+    };
+    let mut values = vec![];
+    msg.vtable
+        .apply(
+            &table.buf[..],
+            &mut |id: ComponentId, view: ComponentView<'_>, _: Option<Timestamp>| {
+                let ComponentView::U64(view) = view else {
+                    panic!("expected u64 counters");
+                };
+                values.push((id, view.buf()[0]));
+            },
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(values[2], (ComponentId::new("link.packets_received"), 3));
+    assert_eq!(
+        values[3],
+        (ComponentId::new("link.bytes_received"), sent.bytes_sent())
+    );
+}
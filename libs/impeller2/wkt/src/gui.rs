@@ -1,5 +1,7 @@
 use crate::Color;
+use impeller2::buf::Buf;
 use impeller2::component::Asset;
+use impeller2::schema::Schema;
 use impeller2::types::{ComponentId, EntityId};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use std::collections::HashMap;
@@ -225,7 +227,59 @@ pub struct Graph<T = ()> {
     pub colors: Vec<crate::Color>,
 }
 
+impl Graph {
+    /// Builds a graph that plots every element of an entity's component, one line per element.
+    ///
+    /// The element count is taken from `schema`, and each line is assigned a distinct color from
+    /// [`Color::from_palette`].
+    pub fn auto<S: Buf<u64>>(entity: &str, component: &str, schema: &Schema<S>) -> Self {
+        let component_name = format!("{entity}.{component}");
+        let len = schema.dim().iter().product::<u64>() as usize;
+        let eql = (0..len)
+            .map(|i| format!("{component_name}[{i}]"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let colors = (0..len).map(Color::from_palette).collect();
+        Graph {
+            eql,
+            name: Some(component_name),
+            graph_type: GraphType::Line,
+            auto_y_range: true,
+            y_range: 0.0..1.0,
+            aux: (),
+            colors,
+        }
+    }
+}
+
 impl<T> Graph<T> {
+    /// Checks every `component[index]` term in the graph's eql against the component's element count
+    ///
+    /// Returns the first out of range index, so a misconfigured dashboard is caught before the renderer reads past
+    /// the end of a component. Terms that aren't a plain index into a component in `schemas` are skipped.
+    pub fn validate(
+        &self,
+        schemas: &HashMap<ComponentId, Schema<Vec<u64>>>,
+    ) -> Result<(), IndexOutOfBounds> {
+        for term in self.eql.split(',') {
+            let Some((name, index)) = term
+                .trim()
+                .strip_suffix(']')
+                .and_then(|t| t.split_once('['))
+            else {
+                continue;
+            };
+            let Ok(index) = index.parse::<usize>() else {
+                continue;
+            };
+            let component_id = ComponentId::new(name);
+            if let Some(schema) = schemas.get(&component_id) {
+                check_index(component_id, schema, index)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn map_aux<U>(&self, f: impl Fn(&T) -> U) -> Graph<U> {
         Graph {
             eql: self.eql.clone(),
@@ -290,6 +344,52 @@ pub struct VectorArrow {
     pub scale: f32,
 }
 
+impl VectorArrow {
+    /// Checks that the arrow's element range fits within its component
+    pub fn validate<S: Buf<u64>>(&self, schema: &Schema<S>) -> Result<(), IndexOutOfBounds> {
+        if self.range.is_empty() {
+            return Ok(());
+        }
+        check_index(self.id, schema, self.range.end - 1)
+    }
+}
+
+/// An element index past the end of a component, see [`Graph::validate`] and [`VectorArrow::validate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexOutOfBounds {
+    pub component_id: ComponentId,
+    pub index: usize,
+    pub len: usize,
+}
+
+impl std::fmt::Display for IndexOutOfBounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "index {} is out of bounds for component {:?} with {} elements",
+            self.index, self.component_id, self.len
+        )
+    }
+}
+
+impl std::error::Error for IndexOutOfBounds {}
+
+fn check_index<S: Buf<u64>>(
+    component_id: ComponentId,
+    schema: &Schema<S>,
+    index: usize,
+) -> Result<(), IndexOutOfBounds> {
+    let len = schema.dim().iter().product::<u64>() as usize;
+    if index >= len {
+        return Err(IndexOutOfBounds {
+            component_id,
+            index,
+            len,
+        });
+    }
+    Ok(())
+}
+
 impl Asset for VectorArrow {
     const NAME: &'static str = "arrow";
 }
@@ -788,3 +888,74 @@ impl<T> DashboardText<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use impeller2::types::PrimType;
+
+    #[test]
+    fn test_graph_auto() {
+        let schema = Schema::<Vec<u64>>::new(PrimType::F64, [3u64]).unwrap();
+        let graph = Graph::auto("rocket", "world_vel", &schema);
+        assert_eq!(
+            graph.eql,
+            "rocket.world_vel[0], rocket.world_vel[1], rocket.world_vel[2]"
+        );
+        assert_eq!(graph.colors.len(), 3);
+        assert_ne!(graph.colors[0], graph.colors[1]);
+        assert_ne!(graph.colors[1], graph.colors[2]);
+        assert_ne!(graph.colors[0], graph.colors[2]);
+    }
+
+    #[test]
+    fn test_graph_auto_past_palette() {
+        let schema = Schema::<Vec<u64>>::new(PrimType::F64, [4u64, 6]).unwrap();
+        let graph = Graph::auto("rocket", "wing_loads", &schema);
+        assert_eq!(graph.colors.len(), 24);
+        for (i, a) in graph.colors.iter().enumerate() {
+            for b in &graph.colors[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_graph_validate() {
+        let schema = Schema::<Vec<u64>>::new(PrimType::F64, [3u64]).unwrap();
+        let component_id = ComponentId::new("rocket.world_vel");
+        let schemas = HashMap::from([(component_id, schema.clone())]);
+
+        assert_eq!(
+            Graph::auto("rocket.world_vel", &schema).validate(&schemas),
+            Ok(())
+        );
+        let graph = Graph {
+            eql: "rocket.world_vel[0], rocket.world_vel[3], unknown[9]".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            graph.validate(&schemas),
+            Err(IndexOutOfBounds {
+                component_id,
+                index: 3,
+                len: 3,
+            })
+        );
+
+        let arrow = VectorArrow {
+            id: component_id,
+            range: 1..4,
+            color: Color::WHITE,
+            attached: false,
+            body_frame: false,
+            scale: 1.0,
+        };
+        assert!(arrow.validate(&schema).is_err());
+        let arrow = VectorArrow {
+            range: 0..3,
+            ..arrow
+        };
+        assert_eq!(arrow.validate(&schema), Ok(()));
+    }
+}
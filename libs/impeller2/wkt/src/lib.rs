@@ -43,6 +43,18 @@ impl Color {
     pub const MINT: Self = Self::rgb(0.53, 0.87, 0.62);
     pub const TRANSPARENT: Self = Self::rgba(0., 0., 0., 0.);
 
+    /// Default palette used when a series of distinct colors is needed, e.g. one per graph line
+    pub const PALETTE: [Self; 8] = [
+        Self::TURQUOISE,
+        Self::SLATE,
+        Self::PUMPKIN,
+        Self::YOLK,
+        Self::PEACH,
+        Self::REDDISH,
+        Self::HYPERBLUE,
+        Self::MINT,
+    ];
+
     pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
         Self { r, g, b, a: 1. }
     }
@@ -50,6 +62,49 @@ impl Color {
     pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
         Self { r, g, b, a }
     }
+
+    /// Returns the `i`-th color of [`Color::PALETTE`]
+    ///
+    /// Once the palette is exhausted, each further pass over it rotates the hue of its colors by another fraction of
+    /// the golden ratio, so no two indexes share a color.
+    pub fn from_palette(i: usize) -> Self {
+        let base = Self::PALETTE[i % Self::PALETTE.len()];
+        let pass = i / Self::PALETTE.len();
+        if pass == 0 {
+            return base;
+        }
+        const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_895;
+        base.rotate_hue((pass as f64 * GOLDEN_RATIO_CONJUGATE).fract() as f32)
+    }
+
+    /// Rotates the hue by `turns` of the color wheel, keeping saturation, value and alpha
+    pub fn rotate_hue(self, turns: f32) -> Self {
+        let Self { r, g, b, a } = self;
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+        if chroma == 0. {
+            return self;
+        }
+        let hue = if max == r {
+            ((g - b) / chroma).rem_euclid(6.)
+        } else if max == g {
+            (b - r) / chroma + 2.
+        } else {
+            (r - g) / chroma + 4.
+        };
+        let hue = (hue + turns * 6.).rem_euclid(6.);
+        let x = chroma * (1. - (hue.rem_euclid(2.) - 1.).abs());
+        let (r, g, b) = match hue as u32 {
+            0 => (chroma, x, 0.),
+            1 => (x, chroma, 0.),
+            2 => (0., chroma, x),
+            3 => (0., x, chroma),
+            4 => (x, 0., chroma),
+            _ => (chroma, 0., x),
+        };
+        Self::rgba(r + min, g + min, b + min, a)
+    }
 }
 
 impl Default for Color {
@@ -1,7 +1,11 @@
 use impeller2::types::{ComponentId, EntityId};
 use postcard_schema::Schema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    str::FromStr,
+};
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Schema)]
 #[cfg_attr(feature = "bevy", derive(bevy::prelude::Component))]
@@ -19,8 +23,120 @@ impl ComponentMetadata {
             .map(|v| v.as_str())
             .unwrap_or_default()
     }
+
+    /// The rate the component is nominally sampled at, in hertz
+    pub fn nominal_rate_hz(&self) -> Option<f64> {
+        self.metadata
+            .get("nominal_rate_hz")
+            .and_then(|v| v.parse().ok())
+            .filter(|hz: &f64| hz.is_finite() && *hz > 0.0)
+    }
+
+    pub fn set_nominal_rate_hz(&mut self, hz: f64) {
+        self.metadata
+            .insert("nominal_rate_hz".to_string(), hz.to_string());
+    }
+
+    /// The unit the component's values are in, like `m` or `deg`
+    pub fn unit(&self) -> Option<&str> {
+        self.metadata.get("unit").map(String::as_str)
+    }
+
+    /// The size of one step of a fixed-point value, so a stored `n` means `n * scale` in [`Self::unit`]s
+    pub fn scale(&self) -> Option<f64> {
+        self.metadata
+            .get("scale")
+            .and_then(|v| v.parse().ok())
+            .filter(|scale: &f64| scale.is_finite() && *scale != 0.0)
+    }
+
+    /// Symbolic names for an integer component's values, like `0 => IDLE` and `1 => ARMED`
+    ///
+    /// They're kept in the `enum` metadata as comma separated `value=name` pairs, so names can't contain `,` or `=`.
+    pub fn enum_values(&self) -> Option<BTreeMap<i64, String>> {
+        self.names("enum")
+    }
+
+    /// Replaces the component's value names, removing them if `values` is empty
+    ///
+    /// Fails without changing anything if a name contains `,` or `=`, which would be read back as other pairs.
+    pub fn set_enum_values(
+        &mut self,
+        values: &BTreeMap<i64, String>,
+    ) -> Result<(), InvalidMetadataName> {
+        self.set_names("enum", values)
+    }
+
+    /// Names for the bits of a flags component, like a status register, keyed by bit index from the least significant
+    /// bit, see [`impeller2::bits::Bits`]
+    ///
+    /// They're kept in the `bits` metadata as comma separated `bit=name` pairs, so names can't contain `,` or `=`.
+    pub fn bit_names(&self) -> Option<BTreeMap<u8, String>> {
+        self.names("bits")
+    }
+
+    /// Replaces the component's bit names, removing them if `names` is empty
+    ///
+    /// Fails without changing anything if a name contains `,` or `=`.
+    pub fn set_bit_names(
+        &mut self,
+        names: &BTreeMap<u8, String>,
+    ) -> Result<(), InvalidMetadataName> {
+        self.set_names("bits", names)
+    }
+
+    /// Reads the comma separated `value=name` pairs kept under `key`, skipping any that don't parse
+    fn names<K: FromStr + Ord>(&self, key: &str) -> Option<BTreeMap<K, String>> {
+        let names = self
+            .metadata
+            .get(key)?
+            .split(',')
+            .filter_map(|pair| {
+                let (value, name) = pair.split_once('=')?;
+                Some((value.trim().parse().ok()?, name.trim().to_string()))
+            })
+            .collect::<BTreeMap<_, _>>();
+        (!names.is_empty()).then_some(names)
+    }
+
+    /// Writes `names` under `key` as comma separated `value=name` pairs, removing `key` if `names` is empty
+    fn set_names<K: Display>(
+        &mut self,
+        key: &str,
+        names: &BTreeMap<K, String>,
+    ) -> Result<(), InvalidMetadataName> {
+        if let Some(name) = names.values().find(|name| name.contains([',', '='])) {
+            return Err(InvalidMetadataName { name: name.clone() });
+        }
+        if names.is_empty() {
+            self.metadata.remove(key);
+            return Ok(());
+        }
+        let names = names
+            .iter()
+            .map(|(value, name)| format!("{value}={name}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        self.metadata.insert(key.to_string(), names);
+        Ok(())
+    }
+}
+
+/// A name containing `,` or `=`, which can't be kept in a comma separated list of pairs, see
+/// [`ComponentMetadata::set_enum_values`] and [`ComponentMetadata::set_bit_names`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidMetadataName {
+    pub name: String,
 }
 
+impl std::fmt::Display for InvalidMetadataName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "name {:?} can't contain ',' or '='", self.name)
+    }
+}
+
+impl std::error::Error for InvalidMetadataName {}
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Schema)]
 #[cfg_attr(feature = "bevy", derive(bevy::prelude::Component))]
 pub struct EntityMetadata {
@@ -68,3 +184,52 @@ impl MetadataExt for EntityMetadata {
         &self.metadata
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> ComponentMetadata {
+        ComponentMetadata {
+            component_id: ComponentId::new("mode"),
+            name: "mode".to_string(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_enum_values_round_trip() {
+        let mut metadata = metadata();
+        let values = BTreeMap::from([(0, "IDLE".to_string()), (-1, "FAULT".to_string())]);
+        metadata.set_enum_values(&values).unwrap();
+        assert_eq!(metadata.enum_values(), Some(values.clone()));
+
+        for name in ["IDLE,ARMED", "ARMED=1"] {
+            let bad = BTreeMap::from([(1, name.to_string())]);
+            assert_eq!(
+                metadata.set_enum_values(&bad),
+                Err(InvalidMetadataName {
+                    name: name.to_string()
+                })
+            );
+            assert_eq!(metadata.enum_values(), Some(values.clone()));
+        }
+
+        metadata.set_enum_values(&BTreeMap::new()).unwrap();
+        assert_eq!(metadata.enum_values(), None);
+    }
+
+    #[test]
+    fn test_bit_names_round_trip() {
+        let mut metadata = metadata();
+        let names = BTreeMap::from([(0, "ARMED".to_string()), (7, "GPS LOCK".to_string())]);
+        metadata.set_bit_names(&names).unwrap();
+        assert_eq!(metadata.bit_names(), Some(names.clone()));
+
+        let bad = BTreeMap::from([(1, "LOW,BATT".to_string())]);
+        assert!(metadata.set_bit_names(&bad).is_err());
+        assert_eq!(metadata.bit_names(), Some(names));
+        // the two lists are kept apart
+        assert_eq!(metadata.enum_values(), None);
+    }
+}
@@ -10,7 +10,10 @@ use impeller2::{
 use postcard_schema::schema::owned::OwnedNamedType;
 use serde::{Deserialize, Serialize};
 use std::{borrow::Cow, path::PathBuf, time::Duration};
-use std::{collections::HashMap, ops::Range};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+};
 
 use crate::{LastUpdated, metadata::ComponentMetadata};
 
@@ -20,6 +23,12 @@ pub struct VTableMsg {
     pub vtable: VTable<Vec<Op>, Vec<u8>, Vec<Field>>,
 }
 
+const _ASSERT_VTABLE_MSG_ID: () = const {
+    assert!(
+        u16::from_le_bytes(VTableMsg::ID) == u16::from_le_bytes(impeller2::encoder::VTABLE_MSG_ID)
+    );
+};
+
 #[derive(Serialize, Deserialize, Debug, Clone, postcard_schema::Schema)]
 pub struct Stream {
     #[serde(default)]
@@ -37,6 +46,16 @@ impl Request for VTableStream {
     type Reply<B: IoBuf + Clone> = StreamReply<B>;
 }
 
+/// Fetches a vtable the db has registered, for decoding a table whose [`VTableMsg`] was missed
+#[derive(Serialize, Deserialize, Debug, Clone, postcard_schema::Schema)]
+pub struct GetVTable {
+    pub id: PacketId,
+}
+
+impl Request for GetVTable {
+    type Reply<B: IoBuf + Clone> = VTableMsg;
+}
+
 #[derive(Clone)]
 pub enum StreamReply<B: IoBuf> {
     Table(OwnedTable<B>),
@@ -62,6 +81,18 @@ impl Request for Stream {
     type Reply<B: IoBuf + Clone> = StreamReply<B>;
 }
 
+/// A [`Stream`] that only carries the listed components, so one subscription can follow several components
+/// without receiving everything else in the db
+#[derive(Serialize, Deserialize, Debug, Clone, postcard_schema::Schema)]
+pub struct FilteredStream {
+    pub stream: Stream,
+    pub component_ids: Vec<ComponentId>,
+}
+
+impl Request for FilteredStream {
+    type Reply<B: IoBuf + Clone> = StreamReply<B>;
+}
+
 #[derive(Serialize, Deserialize, Default, Debug, Clone, postcard_schema::Schema)]
 pub struct FixedRateOp {
     pub stream_id: StreamId,
@@ -72,6 +103,8 @@ pub struct FixedRateOp {
 pub struct FixedRateBehavior {
     pub initial_timestamp: InitialTimestamp,
     /// The time interval between each tick in nanoseconds
+    ///
+    /// A timestep of 0 lets the DB choose one based on the components' nominal sample rates
     pub timestep: u64,
     /// The number of ticks per second
     pub frequency: u64,
@@ -100,6 +133,28 @@ pub enum StreamBehavior {
     #[default]
     RealTime,
     FixedRate(FixedRateBehavior),
+    /// Like [`StreamBehavior::RealTime`], but each component is sent at most `max_rate` times per second
+    ///
+    /// The samples recorded between two sends are combined with `method`, so a slow client can follow a fast
+    /// component without receiving every sample.
+    Decimated {
+        max_rate: u64,
+        method: DecimationMethod,
+    },
+}
+
+/// How a [`StreamBehavior::Decimated`] stream combines the samples recorded since its last send
+///
+/// Each element is combined separately. Bytes and string components always send the latest sample.
+#[derive(
+    Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq, postcard_schema::Schema,
+)]
+pub enum DecimationMethod {
+    #[default]
+    Latest,
+    Min,
+    Max,
+    Mean,
 }
 
 pub type StreamId = u64;
@@ -129,6 +184,22 @@ impl Msg for SetStreamState {
     const ID: PacketId = [224, 2];
 }
 
+/// Sets the state of several streams at once
+///
+/// The server applies every state in the batch before resuming emission on any of them, so streams that are
+/// scrubbed or retimed together step together. If any stream id is unknown, none of the states are applied.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetStreamStateBatch(pub Vec<SetStreamState>);
+
+impl Msg for SetStreamStateBatch {
+    const ID: PacketId = [224, 46];
+}
+
+/// Requests the samples of a component in `range`
+///
+/// If the range holds more than `limit` samples, the db downsamples it to at most `limit` samples from its rollup
+/// tiers, each the mean of a second or a minute of samples reported at the start of that period, so plotting a long
+/// range doesn't read every sample. Use [`GetTimeSeriesPage`] to page through the raw samples instead.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetTimeSeries {
     pub id: PacketId,
@@ -145,6 +216,81 @@ impl Request for GetTimeSeries {
     type Reply<B: IoBuf + Clone> = OwnedTimeSeries<B>;
 }
 
+/// Requests the first `limit` raw samples of a component in `range`, which are never downsampled
+///
+/// To page through a long range, request the next page from just after the last timestamp of a full one.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetTimeSeriesPage {
+    pub id: PacketId,
+    pub range: Range<Timestamp>,
+    pub component_id: ComponentId,
+    pub limit: usize,
+}
+
+impl Msg for GetTimeSeriesPage {
+    const ID: PacketId = [224, 63];
+}
+
+impl Request for GetTimeSeriesPage {
+    type Reply<B: IoBuf + Clone> = OwnedTimeSeries<B>;
+}
+
+/// Requests the most recent sample of a component
+///
+/// Replies with a time series containing at most one sample; it's empty if nothing has been written yet.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetLatest {
+    pub id: PacketId,
+    pub component_id: ComponentId,
+}
+
+impl Msg for GetLatest {
+    const ID: PacketId = [224, 40];
+}
+
+impl Request for GetLatest {
+    type Reply<B: IoBuf + Clone> = OwnedTimeSeries<B>;
+}
+
+/// Requests the time series of a component for every entity that has it
+///
+/// Components are stored per entity as `entity.component` pairs, so this matches every component whose name ends in
+/// `.{component}`. `limit` applies to each entity's series individually, so a reply can contain up to
+/// `limit * entity_count` samples, all buffered into a single msg. Prefer a narrow `range` or a `limit` on large
+/// recordings.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetTimeSeriesAll {
+    pub component: String,
+    pub range: Range<Timestamp>,
+    pub limit: Option<usize>,
+}
+
+impl Msg for GetTimeSeriesAll {
+    const ID: PacketId = [224, 38];
+}
+
+impl Request for GetTimeSeriesAll {
+    type Reply<B: IoBuf + Clone> = TimeSeriesAllResp;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EntityTimeSeries {
+    pub entity: String,
+    pub component_id: ComponentId,
+    pub schema: Schema<Vec<u64>>,
+    pub timestamps: Vec<Timestamp>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimeSeriesAllResp {
+    pub series: Vec<EntityTimeSeries>,
+}
+
+impl Msg for TimeSeriesAllResp {
+    const ID: PacketId = [224, 39];
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct SchemaMsg(pub Schema<Vec<u64>>);
 impl Msg for SchemaMsg {
@@ -167,6 +313,9 @@ impl Request for GetSchema {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct GetComponentMetadata {
     pub component_id: ComponentId,
+    /// Fetches the metadata that was in effect at this time, if the DB has metadata versioning enabled
+    #[serde(default)]
+    pub at: Option<Timestamp>,
 }
 
 impl Msg for GetComponentMetadata {
@@ -196,6 +345,11 @@ impl SetComponentMetadata {
         self.0.metadata = metadata;
         self
     }
+
+    pub fn rate(mut self, hz: f64) -> Self {
+        self.0.set_nominal_rate_hz(hz);
+        self
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -211,7 +365,9 @@ impl Request for DumpMetadata {
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct DumpMetadataResp {
+    /// Sorted by name, then by component id
     pub component_metadata: Vec<ComponentMetadata>,
+    /// Sorted by name
     pub msg_metadata: Vec<MsgMetadata>,
     pub db_config: DbConfig,
 }
@@ -220,6 +376,111 @@ impl Msg for DumpMetadataResp {
     const ID: PacketId = [224, 15];
 }
 
+/// Finds the metadata of the components and msgs matching a query, so clients don't have to dump all of it like with
+/// [`DumpMetadata`]
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct QueryMetadata {
+    /// A glob the name has to match, see [`glob_match`], or `None` to match every name
+    #[serde(default)]
+    pub name_glob: Option<String>,
+    /// Metadata entries that have to be present, all of which must match
+    #[serde(default)]
+    pub metadata_filters: Vec<MetadataFilter>,
+    /// Only returns this kind of metadata, or both kinds if `None`
+    #[serde(default)]
+    pub kind: Option<MetadataKind>,
+}
+
+/// A metadata entry a [`QueryMetadata`] requires
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct MetadataFilter {
+    pub key: String,
+    /// A glob the entry's value has to match, or `None` to only require the key
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataKind {
+    Component,
+    Msg,
+}
+
+impl QueryMetadata {
+    /// Whether something of `kind`, named `name` and with `metadata`, matches the query
+    pub fn matches(
+        &self,
+        kind: MetadataKind,
+        name: &str,
+        metadata: &HashMap<String, String>,
+    ) -> bool {
+        self.kind.is_none_or(|k| k == kind)
+            && self
+                .name_glob
+                .as_deref()
+                .is_none_or(|glob| glob_match(glob, name))
+            && self.metadata_filters.iter().all(|filter| {
+                metadata.get(&filter.key).is_some_and(|value| {
+                    filter
+                        .value
+                        .as_deref()
+                        .is_none_or(|glob| glob_match(glob, value))
+                })
+            })
+    }
+}
+
+impl Msg for QueryMetadata {
+    const ID: PacketId = [224, 58];
+}
+
+impl Request for QueryMetadata {
+    type Reply<B: IoBuf + Clone> = QueryMetadataResp;
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct QueryMetadataResp {
+    /// Sorted by name, then by component id
+    pub component_metadata: Vec<ComponentMetadata>,
+    /// Sorted by name
+    pub msg_metadata: Vec<MsgMetadata>,
+}
+
+impl Msg for QueryMetadataResp {
+    const ID: PacketId = [224, 59];
+}
+
+/// Matches `name` against a glob where `*` matches any run of characters and `?` matches any one character
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // the pattern index after the last `*` seen, and the name index it's currently matched up to
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, n));
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    star = Some((star_p, n));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SubscribeLastUpdated;
 
@@ -269,6 +530,30 @@ impl DbConfig {
     pub fn schematic_content(&self) -> Option<&str> {
         self.metadata.get("schematic.content").map(String::as_str)
     }
+
+    /// Opts into validating each vtable as it is received, rejecting malformed ones immediately
+    pub fn set_validate_vtables(&mut self, validate: bool) {
+        self.metadata
+            .insert("vtable.validate".to_string(), validate.to_string());
+    }
+
+    pub fn validate_vtables(&self) -> bool {
+        self.metadata
+            .get("vtable.validate")
+            .is_some_and(|v| v == "true")
+    }
+
+    /// Opts into keeping a timestamped history of component metadata, rather than overwriting it on every change
+    pub fn set_version_metadata(&mut self, versioned: bool) {
+        self.metadata
+            .insert("metadata.versioned".to_string(), versioned.to_string());
+    }
+
+    pub fn version_metadata(&self) -> bool {
+        self.metadata
+            .get("metadata.versioned")
+            .is_some_and(|v| v == "true")
+    }
 }
 
 impl Default for DbConfig {
@@ -321,21 +606,33 @@ macro_rules! impl_user_data_msg {
 }
 
 impl_user_data_msg!(VTableStream);
+impl_user_data_msg!(GetVTable);
 impl_user_data_msg!(VTableMsg);
 impl_user_data_msg!(Stream);
+impl_user_data_msg!(FilteredStream);
 impl_user_data_msg!(MsgStream);
 impl_user_data_msg!(SetStreamState);
+impl_user_data_msg!(SetStreamStateBatch);
+impl_user_data_msg!(SetComponentRecording);
+impl_user_data_msg!(NegotiateCompression);
+impl_user_data_msg!(MigrateComponent);
+impl_user_data_msg!(SetComponentEnum);
+impl_user_data_msg!(SetComponentBits);
 impl_user_data_msg!(SetComponentMetadata);
 impl_user_data_msg!(UdpUnicast);
 impl_user_data_msg!(UdpVTableStream);
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GetEarliestTimestamp;
 
 impl Msg for GetEarliestTimestamp {
     const ID: PacketId = [224, 22];
 }
 
+impl Request for GetEarliestTimestamp {
+    type Reply<B: IoBuf + Clone> = EarliestTimestamp;
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy)]
 #[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
 pub struct EarliestTimestamp(pub Timestamp);
@@ -414,6 +711,42 @@ impl Request for SQLQuery {
     type Reply<B: IoBuf + Clone> = ArrowIPC<'static>;
 }
 
+pub type QueryId = u64;
+
+/// A cancellable variant of [`SQLQuery`].
+///
+/// The query runs in the background on the server, so a [`CancelQuery`] with the same `id` can be sent over the same
+/// connection while results are still streaming back. Replies are the same as [`SQLQuery`]: a series of [`ArrowIPC`]
+/// batches terminated by an empty one.
+///
+/// The `id` is free again once the query finishes; reusing one that's still running on the connection is an error.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SQLQueryStream {
+    pub id: QueryId,
+    pub query: String,
+}
+
+impl Msg for SQLQueryStream {
+    const ID: PacketId = [224, 36];
+}
+
+impl Request for SQLQueryStream {
+    type Reply<B: IoBuf + Clone> = ArrowIPC<'static>;
+}
+
+/// Cancels a running [`SQLQueryStream`].
+///
+/// The server checks for cancellation between record batches, so the batch currently being produced is still sent,
+/// followed by the terminating empty [`ArrowIPC`]. Cancelling a query that has already finished is a no-op.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CancelQuery {
+    pub query_id: QueryId,
+}
+
+impl Msg for CancelQuery {
+    const ID: PacketId = [224, 37];
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct MsgMetadata {
     pub name: String,
@@ -425,6 +758,17 @@ impl Msg for MsgMetadata {
     const ID: PacketId = [224, 30];
 }
 
+impl MsgMetadata {
+    /// The unit of the field at `path`, from the `unit.<path>` metadata entry
+    ///
+    /// Nested fields are addressed with dots, like `unit.pos.x`.
+    pub fn field_unit(&self, path: &str) -> Option<&str> {
+        self.metadata
+            .get(&format!("unit.{path}"))
+            .map(String::as_str)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct SetMsgMetadata {
     pub id: PacketId,
@@ -491,6 +835,322 @@ impl Msg for MsgBatch {
     const ID: PacketId = [224, 35];
 }
 
+/// A streamed variant of [`GetMsgs`].
+///
+/// Rather than buffering the whole range into a [`MsgBatch`], the server replies with one [`MsgEntry`] per msg as it
+/// is read from the log, terminated by an empty one.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetMsgsStream {
+    pub msg_id: PacketId,
+    pub range: Range<Timestamp>,
+    pub limit: Option<usize>,
+}
+
+impl Msg for GetMsgsStream {
+    const ID: PacketId = [224, 44];
+}
+
+impl Request for GetMsgsStream {
+    type Reply<B: IoBuf + Clone> = MsgEntry<'static>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MsgEntry<'a> {
+    pub msg: Option<(Timestamp, Cow<'a, [u8]>)>,
+}
+
+impl Msg for MsgEntry<'_> {
+    const ID: PacketId = [224, 45];
+}
+
+/// Asks the server to sync every write it has received to disk
+///
+/// Packets on a connection are handled in order, so once the [`FlushAck`] arrives everything sent before the
+/// `Flush` on the same connection is durable.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Flush;
+
+impl Msg for Flush {
+    const ID: PacketId = [224, 47];
+}
+
+impl Request for Flush {
+    type Reply<B: IoBuf + Clone> = FlushAck;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FlushAck;
+
+impl Msg for FlushAck {
+    const ID: PacketId = [224, 48];
+}
+
+/// Asks for the server's current time, so clients can build ranges relative to the server's clock and measure skew
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetServerTime;
+
+impl Msg for GetServerTime {
+    const ID: PacketId = [224, 49];
+}
+
+impl Request for GetServerTime {
+    type Reply<B: IoBuf + Clone> = ServerTime;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ServerTime(pub Timestamp);
+
+impl Msg for ServerTime {
+    const ID: PacketId = [224, 50];
+}
+
+/// Includes or excludes a single component from recording
+///
+/// Values for an excluded component are still forwarded to real-time subscribers, but aren't written to its time
+/// series. This only narrows what [`DbConfig::recording`] allows: when global recording is off, setting `recording`
+/// to `true` here doesn't turn it back on for this component. The setting isn't persisted across restarts.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetComponentRecording {
+    pub component_id: ComponentId,
+    pub recording: bool,
+}
+
+impl Msg for SetComponentRecording {
+    const ID: PacketId = [224, 51];
+}
+
+/// A codec for compressing packet bodies
+///
+/// A compressed packet has the codec in the top two bits of its packet type byte, and a body of the uncompressed
+/// length as a little endian `u32` followed by the compressed bytes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, postcard_schema::Schema)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum Compression {
+    Lz4 = 1,
+    Zstd = 2,
+}
+
+/// Asks the peer to compress the packets it sends on this connection with the first of `codecs` it supports
+///
+/// Once the peer replies with [`CompressionAccepted`], the requester compresses what it sends with the same codec.
+/// An empty list turns compression off. Receivers decompress regardless of what was negotiated, so packets already
+/// in flight are read either way.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NegotiateCompression {
+    pub codecs: Vec<Compression>,
+}
+
+impl Msg for NegotiateCompression {
+    const ID: PacketId = [224, 52];
+}
+
+impl Request for NegotiateCompression {
+    type Reply<B: IoBuf + Clone> = CompressionAccepted;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompressionAccepted {
+    pub codec: Option<Compression>,
+}
+
+impl Msg for CompressionAccepted {
+    const ID: PacketId = [224, 53];
+}
+
+/// Changes the shape or prim type of a component, starting a new version of its schema
+///
+/// Data recorded under earlier versions stays readable, and the DB replies with the schema including its new version.
+/// Migrating to the schema the component already has is a no-op.
+///
+/// Schemas gained their version along with this msg. Postcard isn't self-describing, so every msg carrying a [`Schema`],
+/// like [`SchemaMsg`] and [`DumpSchemaResp`], can't be exchanged with peers built before then.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MigrateComponent {
+    pub component_id: ComponentId,
+    pub schema: Schema<Vec<u64>>,
+}
+
+impl Msg for MigrateComponent {
+    const ID: PacketId = [224, 54];
+}
+
+impl Request for MigrateComponent {
+    type Reply<B: IoBuf + Clone> = SchemaMsg;
+}
+
+/// Names a component's integer values, like the modes of a flight mode component, see
+/// [`ComponentMetadata::enum_values`]
+///
+/// The names replace any the component already had, and an empty map removes them. Names can't contain `,` or `=`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetComponentEnum {
+    pub component_id: ComponentId,
+    pub values: BTreeMap<i64, String>,
+}
+
+impl Msg for SetComponentEnum {
+    const ID: PacketId = [224, 55];
+}
+
+/// Names the bits of a flags component, like a status register, see [`ComponentMetadata::bit_names`]
+///
+/// Bits are indexed from the least significant one. The names replace any the component already had, and an empty
+/// map removes them. Names can't contain `,` or `=`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetComponentBits {
+    pub component_id: ComponentId,
+    pub bits: BTreeMap<u8, String>,
+}
+
+impl Msg for SetComponentBits {
+    const ID: PacketId = [224, 57];
+}
+
+/// Acknowledges a msg sent as a [`impeller2::types::PacketTy::MsgWithAck`], echoing its sequence number
+///
+/// Receivers ack every copy of a msg they get, including retransmits of one they already handled, since the first ack
+/// may have been the packet that was lost.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ack {
+    pub seq: u32,
+}
+
+impl Msg for Ack {
+    const ID: PacketId = [224, 56];
+}
+
+/// How much of a component's time series the db keeps, see [`SetRetention`]
+///
+/// Every limit is optional, and the oldest samples are dropped until all of them hold. The latest sample is always
+/// kept, so a component never loses its current value.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Drops samples older than this, measured back from the component's latest sample rather than the wall clock
+    pub max_age: Option<Duration>,
+    /// Drops samples once their timestamps and values take up more than this many bytes
+    pub max_bytes: Option<u64>,
+    pub max_samples: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Whether the policy keeps everything, which is the default
+    pub fn is_unbounded(&self) -> bool {
+        self.max_age.is_none() && self.max_bytes.is_none() && self.max_samples.is_none()
+    }
+}
+
+/// Sets a component's [`RetentionPolicy`], which the db's compaction task enforces in the background
+///
+/// The policy applies to every schema version of the component and is saved with it, an unbounded policy removes it.
+/// Samples aren't dropped until the next compaction, send a [`Compact`] to run one right away.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetRetention {
+    pub component_id: ComponentId,
+    pub policy: RetentionPolicy,
+}
+
+impl Msg for SetRetention {
+    const ID: PacketId = [224, 60];
+}
+
+/// Runs a compaction now, rather than waiting for the db's compaction task
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Compact;
+
+impl Msg for Compact {
+    const ID: PacketId = [224, 61];
+}
+
+impl Request for Compact {
+    type Reply<B: IoBuf + Clone> = CompactionReport;
+}
+
+/// What a compaction dropped
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    pub samples_dropped: u64,
+    /// The size of the dropped timestamps and values
+    ///
+    /// On Linux the space is handed back to the filesystem a page at a time, so a little of it may only be reclaimed by
+    /// a later compaction.
+    pub bytes_reclaimed: u64,
+}
+
+impl Msg for CompactionReport {
+    const ID: PacketId = [224, 62];
+}
+
+impl std::ops::AddAssign for CompactionReport {
+    fn add_assign(&mut self, rhs: Self) {
+        self.samples_dropped += rhs.samples_dropped;
+        self.bytes_reclaimed += rhs.bytes_reclaimed;
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Default,
+    postcard_schema::Schema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EventSeverity {
+    Debug,
+    #[default]
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+/// A discrete event, like a mode change or fault, as opposed to a continuous component
+///
+/// The db appends events to a log keyed by their own `timestamp`, so they should be sent in time order.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Event {
+    pub timestamp: Timestamp,
+    pub severity: EventSeverity,
+    pub code: u32,
+    pub message: String,
+}
+
+impl Msg for Event {
+    const ID: PacketId = [224, 41];
+}
+
+/// Requests every logged [`Event`] in `range` at or above `min_severity`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetEvents {
+    pub range: Range<Timestamp>,
+    pub min_severity: EventSeverity,
+    pub limit: Option<usize>,
+}
+
+impl Msg for GetEvents {
+    const ID: PacketId = [224, 42];
+}
+
+impl Request for GetEvents {
+    type Reply<B: IoBuf + Clone> = EventBatch;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventBatch {
+    pub events: Vec<Event>,
+}
+
+impl Msg for EventBatch {
+    const ID: PacketId = [224, 43];
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, postcard_schema::Schema)]
 pub struct UdpUnicast {
     pub stream: Stream,
@@ -526,6 +1186,86 @@ pub enum ArchiveFormat {
     Csv,
 }
 
+/// Writes a consistent copy of the db to `path` on the db's host, which must not exist yet
+///
+/// Every log is copied as it was when the snapshot started, while writes carry on. Files that never change once
+/// written, like schemas, are hard linked where the filesystem allows it. The snapshot is itself a db directory, so
+/// it can be served directly or loaded back with [`RestoreSnapshot`].
+#[derive(Serialize, Deserialize, Debug, Clone, postcard_schema::Schema)]
+pub struct CreateSnapshot {
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, postcard_schema::Schema)]
+pub struct SnapshotCreated {
+    pub path: PathBuf,
+}
+
+impl Request for CreateSnapshot {
+    type Reply<B: IoBuf + Clone> = SnapshotCreated;
+}
+
+/// Replaces the db's contents with a copy of the snapshot at `path`, which is left untouched
+///
+/// Streams opened before the restore keep reading the old data, so clients should resubscribe. Vtables are kept, but
+/// producers should resend them so components missing from the snapshot are created again.
+#[derive(Serialize, Deserialize, Debug, Clone, postcard_schema::Schema)]
+pub struct RestoreSnapshot {
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, postcard_schema::Schema)]
+pub struct SnapshotRestored {
+    pub path: PathBuf,
+}
+
+impl Request for RestoreSnapshot {
+    type Reply<B: IoBuf + Clone> = SnapshotRestored;
+}
+
+/// Requests what a follower needs to mirror the db: every component and msg log, with its metadata and the timestamp
+/// of its latest sample
+///
+/// A follower compares the reply against its own copy and pages through whatever it's missing with
+/// [`GetTimeSeriesPage`] and [`GetMsgs`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetReplicationState;
+
+impl Msg for GetReplicationState {
+    const ID: PacketId = [224, 64];
+}
+
+impl Request for GetReplicationState {
+    type Reply<B: IoBuf + Clone> = ReplicationState;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplicationState {
+    pub components: Vec<ReplicatedComponent>,
+    pub msgs: Vec<ReplicatedMsgLog>,
+}
+
+impl Msg for ReplicationState {
+    const ID: PacketId = [224, 65];
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplicatedComponent {
+    pub component_id: ComponentId,
+    /// The schema of the component's current version
+    pub schema: Schema<Vec<u64>>,
+    pub metadata: Option<ComponentMetadata>,
+    /// The timestamp of the latest sample of the current version, or `None` if it's empty
+    pub last: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplicatedMsgLog {
+    pub msg_id: PacketId,
+    pub metadata: Option<MsgMetadata>,
+    pub last: Option<Timestamp>,
+}
+
 #[derive(Serialize, Deserialize, Default, Debug, Clone, postcard_schema::Schema)]
 pub struct MeanOp {
     pub window: u16,
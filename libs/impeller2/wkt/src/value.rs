@@ -1,4 +1,4 @@
-use impeller2::types::{ComponentView, ElementValue, PrimType};
+use impeller2::types::{ComponentView, ElementValue, PrimType, Q15_16, bf16, f16, var_len_payload};
 use nox::{Array, ArrayBuf, Dyn, array::ArrayViewExt};
 use serde::{Deserialize, Serialize};
 use zerocopy::IntoBytes;
@@ -18,6 +18,13 @@ pub enum ComponentValue {
     Bool(Array<bool, Dyn>),
     F32(Array<f32, Dyn>),
     F64(Array<f64, Dyn>),
+    F16(Array<f16, Dyn>),
+    BF16(Array<bf16, Dyn>),
+    /// A whole [`PrimType::Bytes`] slot, length prefix included
+    Bytes(Array<u8, Dyn>),
+    /// A whole [`PrimType::String`] slot, length prefix included
+    String(Array<u8, Dyn>),
+    Q15_16(Array<Q15_16, Dyn>),
 }
 
 impl std::fmt::Display for ComponentValue {
@@ -34,6 +41,19 @@ impl std::fmt::Display for ComponentValue {
             Self::Bool(arr) => write!(f, "{}", arr.view()),
             Self::F32(arr) => write!(f, "{}", arr.view()),
             Self::F64(arr) => write!(f, "{}", arr.view()),
+            Self::F16(arr) => write!(f, "{}", arr.view()),
+            Self::BF16(arr) => write!(f, "{}", arr.view()),
+            Self::Bytes(arr) => {
+                for byte in var_len_payload(arr.buf.as_buf()).unwrap_or_default() {
+                    write!(f, "{byte:02x}")?;
+                }
+                Ok(())
+            }
+            Self::String(arr) => {
+                let payload = var_len_payload(arr.buf.as_buf()).unwrap_or_default();
+                write!(f, "{:?}", String::from_utf8_lossy(payload))
+            }
+            Self::Q15_16(arr) => write!(f, "{}", arr.view()),
         }
     }
 }
@@ -52,12 +72,17 @@ impl ComponentValue {
             PrimType::Bool => Self::Bool(Array::zeroed(shape)),
             PrimType::F32 => Self::F32(Array::zeroed(shape)),
             PrimType::F64 => Self::F64(Array::zeroed(shape)),
+            PrimType::F16 => Self::F16(Array::zeroed(shape)),
+            PrimType::BF16 => Self::BF16(Array::zeroed(shape)),
+            PrimType::Bytes => Self::Bytes(Array::zeroed(shape)),
+            PrimType::String => Self::String(Array::zeroed(shape)),
+            PrimType::Q15_16 => Self::Q15_16(Array::zeroed(shape)),
         }
     }
 
     pub fn fill_zeros(&mut self) {
         match self {
-            Self::U8(a) => {
+            Self::U8(a) | Self::Bytes(a) | Self::String(a) => {
                 a.buf.as_mut_buf().fill(0);
             }
             Self::U16(a) => {
@@ -90,6 +115,15 @@ impl ComponentValue {
             Self::F64(a) => {
                 a.buf.as_mut_buf().fill(0.0);
             }
+            Self::F16(a) => {
+                a.buf.as_mut_buf().fill(f16::ZERO);
+            }
+            Self::BF16(a) => {
+                a.buf.as_mut_buf().fill(bf16::ZERO);
+            }
+            Self::Q15_16(a) => {
+                a.buf.as_mut_buf().fill(Q15_16::ZERO);
+            }
         }
     }
 
@@ -106,6 +140,10 @@ impl ComponentValue {
             Self::Bool(arr) => arr.shape(),
             Self::F32(arr) => arr.shape(),
             Self::F64(arr) => arr.shape(),
+            Self::F16(arr) => arr.shape(),
+            Self::BF16(arr) => arr.shape(),
+            Self::Bytes(arr) | Self::String(arr) => arr.shape(),
+            Self::Q15_16(arr) => arr.shape(),
         }
     }
 
@@ -188,6 +226,32 @@ impl ComponentValue {
                     }
                 }
             }
+            (Self::F16(arr), ComponentView::F16(view)) => {
+                for (i, &val) in view.buf().iter().enumerate() {
+                    if let Some(r) = arr.buf.as_mut_buf().get_mut(i) {
+                        *r += val;
+                    }
+                }
+            }
+            (Self::BF16(arr), ComponentView::BF16(view)) => {
+                for (i, &val) in view.buf().iter().enumerate() {
+                    if let Some(r) = arr.buf.as_mut_buf().get_mut(i) {
+                        *r += val;
+                    }
+                }
+            }
+            (Self::Q15_16(arr), ComponentView::Q15_16(view)) => {
+                for (i, &val) in view.buf().iter().enumerate() {
+                    if let Some(r) = arr.buf.as_mut_buf().get_mut(i) {
+                        *r += val;
+                    }
+                }
+            }
+            // slots can't be summed, so the latest value wins
+            (Self::Bytes(arr), ComponentView::Bytes(view))
+            | (Self::String(arr), ComponentView::String(view)) => {
+                arr.buf.as_mut_buf().copy_from_slice(view.buf());
+            }
             _ => panic!("Cannot add values of different types"),
         }
     }
@@ -245,6 +309,22 @@ impl ComponentValue {
                     *r /= count;
                 }
             }
+            Self::F16(a) => {
+                for r in a.buf.as_mut_buf().iter_mut() {
+                    *r = f16::from_f64(r.to_f64() / count);
+                }
+            }
+            Self::BF16(a) => {
+                for r in a.buf.as_mut_buf().iter_mut() {
+                    *r = bf16::from_f64(r.to_f64() / count);
+                }
+            }
+            Self::Q15_16(a) => {
+                for r in a.buf.as_mut_buf().iter_mut() {
+                    *r = Q15_16::from_f64(r.to_f64() / count);
+                }
+            }
+            Self::Bytes(_) | Self::String(_) => {}
         }
     }
     pub fn copy_from_view(&mut self, view: ComponentView<'_>) {
@@ -282,6 +362,19 @@ impl ComponentValue {
             (Self::F64(arr), ComponentView::F64(view)) => {
                 arr.buf.as_mut_buf().copy_from_slice(view.buf());
             }
+            (Self::F16(arr), ComponentView::F16(view)) => {
+                arr.buf.as_mut_buf().copy_from_slice(view.buf());
+            }
+            (Self::BF16(arr), ComponentView::BF16(view)) => {
+                arr.buf.as_mut_buf().copy_from_slice(view.buf());
+            }
+            (Self::Q15_16(arr), ComponentView::Q15_16(view)) => {
+                arr.buf.as_mut_buf().copy_from_slice(view.buf());
+            }
+            (Self::Bytes(arr), ComponentView::Bytes(view))
+            | (Self::String(arr), ComponentView::String(view)) => {
+                arr.buf.as_mut_buf().copy_from_slice(view.buf());
+            }
             _ => panic!("Incompatible component value and view types"),
         }
     }
@@ -299,6 +392,11 @@ impl ComponentValue {
             ComponentView::Bool(view) => Self::Bool(view.to_dyn_owned()),
             ComponentView::F32(view) => Self::F32(view.to_dyn_owned()),
             ComponentView::F64(view) => Self::F64(view.to_dyn_owned()),
+            ComponentView::F16(view) => Self::F16(view.to_dyn_owned()),
+            ComponentView::BF16(view) => Self::BF16(view.to_dyn_owned()),
+            ComponentView::Bytes(view) => Self::Bytes(view.to_dyn_owned()),
+            ComponentView::String(view) => Self::String(view.to_dyn_owned()),
+            ComponentView::Q15_16(view) => Self::Q15_16(view.to_dyn_owned()),
         }
     }
 
@@ -337,6 +435,18 @@ impl ComponentValue {
             ComponentValue::F64(f64) => {
                 Box::new(f64.buf.as_buf().iter().map(|&x| ElementValue::F64(x)))
             }
+            ComponentValue::F16(f16) => {
+                Box::new(f16.buf.as_buf().iter().map(|&x| ElementValue::F16(x)))
+            }
+            ComponentValue::BF16(bf16) => {
+                Box::new(bf16.buf.as_buf().iter().map(|&x| ElementValue::BF16(x)))
+            }
+            ComponentValue::Bytes(slot) | ComponentValue::String(slot) => {
+                Box::new(slot.buf.as_buf().iter().map(|&x| ElementValue::U8(x)))
+            }
+            ComponentValue::Q15_16(q) => {
+                Box::new(q.buf.as_buf().iter().map(|&x| ElementValue::Q15_16(x)))
+            }
         }
     }
 
@@ -353,6 +463,12 @@ impl ComponentValue {
             ComponentValue::Bool(x) => x.buf.as_buf().get(i).map(|&x| ElementValue::Bool(x)),
             ComponentValue::F32(x) => x.buf.as_buf().get(i).map(|&x| ElementValue::F32(x)),
             ComponentValue::F64(x) => x.buf.as_buf().get(i).map(|&x| ElementValue::F64(x)),
+            ComponentValue::F16(x) => x.buf.as_buf().get(i).map(|&x| ElementValue::F16(x)),
+            ComponentValue::BF16(x) => x.buf.as_buf().get(i).map(|&x| ElementValue::BF16(x)),
+            ComponentValue::Bytes(x) | ComponentValue::String(x) => {
+                x.buf.as_buf().get(i).map(|&x| ElementValue::U8(x))
+            }
+            ComponentValue::Q15_16(x) => x.buf.as_buf().get(i).map(|&x| ElementValue::Q15_16(x)),
         }
     }
 
@@ -369,6 +485,11 @@ impl ComponentValue {
             ComponentValue::Bool(_) => PrimType::Bool,
             ComponentValue::F32(_) => PrimType::F32,
             ComponentValue::F64(_) => PrimType::F64,
+            ComponentValue::F16(_) => PrimType::F16,
+            ComponentValue::BF16(_) => PrimType::BF16,
+            ComponentValue::Bytes(_) => PrimType::Bytes,
+            ComponentValue::String(_) => PrimType::String,
+            ComponentValue::Q15_16(_) => PrimType::Q15_16,
         }
     }
 
@@ -385,6 +506,10 @@ impl ComponentValue {
             ComponentValue::Bool(x) => x.buf.as_buf().as_bytes(),
             ComponentValue::F32(x) => x.buf.as_buf().as_bytes(),
             ComponentValue::F64(x) => x.buf.as_buf().as_bytes(),
+            ComponentValue::F16(x) => x.buf.as_buf().as_bytes(),
+            ComponentValue::BF16(x) => x.buf.as_buf().as_bytes(),
+            ComponentValue::Bytes(x) | ComponentValue::String(x) => x.buf.as_buf().as_bytes(),
+            ComponentValue::Q15_16(x) => x.buf.as_buf().as_bytes(),
         }
     }
 }
@@ -402,4 +527,7 @@ pub enum ElementValueMut<'a> {
     F64(&'a mut f64),
     F32(&'a mut f32),
     Bool(&'a mut bool),
+    F16(&'a mut f16),
+    BF16(&'a mut bf16),
+    Q15_16(&'a mut Q15_16),
 }
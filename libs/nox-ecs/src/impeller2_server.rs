@@ -54,6 +54,9 @@ impl Server {
     }
 }
 
+/// Seeds the db with the world's initial state, component and entity metadata, and schematic
+///
+/// This must run before any connections are accepted, so that clients never observe a db without metadata.
 pub fn init_db(
     db: &elodin_db::DB,
     world: &mut World,
@@ -94,14 +97,14 @@ pub fn init_db(
                 let buf = &column.buffer[offset..offset + size];
                 component.time_series.push_buf(start_timestamp, buf)?;
             }
-            if let Some(path) = &world.metadata.schematic_path {
-                state
-                    .db_config
-                    .set_schematic_path(path.to_string_lossy().to_string());
-            }
-            if let Some(content) = &world.metadata.schematic {
-                state.db_config.set_schematic_content(content.clone());
-            }
+        }
+        if let Some(path) = &world.metadata.schematic_path {
+            state
+                .db_config
+                .set_schematic_path(path.to_string_lossy().to_string());
+        }
+        if let Some(content) = &world.metadata.schematic {
+            state.db_config.set_schematic_content(content.clone());
         }
         for entity_metadata in world.entity_metadata().values() {
             state.set_component_metadata(
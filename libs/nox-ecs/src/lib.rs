@@ -664,6 +664,34 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_snapshot_round_trip() {
+        #[derive(Component, ReprMonad)]
+        struct A<R: OwnedRepr = Op>(Scalar<f64, R>);
+
+        fn tick(a: ComponentArray<A>) -> ComponentArray<A> {
+            a.map(|a: A| A(a.0 + 1.0)).unwrap()
+        }
+
+        let mut world = World::default();
+        world.spawn(A(1.0.into()));
+        world.spawn(A(5.0.into()));
+        let world = world.builder().tick_pipeline(tick).run();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshot");
+        world.snapshot(&path).unwrap();
+        let loaded = World::load_snapshot(&path).unwrap();
+
+        assert_eq!(loaded.host, world.host);
+        assert_eq!(loaded.metadata, world.metadata);
+        assert_eq!(
+            loaded.column::<A>().unwrap().typed_buf::<f64>().unwrap(),
+            &[2.0, 6.0]
+        );
+        assert!(loaded.dirty_components.contains(&A::COMPONENT_ID));
+    }
+
     #[test]
     fn test_startup() {
         #[derive(Component, ReprMonad)]
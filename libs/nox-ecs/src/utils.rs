@@ -13,17 +13,23 @@ pub trait PrimTypeExt {
 impl PrimTypeExt for PrimType {
     fn to_element_type(&self) -> ElementType {
         match self {
-            impeller2::types::PrimType::U8 => ElementType::U8,
+            impeller2::types::PrimType::U8
+            | impeller2::types::PrimType::Bytes
+            | impeller2::types::PrimType::String => ElementType::U8,
             impeller2::types::PrimType::U16 => ElementType::U16,
             impeller2::types::PrimType::U32 => ElementType::U32,
             impeller2::types::PrimType::U64 => ElementType::U64,
             impeller2::types::PrimType::I8 => ElementType::S8,
             impeller2::types::PrimType::I16 => ElementType::S16,
-            impeller2::types::PrimType::I32 => ElementType::S32,
+            impeller2::types::PrimType::I32 | impeller2::types::PrimType::Q15_16 => {
+                ElementType::S32
+            }
             impeller2::types::PrimType::I64 => ElementType::S64,
             impeller2::types::PrimType::Bool => ElementType::Pred,
             impeller2::types::PrimType::F32 => ElementType::F32,
             impeller2::types::PrimType::F64 => ElementType::F64,
+            impeller2::types::PrimType::F16 => ElementType::F16,
+            impeller2::types::PrimType::BF16 => ElementType::Bf16,
         }
     }
 }
@@ -44,17 +50,23 @@ impl SchemaExt for elodin_db::ComponentSchema {
 impl SchemaExt for Schema<Vec<u64>> {
     fn element_type(&self) -> ElementType {
         match self.prim_type() {
-            impeller2::types::PrimType::U8 => ElementType::U8,
+            impeller2::types::PrimType::U8
+            | impeller2::types::PrimType::Bytes
+            | impeller2::types::PrimType::String => ElementType::U8,
             impeller2::types::PrimType::U16 => ElementType::U16,
             impeller2::types::PrimType::U32 => ElementType::U32,
             impeller2::types::PrimType::U64 => ElementType::U64,
             impeller2::types::PrimType::I8 => ElementType::S8,
             impeller2::types::PrimType::I16 => ElementType::S16,
-            impeller2::types::PrimType::I32 => ElementType::S32,
+            impeller2::types::PrimType::I32 | impeller2::types::PrimType::Q15_16 => {
+                ElementType::S32
+            }
             impeller2::types::PrimType::I64 => ElementType::S64,
             impeller2::types::PrimType::Bool => ElementType::Pred,
             impeller2::types::PrimType::F32 => ElementType::F32,
             impeller2::types::PrimType::F64 => ElementType::F64,
+            impeller2::types::PrimType::F16 => ElementType::F16,
+            impeller2::types::PrimType::BF16 => ElementType::Bf16,
         }
     }
 
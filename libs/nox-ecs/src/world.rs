@@ -1,5 +1,5 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use crate::utils::SchemaExt;
@@ -255,6 +255,20 @@ impl World {
     pub fn advance_tick(&mut self) {
         self.metadata.tick += 1;
     }
+
+    /// Writes every component buffer along with the schemas, entity metadata, and tick to a single file
+    pub fn snapshot(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.write(path)?;
+        Ok(())
+    }
+
+    /// Loads a world written by [`World::snapshot`], to be used as the initial state of a new sim
+    pub fn load_snapshot(path: impl AsRef<Path>) -> Result<World, Error> {
+        let mut world = World::read(path)?;
+        // every column needs to be copied to the client on the first tick
+        world.dirty_components = world.host.keys().copied().collect();
+        Ok(world)
+    }
 }
 
 impl Clone for World {
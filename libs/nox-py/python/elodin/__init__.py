@@ -402,6 +402,7 @@ class World(WorldBuilder):
         default_playback_speed: float = 1.0,
         max_ticks: Optional[int] = None,
         optimize: bool = False,
+        s10_worker_threads: Optional[int] = None,
     ):
         current_frame = inspect.currentframe()
         if current_frame is None:
@@ -416,6 +417,7 @@ class World(WorldBuilder):
             default_playback_speed,
             max_ticks,
             optimize,
+            s10_worker_threads,
         )
         locals = frame.f_locals
         if addr is not None:
@@ -39,6 +39,8 @@ class SystemBuilder:
     def var_arrays(self) -> list[jax.typing.ArrayLike]: ...
 
 class WorldBuilder:
+    @staticmethod
+    def load_snapshot(path: str) -> WorldBuilder: ...
     def spawn(
         self,
         archetypes: Archetype | list[Archetype],
@@ -53,6 +55,7 @@ class WorldBuilder:
         default_playback_speed: float = 1.0,
         max_ticks: Optional[int] = None,
         optimize: bool = False,
+        s10_worker_threads: Optional[int] = None,
     ): ...
     def serve(
         self,
@@ -246,7 +249,9 @@ class Impeller:
 class Exec:
     def run(self, ticks: int = 1, show_progress: bool = True): ...
     def profile(self) -> dict[str, float]: ...
+    def components(self) -> list[Tuple[int, str, ComponentType, dict[str, str]]]: ...
     def save_archive(self, path: str, format: str): ...
+    def snapshot(self, path: str): ...
     def history(self, components: str | list[str]) -> pl.DataFrame: ...
 
 class GraphEntity:
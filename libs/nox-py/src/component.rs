@@ -249,6 +249,11 @@ pub enum PrimitiveType {
     I16,
     I8,
     Bool,
+    F16,
+    BF16,
+    Bytes,
+    String,
+    Q15_16,
 }
 
 impl From<impeller2::types::PrimType> for PrimitiveType {
@@ -265,6 +270,11 @@ impl From<impeller2::types::PrimType> for PrimitiveType {
             impeller2::types::PrimType::I16 => PrimitiveType::I16,
             impeller2::types::PrimType::I8 => PrimitiveType::I8,
             impeller2::types::PrimType::Bool => PrimitiveType::Bool,
+            impeller2::types::PrimType::F16 => PrimitiveType::F16,
+            impeller2::types::PrimType::BF16 => PrimitiveType::BF16,
+            impeller2::types::PrimType::Bytes => PrimitiveType::Bytes,
+            impeller2::types::PrimType::String => PrimitiveType::String,
+            impeller2::types::PrimType::Q15_16 => PrimitiveType::Q15_16,
         }
     }
 }
@@ -283,6 +293,11 @@ impl From<PrimitiveType> for impeller2::types::PrimType {
             PrimitiveType::I16 => impeller2::types::PrimType::I16,
             PrimitiveType::I8 => impeller2::types::PrimType::I8,
             PrimitiveType::Bool => impeller2::types::PrimType::Bool,
+            PrimitiveType::F16 => impeller2::types::PrimType::F16,
+            PrimitiveType::BF16 => impeller2::types::PrimType::BF16,
+            PrimitiveType::Bytes => impeller2::types::PrimType::Bytes,
+            PrimitiveType::String => impeller2::types::PrimType::String,
+            PrimitiveType::Q15_16 => impeller2::types::PrimType::Q15_16,
         }
     }
 }
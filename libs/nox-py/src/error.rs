@@ -28,6 +28,10 @@ pub enum Error {
     Impeller(#[from] impeller2::error::Error),
     #[error("elodin db error {0}")]
     DB(#[from] elodin_db::Error),
+    #[error("the world has no entities, spawn at least one before building")]
+    EmptyWorld,
+    #[error("the system writes no components, pass a system that outputs at least one component")]
+    EmptySystem,
 }
 
 impl From<Error> for PyErr {
@@ -39,6 +43,7 @@ impl From<Error> for PyErr {
             Error::NoxEcs(nox_ecs::Error::ValueSizeMismatch) => {
                 PyValueError::new_err("value size mismatch")
             }
+            Error::EmptyWorld | Error::EmptySystem => PyValueError::new_err(value.to_string()),
             Error::NoxEcs(nox_ecs::Error::PyO3(err)) | Error::PyErr(err) => err,
             err => PyRuntimeError::new_err(err.to_string()),
         }
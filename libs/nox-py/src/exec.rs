@@ -53,6 +53,26 @@ impl Exec {
         self.exec.profile()
     }
 
+    /// Lists every component defined in the world as `(id, name, type, metadata)`, sorted by name
+    pub fn components(&self) -> Vec<(u64, String, ComponentType, HashMap<String, String>)> {
+        let mut components = self
+            .exec
+            .world
+            .component_map()
+            .iter()
+            .map(|(id, (schema, metadata))| {
+                (
+                    id.0,
+                    metadata.name.clone(),
+                    ComponentType::from(schema.clone()),
+                    metadata.metadata.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        components.sort_by(|a, b| a.1.cmp(&b.1));
+        components
+    }
+
     pub fn save_archive(&self, path: String, format: String) -> Result<(), Error> {
         let format = match format.as_str() {
             "arrow_ipc" | "arrow" => ArchiveFormat::ArrowIpc,
@@ -64,6 +84,12 @@ impl Exec {
         Ok(())
     }
 
+    /// Writes the world's current state to `path`, see `WorldBuilder.load_snapshot`
+    pub fn snapshot(&self, path: String) -> Result<(), Error> {
+        self.exec.world.snapshot(path)?;
+        Ok(())
+    }
+
     pub fn history<'a>(
         &self,
         py: Python<'a>,
@@ -2,7 +2,7 @@ use crate::*;
 use ::s10::{GroupRecipe, SimRecipe, cli::run_recipe};
 use clap::Parser;
 use convert_case::Casing;
-use impeller2::types::{PrimType, Timestamp};
+use impeller2::types::{PrimType, Timestamp, bf16, f16};
 use impeller2_wkt::{ComponentMetadata, EntityMetadata};
 use miette::miette;
 use nox_ecs::{ComponentSchema, IntoSystem, System as _, TimeStep, World, increment_sim_tick, nox};
@@ -42,6 +42,9 @@ pub enum Args {
     Bench {
         #[arg(long, default_value = "1000")]
         ticks: usize,
+        /// Print the profile as a single JSON object instead of a human-readable summary
+        #[arg(long, default_value = "false")]
+        json: bool,
     },
 }
 
@@ -91,6 +94,15 @@ impl WorldBuilder {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Starts from a world written by `Exec.snapshot`, keeping its entities, component values, and tick
+    #[staticmethod]
+    pub fn load_snapshot(path: String) -> Result<Self, Error> {
+        Ok(Self {
+            world: World::load_snapshot(path)?,
+            ..Default::default()
+        })
+    }
     #[pyo3(signature = (spawnable, name=None, id=None))]
     pub fn spawn(
         &mut self,
@@ -188,6 +200,7 @@ impl WorldBuilder {
         default_playback_speed = 1.0,
         max_ticks = None,
         optimize = false,
+        s10_worker_threads = None,
     ))]
     pub fn run(
         &mut self,
@@ -198,6 +211,7 @@ impl WorldBuilder {
         default_playback_speed: f64,
         max_ticks: Option<u64>,
         optimize: bool,
+        s10_worker_threads: Option<usize>,
     ) -> Result<Option<String>, Error> {
         let _ = tracing_subscriber::fmt::fmt()
             .with_env_filter(
@@ -239,10 +253,16 @@ impl WorldBuilder {
                 let recipes = self.recipes.clone();
                 if !no_s10 {
                     std::thread::spawn(move || {
-                        let rt = tokio::runtime::Builder::new_current_thread()
-                            .build()
-                            .map_err(|err| miette!("rt err {}", err))
-                            .unwrap();
+                        // recipes run on a single thread unless the caller asks for worker threads
+                        let rt = match s10_worker_threads {
+                            None => tokio::runtime::Builder::new_current_thread().build(),
+                            Some(threads) => tokio::runtime::Builder::new_multi_thread()
+                                .worker_threads(threads.max(1))
+                                .enable_all()
+                                .build(),
+                        }
+                        .map_err(|err| miette!("rt err {}", err))
+                        .unwrap();
                         let group = ::s10::Recipe::Group(GroupRecipe {
                             recipes,
                             ..Default::default()
@@ -278,7 +298,7 @@ impl WorldBuilder {
                 std::fs::write(&plan_path, toml)?;
                 Ok(None)
             }
-            Args::Bench { ticks } => {
+            Args::Bench { ticks, json } => {
                 let mut exec = self.build(
                     py,
                     sys,
@@ -290,6 +310,12 @@ impl WorldBuilder {
                 )?;
                 exec.run(py, ticks, true)?;
                 let profile = exec.profile();
+                if json {
+                    let json = serde_json::to_string(&profile)
+                        .map_err(|err| PyValueError::new_err(err.to_string()))?;
+                    println!("{json}");
+                    return Ok(None);
+                }
                 println!("copy_to_client time:  {:.3} ms", profile["copy_to_client"]);
                 println!("execute_buffers time: {:.3} ms", profile["execute_buffers"]);
                 println!("copy_to_host time:    {:.3} ms", profile["copy_to_host"]);
@@ -514,6 +540,11 @@ impl WorldBuilder {
                 PrimType::Bool => "bool",
                 PrimType::F32 => "f32",
                 PrimType::F64 => "f64",
+                PrimType::F16 => "f16",
+                PrimType::BF16 => "bf16",
+                PrimType::Bytes => "bytes",
+                PrimType::String => "string",
+                PrimType::Q15_16 => "q15_16",
             };
 
             // Extract shape if it's a tensor
@@ -589,7 +620,25 @@ impl WorldBuilder {
     }
 }
 
+/// Fails if the only components written are the sim tick managed by [`increment_sim_tick`]
+fn check_system_outputs(compiled: &nox_ecs::CompiledSystem) -> Result<(), Error> {
+    let tick_id = <nox_ecs::Tick as impeller2::component::Component>::COMPONENT_ID;
+    if compiled.outputs.iter().all(|id| *id == tick_id) {
+        return Err(Error::EmptySystem);
+    }
+    Ok(())
+}
+
 impl WorldBuilder {
+    /// Fails if nothing but the globals entity has been spawned
+    fn check_not_empty(&self) -> Result<(), Error> {
+        let globals = impeller2::types::EntityId(0);
+        if self.world.entity_ids().iter().all(|id| *id == globals) {
+            return Err(Error::EmptyWorld);
+        }
+        Ok(())
+    }
+
     fn build_uncompiled(
         &mut self,
         py: Python<'_>,
@@ -613,10 +662,12 @@ impl WorldBuilder {
         }
 
         self.world.set_globals();
+        self.check_not_empty()?;
 
         let world = std::mem::take(&mut self.world);
-        let xla_exec = increment_sim_tick.pipe(sys).compile(&world).unwrap();
-        let tick_exec = xla_exec.compile_hlo_module(py, &world).unwrap();
+        let xla_exec = increment_sim_tick.pipe(sys).compile(&world)?;
+        check_system_outputs(&xla_exec)?;
+        let tick_exec = xla_exec.compile_hlo_module(py, &world)?;
 
         let mut exec = nox_ecs::WorldExec::new(world, tick_exec, None);
         exec.profiler.build.observe(&mut start);
@@ -663,9 +714,11 @@ impl WorldBuilder {
         let entity_dict = PyDict::new(py);
         let component_entity_dict = PyDict::new(py);
         self.world.set_globals();
+        self.check_not_empty()?;
 
         let world = std::mem::take(&mut self.world);
         let xla_exec = sys.compile(&world)?;
+        check_system_outputs(&xla_exec)?;
 
         for out_id in xla_exec.outputs.iter() {
             output_id.push(out_id.0);
@@ -695,7 +748,7 @@ impl WorldBuilder {
             component_entity_dict.set_item(&comp_name, entity_vec)?;
 
             match schema.prim_type {
-                PrimType::U8 => {
+                PrimType::U8 | PrimType::Bytes | PrimType::String => {
                     let slice = <[u8]>::ref_from_bytes(data).unwrap();
                     let py_array = PyArray::from_slice(py, slice)
                         .reshape(dim.into_dimension())
@@ -743,7 +796,8 @@ impl WorldBuilder {
 
                     state.push(py_array.into_py_any(py)?);
                 }
-                PrimType::I32 => {
+                // fixed-point values go to the sim as their raw bits, matching the S32 element type nox-ecs gives them
+                PrimType::I32 | PrimType::Q15_16 => {
                     let slice = <[i32]>::ref_from_bytes(data).unwrap();
                     let py_array = PyArray::from_slice(py, slice)
                         .reshape(dim.into_dimension())
@@ -775,6 +829,22 @@ impl WorldBuilder {
 
                     state.push(py_array.into_py_any(py)?);
                 }
+                PrimType::F16 => {
+                    let slice = <[f16]>::ref_from_bytes(data).unwrap();
+                    let py_array = PyArray::from_slice(py, slice)
+                        .reshape(dim.into_dimension())
+                        .unwrap();
+
+                    state.push(py_array.into_py_any(py)?);
+                }
+                PrimType::BF16 => {
+                    let slice = <[bf16]>::ref_from_bytes(data).unwrap();
+                    let py_array = PyArray::from_slice(py, slice)
+                        .reshape(dim.into_dimension())
+                        .unwrap();
+
+                    state.push(py_array.into_py_any(py)?);
+                }
                 PrimType::Bool => {
                     let slice = <[bool]>::try_ref_from_bytes(data).unwrap();
                     let py_array = PyArray::from_slice(py, slice)
@@ -21,10 +21,29 @@ pub fn as_vtable(input: TokenStream) -> TokenStream {
         generics,
         data,
         parent,
-    } = AsVTable::from_derive_input(&input).unwrap();
+    } = match AsVTable::from_derive_input(&input) {
+        Ok(input) => input,
+        Err(err) => return err.write_errors().into(),
+    };
     let where_clause = &generics.where_clause;
     let impeller = quote! { #crate_name::impeller2 };
     let fields = data.take_struct().unwrap();
+    let mut errors = crate::validate_fields(&fields.fields)
+        .err()
+        .into_iter()
+        .collect::<Vec<_>>();
+    for field in &fields.fields {
+        if field.option_inner().is_some() {
+            let ident = field.ident.as_ref().expect("only named fields allowed");
+            errors.push(
+                darling::Error::custom("optional fields can't be laid out in a vtable")
+                    .with_span(ident),
+            );
+        }
+    }
+    if !errors.is_empty() {
+        return darling::Error::multiple(errors).write_errors().into();
+    }
     let vtable_items = fields.fields.iter().map(|field| {
         let ty = &field.ty;
         let component_id = field.component_id();
@@ -35,16 +54,17 @@ pub fn as_vtable(input: TokenStream) -> TokenStream {
                 component_id.to_string()
             };
         let ident = &field.ident;
-        if !field.nest {
+        if !field.nested {
+            let schema = field.value_ty().schema();
             quote! {
                 {
 
-                    let schema = <#ty as #impeller::component::Component>::schema();
+                    let schema = #schema;
                     assert_eq!(schema.size(), #impeller::vtable::builder::field_size!(Self, #ident), "to cast to a vtable each field must be the same size as the component");
                     builder.push(
                         #impeller::vtable::builder::field!(
                             Self::#ident,
-                            #impeller::vtable::builder::schema(
+                            #impeller::vtable::builder::hashed_schema(
                                 schema.prim_type(),
                                 schema.dim(),
                                 #impeller::vtable::builder::component(#component_id)
@@ -55,7 +75,15 @@ pub fn as_vtable(input: TokenStream) -> TokenStream {
             }
         } else {
             quote! {
-                <#ty as #crate_name::AsVTable>::populate_vtable_builder(builder)?;
+                {
+                    let mut fields = vec![];
+                    <#ty as #crate_name::AsVTable>::populate_vtable_fields(&mut fields)?;
+                    let offset = u16::try_from(core::mem::offset_of!(Self, #ident))
+                        .map_err(|_| #impeller::error::Error::OffsetOverflow)?;
+                    for field in fields {
+                        builder.push(field.offset_by(offset)?);
+                    }
+                }
             }
         }
     });
@@ -21,10 +21,16 @@ pub fn componentize(input: TokenStream) -> TokenStream {
         generics,
         data,
         parent,
-    } = Componentize::from_derive_input(&input).unwrap();
+    } = match Componentize::from_derive_input(&input) {
+        Ok(input) => input,
+        Err(err) => return err.write_errors().into(),
+    };
     let where_clause = &generics.where_clause;
     let impeller = quote! { #crate_name::impeller2 };
     let fields = data.take_struct().unwrap();
+    if let Err(err) = crate::validate_fields(&fields.fields) {
+        return err.write_errors().into();
+    }
     let sink_calls = fields.fields.iter().map(|field| {
         let component_id = field.component_id();
 
@@ -34,17 +40,33 @@ pub fn componentize(input: TokenStream) -> TokenStream {
             component_id.to_string()
         };
         let ident = field.ident.as_ref().expect("only named fields allowed");
-        if !field.nest {
-            quote! {
+        if field.nested {
+            return quote! {
+                #crate_name::Componentize::sink_columns(&self.#ident, output);
+            };
+        }
+        let view = field.value_ty().as_view(quote! { value });
+        let sink = quote! {
+            if let Some(view) = #view {
                 let _ = output.apply_value(
                     #impeller::types::ComponentId::new(#component_id),
-                    self.#ident.as_component_view(),
+                    view,
                     None
                 );
             }
+        };
+        if field.option_inner().is_some() {
+            quote! {
+                if let Some(value) = &self.#ident {
+                    #sink
+                }
+            }
         } else {
             quote! {
-                self.#ident.sink_columns(output);
+                {
+                    let value = &self.#ident;
+                    #sink
+                }
             }
         }
     });
@@ -52,7 +74,6 @@ pub fn componentize(input: TokenStream) -> TokenStream {
     quote! {
         impl #crate_name::Componentize for #ident #generics #where_clause {
             fn sink_columns(&self, output: &mut impl #crate_name::Decomponentize) {
-                use #impeller::com_de::AsComponentView;
                 #(#sink_calls)*
             }
 
@@ -23,12 +23,17 @@ pub fn decomponentize(input: TokenStream) -> TokenStream {
         generics,
         data,
         parent,
-    } = Decomponentize::from_derive_input(&input).unwrap();
+    } = match Decomponentize::from_derive_input(&input) {
+        Ok(input) => input,
+        Err(err) => return err.write_errors().into(),
+    };
     let where_clause = &generics.where_clause;
     let impeller = quote! { #crate_name::impeller2 };
     let fields = data.take_struct().unwrap();
+    if let Err(err) = crate::validate_fields(&fields.fields) {
+        return err.write_errors().into();
+    }
     let if_arms = fields.fields.iter().map(|field| {
-        let ty = &field.ty;
         let ident = &field.ident;
         let name = field
             .ident
@@ -44,20 +49,25 @@ pub fn decomponentize(input: TokenStream) -> TokenStream {
             component_id.to_string()
         };
         let component_id = quote! { #impeller::types::ComponentId::new(#component_id) };
-        if !field.nest {
+        if field.nested {
+            return quote! {
+                #crate_name::Decomponentize::apply_value(&mut self.#ident, component_id, view.clone(), timestamp)?;
+            };
+        }
         let const_name = format!("{name}_ID");
-            let const_name = syn::Ident::new(&const_name, Span::call_site());
-            quote! {
-                const #const_name: #impeller::types::ComponentId = #component_id;
-                if component_id == #const_name {
-                    if let Ok(val) = <#ty as #impeller::com_de::FromComponentView>::from_component_view(view.clone()) {
-                        self.#ident = val;
-                    }
-                }
-            }
+        let const_name = syn::Ident::new(&const_name, Span::call_site());
+        let read = field.value_ty().from_view(quote! { view });
+        let assign = if field.option_inner().is_some() {
+            quote! { self.#ident = Some(val); }
         } else {
-            quote! {
-                self.#ident.apply_value(component_id,  value.clone(), timestamp.clone())?;
+            quote! { self.#ident = val; }
+        };
+        quote! {
+            const #const_name: #impeller::types::ComponentId = #component_id;
+            if component_id == #const_name {
+                if let Some(val) = #read {
+                    #assign
+                }
             }
         }
     });
@@ -69,6 +79,7 @@ pub fn decomponentize(input: TokenStream) -> TokenStream {
                             view: #impeller::types::ComponentView<'_>,
                             timestamp: Option<#impeller::types::Timestamp>
             ) -> Result<(), Self::Error>{
+                let _ = timestamp;
                 #(#if_arms)*
                 Ok(())
             }
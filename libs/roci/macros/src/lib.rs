@@ -16,8 +16,9 @@ struct Field {
     ident: Option<syn::Ident>,
     ty: syn::Type,
     component_id: Option<String>,
-    #[darling(skip, default)]
-    nest: bool,
+    /// Sinks the field through its own derived impls, instead of as a single component
+    #[darling(default)]
+    nested: bool,
 }
 
 impl Field {
@@ -35,6 +36,161 @@ impl Field {
             }
         }
     }
+
+    /// The `T` of an `Option<T>` field, which is only sunk when it's `Some`
+    pub fn option_inner(&self) -> Option<&syn::Type> {
+        option_inner(&self.ty)
+    }
+
+    /// The type stored as a component, without the field's `Option`
+    pub fn value_ty(&self) -> ValueTy<'_> {
+        ValueTy::new(self.option_inner().unwrap_or(&self.ty))
+    }
+
+    /// Checks the field is laid out in a way the derives understand
+    pub fn validate(&self) -> darling::Result<()> {
+        let ident = self.ident.as_ref().expect("only named fields allowed");
+        if self.nested && self.option_inner().is_some() {
+            return Err(darling::Error::custom("nested fields can't be optional").with_span(ident));
+        }
+        if self.nested && matches!(self.value_ty(), ValueTy::Array { .. }) {
+            return Err(darling::Error::custom(
+                "nested fields can't be arrays, use an array of primitives for a shaped column",
+            )
+            .with_span(ident));
+        }
+        if self.option_inner().and_then(option_inner).is_some() {
+            return Err(
+                darling::Error::custom("optional fields can't be nested options").with_span(ident),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Validates every field, collecting all their errors
+fn validate_fields<'a>(fields: impl IntoIterator<Item = &'a Field>) -> darling::Result<()> {
+    let errors = fields
+        .into_iter()
+        .filter_map(|field| field.validate().err())
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(darling::Error::multiple(errors))
+    }
+}
+
+fn ungroup(mut ty: &syn::Type) -> &syn::Type {
+    while let syn::Type::Group(group) = ty {
+        ty = &group.elem;
+    }
+    ty
+}
+
+fn option_inner(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ungroup(ty) else {
+        return None;
+    };
+    if path.qself.is_some() {
+        return None;
+    }
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        syn::GenericArgument::Type(ty) if args.args.len() == 1 => Some(ty),
+        _ => None,
+    }
+}
+
+/// How a field's value maps onto a component
+enum ValueTy<'a> {
+    /// A type that views itself as a component, like a primitive or a nox tensor
+    Scalar(&'a syn::Type),
+    /// A fixed-size array of primitives, possibly multi-dimensional, stored as a column shaped like the array
+    Array {
+        ty: &'a syn::Type,
+        elem: &'a syn::Type,
+        dims: Vec<&'a syn::Expr>,
+    },
+}
+
+impl<'a> ValueTy<'a> {
+    fn new(ty: &'a syn::Type) -> Self {
+        let mut elem = ungroup(ty);
+        let mut dims = vec![];
+        while let syn::Type::Array(array) = elem {
+            dims.push(&array.len);
+            elem = ungroup(&array.elem);
+        }
+        if dims.is_empty() {
+            ValueTy::Scalar(ty)
+        } else {
+            ValueTy::Array { ty, elem, dims }
+        }
+    }
+
+    /// An `Option<ComponentView>` of `value`, an expression borrowing the field's value
+    pub fn as_view(&self, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let crate_name = crate::roci_crate_name();
+        let impeller = quote! { #crate_name::impeller2 };
+        match self {
+            ValueTy::Scalar(_) => quote! {
+                Some(#impeller::com_de::AsComponentView::as_component_view(#value))
+            },
+            ValueTy::Array { elem, dims, .. } => quote! {
+                #impeller::types::ComponentView::try_from_bytes_shape(
+                    #crate_name::zerocopy::IntoBytes::as_bytes(#value),
+                    &[#(#dims),*],
+                    <#elem as #impeller::component::PrimTypeElem>::PRIM_TYPE,
+                )
+                .ok()
+            },
+        }
+    }
+
+    /// An `Option` of the value read from `view`, which is `None` if the view has the wrong type or shape
+    pub fn from_view(&self, view: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let crate_name = crate::roci_crate_name();
+        let impeller = quote! { #crate_name::impeller2 };
+        match self {
+            ValueTy::Scalar(ty) => quote! {
+                <#ty as #impeller::com_de::FromComponentView>::from_component_view(#view.clone()).ok()
+            },
+            ValueTy::Array { ty, elem, dims } => quote! {
+                if #view.prim_type() == <#elem as #impeller::component::PrimTypeElem>::PRIM_TYPE
+                    && #view.shape() == [#(#dims),*]
+                {
+                    <#ty as #crate_name::zerocopy::TryFromBytes>::try_read_from_bytes(#view.as_bytes()).ok()
+                } else {
+                    None
+                }
+            },
+        }
+    }
+
+    /// The `Schema` of the component
+    pub fn schema(&self) -> proc_macro2::TokenStream {
+        let crate_name = crate::roci_crate_name();
+        let impeller = quote! { #crate_name::impeller2 };
+        match self {
+            ValueTy::Scalar(ty) => quote! {
+                <#ty as #impeller::component::Component>::schema()
+            },
+            ValueTy::Array { elem, dims, .. } => quote! {
+                #impeller::schema::Schema::new(
+                    <#elem as #impeller::component::PrimTypeElem>::PRIM_TYPE,
+                    [#((#dims) as u64),*],
+                )
+                .unwrap()
+            },
+        }
+    }
 }
 
 #[proc_macro_derive(Componentize, attributes(roci))]
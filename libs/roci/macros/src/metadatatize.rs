@@ -21,15 +21,21 @@ pub fn metadatatize(input: TokenStream) -> TokenStream {
         generics,
         data,
         parent,
-    } = Metadatatize::from_derive_input(&input).unwrap();
+    } = match Metadatatize::from_derive_input(&input) {
+        Ok(input) => input,
+        Err(err) => return err.write_errors().into(),
+    };
     let where_clause = &generics.where_clause;
     let impeller = quote! { #crate_name::impeller2 };
     let impeller_wkt = quote! { #crate_name::impeller2_wkt };
     let fields = data.take_struct().unwrap();
+    if let Err(err) = crate::validate_fields(&fields.fields) {
+        return err.write_errors().into();
+    }
 
     let metadata_items = fields.fields.iter().map(|field| {
         let ty = &field.ty;
-        if !field.nest {
+        if !field.nested {
             let name = field
                 .ident
                 .as_ref()
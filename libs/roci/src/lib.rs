@@ -10,7 +10,7 @@ pub use impeller2::com_de::{Componentize, Decomponentize};
 pub use impeller2_wkt;
 pub use roci_macros::{AsVTable, Componentize, Decomponentize, Metadatatize};
 pub use system_fn::*;
-pub use vtable::AsVTable;
+pub use vtable::{AsVTable, TableEncoder, encode_table};
 pub use zerocopy;
 
 pub mod combinators;
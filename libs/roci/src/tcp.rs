@@ -1,7 +1,9 @@
 use impeller2::buf::{Slice, deref};
 use impeller2::types::{Msg, PacketId};
 use impeller2_stellar::{Client, Error, SubStream};
-use impeller2_wkt::{MsgMetadata, SetMsgMetadata, StreamReply, VTableMsg, VTableStream};
+use impeller2_wkt::{
+    DumpSchema, MsgMetadata, SetMsgMetadata, StreamReply, VTableMsg, VTableStream,
+};
 use std::future::Future;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
@@ -88,15 +90,30 @@ impl SinkExt for Client {
 }
 
 pub trait StreamExt {
+    /// Checks `T`'s vtable against the schemas the db has registered, failing with
+    /// [`impeller2::error::Error::SchemaMismatch`] naming the first component that differs
+    fn verify_schemas<T: AsVTable>(&mut self) -> impl Future<Output = Result<(), Error>>;
     fn subscribe<T>(&mut self) -> impl Future<Output = Result<Subscription<'_, T>, Error>>
     where
         T: AsVTable + zerocopy::TryFromBytes + Immutable + KnownLayout + Clone;
 }
 
 impl StreamExt for Client {
+    async fn verify_schemas<T: AsVTable>(&mut self) -> Result<(), Error> {
+        let schemas = self.request(&DumpSchema).await?.schemas;
+        T::as_vtable().verify_schemas(|component_id| {
+            schemas
+                .get(&component_id)
+                .map(|schema| schema.stable_hash())
+        })?;
+        Ok(())
+    }
+
     async fn subscribe<T: AsVTable + zerocopy::TryFromBytes + Immutable + KnownLayout + Clone>(
         &mut self,
     ) -> Result<Subscription<'_, T>, Error> {
+        // otherwise the db rejects the vtable out of band, and the stream never produces a table
+        self.verify_schemas::<T>().await?;
         let vtable = T::as_vtable();
         let id = fastrand::u16(..).to_le_bytes();
         self.send(&VTableMsg { id, vtable }).await.0?;
@@ -1,10 +1,13 @@
 use impeller2::{
+    com_de::{Componentize, Decomponentize},
     error::Error,
+    types::{ComponentId, ComponentView, LenPacket, PacketId, Timestamp},
     vtable::{
         VTable,
-        builder::{FieldBuilder, vtable},
+        builder::{FieldBuilder, component, hashed_schema, raw_field, vtable},
     },
 };
+use impeller2_wkt::VTableMsg;
 
 pub trait AsVTable {
     fn populate_vtable_fields(builder: &mut Vec<FieldBuilder>) -> Result<(), Error>;
@@ -14,3 +17,240 @@ pub trait AsVTable {
         vtable(fields)
     }
 }
+
+/// A sink that lays out every value it receives as one field of a single table
+///
+/// Each value is padded to its primitive type's alignment and appended, and a matching vtable field is recorded.
+/// Timestamps passed to `apply_value` are ignored.
+#[derive(Default)]
+pub struct TableEncoder {
+    fields: Vec<FieldBuilder>,
+    table: Vec<u8>,
+    error: Option<Error>,
+}
+
+impl TableEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the vtable message registering the table under `id`, along with the table packet itself
+    pub fn finish(self, id: PacketId) -> Result<(VTableMsg, LenPacket), Error> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        let mut packet = LenPacket::table(id, self.table.len());
+        packet.extend_from_slice(&self.table);
+        let vtable = vtable(self.fields);
+        Ok((VTableMsg { id, vtable }, packet))
+    }
+
+    fn push(&mut self, component_id: ComponentId, value: ComponentView<'_>) -> Result<(), Error> {
+        let prim_type = value.prim_type();
+        self.table
+            .resize(self.table.len() + prim_type.padding(self.table.len()), 0);
+        let offset = u16::try_from(self.table.len()).map_err(|_| Error::OffsetOverflow)?;
+        let bytes = value.as_bytes();
+        let len = u16::try_from(bytes.len()).map_err(|_| Error::OffsetOverflow)?;
+        let dim: Vec<u64> = value.shape().iter().map(|&d| d as u64).collect();
+        self.table.extend_from_slice(bytes);
+        self.fields.push(raw_field(
+            offset,
+            len,
+            hashed_schema(prim_type, &dim, component(component_id)),
+        ));
+        Ok(())
+    }
+}
+
+impl Decomponentize for TableEncoder {
+    type Error = Error;
+
+    fn apply_value(
+        &mut self,
+        component_id: ComponentId,
+        value: ComponentView<'_>,
+        _timestamp: Option<Timestamp>,
+    ) -> Result<(), Self::Error> {
+        // derived `Componentize` impls discard sink errors, so the first one is also kept for `finish`
+        let res = self.push(component_id, value);
+        if let Err(err) = &res {
+            self.error.get_or_insert(err.clone());
+        }
+        res
+    }
+}
+
+/// Encodes `value` as a table packet, along with the vtable that describes it
+///
+/// The vtable is built from the same [`Componentize`] pass that writes the table, so it always matches the bytes.
+pub fn encode_table<C: Componentize>(
+    id: PacketId,
+    value: &C,
+) -> Result<(VTableMsg, LenPacket), Error> {
+    let mut encoder = TableEncoder::new();
+    value.sink_columns(&mut encoder);
+    encoder.finish(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use impeller2::types::{OwnedPacket, PrimType};
+    use zerocopy::IntoBytes;
+
+    #[derive(crate::Componentize)]
+    struct Baro {
+        temp: f32,
+        pressure: f64,
+    }
+
+    #[test]
+    fn test_encode_table_round_trip() {
+        let baro = Baro {
+            temp: 21.5,
+            pressure: 101_325.0,
+        };
+        let (msg, packet) = encode_table([1, 0], &baro).unwrap();
+        assert_eq!(msg.id, [1, 0]);
+
+        let mut expected = vec![];
+        baro.sink_columns(
+            &mut |id: ComponentId, view: ComponentView<'_>, _: Option<Timestamp>| {
+                expected.push((id, view.as_bytes().to_vec()));
+            },
+        );
+
+        let OwnedPacket::Table(table) = OwnedPacket::parse_with_offset(packet.inner, 4).unwrap()
+        else {
+            panic!("expected table packet");
+        };
+        assert_eq!(table.id, [1, 0]);
+        // `pressure` is padded out to its 8 byte alignment
+        assert_eq!(table.buf.len(), 16);
+        let mut decoded = vec![];
+        msg.vtable
+            .apply(
+                &table.buf[..],
+                &mut |id: ComponentId, view: ComponentView<'_>, _: Option<Timestamp>| {
+                    decoded.push((id, view.as_bytes().to_vec()));
+                },
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded, expected);
+    }
+
+    #[derive(
+        crate::Componentize, crate::Decomponentize, crate::AsVTable, Default, Debug, PartialEq,
+    )]
+    #[repr(C)]
+    struct Wheel {
+        #[roci(component_id = "wheel.speed")]
+        speed: f64,
+        #[roci(component_id = "wheel.current")]
+        current: [f32; 2],
+    }
+
+    #[derive(crate::Componentize, crate::Decomponentize, Default, Debug, PartialEq)]
+    struct Rover {
+        #[roci(nested)]
+        wheel: Wheel,
+        dcm: [[f64; 3]; 3],
+        fix: Option<u8>,
+    }
+
+    #[derive(crate::AsVTable)]
+    #[repr(C)]
+    struct RoverTable {
+        mode: u64,
+        #[roci(nested)]
+        wheel: Wheel,
+        dcm: [[f64; 3]; 3],
+    }
+
+    #[test]
+    fn test_derive_nested_arrays_and_options() {
+        let rover = Rover {
+            wheel: Wheel {
+                speed: 2.5,
+                current: [0.5, 0.75],
+            },
+            dcm: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            fix: None,
+        };
+        let mut sunk = vec![];
+        rover.sink_columns(&mut |id: ComponentId,
+                                 view: ComponentView<'_>,
+                                 _: Option<Timestamp>| {
+            sunk.push((id, view.shape().to_vec()));
+        });
+        // `fix` is `None`, so it isn't sunk at all
+        assert_eq!(
+            sunk,
+            [
+                (ComponentId::new("wheel.speed"), vec![]),
+                (ComponentId::new("wheel.current"), vec![2]),
+                (ComponentId::new("dcm"), vec![3, 3]),
+            ]
+        );
+
+        let rover = Rover {
+            fix: Some(3),
+            ..rover
+        };
+        let mut decoded = Rover::default();
+        rover.sink_columns(&mut decoded);
+        assert_eq!(decoded, rover);
+
+        // a view with the wrong shape is ignored rather than misread
+        let mut decoded = Rover::default();
+        let view = ComponentView::try_from_bytes_shape([1.0f64; 9].as_bytes(), &[9], PrimType::F64)
+            .unwrap();
+        let Ok(()) = decoded.apply_value(ComponentId::new("dcm"), view, None);
+        assert_eq!(decoded.dcm, [[0.0; 3]; 3]);
+    }
+
+    #[test]
+    fn test_derive_vtable_nested_and_arrays() {
+        let columns = RoverTable::as_vtable().columns().unwrap();
+        let layout = columns
+            .iter()
+            .map(|col| (col.component_id, col.shape.clone(), col.offset, col.len))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            layout,
+            [
+                (ComponentId::new("mode"), vec![], 0, 8),
+                (ComponentId::new("wheel.speed"), vec![], 8, 8),
+                (ComponentId::new("wheel.current"), vec![2], 16, 8),
+                (ComponentId::new("dcm"), vec![3, 3], 24, 72),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_derive_vtable_schema_hashes() {
+        use impeller2::schema::schema_hash;
+
+        let vtable = RoverTable::as_vtable();
+        vtable.validate().unwrap();
+        let dcm = ComponentId::new("dcm");
+        let current = schema_hash(PrimType::F64, &[3, 3]);
+        vtable
+            .verify_schemas(|id| (id == dcm).then_some(current))
+            .unwrap();
+
+        // the db still has the shape from before `dcm` was flattened
+        let stale = schema_hash(PrimType::F64, &[9]);
+        let err = vtable
+            .verify_schemas(|id| (id == dcm).then_some(stale))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::SchemaMismatch { component_id, expected, found }
+                if component_id == dcm && expected == stale && found == current
+        ));
+    }
+}
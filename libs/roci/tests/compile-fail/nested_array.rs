@@ -0,0 +1,14 @@
+#![allow(dead_code)]
+
+#[derive(roci::Componentize)]
+struct Wheel {
+    speed: f64,
+}
+
+#[derive(roci::Componentize)]
+struct Rover {
+    #[roci(nested)]
+    wheels: [Wheel; 4],
+}
+
+fn main() {}
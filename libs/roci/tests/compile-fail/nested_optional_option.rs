@@ -0,0 +1,8 @@
+#![allow(dead_code)]
+
+#[derive(roci::Decomponentize, Default)]
+struct Gps {
+    fix: Option<Option<u8>>,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#![allow(dead_code)]
+
+#[derive(roci::AsVTable)]
+#[repr(C)]
+struct Gps {
+    lat: f64,
+    fix: Option<u8>,
+}
+
+fn main() {}
@@ -0,0 +1,5 @@
+#[test]
+fn derive_compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/compile-fail/*.rs");
+}
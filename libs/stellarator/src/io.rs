@@ -33,6 +33,12 @@ pub trait AsyncRead {
 pub trait AsyncWrite {
     fn write<B: IoBuf>(&self, buf: B) -> impl Future<Output = BufResult<usize, B>>;
 
+    /// Shuts down both directions of the connection behind this writer, failing every pending and future read and
+    /// write on it, or does nothing if the writer isn't a connection
+    fn shutdown(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
     fn write_all<B: IoBuf>(&self, mut buf: B) -> impl Future<Output = BufResult<(), B>> {
         async {
             let mut total_written = 0;
@@ -233,6 +239,10 @@ impl<W: AsyncWrite> AsyncWrite for OwnedWriter<W> {
     fn write<B: IoBuf>(&self, buf: B) -> impl Future<Output = BufResult<usize, B>> {
         self.inner.write(buf)
     }
+
+    fn shutdown(&self) -> std::io::Result<()> {
+        self.inner.shutdown()
+    }
 }
 
 pub trait SplitExt: AsyncRead + AsyncWrite + Sized {
@@ -58,6 +58,10 @@ impl AsyncWrite for TcpStream {
     fn write<B: IoBuf>(&self, buf: B) -> impl std::future::Future<Output = BufResult<usize, B>> {
         self.write(buf)
     }
+
+    fn shutdown(&self) -> io::Result<()> {
+        self.socket.shutdown(std::net::Shutdown::Both)
+    }
 }
 
 pub struct TcpListener {